@@ -21,7 +21,7 @@ fn generate_manpage<P: AsRef<Path>>(outdir: P) -> io::Result<()> {
         .arg("--backend")
         .arg("manpage")
         .arg("--destination-dir")
-        .arg(&outdir)
+        .arg(outdir)
         .arg(&template_path)
         .spawn()?
         .wait()?;
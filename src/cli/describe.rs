@@ -0,0 +1,148 @@
+/// Short ripgrep options which take a value. When forwarding them, the value is the following argument.
+pub(crate) const SHORT_OPTIONS_WITH_VALUES: &[&str] =
+    &["-A", "-B", "-C", "-d", "-g", "-j", "-M", "-m", "-t", "-T"];
+
+/// Describes the arguments forwarded to ripgrep (see `RgArgs::other_args`) in a more readable way than the
+/// command line, as rows of a label and a value. Rows without any arguments are left out.
+pub fn describe_rg_args(args: &[String]) -> Vec<(&'static str, String)> {
+    let mut globs = vec![];
+    let mut types = vec![];
+    let mut before = None;
+    let mut after = None;
+    let mut context = None;
+    let mut unrestricted = 0;
+    let mut files = vec![];
+    let mut paths = vec![];
+    let mut other = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_owned())),
+            _ if SHORT_OPTIONS_WITH_VALUES.contains(&arg.as_str()) => {
+                (arg.as_str(), args.next().cloned())
+            }
+            _ => (arg.as_str(), None),
+        };
+
+        match (name, value) {
+            ("-g" | "--glob", Some(glob)) => globs.push(glob),
+            ("--iglob", Some(glob)) => globs.push(format!("{} (ignore case)", glob)),
+            ("-t" | "--type", Some(name)) => types.push(name),
+            ("-T" | "--type-not", Some(name)) => types.push(format!("not {}", name)),
+            ("-A" | "--after-context", Some(n)) => after = Some(n),
+            ("-B" | "--before-context", Some(n)) => before = Some(n),
+            ("-C" | "--context", Some(n)) => context = Some(n),
+            ("-u" | "--unrestricted", None) => unrestricted += 1,
+            ("-." | "--hidden", None) => files.push("hidden"),
+            ("--no-ignore", None) => files.push("no ignore"),
+            ("-L" | "--follow", None) => files.push("follow symlinks"),
+            ("--binary", None) => files.push("binary"),
+            (path, None) if !path.starts_with('-') => paths.push(path.to_owned()),
+            (name, Some(value)) if name.starts_with("--") => {
+                other.push(format!("{}={}", name, value))
+            }
+            (name, Some(value)) => other.push(format!("{} {}", name, value)),
+            (name, None) => other.push(name.to_owned()),
+        }
+    }
+
+    // each -u searches more files: first ignored files, then hidden files, then binary files
+    for (count, description) in [(1, "no ignore"), (2, "hidden"), (3, "binary")] {
+        if unrestricted >= count && !files.contains(&description) {
+            files.push(description);
+        }
+    }
+
+    // -A and -B take precedence over -C
+    let context = vec![
+        ("before", before.or(context.clone())),
+        ("after", after.or(context)),
+    ]
+    .into_iter()
+    .filter_map(|(label, n)| n.map(|n| format!("{} line(s) {}", n, label)))
+    .collect::<Vec<_>>();
+
+    vec![
+        ("Globs", globs.join(", ")),
+        ("Types", types.join(", ")),
+        ("Context", context.join(", ")),
+        ("Files", files.join(", ")),
+        ("Paths", paths.join(", ")),
+        ("Other", other.join(" ")),
+    ]
+    .into_iter()
+    .filter(|(_, value)| !value.is_empty())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::cli::describe::describe_rg_args;
+
+    fn describe(args: &[&str]) -> Vec<(&'static str, String)> {
+        describe_rg_args(&args.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn it_describes_nothing() {
+        assert!(describe(&[]).is_empty());
+    }
+
+    #[test]
+    fn it_describes_globs_and_context() {
+        assert_eq!(
+            describe(&[
+                "-g",
+                "*.rs",
+                "--glob=!target/*",
+                "--iglob=*.MD",
+                "-t",
+                "rust",
+                "--type-not=js",
+                "-C",
+                "3",
+                "-A",
+                "1",
+                "--hidden",
+                "-i",
+                "--max-count=5",
+                "-m",
+                "2",
+                "src",
+                "tests",
+            ]),
+            [
+                ("Globs", "*.rs, !target/*, *.MD (ignore case)".into()),
+                ("Types", "rust, not js".into()),
+                ("Context", "3 line(s) before, 1 line(s) after".into()),
+                ("Files", "hidden".into()),
+                ("Paths", "src, tests".into()),
+                ("Other", "-i --max-count=5 -m 2".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_describes_context() {
+        assert_eq!(
+            describe(&["--before-context=2"]),
+            [("Context", "2 line(s) before".into())]
+        );
+        assert_eq!(
+            describe(&["--context=2", "--after-context=0"]),
+            [("Context", "2 line(s) before, 0 line(s) after".into())]
+        );
+    }
+
+    #[test]
+    fn it_describes_unrestricted_searches() {
+        assert_eq!(describe(&["-u"]), [("Files", "no ignore".into())]);
+        assert_eq!(
+            describe(&["--hidden", "-u", "-u", "-u", "-L"]),
+            [("Files", "hidden, follow symlinks, no ignore, binary".into())]
+        );
+    }
+}
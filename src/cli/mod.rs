@@ -1,9 +1,41 @@
+mod describe;
+
+use std::ffi::OsString;
 use std::{fs, process};
 
-use anyhow::{bail, Result};
-use lexopt::Parser;
+use anyhow::{bail, Context, Result};
+use lexopt::{Arg, Parser, ValueExt};
+use regex::bytes::{Regex, RegexBuilder};
+
+pub use describe::describe_rg_args;
+use describe::SHORT_OPTIONS_WITH_VALUES;
+
+use crate::model::{LineEnding, SortMode, WrapMode};
+use crate::rg::de::RgMessage;
+use crate::rg::exec::ripgrep_version;
 
 pub const ENV_JSON_FILE: &str = "RGR_JSON_FILE";
+/// The flag which reads `rg`'s JSON output from stdin, rather than running `rg`.
+pub const FLAG_JSON_STDIN: &str = "--json-stdin";
+/// The name of the binary (`CARGO_BIN_NAME` isn't available to the library).
+const BIN_NAME: &str = "rgr";
+
+/// The text printed for `--version`. With `--verbose`, the version of `rg` (or why it couldn't be found) is
+/// printed too, which helps with bug reports.
+pub fn version_text(rg_version: Option<Result<String>>) -> String {
+    let mut text = format!(
+        "{crate_name} {crate_version}",
+        crate_name = env!("CARGO_PKG_NAME"),
+        crate_version = env!("CARGO_PKG_VERSION")
+    );
+    match rg_version {
+        Some(Ok(rg_version)) => text.push_str(&format!("\nrg: {}", rg_version)),
+        Some(Err(e)) => text.push_str(&format!("\nrg: unavailable ({})", e)),
+        None => {}
+    }
+
+    text
+}
 
 pub fn print_help() {
     println!(
@@ -36,10 +68,17 @@ EXAMPLES:
             Find and replace all occurrences of "foo", but now "$1" will be set to "f".
             This uses regular expression capturing groups, for more info, see `rg --help`.
 
-    2: {env_file}=path/to/rg.json rgr [REGEX]
+        {bin} "(?P<word>f)oo"
+            Named capturing groups may be used too, here "$word" (or "${{word}}") will be set to "f".
+            Capturing groups aren't expanded when rg uses PCRE2 (-P or --engine=pcre2).
+
+    2: {env_file}=path/to/rg.json rgr [REGEX | -e REGEX...]
         Alternatively, you may store all the JSON results from rg into a file, and have {bin} read
         that file for results when running. When running it this way, only a single optional argument
         is used, a regular expression. This is to provide capture group support.
+        Many patterns may be passed with -e/--regexp, and they're combined into one alternation, so
+        groups are numbered across all of them (e.g. with -e "(a)" -e "(b)", "$2" is "b").
+        Large files are parsed on many threads, as many as -j/--threads (or one per CPU).
         This is mainly used to cache results for expensive or long-running searches.
 
         rg --json "foo" > rg.json && {env_file}=rg.json {bin}
@@ -50,9 +89,156 @@ EXAMPLES:
             The pattern provided this way will be run on each match, and can be used to provide
             capturing group powered replacements. In the above example, providing the replacement
             text `$1$1` would result in occurrences of "foo" being replaced with "fofo".
+
+    3: rg --json ... | {bin} {json_stdin} [REGEX | -e REGEX...]
+        Like the above, but the JSON results are read from stdin instead of a file. The interface
+        reads the keyboard from the terminal itself, so it can still be used.
+
+        rg --json "(f)oo" | {bin} {json_stdin} "(f)oo"
+
+RGR OPTIONS:
+    These options are handled by {bin} itself, and are not passed through to rg.
+
+    --wrap-indent
+        Prefix wrapped continuation lines with a marker, to make them easier to tell apart
+        from new lines.
+
+    --no-wrap
+        Don't wrap long lines, instead truncate them at the edge of the terminal. Press <z> to
+        scroll them horizontally.
+
+    --tab-width <N>
+        Expand tabs to the next tab stop, every N columns (8 by default). This is only used
+        when control characters are hidden (see <control+v>).
+
+    --sort-display <MODE>
+        Reorder the files displayed in the matches list, independently of rg's own sorting.
+        Press <o> to cycle through the modes. MODE is one of:
+          rg      the order in which rg returned the files (default)
+          path    sorted by file path
+          count   files with the most matches first
+          length  files with the longest matched lines first
+
+    --replace-command <CMD>
+        WARNING: this executes arbitrary shell commands!
+        Instead of using the replacement text, run CMD (with `sh -c`, or `cmd /C` on Windows)
+        once for each match, passing the matched text on its stdin, and replace the match with
+        whatever CMD writes to its stdout. For example: --replace-command 'tr a-z A-Z'.
+        A trailing newline in the output is removed, unless the match also ended with one.
+
+    --after-replace-command <CMD>
+        WARNING: this executes arbitrary shell commands!
+        Once the replacements are written, run CMD (with `sh -c`, or `cmd /C` on Windows) with
+        the paths of the files that were changed appended to it, e.g. to format them with
+        --after-replace-command 'prettier --write'. If CMD fails, the replacements are kept.
+
+    --output-patch <PATH>
+        Don't change any files. Instead, write a unified diff of the replacements to PATH, which
+        can be reviewed and then applied with `git apply PATH`.
+
+    --color <WHEN>
+        Whether to use colors in what's printed to stdout once rg has run (such as --count's
+        output, or the report printed after replacing): never, always, or auto (the default),
+        which only uses them when stdout is a terminal. This is not passed through to rg.
+
+    --count
+        Print the number of matched lines in each file, followed by the total, and exit
+        without starting the interface.
+
+    --vimgrep
+        Print each match as path:line:column:text (like rg --vimgrep), and exit without starting
+        the interface. Useful for loading the matches into an editor's quickfix list.
+
+    --encoding-override
+        When replacing, use the encoding passed with --encoding even for files with a BOM
+        (byte order mark) for a different encoding. Useful when a file's BOM is wrong.
+
+    -l, --files-with-matches
+        Start by listing only the files with matches, and choose which of them to show the
+        matches of. This is not passed through to rg.
+
+    --skip-symlinks
+        When replacing, skip files which are symlinks (e.g. with --follow). By default the
+        files they link to are written instead, and the symlinks are kept.
+
+    --allow-binary
+        When replacing, also write files which rg found binary data in (e.g. with -uuu or
+        --binary), or whose lines aren't valid UTF-8. By default they're skipped, since
+        replacing in them may corrupt them. They're marked with [binary] in the interface.
+
+    --atomic=false
+        When replacing, truncate and write each file in place, rather than writing a temporary
+        file and renaming it over the original. Useful on filesystems where renaming fails (or
+        loses ACLs), but not atomic: if writing fails part way, the file may be left incomplete.
+
+    --normalize-eol <EOL>
+        When replacing, convert every line ending in each file that's written to EOL (either
+        lf or crlf), including any in the replacement text. By default, they're kept as-is.
+
+    --json-preview
+        Instead of starting the interface, print each match as a line of JSON (like rg --json)
+        where each submatch also has a "replacement" with the text it would be replaced with.
+        Needs -r/--replace (or --replace-command). Nothing is written to disk.
+
+    --verify
+        After the replacements are written, search the files again for the replacement
+        text (with rg), and report any files where it was found fewer times than expected.
+
+    --stats
+        After the replacements are written, print how many files were changed, how many
+        replacements were made and skipped, and how long it took (to stderr).
+
+    -r, --replace <TEXT>
+        When stdout isn't a terminal (e.g. it's piped or redirected) the interface can't be
+        started, so instead every match is replaced with TEXT. Otherwise, the replacement is
+        pre-filled with TEXT when the REPLACE mode is entered. This is not passed through to rg.
+
+    --replace-from-file <PATH>
+        Like -r/--replace, but the replacement text is the contents of the file at PATH (as-is,
+        including any trailing newline). Useful for long or multi-line replacements, which
+        the interface's replacement is pre-filled with too. Can't be used together with
+        -r/--replace.
+
+    --max-replacements-per-file <N>
+        Only replace the first N selected matches (in the order they appear) in each file.
+        Any other matches are left as they are, and reported once the replacements are written.
+
+    --confirm-threshold <N>
+        When the selected matches are in more than N files (100 by default), "yes" has to be
+        typed to confirm writing the replacements, to prevent accidentally changing many files.
+
+    -q, --quiet
+        Don't print informational messages to stderr, such as "Cancelled" when quitting, or
+        where the logs are when something fails. Errors are still printed. This is not passed
+        through to rg (which would stop it from printing any results).
+
+    --count-start <N>, --count-step <N>
+        $COUNT (or ${{COUNT}}) in the replacement text is replaced with the number of each match
+        in its file, in the order they appear: e.g. -r 'item_$COUNT' gives item_1, item_2 and so
+        on. These set what the first match is numbered (1 by default) and how much each following
+        match adds (1 by default, and may be negative).
+
+    --capture-from-line
+        Match the pattern against the whole line of each match when expanding capturing groups
+        (e.g. $1) in the replacement, rather than just the matched text. This lets groups capture
+        the text around a match. Only the matched text is replaced.
+
+    --replace-empty-matches
+        Insert the replacement text at zero-length matches (e.g. of ^ or \b), rather than
+        skipping them. By default they're skipped, since there's nothing to replace.
+
+    --session <PATH>
+        Save the state of the interface (which matches are selected, the filter, the sort order
+        and how whitespace is shown) to PATH when {bin} exits, and restore it from there the
+        next time the same search is run.
+
+    -v, --version [--verbose]
+        Print the version of {bin}. With --verbose, also print the version of rg (from rg --version),
+        which is useful to include in bug reports.
 "#,
             env_file = ENV_JSON_FILE,
-            bin = env!("CARGO_BIN_NAME"),
+            json_stdin = FLAG_JSON_STDIN,
+            bin = BIN_NAME,
             crate_name = env!("CARGO_PKG_NAME"),
             crate_version = env!("CARGO_PKG_VERSION"),
             crate_homepage = env!("CARGO_PKG_HOMEPAGE"),
@@ -65,6 +251,348 @@ EXAMPLES:
     );
 }
 
+/// Options which configure `rgr` itself, rather than being forwarded to `rg`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RgrOptions {
+    /// How long lines should be rendered in the matches list.
+    pub wrap_mode: WrapMode,
+    /// How many columns apart the tab stops are in the matches list.
+    pub tab_width: Option<usize>,
+    /// The order in which files are displayed in the matches list.
+    pub sort_mode: SortMode,
+    /// A shell command used to compute each replacement, instead of the user's replacement text.
+    pub replace_command: Option<String>,
+    /// A shell command which is run with the paths of the changed files after the replacements are written.
+    pub after_replace_command: Option<String>,
+    /// Write a patch of the replacements to this path, rather than writing them to the files.
+    pub output_patch: Option<String>,
+    /// Print the number of matched lines in each file instead of starting the interface.
+    pub count: bool,
+    /// Print each match as `path:line:column:text` instead of starting the interface.
+    pub vimgrep: bool,
+    /// The most matches that are replaced in each file.
+    pub max_replacements_per_file: Option<usize>,
+    /// How many files may be changed before "yes" has to be typed to confirm the replacements.
+    pub confirm_threshold: Option<usize>,
+    /// Use the encoding passed to `rg` even for files with a BOM for a different encoding.
+    pub encoding_override: bool,
+    /// Search the files for the replacement text after the replacements are written.
+    pub verify: bool,
+    /// Print how many matches were replaced and skipped after the replacements are written.
+    pub stats: bool,
+    /// Start by choosing which files to show the matches of.
+    pub files_with_matches: bool,
+    /// Skip files which are symlinks, rather than writing the files they link to.
+    pub skip_symlinks: bool,
+    /// Write files in place rather than replacing them with a temporary file (`--atomic=false`).
+    pub in_place: bool,
+    /// Print each match and its replacement as JSON instead of starting the interface.
+    pub json_preview: bool,
+    /// Replace matches in files which ripgrep found binary data in, rather than skipping them.
+    pub allow_binary: bool,
+    /// Convert the line endings of each file that's written to these.
+    pub normalize_eol: Option<LineEnding>,
+    /// What `$COUNT` is for the first match in each file.
+    pub count_start: Option<i64>,
+    /// How much `$COUNT` is incremented by for each following match.
+    pub count_step: Option<i64>,
+    /// Don't print informational messages to stderr (such as "Cancelled"), only errors.
+    pub quiet: bool,
+    /// Match the capture pattern against the whole line of each match, rather than just the matched text.
+    pub capture_from_line: bool,
+    /// Insert the replacement at zero-length matches, rather than skipping them.
+    pub replace_empty_matches: bool,
+    /// Save the state of the interface to this path on exit, and restore it from there for the same search.
+    pub session: Option<String>,
+    /// Print the arguments that would be passed to ripgrep and exit, rather than running it (hidden, for debugging).
+    pub dump_args: bool,
+}
+
+impl RgrOptions {
+    /// Attempts to parse a long flag as an `rgr` option. Returns `false` if the flag isn't one of ours.
+    fn parse_long(&mut self, name: &str, parser: &mut Parser) -> Result<bool> {
+        match name {
+            "wrap-indent" => self.wrap_mode = WrapMode::WrapIndent,
+            "no-wrap" => self.wrap_mode = WrapMode::NoWrap,
+            "tab-width" => {
+                self.tab_width = match parser.value()?.parse()? {
+                    0 => bail!("--tab-width must be at least 1"),
+                    n => Some(n),
+                }
+            }
+            "replace-command" => self.replace_command = Some(parser.value()?.string()?),
+            "output-patch" => self.output_patch = Some(parser.value()?.string()?),
+            "after-replace-command" => self.after_replace_command = Some(parser.value()?.string()?),
+            "sort-display" => self.sort_mode = parser.value()?.string()?.parse()?,
+            "count" => self.count = true,
+            "vimgrep" => self.vimgrep = true,
+            "encoding-override" => self.encoding_override = true,
+            "verify" => self.verify = true,
+            "stats" => self.stats = true,
+            "files-with-matches" => self.files_with_matches = true,
+            "skip-symlinks" => self.skip_symlinks = true,
+            "json-preview" => self.json_preview = true,
+            "allow-binary" => self.allow_binary = true,
+            "normalize-eol" => self.normalize_eol = Some(parser.value()?.string()?.parse()?),
+            "atomic" => {
+                self.in_place = match parser.optional_value() {
+                    None => false,
+                    Some(value) => match value.string()?.as_str() {
+                        "true" => false,
+                        "false" => true,
+                        other => bail!(
+                            "invalid value \"{}\" for --atomic, expected one of: true, false",
+                            other
+                        ),
+                    },
+                }
+            }
+            "max-replacements-per-file" => {
+                self.max_replacements_per_file = Some(parser.value()?.parse()?)
+            }
+            "confirm-threshold" => self.confirm_threshold = Some(parser.value()?.parse()?),
+            "count-start" => self.count_start = Some(parser.value()?.parse()?),
+            "count-step" => self.count_step = Some(parser.value()?.parse()?),
+            "quiet" => self.quiet = true,
+            "capture-from-line" => self.capture_from_line = true,
+            "replace-empty-matches" => self.replace_empty_matches = true,
+            "dump-args" => self.dump_args = true,
+            "session" => self.session = Some(parser.value()?.string()?),
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
+
+/// Where `rg`'s JSON output is read from when `rgr` doesn't run `rg` itself.
+#[derive(Debug, PartialEq, Eq)]
+pub enum JsonSource {
+    /// The file in `ENV_JSON_FILE`.
+    File(OsString),
+    /// Piped to `rgr` with `FLAG_JSON_STDIN`.
+    Stdin,
+}
+
+/// Returns where `rg`'s JSON output should be read from, if it was given to `rgr` rather than `rgr` running
+/// `rg` itself. In either case the arguments are parsed with `RgArgs::parse_pattern`.
+pub fn json_source() -> Option<JsonSource> {
+    json_source_impl(std::env::var_os(ENV_JSON_FILE), std::env::args_os().skip(1))
+}
+
+fn json_source_impl(
+    env_file: Option<OsString>,
+    args: impl IntoIterator<Item = OsString>,
+) -> Option<JsonSource> {
+    if let Some(path) = env_file {
+        return Some(JsonSource::File(path));
+    }
+
+    // anything after `--` is a pattern, not a flag
+    args.into_iter()
+        .take_while(|arg| arg != "--")
+        .any(|arg| arg == FLAG_JSON_STDIN)
+        .then_some(JsonSource::Stdin)
+}
+
+/// How `rgr` should run once it has the results from `rg`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RunMode<'a> {
+    /// Start the interface.
+    Interactive,
+    /// Replace every match with the given text, without starting the interface.
+    ReplaceAll(&'a str),
+    /// The interface can't be started, and there's nothing else to do.
+    NoTerminal,
+}
+
+/// Decides how `rgr` should run. The interface needs a terminal, so if stdout isn't one (e.g. it's piped or
+/// redirected) then every match is replaced with the `-r/--replace` text instead, if it was passed.
+pub fn run_mode(stdout_is_terminal: bool, replacement: Option<&str>) -> RunMode<'_> {
+    match (stdout_is_terminal, replacement) {
+        (true, _) => RunMode::Interactive,
+        (false, Some(replacement)) => RunMode::ReplaceAll(replacement),
+        (false, None) => RunMode::NoTerminal,
+    }
+}
+
+/// The messages `rgr` may print to stderr as it exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitMessage {
+    /// The interface was quit without replacing anything.
+    Cancelled,
+    /// Some of the replacements couldn't be written.
+    Partial,
+    /// `rgr` failed.
+    Error,
+}
+
+/// What's printed to stderr along with an `ExitMessage`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExitOutput {
+    /// Whether the message itself is printed.
+    pub message: bool,
+    /// Whether where the logs are is printed after the message.
+    pub log_path: bool,
+}
+
+/// Decides what's printed to stderr as `rgr` exits. With `--quiet` only failures are printed, and not where
+/// the logs are. The logs are only mentioned for failures, and when errors are logged (`logging`).
+pub fn exit_output(message: ExitMessage, quiet: bool, logging: bool) -> ExitOutput {
+    let is_failure = message != ExitMessage::Cancelled;
+    ExitOutput {
+        message: is_failure || !quiet,
+        log_path: is_failure && logging && !quiet,
+    }
+}
+
+/// Reads the replacement text from the file passed with `--replace-from-file`. The contents are used as-is, so
+/// any trailing newline is kept.
+fn read_replacement_file(path: OsString) -> Result<String> {
+    let bytes = fs::read(&path)
+        .with_context(|| format!("Failed to read --replace-from-file {:?}", path))?;
+    String::from_utf8(bytes)
+        .with_context(|| format!("--replace-from-file {:?} isn't valid UTF-8", path))
+}
+
+/// Picks the replacement text from either `-r/--replace` or `--replace-from-file`, which can't both be passed.
+fn pick_replacement(
+    replacement: Option<String>,
+    replacement_file: Option<String>,
+) -> Result<Option<String>> {
+    match (replacement, replacement_file) {
+        (Some(_), Some(_)) => bail!("--replace and --replace-from-file can't be used together"),
+        (replacement, replacement_file) => Ok(replacement.or(replacement_file)),
+    }
+}
+
+/// How ripgrep matches the case of its patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    /// `-s/--case-sensitive`
+    Sensitive,
+    /// `-i/--ignore-case`
+    Insensitive,
+    /// `-S/--smart-case`: case insensitive, unless the pattern contains an uppercase character.
+    Smart,
+}
+
+/// Which regex engine ripgrep uses (`--engine`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    /// ripgrep's default engine, which is the same as the one `rgr` uses.
+    Default,
+    /// `-P/--pcre2`: supports features such as look-around and backreferences, which `rgr`'s engine doesn't.
+    Pcre2,
+    /// The default engine, unless a pattern needs features only PCRE2 supports.
+    Auto,
+}
+
+impl Engine {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Engine::Default => "default",
+            Engine::Pcre2 => "pcre2",
+            Engine::Auto => "auto",
+        }
+    }
+}
+
+/// Which of ripgrep's filters on the files it searches were turned off (e.g. with `--no-ignore` or `-u`), and how
+/// deep it searched. The flags are still forwarded to ripgrep, this is only used to show how far the search reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchScope {
+    /// `--no-ignore` (or `-u`): no ignore files (such as `.gitignore` or `.ignore`) are respected.
+    pub no_ignore: bool,
+    /// `--no-ignore-vcs`: only version control ignore files (such as `.gitignore`) aren't respected.
+    pub no_ignore_vcs: bool,
+    /// `-./--hidden` (or `-uu`): hidden files and directories are searched.
+    pub hidden: bool,
+    /// `--binary` (or `-uuu`): binary files are searched.
+    pub binary: bool,
+    /// `-d/--max-depth`: directories are only descended into this many levels below the paths that were searched.
+    pub max_depth: Option<usize>,
+}
+
+impl SearchScope {
+    /// Each `-u/--unrestricted` turns off another filter: first ignore files, then hidden files, then binary files.
+    fn unrestrict(&mut self, count: usize) {
+        match count {
+            1 => self.no_ignore = true,
+            2 => self.hidden = true,
+            _ => self.binary = true,
+        }
+    }
+
+    /// A short description of the filters which were turned off and the depth limit (e.g. `no-ignore, hidden,
+    /// max-depth=2`), or `None` if ripgrep searched the files it does by default.
+    pub fn indicator(&self) -> Option<String> {
+        let mut filters = [
+            (self.no_ignore, "no-ignore"),
+            (self.no_ignore_vcs && !self.no_ignore, "no-ignore-vcs"),
+            (self.hidden, "hidden"),
+            (self.binary, "binary"),
+        ]
+        .iter()
+        .filter(|(off, _)| *off)
+        .map(|(_, name)| name.to_string())
+        .collect::<Vec<_>>();
+        filters.extend(self.max_depth.map(|depth| format!("max-depth={}", depth)));
+
+        (!filters.is_empty()).then(|| filters.join(", "))
+    }
+}
+
+/// When `rgr` uses colors in what it prints to stdout (`--color`), such as counts and reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    Never,
+    Always,
+    /// Only when stdout is a terminal.
+    #[default]
+    Auto,
+}
+
+impl ColorChoice {
+    fn parse(value: &str) -> Result<ColorChoice> {
+        Ok(match value {
+            "never" => ColorChoice::Never,
+            "always" => ColorChoice::Always,
+            "auto" => ColorChoice::Auto,
+            other => bail!(
+                "invalid color choice \"{}\", expected one of: never, always, auto",
+                other
+            ),
+        })
+    }
+
+    /// Whether colors should be used, given whether stdout is a terminal.
+    pub fn use_color(self, stdout_is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Never => false,
+            ColorChoice::Always => true,
+            ColorChoice::Auto => stdout_is_terminal,
+        }
+    }
+}
+
+/// Whether a pattern contains an uppercase character, ignoring escape sequences (such as `\S`) like ripgrep
+/// does for `--smart-case`.
+fn has_uppercase_literal(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                chars.next();
+            }
+            ch if ch.is_uppercase() => return true,
+            _ => {}
+        }
+    }
+
+    false
+}
+
 #[derive(Debug, PartialEq, Eq)]
 enum ExecStyle {
     Normal,
@@ -80,10 +608,49 @@ pub struct RgArgs {
     pub encoding: Option<String>,
     /// Whether fixed strings was enabled - means we only need to substring search rather than
     /// regular expression searching.
-    /// TODO: this is currently unused, we need to update `replace.rs` to use it
     pub fixed_strings: bool,
+    /// Whether leading whitespace should be trimmed from each line. ripgrep ignores this when
+    /// printing JSON, so we need to trim lines ourselves when rendering the TUI.
+    pub trim: bool,
+    /// How the case of the patterns was matched (`-i`, `-s` or `-S`), if it was passed.
+    pub case_mode: Option<CaseMode>,
+    /// Whether matches may span multiple lines (`-U/--multiline`).
+    pub multiline: bool,
+    /// Whether the patterns only match whole lines (`-x/--line-regexp`). ripgrep's match is then the whole
+    /// line, so the patterns are anchored when they're compiled in `build_regex`.
+    pub line_regexp: bool,
+    /// Whether `.` also matches newlines in multiline mode (`--multiline-dotall`).
+    pub multiline_dotall: bool,
+    /// The regex engine ripgrep should use (`--engine` or `-P/--pcre2`), if it was passed.
+    pub engine: Option<Engine>,
+    /// Any preprocessor command that was passed (`--pre`). ripgrep searches the preprocessor's output
+    /// rather than the files themselves, so the matches can't be replaced in the files.
+    pub preprocessor: Option<String>,
+    /// The replacement text passed with `-r/--replace`, which is used to replace every match when the
+    /// interface can't be started (see `run_mode`). It isn't forwarded to ripgrep.
+    pub replacement: Option<String>,
+    /// When to use colors in what's printed to stdout (`--color`). ripgrep's own output is JSON, so it isn't
+    /// forwarded to ripgrep.
+    pub color: ColorChoice,
+    /// How many threads to parse the results with when they're read from a file (`-j/--threads`, see
+    /// `ENV_JSON_FILE`). Otherwise ripgrep is run, and the flag is forwarded to it in `other_args`.
+    pub threads: Option<usize>,
+    /// Whether ripgrep prints every line of the files, not just the matches (`--passthru`). The lines which
+    /// don't match are printed as context, which is dimmed in the interface.
+    pub passthru: bool,
+    /// The longest line ripgrep prints in full (`-M/--max-columns`), if there's a limit. Longer lines may be
+    /// truncated, so their matches aren't replaced. This is still forwarded to ripgrep in `other_args`.
+    pub max_columns: Option<u64>,
+    /// Which of ripgrep's filters on the files it searches were turned off. These flags are forwarded to
+    /// ripgrep along with `other_args`.
+    pub search_scope: SearchScope,
+    /// The paths ripgrep was told to search (its positional arguments other than the pattern). These are
+    /// still forwarded to ripgrep in `other_args`.
+    pub paths: Vec<String>,
     /// All other args that were passed will be forwarded to ripgrep.
     pub other_args: Vec<String>,
+    /// Options specific to `rgr`.
+    pub options: RgrOptions,
 
     exec_style: ExecStyle,
 }
@@ -96,14 +663,128 @@ impl RgArgs {
         }
     }
 
+    /// The arguments passed to ripgrep (see `rg_args`) for `--dump-args`: one per line, quoted so that any
+    /// whitespace or empty arguments are visible. `rgr` always adds `--json` (and disables binary output) too.
+    pub fn dump_args(&self) -> String {
+        self.rg_args()
+            .iter()
+            .map(|arg| format!("{:?}", arg))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Compiles a pattern with the same flags that were passed to ripgrep, so that it matches in the same
+    /// way (e.g. when expanding capturing groups).
+    pub fn build_regex(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        let case_insensitive = match self.case_mode {
+            Some(CaseMode::Insensitive) => true,
+            Some(CaseMode::Smart) => !has_uppercase_literal(pattern),
+            Some(CaseMode::Sensitive) | None => false,
+        };
+
+        // anchor the pattern like ripgrep does, otherwise it may capture a different part of the line
+        let pattern = if self.line_regexp {
+            format!("^(?:{})$", pattern)
+        } else {
+            pattern.to_owned()
+        };
+
+        RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .multi_line(self.multiline)
+            .dot_matches_new_line(self.multiline && self.multiline_dotall)
+            .build()
+    }
+
+    /// Compiles all the patterns (see `build_regex`). If ripgrep matched them with PCRE2 then they may not
+    /// compile (or may match differently), so none are returned and capturing groups aren't expanded.
+    pub fn build_patterns(&self) -> Result<Vec<Regex>, regex::Error> {
+        if self.uses_pcre2() {
+            return Ok(vec![]);
+        }
+
+        self.patterns.iter().map(|p| self.build_regex(p)).collect()
+    }
+
+    /// Compiles the patterns used to expand capturing groups in the replacement. When the results were read from a
+    /// file (see `ENV_JSON_FILE`) many patterns are combined into a single alternation, so the groups of any of
+    /// them may be used. Groups are numbered across the whole alternation, as if it were one pattern.
+    ///
+    /// With `--fixed-strings` none are returned, since the patterns are literal text rather than regexes, so
+    /// references like `$1` in the replacement are kept as they are.
+    pub fn build_capture_patterns(&self) -> Result<Vec<Regex>, regex::Error> {
+        if self.fixed_strings {
+            return Ok(vec![]);
+        }
+
+        match self.exec_style {
+            ExecStyle::Json if self.patterns.len() > 1 => {
+                let alternation = self
+                    .patterns
+                    .iter()
+                    .map(|p| format!("(?:{})", p))
+                    .collect::<Vec<_>>()
+                    .join("|");
+                Ok(vec![self.build_regex(&alternation)?])
+            }
+            _ => self.build_patterns(),
+        }
+    }
+
+    /// Whether ripgrep matched the patterns with PCRE2 rather than the same engine `rgr` uses. With
+    /// `--engine=auto` ripgrep only uses PCRE2 if a pattern fails to compile with the default engine.
+    pub fn uses_pcre2(&self) -> bool {
+        match self.engine {
+            Some(Engine::Pcre2) => true,
+            Some(Engine::Auto) => self.patterns.iter().any(|p| self.build_regex(p).is_err()),
+            Some(Engine::Default) | None => false,
+        }
+    }
+
+    /// Trims the leading whitespace of each line with `--trim`, since ripgrep doesn't trim lines in its JSON output.
+    pub fn trim_messages(&self, rg_messages: &mut [RgMessage]) {
+        if self.trim {
+            rg_messages
+                .iter_mut()
+                .for_each(RgMessage::trim_leading_whitespace);
+        }
+    }
+
     pub fn rg_args(&self) -> Vec<String> {
         let mut args = self.other_args.clone();
         if self.fixed_strings {
             args.push("--fixed-strings".into());
         }
+        if self.trim {
+            args.push("--trim".into());
+        }
         if let Some(encoding) = &self.encoding {
             args.push(format!("--encoding={}", encoding));
         }
+        if let Some(preprocessor) = &self.preprocessor {
+            args.push(format!("--pre={}", preprocessor));
+        }
+        match self.case_mode {
+            Some(CaseMode::Sensitive) => args.push("--case-sensitive".into()),
+            Some(CaseMode::Insensitive) => args.push("--ignore-case".into()),
+            Some(CaseMode::Smart) => args.push("--smart-case".into()),
+            None => {}
+        }
+        if self.multiline {
+            args.push("--multiline".into());
+        }
+        if self.line_regexp {
+            args.push("--line-regexp".into());
+        }
+        if self.multiline_dotall {
+            args.push("--multiline-dotall".into());
+        }
+        if self.passthru {
+            args.push("--passthru".into());
+        }
+        if let Some(engine) = self.engine {
+            args.push(format!("--engine={}", engine.as_str()));
+        }
         for pattern in &self.patterns {
             args.push(format!("--regexp={}", pattern));
         }
@@ -111,6 +792,26 @@ impl RgArgs {
         args
     }
 
+    /// Whether ripgrep can be run again with these arguments (e.g. with a different number of context lines).
+    /// It can't be when its output was read from a file (see `ENV_JSON_FILE`).
+    pub fn can_rerun(&self) -> bool {
+        self.exec_style == ExecStyle::Normal
+    }
+
+    /// Sets how many lines of context ripgrep prints around each match (`--context`), replacing any
+    /// `-A/--after-context`, `-B/--before-context` or `-C/--context` that were passed, since the first two
+    /// would take precedence over it.
+    pub fn set_context(&mut self, lines: usize) {
+        // these are always forwarded with their values, see `parse_rg_args_impl`
+        self.other_args.retain(|arg| {
+            !matches!(
+                arg.split_once('='),
+                Some(("--after-context" | "--before-context" | "--context", _))
+            )
+        });
+        self.other_args.push(format!("--context={}", lines));
+    }
+
     pub fn parse_pattern() -> Result<RgArgs> {
         RgArgs::parse_pattern_impl(Parser::from_env())
     }
@@ -119,21 +820,69 @@ impl RgArgs {
         use lexopt::prelude::*;
 
         let mut patterns = vec![];
+        let mut pattern_positional = None;
+        let mut replacement = None;
+        let mut replacement_file = None;
+        let mut color = ColorChoice::default();
+        let mut threads = None;
+        let mut options = RgrOptions::default();
 
         while let Some(arg) = parser.next()? {
             match arg {
-                Value(pat) if patterns.is_empty() => patterns.push(pat.string()?),
+                Value(pat) if pattern_positional.is_none() => {
+                    pattern_positional = Some(pat.string()?)
+                }
+                Short('e') | Long("regexp") => patterns.push(parser.value()?.string()?),
+                Short('r') | Long("replace") => replacement = Some(parser.value()?.string()?),
+                Long("replace-from-file") => {
+                    replacement_file = Some(read_replacement_file(parser.value()?)?)
+                }
+                Long("color") => color = ColorChoice::parse(&parser.value()?.string()?)?,
+                Short('j') | Long("threads") => threads = Some(parser.value()?.parse()?),
+                // already handled by `json_source`
+                Long("json-stdin") => {}
+                Long(name) => {
+                    let name = name.to_string();
+                    if !options.parse_long(&name, &mut parser)? {
+                        bail!("{}\nSee --help for usage", Arg::Long(&name).unexpected())
+                    }
+                }
                 _ => {
                     bail!("{}\nSee --help for usage", arg.unexpected())
                 }
             }
         }
 
+        // there are no paths to search, so unlike ripgrep a positional argument is never a path
+        if let Some(pattern) = pattern_positional {
+            if !patterns.is_empty() {
+                bail!("a pattern can't be passed positionally when -e/--regexp is used\nSee --help for usage");
+            }
+            patterns.push(pattern);
+        }
+
+        let replacement = pick_replacement(replacement, replacement_file)?;
+
         Ok(RgArgs {
             patterns,
             encoding: None,
             fixed_strings: false,
+            trim: false,
+            preprocessor: None,
+            case_mode: None,
+            multiline: false,
+            line_regexp: false,
+            multiline_dotall: false,
+            engine: None,
+            replacement,
+            color,
+            threads,
+            passthru: false,
+            max_columns: None,
+            search_scope: SearchScope::default(),
+            paths: vec![],
             other_args: vec![],
+            options,
             exec_style: ExecStyle::Json,
         })
     }
@@ -152,7 +901,25 @@ impl RgArgs {
         let mut patterns: Vec<String> = vec![];
         let mut encoding: Option<String> = None;
         let mut fixed_strings = false;
+        let mut trim = false;
+        let mut preprocessor: Option<String> = None;
+        let mut case_mode: Option<CaseMode> = None;
+        let mut multiline = false;
+        let mut line_regexp = false;
+        let mut multiline_dotall = false;
+        let mut engine: Option<Engine> = None;
+        let mut replacement: Option<String> = None;
+        let mut replacement_file: Option<String> = None;
+        let mut color = ColorChoice::default();
+        let mut passthru = false;
+        let mut max_columns: Option<u64> = None;
+        let mut search_scope = SearchScope::default();
+        let mut unrestricted = 0;
+        let mut paths: Vec<String> = vec![];
         let mut other_args: Vec<String> = vec![];
+        let mut options = RgrOptions::default();
+        let mut print_version = false;
+        let mut verbose = false;
 
         // as per ripgrep's documentation:
         // > When -f/--file or -e/--regexp is used, then ripgrep treats all positional arguments as
@@ -192,6 +959,145 @@ impl RgArgs {
                 Long("no-fixed-strings") => {
                     fixed_strings = false;
                 }
+                Long("trim") => {
+                    trim = true;
+                }
+                Long("no-trim") => {
+                    trim = false;
+                }
+                Long("pre") => {
+                    preprocessor = Some(parser.value()?.string()?);
+                }
+                Long("no-pre") => {
+                    preprocessor = None;
+                }
+                Short('s') | Long("case-sensitive") => {
+                    case_mode = Some(CaseMode::Sensitive);
+                }
+                Short('i') | Long("ignore-case") => {
+                    case_mode = Some(CaseMode::Insensitive);
+                }
+                Short('S') | Long("smart-case") => {
+                    case_mode = Some(CaseMode::Smart);
+                }
+                Short('U') | Long("multiline") => {
+                    multiline = true;
+                }
+                Long("no-multiline") => {
+                    multiline = false;
+                }
+                Short('x') | Long("line-regexp") => {
+                    line_regexp = true;
+                }
+                Long("no-line-regexp") => {
+                    line_regexp = false;
+                }
+                Long("multiline-dotall") => {
+                    multiline_dotall = true;
+                }
+                Long("no-multiline-dotall") => {
+                    multiline_dotall = false;
+                }
+                Long("engine") => {
+                    engine = Some(match parser.value()?.string()?.as_str() {
+                        "default" => Engine::Default,
+                        "pcre2" => Engine::Pcre2,
+                        "auto" => Engine::Auto,
+                        other => bail!(
+                            "invalid engine \"{}\", expected one of: default, pcre2, auto",
+                            other
+                        ),
+                    });
+                }
+                Short('P') | Long("pcre2") => {
+                    engine = Some(Engine::Pcre2);
+                }
+                Long("no-pcre2") => {
+                    engine = Some(Engine::Default);
+                }
+                Short('r') | Long("replace") => {
+                    replacement = Some(parser.value()?.string()?);
+                }
+                Long("replace-from-file") => {
+                    replacement_file = Some(read_replacement_file(parser.value()?)?);
+                }
+                Long("color") => {
+                    color = ColorChoice::parse(&parser.value()?.string()?)?;
+                }
+                Short('l') => {
+                    options.files_with_matches = true;
+                }
+                Short('q') => {
+                    options.quiet = true;
+                }
+                Long("passthru" | "passthrough") => {
+                    passthru = true;
+                }
+                // still forwarded to ripgrep, but its matches may not be at the offsets in the files
+                Short('M') | Long("max-columns") => {
+                    let value: u64 = parser.value()?.parse()?;
+                    // as with ripgrep, a limit of 0 means there isn't one
+                    max_columns = Some(value).filter(|n| *n > 0);
+                    other_args.push(format!("--max-columns={}", value));
+                }
+
+                // ripgrep: filters on the files that are searched, which are still forwarded to ripgrep
+                Long("no-ignore") => {
+                    search_scope.no_ignore = true;
+                    other_args.push("--no-ignore".into());
+                }
+                Long("ignore") => {
+                    search_scope.no_ignore = false;
+                    other_args.push("--ignore".into());
+                }
+                Long("no-ignore-vcs") => {
+                    search_scope.no_ignore_vcs = true;
+                    other_args.push("--no-ignore-vcs".into());
+                }
+                Long("ignore-vcs") => {
+                    search_scope.no_ignore_vcs = false;
+                    other_args.push("--ignore-vcs".into());
+                }
+                Short('.') | Long("hidden") => {
+                    search_scope.hidden = true;
+                    other_args.push("--hidden".into());
+                }
+                Long("no-hidden") => {
+                    search_scope.hidden = false;
+                    other_args.push("--no-hidden".into());
+                }
+                Long("binary") => {
+                    search_scope.binary = true;
+                    other_args.push("--binary".into());
+                }
+                Long("no-binary") => {
+                    search_scope.binary = false;
+                    other_args.push("--no-binary".into());
+                }
+                Short('u') | Long("unrestricted") => {
+                    unrestricted += 1;
+                    search_scope.unrestrict(unrestricted);
+                    other_args.push("-u".into());
+                }
+                Short('d') | Long("max-depth" | "maxdepth") => {
+                    let value: usize = parser.value()?.parse()?;
+                    search_scope.max_depth = Some(value);
+                    other_args.push(format!("--max-depth={}", value));
+                }
+
+                // ripgrep: context lines, which are still forwarded to ripgrep (see `set_context`)
+                Short('A') | Long("after-context") => {
+                    let value: usize = parser.value()?.parse()?;
+                    other_args.push(format!("--after-context={}", value));
+                }
+                Short('B') | Long("before-context") => {
+                    let value: usize = parser.value()?.parse()?;
+                    other_args.push(format!("--before-context={}", value));
+                }
+                Short('C') | Long("context") => {
+                    let value: usize = parser.value()?.parse()?;
+                    other_args.push(format!("--context={}", value));
+                }
 
                 // capture help to display our help
                 // also important to capture these since they make `rg` not output JSON!
@@ -199,22 +1105,35 @@ impl RgArgs {
                     print_help();
                     process::exit(0);
                 }
+                // the version is printed once all arguments are parsed, since `--verbose` may follow it
                 Short('v') | Long("version") => {
-                    println!(
-                        "{crate_name} {crate_version}",
-                        crate_name = env!("CARGO_PKG_NAME"),
-                        crate_version = env!("CARGO_PKG_VERSION")
-                    );
-                    process::exit(0);
+                    print_version = true;
+                }
+                Long("verbose") => {
+                    verbose = true;
                 }
 
                 // ripgrep: all other arguments and flags
-                Short(ch) => other_args.push(format!("-{}", ch)),
+                Short(ch) => {
+                    let flag = format!("-{}", ch);
+                    // keep the value with its flag, so it isn't mistaken for the pattern (e.g. `-C 3 foo`)
+                    let value = if SHORT_OPTIONS_WITH_VALUES.contains(&flag.as_str()) {
+                        Some(parser.value()?.string()?)
+                    } else {
+                        None
+                    };
+                    other_args.push(flag);
+                    other_args.extend(value);
+                }
                 Long(name) => {
                     // at this point we don't know if the argument we're passing is a `--flag` or an
                     // `--option=something`. So, peek at the next argument (if any) and see if it
                     // starts with `-`.
                     let name = name.to_string();
+                    if options.parse_long(&name, &mut parser)? {
+                        continue;
+                    }
+
                     let next_is_flag = parser
                         .try_raw_args()
                         .map(|raw_args| {
@@ -235,12 +1154,28 @@ impl RgArgs {
                         other_args.push(format!("--{}={}", name, parser.value()?.string()?));
                     }
                 }
-                Value(other) => other_args.push(other.string()?),
+                Value(path) => {
+                    let path = path.string()?;
+                    paths.push(path.clone());
+                    other_args.push(path);
+                }
             }
         }
 
+        if print_version {
+            println!("{}", version_text(verbose.then(ripgrep_version)));
+            process::exit(0);
+        }
+        // `--verbose` is only ours when printing the version, otherwise it's left for ripgrep
+        if verbose {
+            other_args.push("--verbose".into());
+        }
+
+        let replacement = pick_replacement(replacement, replacement_file)?;
+
         if let Some(pattern) = pattern_positional {
             if positional_disabled {
+                paths.insert(0, pattern.clone());
                 other_args.push(pattern);
             } else {
                 patterns.push(pattern);
@@ -250,8 +1185,23 @@ impl RgArgs {
         Ok(RgArgs {
             patterns,
             fixed_strings,
+            trim,
+            preprocessor,
+            case_mode,
+            multiline,
+            line_regexp,
+            multiline_dotall,
+            engine,
+            replacement,
+            color,
+            threads: None,
+            passthru,
+            max_columns,
+            search_scope,
             encoding,
+            paths,
             other_args,
+            options,
             exec_style: ExecStyle::Normal,
         })
     }
@@ -278,6 +1228,35 @@ mod tests {
         assert_eq!(args.exec_style, ExecStyle::Json);
     }
 
+    #[test]
+    fn json_source() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+
+        assert_eq!(json_source_impl(None, args(&["foo", "src"])), None);
+        assert_eq!(
+            json_source_impl(None, args(&["--json-stdin", "(f)oo"])),
+            Some(JsonSource::Stdin)
+        );
+        assert_eq!(
+            json_source_impl(None, args(&["-e", "foo", "--json-stdin"])),
+            Some(JsonSource::Stdin)
+        );
+        // after `--` it's a pattern
+        assert_eq!(json_source_impl(None, args(&["--", "--json-stdin"])), None);
+        // the file takes precedence
+        assert_eq!(
+            json_source_impl(Some("rg.json".into()), args(&["--json-stdin"])),
+            Some(JsonSource::File("rg.json".into()))
+        );
+    }
+
+    #[test]
+    fn pattern_json_stdin() {
+        let args = parse_pattern!["--json-stdin", "(f)oo"];
+        assert_eq!(args.patterns, ["(f)oo"]);
+        assert_eq!(args.exec_style, ExecStyle::Json);
+    }
+
     #[test]
     fn pattern_one() {
         let args = parse_pattern!["pattern"];
@@ -290,12 +1269,72 @@ mod tests {
         parse_pattern!["pattern", "unexpected"];
     }
 
+    #[test]
+    fn pattern_regexp_many() {
+        let args = parse_pattern!["-e", "(foo)", "--regexp", "b(a)r"];
+        assert_eq!(args.patterns, ["(foo)", "b(a)r"]);
+
+        // a single pattern is compiled on its own
+        let args = parse_pattern!["-e", "(foo)"];
+        assert_eq!(args.build_capture_patterns().unwrap()[0].as_str(), "(foo)");
+    }
+
+    #[test]
+    fn pattern_regexp_many_captures() {
+        let args = parse_pattern!["-e", "f(o+)", "-e", "b(a)r"];
+        let patterns = args.build_capture_patterns().unwrap();
+        assert_eq!(patterns.len(), 1);
+
+        // the groups of either pattern may be used, and they're numbered across the whole alternation
+        let expand = |haystack: &[u8]| {
+            let mut dst = vec![];
+            patterns[0]
+                .captures(haystack)
+                .unwrap()
+                .expand(b"[$1$2]", &mut dst);
+            String::from_utf8(dst).unwrap()
+        };
+        assert_eq!(expand(b"a foo b"), "[oo]");
+        assert_eq!(expand(b"a bar b"), "[a]");
+    }
+
+    #[test]
+    fn pattern_threads() {
+        assert_eq!(parse_pattern!["pattern"].threads, None);
+        assert_eq!(parse_pattern!["-j", "4", "pattern"].threads, Some(4));
+        assert_eq!(parse_pattern!["--threads=1"].threads, Some(1));
+    }
+
+    #[test]
+    #[should_panic = "a pattern can't be passed positionally when -e/--regexp is used"]
+    fn pattern_regexp_and_positional() {
+        parse_pattern!["-e", "foo", "bar"];
+    }
+
     #[test]
     #[should_panic = "invalid option '--flag'"]
     fn pattern_flag() {
         parse_pattern!["pattern", "--flag"];
     }
 
+    #[test]
+    fn pattern_rgr_options() {
+        let args = parse_pattern!["--wrap-indent", "pattern"];
+        assert_eq!(args.patterns, ["pattern"]);
+        assert_eq!(args.options.wrap_mode, WrapMode::WrapIndent);
+
+        let args = parse_pattern!["--replace-command", "cat", "pattern"];
+        assert_eq!(args.patterns, ["pattern"]);
+        assert_eq!(args.options.replace_command.as_deref(), Some("cat"));
+
+        let args = parse_pattern!["--count"];
+        assert!(args.options.count);
+
+        let args = parse_pattern!["-r", "bar", "pattern"];
+        assert_eq!(args.patterns, ["pattern"]);
+        assert_eq!(args.replacement.as_deref(), Some("bar"));
+    }
+
     macro_rules! parse_rg {
         [$($arg:expr$(,)?)*] => {
             RgArgs::parse_rg_args_impl(Parser::from_iter(["rgr".to_string(), $($arg.into(),)*])).unwrap()
@@ -335,6 +1374,31 @@ mod tests {
         ];
         assert_eq!(args.patterns, ["e1", "e2", "e3"]);
         assert_eq!(args.other_args, ["another_positional", "positional"]);
+        // the paths keep the order they were passed in
+        assert_eq!(args.paths, ["positional", "another_positional"]);
+    }
+
+    #[test]
+    fn rg_paths() {
+        let args = parse_rg!["foo"];
+        assert!(args.paths.is_empty());
+
+        let args = parse_rg![
+            "foo",
+            "src",
+            "-C",
+            "2",
+            "--type=rust",
+            "tests",
+            "--",
+            "-dir"
+        ];
+        assert_eq!(args.patterns, ["foo"]);
+        assert_eq!(args.paths, ["src", "tests", "-dir"]);
+        assert_eq!(
+            args.other_args,
+            ["src", "--context=2", "--type=rust", "tests", "-dir"]
+        );
     }
 
     #[test]
@@ -374,6 +1438,635 @@ mod tests {
         assert!(!args.fixed_strings);
     }
 
+    #[test]
+    fn rg_fixed_strings_captures() {
+        // literal patterns have no capturing groups to expand, even if they look like they do
+        let args = parse_rg!["-F", "price: (\\d+)"];
+        assert!(args.fixed_strings);
+        assert!(args.build_capture_patterns().unwrap().is_empty());
+
+        // and they don't need to be valid regexes
+        let args = parse_rg!["--fixed-strings", "-e", "foo(", "-e", "(bar)"];
+        assert!(args.build_capture_patterns().unwrap().is_empty());
+
+        let args = parse_rg!["-F", "--no-fixed-strings", "(foo)"];
+        assert_eq!(args.build_capture_patterns().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rg_trim() {
+        let args = parse_rg!["--trim"];
+        assert!(args.trim);
+        assert_eq!(args.rg_args(), ["--trim"]);
+
+        let args = parse_rg!["--trim", "--no-trim"];
+        assert!(!args.trim);
+        assert!(args.rg_args().is_empty());
+    }
+
+    #[test]
+    fn rg_dump_args() {
+        let args = parse_rg!["--dump-args", "foo"];
+        assert!(args.options.dump_args);
+        assert_eq!(args.dump_args(), r#""--regexp=foo""#);
+
+        // options with values, flags, paths and rgr's own options (which aren't forwarded)
+        let args = parse_rg![
+            "-C",
+            "3",
+            "--glob=*.rs",
+            "--dump-args",
+            "-i",
+            "--no-wrap",
+            "foo bar",
+            "src",
+            "--hidden"
+        ];
+        assert_eq!(
+            args.dump_args(),
+            [
+                r#""--context=3""#,
+                r#""--glob=*.rs""#,
+                r#""src""#,
+                r#""--hidden""#,
+                r#""--ignore-case""#,
+                r#""--regexp=foo bar""#,
+            ]
+            .join("\n")
+        );
+
+        // special characters are escaped
+        let args = parse_rg!["-e", "a\tb\"c", "-e", "", "--dump-args"];
+        assert_eq!(
+            args.dump_args(),
+            r#""--regexp=a\tb\"c"
+"--regexp=""#
+        );
+
+        // nothing is forwarded
+        assert_eq!(parse_rg!["--dump-args"].dump_args(), "");
+    }
+
+    #[test]
+    fn rg_passthru() {
+        let args = parse_rg!["foo"];
+        assert!(!args.passthru);
+
+        let args = parse_rg!["--passthrough", "foo"];
+        assert!(args.passthru);
+        assert_eq!(args.rg_args(), ["--passthru", "--regexp=foo"]);
+    }
+
+    #[test]
+    fn rg_max_columns() {
+        let args = parse_rg!["foo"];
+        assert_eq!(args.max_columns, None);
+
+        let args = parse_rg!["-M", "80", "foo"];
+        assert_eq!(args.max_columns, Some(80));
+        assert_eq!(args.rg_args(), ["--max-columns=80", "--regexp=foo"]);
+
+        let args = parse_rg!["--max-columns=0", "foo"];
+        assert_eq!(args.max_columns, None);
+        assert_eq!(args.rg_args(), ["--max-columns=0", "--regexp=foo"]);
+
+        // the preview of truncated lines is forwarded as-is
+        let args = parse_rg!["foo", "--max-columns-preview"];
+        assert_eq!(args.max_columns, None);
+        assert_eq!(args.rg_args(), ["--max-columns-preview", "--regexp=foo"]);
+    }
+
+    #[test]
+    fn rg_threads() {
+        // it's forwarded to ripgrep when rgr runs it
+        let args = parse_rg!["--threads=4", "pattern"];
+        assert_eq!(args.threads, None);
+        assert!(args.rg_args().contains(&"--threads=4".to_string()));
+    }
+
+    #[test]
+    fn rg_case_mode() {
+        let args = parse_rg!["(foo)"];
+        assert_eq!(args.case_mode, None);
+        assert!(args
+            .build_regex("(foo)")
+            .unwrap()
+            .captures(b"FOO")
+            .is_none());
+
+        let args = parse_rg!["-i", "(foo)"];
+        assert_eq!(args.case_mode, Some(CaseMode::Insensitive));
+        assert_eq!(args.rg_args(), ["--ignore-case", "--regexp=(foo)"]);
+        let captures = args.build_patterns().unwrap()[0]
+            .captures(b"a FoO b")
+            .unwrap();
+        assert_eq!(&captures[1], b"FoO");
+
+        // the last one wins, like in ripgrep
+        let args = parse_rg!["--ignore-case", "-s", "foo"];
+        assert_eq!(args.case_mode, Some(CaseMode::Sensitive));
+        assert_eq!(args.rg_args(), ["--case-sensitive", "--regexp=foo"]);
+        assert!(!args.build_regex("foo").unwrap().is_match(b"FOO"));
+    }
+
+    #[test]
+    fn rg_smart_case() {
+        let args = parse_rg!["--smart-case", "foo"];
+        assert_eq!(args.rg_args(), ["--smart-case", "--regexp=foo"]);
+        assert!(args.build_regex("(foo)").unwrap().is_match(b"FOO"));
+        assert!(args.build_regex(r"\Sfoo").unwrap().is_match(b"_FOO"));
+        assert!(!args.build_regex("(Foo)").unwrap().is_match(b"FOO"));
+    }
+
+    #[test]
+    fn rg_inline_flags() {
+        // inline flags take precedence over the case mode, like they do in ripgrep
+        let args = parse_rg!["(?i)(foo)"];
+        let captures = args.build_patterns().unwrap()[0]
+            .captures(b"a FoO b")
+            .unwrap();
+        assert_eq!(&captures[1], b"FoO");
+
+        let args = parse_rg!["-s", "foo"];
+        assert!(args.build_regex("(?i)foo").unwrap().is_match(b"FOO"));
+        assert!(args.build_regex("f(?i:oo)").unwrap().is_match(b"fOO"));
+        assert!(!args.build_regex("f(?i:oo)").unwrap().is_match(b"FOO"));
+
+        let args = parse_rg!["-i", "foo"];
+        assert!(!args.build_regex("(?-i)foo").unwrap().is_match(b"FOO"));
+
+        // the uppercase literal makes smart case sensitive, but the inline flag still ignores case
+        let args = parse_rg!["-S", "foo"];
+        assert!(args.build_regex("(?i)Foo").unwrap().is_match(b"fOO"));
+    }
+
+    #[test]
+    fn rg_line_regexp() {
+        // the first alternative would match at the start of the line, if the pattern wasn't anchored
+        let args = parse_rg![r"(\w+)|(\w+) (\w+)"];
+        assert!(!args.line_regexp);
+        let captures = args.build_patterns().unwrap()[0]
+            .captures(b"foo bar")
+            .unwrap();
+        assert_eq!(&captures[0], b"foo");
+
+        let args = parse_rg!["-x", r"(\w+)|(\w+) (\w+)"];
+        assert!(args.line_regexp);
+        assert_eq!(
+            args.rg_args(),
+            ["--line-regexp", r"--regexp=(\w+)|(\w+) (\w+)"]
+        );
+        let captures = args.build_patterns().unwrap()[0]
+            .captures(b"foo bar")
+            .unwrap();
+        assert_eq!(&captures[0], b"foo bar");
+        assert!(captures.get(1).is_none());
+        assert_eq!(&captures[3], b"bar");
+
+        // inline flags and smart case still apply to the anchored pattern
+        let args = parse_rg!["--line-regexp", "--smart-case", "(?i)fo+"];
+        assert!(args.build_regex("(?i)fo+").unwrap().is_match(b"FOO"));
+        assert!(!args.build_regex("(?i)fo+").unwrap().is_match(b"FOO BAR"));
+
+        let args = parse_rg!["-x", "--no-line-regexp", "foo"];
+        assert!(!args.line_regexp);
+        assert!(args.rg_args().iter().all(|arg| arg != "--line-regexp"));
+    }
+
+    #[test]
+    fn rg_multiline() {
+        let args = parse_rg!["foo"];
+        assert!(!args.build_regex("^foo").unwrap().is_match(b"bar\nfoo"));
+
+        let args = parse_rg!["-U", "foo"];
+        assert!(args.multiline);
+        assert_eq!(args.rg_args(), ["--multiline", "--regexp=foo"]);
+        assert!(args.build_regex("^foo").unwrap().is_match(b"bar\nfoo"));
+        assert!(!args.build_regex("r.f").unwrap().is_match(b"bar\nfoo"));
+
+        let args = parse_rg!["--multiline", "--multiline-dotall", "foo"];
+        assert_eq!(
+            args.rg_args(),
+            ["--multiline", "--multiline-dotall", "--regexp=foo"]
+        );
+        assert!(args.build_regex("r.f").unwrap().is_match(b"bar\nfoo"));
+
+        let args = parse_rg!["-U", "--no-multiline", "foo"];
+        assert!(!args.multiline);
+    }
+
+    #[test]
+    fn version() {
+        let version = format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        assert_eq!(version_text(None), version);
+        assert_eq!(
+            version_text(Some(Ok("ripgrep 14.1.0".into()))),
+            format!("{}\nrg: ripgrep 14.1.0", version)
+        );
+        assert_eq!(
+            version_text(Some(Err(anyhow::anyhow!("Failed to find `rg`!")))),
+            format!("{}\nrg: unavailable (Failed to find `rg`!)", version)
+        );
+    }
+
+    #[test]
+    fn rg_engine() {
+        let args = parse_rg!["foo"];
+        assert_eq!(args.engine, None);
+        assert!(!args.uses_pcre2());
+
+        let args = parse_rg!["--engine=pcre2", "(foo)"];
+        assert_eq!(args.engine, Some(Engine::Pcre2));
+        assert_eq!(args.rg_args(), ["--engine=pcre2", "--regexp=(foo)"]);
+        assert!(args.uses_pcre2());
+        // capturing groups aren't expanded, since the pattern was matched by PCRE2
+        assert!(args.build_patterns().unwrap().is_empty());
+
+        let args = parse_rg!["-P", "--engine", "default", "(foo)"];
+        assert_eq!(args.rg_args(), ["--engine=default", "--regexp=(foo)"]);
+        assert_eq!(args.build_patterns().unwrap().len(), 1);
+
+        let args = parse_rg!["-P", "foo"];
+        assert_eq!(args.rg_args(), ["--engine=pcre2", "--regexp=foo"]);
+        let args = parse_rg!["--pcre2", "--no-pcre2", "foo"];
+        assert!(!args.uses_pcre2());
+
+        // auto only uses PCRE2 for patterns the default engine can't compile
+        let args = parse_rg!["--engine=auto", "(foo)"];
+        assert_eq!(args.rg_args(), ["--engine=auto", "--regexp=(foo)"]);
+        assert!(!args.uses_pcre2());
+        assert_eq!(args.build_patterns().unwrap().len(), 1);
+        let args = parse_rg!["--engine=auto", "(foo)(?=bar)"];
+        assert!(args.uses_pcre2());
+        assert!(args.build_patterns().unwrap().is_empty());
+
+        assert!(
+            RgArgs::parse_rg_args_impl(Parser::from_iter(["rgr", "--engine=pcre", "foo"])).is_err()
+        );
+    }
+
+    #[test]
+    fn rg_describe_args() {
+        let args = parse_rg!["foo", "-g", "*.rs", "--glob", "!target", "-C", "2", "src"];
+        assert_eq!(
+            describe_rg_args(&args.other_args),
+            [
+                ("Globs", "*.rs, !target".into()),
+                ("Context", "2 line(s) before, 2 line(s) after".into()),
+                ("Paths", "src".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rg_preprocessor() {
+        let args = parse_rg![];
+        assert_eq!(args.preprocessor, None);
+
+        let args = parse_rg!["--pre", "gunzip"];
+        assert_eq!(args.preprocessor.as_deref(), Some("gunzip"));
+        assert_eq!(args.rg_args(), ["--pre=gunzip"]);
+
+        let args = parse_rg!["--pre=gunzip", "--pre-glob=*.gz"];
+        assert_eq!(args.preprocessor.as_deref(), Some("gunzip"));
+        assert_eq!(args.rg_args(), ["--pre-glob=*.gz", "--pre=gunzip"]);
+
+        let args = parse_rg!["--pre=gunzip", "--no-pre"];
+        assert_eq!(args.preprocessor, None);
+        assert!(args.rg_args().is_empty());
+    }
+
+    #[test]
+    fn rg_replacement() {
+        let args = parse_rg!["foo"];
+        assert_eq!(args.replacement, None);
+
+        let args = parse_rg!["foo", "-r", "bar"];
+        assert_eq!(args.replacement.as_deref(), Some("bar"));
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--replace=bar", "foo"];
+        assert_eq!(args.replacement.as_deref(), Some("bar"));
+        assert_eq!(args.patterns, ["foo"]);
+    }
+
+    #[test]
+    fn rg_replacement_from_file() {
+        use crate::model::ReplacementCriteria;
+        use crate::replace::perform_replacements;
+        use crate::rg::de::test_utilities::RgMessageBuilder;
+        use crate::rg::de::{RgMessageKind, SubMatch};
+
+        let replacement_file = temp_file!("bar\n  baz\n");
+        let args = parse_rg![
+            "foo",
+            format!("--replace-from-file={}", replacement_file.display())
+        ];
+        assert_eq!(args.replacement.as_deref(), Some("bar\n  baz\n"));
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        // the replacement is used verbatim
+        let p = temp_file!("a foo b\n");
+        let rg_messages = vec![RgMessageBuilder::new(RgMessageKind::Match)
+            .with_path_text(p.to_string_lossy())
+            .with_lines_text("a foo b\n")
+            .with_offset(0)
+            .with_submatches(vec![SubMatch::new_text("foo", 2..5)])
+            .build()];
+        let criteria =
+            ReplacementCriteria::from_rg_messages(rg_messages, None, args.replacement.unwrap())
+                .unwrap();
+        perform_replacements(criteria).unwrap();
+        assert_eq!(fs::read_to_string(&p).unwrap(), "a bar\n  baz\n b\n");
+
+        // it can't be passed with --replace
+        let from_file = format!("--replace-from-file={}", replacement_file.display());
+        assert!(RgArgs::parse_rg_args_impl(Parser::from_iter([
+            "rgr", "-r", "bar", &from_file, "foo"
+        ]))
+        .is_err());
+        assert!(RgArgs::parse_rg_args_impl(Parser::from_iter([
+            "rgr",
+            "--replace-from-file=/does/not/exist",
+            "foo"
+        ]))
+        .is_err());
+
+        let args = parse_pattern!["foo", from_file];
+        assert_eq!(args.replacement.as_deref(), Some("bar\n  baz\n"));
+    }
+
+    #[test]
+    fn rg_color() {
+        let args = parse_rg!["foo"];
+        assert_eq!(args.color, ColorChoice::Auto);
+
+        let args = parse_rg!["--color=never", "foo"];
+        assert_eq!(args.color, ColorChoice::Never);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--color", "always", "foo"];
+        assert_eq!(args.color, ColorChoice::Always);
+        let args = parse_pattern!["--color=always", "foo"];
+        assert_eq!(args.color, ColorChoice::Always);
+
+        assert!(
+            RgArgs::parse_rg_args_impl(Parser::from_iter(["rgr", "--color=red", "foo"])).is_err()
+        );
+    }
+
+    #[test]
+    fn rg_set_context() {
+        let mut args = parse_rg!["foo", "-A", "2", "--before-context=1", "-w", "src"];
+        assert!(args.can_rerun());
+        args.set_context(4);
+        assert_eq!(args.other_args, ["-w", "src", "--context=4"]);
+
+        // replaces the context that was already set
+        args.set_context(0);
+        assert_eq!(args.other_args, ["-w", "src", "--context=0"]);
+        assert_eq!(args.rg_args(), ["-w", "src", "--context=0", "--regexp=foo"]);
+
+        let mut args = parse_rg!["-e", "foo", "-C", "3", "--after-context=5", "src"];
+        args.set_context(1);
+        assert_eq!(args.other_args, ["src", "--context=1"]);
+
+        // the value of a short flag isn't mistaken for the pattern
+        let mut args = parse_rg!["-C", "3", "foo", "-m1", "src"];
+        assert_eq!(args.patterns, ["foo"]);
+        assert_eq!(args.other_args, ["--context=3", "-m", "1", "src"]);
+        args.set_context(2);
+        assert_eq!(args.other_args, ["-m", "1", "src", "--context=2"]);
+
+        // the value must be a number, so a following flag isn't taken as it (nor is the argument after that)
+        let mut args = parse_rg!["foo", "-A3", "--context", "1", "-B", "2", "src"];
+        assert_eq!(
+            args.other_args,
+            [
+                "--after-context=3",
+                "--context=1",
+                "--before-context=2",
+                "src"
+            ]
+        );
+        args.set_context(5);
+        assert_eq!(args.other_args, ["src", "--context=5"]);
+        for flag in ["-A", "-B", "-C"] {
+            assert!(RgArgs::parse_rg_args_impl(Parser::from_iter([
+                "rgr", "foo", flag, "-C", "src"
+            ]))
+            .is_err());
+        }
+
+        // the results were read from a file
+        assert!(!parse_pattern!["foo"].can_rerun());
+    }
+
+    #[test]
+    fn rg_search_scope() {
+        let args = parse_rg!["foo"];
+        assert_eq!(args.search_scope, SearchScope::default());
+        assert_eq!(args.search_scope.indicator(), None);
+
+        // the flags are still forwarded to ripgrep
+        let args = parse_rg!["--no-ignore", "-.", "foo", "src"];
+        assert_eq!(
+            args.search_scope,
+            SearchScope {
+                no_ignore: true,
+                hidden: true,
+                ..SearchScope::default()
+            }
+        );
+        assert_eq!(args.other_args, ["--no-ignore", "--hidden", "src"]);
+
+        let args = parse_rg!["--no-ignore", "--hidden", "--ignore", "--no-hidden", "foo"];
+        assert_eq!(args.search_scope, SearchScope::default());
+
+        let args = parse_rg!["-uu", "foo"];
+        assert_eq!(
+            args.search_scope,
+            SearchScope {
+                no_ignore: true,
+                hidden: true,
+                ..SearchScope::default()
+            }
+        );
+        assert_eq!(args.other_args, ["-u", "-u"]);
+
+        let args = parse_rg!["-u", "--unrestricted", "-u", "foo"];
+        assert_eq!(
+            args.search_scope.indicator().as_deref(),
+            Some("no-ignore, hidden, binary")
+        );
+
+        let args = parse_rg!["--no-ignore-vcs", "--binary", "foo"];
+        assert_eq!(
+            args.search_scope.indicator().as_deref(),
+            Some("no-ignore-vcs, binary")
+        );
+        let args = parse_rg![
+            "--no-ignore-vcs",
+            "--ignore-vcs",
+            "--binary",
+            "--no-binary",
+            "foo"
+        ];
+        assert_eq!(args.search_scope.indicator(), None);
+    }
+
+    #[test]
+    fn rg_max_depth() {
+        // each form is forwarded the same way, with its value
+        for max_depth in [
+            &["--max-depth", "3"][..],
+            &["--max-depth=3"],
+            &["--maxdepth", "3"],
+            &["-d", "3"],
+            &["-d3"],
+        ] {
+            let args = RgArgs::parse_rg_args_impl(Parser::from_iter(
+                ["rgr"].iter().chain(max_depth).chain(&["foo", "src"]),
+            ))
+            .unwrap();
+            assert_eq!(args.search_scope.max_depth, Some(3), "{:?}", max_depth);
+            assert_eq!(
+                args.rg_args(),
+                ["--max-depth=3", "src", "--regexp=foo"],
+                "{:?}",
+                max_depth
+            );
+        }
+
+        // the last one wins, as with ripgrep
+        let args = parse_rg!["--max-depth", "3", "-d", "1", "foo"];
+        assert_eq!(args.search_scope.max_depth, Some(1));
+        assert_eq!(
+            args.search_scope.indicator().as_deref(),
+            Some("max-depth=1")
+        );
+        assert_eq!(
+            args.rg_args(),
+            ["--max-depth=3", "--max-depth=1", "--regexp=foo"]
+        );
+
+        assert!(RgArgs::parse_rg_args_impl(Parser::from_iter([
+            "rgr",
+            "--max-depth",
+            "deep",
+            "foo"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn search_scope_indicators() {
+        for (scope, indicator) in [
+            (SearchScope::default(), None),
+            (
+                SearchScope {
+                    no_ignore: true,
+                    ..SearchScope::default()
+                },
+                Some("no-ignore"),
+            ),
+            (
+                SearchScope {
+                    no_ignore_vcs: true,
+                    hidden: true,
+                    ..SearchScope::default()
+                },
+                Some("no-ignore-vcs, hidden"),
+            ),
+            // `--no-ignore` already covers `--no-ignore-vcs`
+            (
+                SearchScope {
+                    no_ignore: true,
+                    no_ignore_vcs: true,
+                    hidden: true,
+                    binary: true,
+                    max_depth: None,
+                },
+                Some("no-ignore, hidden, binary"),
+            ),
+            (
+                SearchScope {
+                    hidden: true,
+                    max_depth: Some(2),
+                    ..SearchScope::default()
+                },
+                Some("hidden, max-depth=2"),
+            ),
+            (
+                SearchScope {
+                    max_depth: Some(0),
+                    ..SearchScope::default()
+                },
+                Some("max-depth=0"),
+            ),
+        ] {
+            assert_eq!(scope.indicator().as_deref(), indicator, "{:?}", scope);
+        }
+    }
+
+    #[test]
+    fn color_choices() {
+        for (choice, when_terminal, otherwise) in [
+            (ColorChoice::Never, false, false),
+            (ColorChoice::Always, true, true),
+            (ColorChoice::Auto, true, false),
+        ] {
+            assert_eq!(choice.use_color(true), when_terminal, "{:?}", choice);
+            assert_eq!(choice.use_color(false), otherwise, "{:?}", choice);
+        }
+    }
+
+    #[test]
+    fn run_modes() {
+        assert_eq!(run_mode(true, None), RunMode::Interactive);
+        assert_eq!(run_mode(true, Some("bar")), RunMode::Interactive);
+        assert_eq!(run_mode(false, Some("bar")), RunMode::ReplaceAll("bar"));
+        assert_eq!(run_mode(false, Some("")), RunMode::ReplaceAll(""));
+        assert_eq!(run_mode(false, None), RunMode::NoTerminal);
+    }
+
+    #[test]
+    fn exit_outputs() {
+        let output = |message: bool, log_path: bool| ExitOutput { message, log_path };
+
+        // the log path is only printed for failures, when errors are logged
+        assert_eq!(
+            exit_output(ExitMessage::Error, false, true),
+            output(true, true)
+        );
+        assert_eq!(
+            exit_output(ExitMessage::Error, false, false),
+            output(true, false)
+        );
+        assert_eq!(
+            exit_output(ExitMessage::Partial, false, true),
+            output(true, true)
+        );
+        assert_eq!(
+            exit_output(ExitMessage::Cancelled, false, true),
+            output(true, false)
+        );
+
+        // failures are still printed when quiet
+        assert_eq!(
+            exit_output(ExitMessage::Error, true, true),
+            output(true, false)
+        );
+        assert_eq!(
+            exit_output(ExitMessage::Partial, true, true),
+            output(true, false)
+        );
+        assert_eq!(
+            exit_output(ExitMessage::Cancelled, true, true),
+            output(false, false)
+        );
+    }
+
     #[test]
     fn rg_encoding() {
         let args = parse_rg![];
@@ -426,6 +2119,159 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rg_rgr_options() {
+        let args = parse_rg!["foo"];
+        assert_eq!(args.options, RgrOptions::default());
+
+        let args = parse_rg!["--wrap-indent", "foo"];
+        assert_eq!(args.options.wrap_mode, WrapMode::WrapIndent);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--no-wrap", "foo"];
+        assert_eq!(args.options.wrap_mode, WrapMode::NoWrap);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--tab-width", "4", "foo"];
+        assert_eq!(args.options.tab_width, Some(4));
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = RgArgs::parse_rg_args_impl(Parser::from_iter(["rgr", "--tab-width=0", "foo"]));
+        assert!(args.is_err());
+
+        let args = parse_rg!["--sort-display", "count", "foo"];
+        assert_eq!(args.options.sort_mode, SortMode::MatchCount);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args =
+            RgArgs::parse_rg_args_impl(Parser::from_iter(["rgr", "--sort-display=x", "foo"]));
+        assert!(args.is_err());
+
+        let args = parse_rg![
+            "--replace-command",
+            "tr a-z A-Z",
+            "foo",
+            "--replace-command=cat"
+        ];
+        assert_eq!(args.options.replace_command.as_deref(), Some("cat"));
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--after-replace-command", "cargo fmt --", "foo"];
+        assert_eq!(
+            args.options.after_replace_command.as_deref(),
+            Some("cargo fmt --")
+        );
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--output-patch", "out.patch", "foo"];
+        assert_eq!(args.options.output_patch.as_deref(), Some("out.patch"));
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--session", "rgr.session", "foo"];
+        assert_eq!(args.options.session.as_deref(), Some("rgr.session"));
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--count", "foo"];
+        assert!(args.options.count);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--vimgrep", "foo"];
+        assert!(args.options.vimgrep);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--encoding-override", "-E", "utf-8", "foo"];
+        assert!(args.options.encoding_override);
+        assert_eq!(args.rg_args(), ["--encoding=utf-8", "--regexp=foo"]);
+
+        let args = parse_rg!["-l", "foo"];
+        assert!(args.options.files_with_matches);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+        let args = parse_rg!["--files-with-matches", "foo"];
+        assert!(args.options.files_with_matches);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["-q", "foo"];
+        assert!(args.options.quiet);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+        let args = parse_rg!["--quiet", "foo"];
+        assert!(args.options.quiet);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+        assert!(parse_pattern!["--quiet"].options.quiet);
+
+        let args = parse_rg!["--skip-symlinks", "foo"];
+        assert!(args.options.skip_symlinks);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--allow-binary", "foo"];
+        assert!(args.options.allow_binary);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--normalize-eol=crlf", "foo"];
+        assert_eq!(args.options.normalize_eol, Some(LineEnding::Crlf));
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+        let args = parse_rg!["--normalize-eol", "lf", "foo"];
+        assert_eq!(args.options.normalize_eol, Some(LineEnding::Lf));
+        assert!(RgArgs::parse_rg_args_impl(Parser::from_iter([
+            "rgr",
+            "--normalize-eol=cr",
+            "foo"
+        ]))
+        .is_err());
+
+        let args = parse_rg!["--atomic=false", "foo"];
+        assert!(args.options.in_place);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+        let args = parse_rg!["--atomic=false", "--atomic", "foo"];
+        assert!(!args.options.in_place);
+        assert!(
+            RgArgs::parse_rg_args_impl(Parser::from_iter(["rgr", "--atomic=no", "foo"])).is_err()
+        );
+
+        let args = parse_rg!["--json-preview", "-r", "bar", "foo"];
+        assert!(args.options.json_preview);
+        assert_eq!(args.replacement.as_deref(), Some("bar"));
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--verify", "foo"];
+        assert!(args.options.verify);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--stats", "foo"];
+        assert!(args.options.stats);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--max-replacements-per-file", "2", "foo"];
+        assert_eq!(args.options.max_replacements_per_file, Some(2));
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = RgArgs::parse_rg_args_impl(Parser::from_iter([
+            "rgr",
+            "--max-replacements-per-file=x",
+            "foo",
+        ]));
+        assert!(args.is_err());
+
+        let args = parse_rg!["--confirm-threshold", "5", "foo"];
+        assert_eq!(args.options.confirm_threshold, Some(5));
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+        assert_eq!(parse_rg!["foo"].options.confirm_threshold, None);
+
+        let args = parse_rg!["--count-start=0", "--count-step", "-5", "foo"];
+        assert_eq!(args.options.count_start, Some(0));
+        assert_eq!(args.options.count_step, Some(-5));
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+        let args = parse_pattern!["--count-start", "10", "foo"];
+        assert_eq!(args.options.count_start, Some(10));
+
+        let args = parse_rg!["--capture-from-line", "foo"];
+        assert!(args.options.capture_from_line);
+        assert_eq!(args.rg_args(), ["--regexp=foo"]);
+
+        let args = parse_rg!["--replace-empty-matches", "^"];
+        assert!(args.options.replace_empty_matches);
+        assert_eq!(args.rg_args(), ["--regexp=^"]);
+    }
+
     #[test]
     fn rg_case1() {
         let args = parse_rg!["--sort", "path", "--sort=modified", "foo"];
@@ -433,6 +2279,11 @@ mod tests {
             args.rg_args(),
             ["--sort=path", "--sort=modified", "--regexp=foo"]
         );
+
+        // it's passed through to rg, and doesn't change the sort mode of the list
+        let args = parse_rg!["--sort=none", "foo"];
+        assert_eq!(args.rg_args(), ["--sort=none", "--regexp=foo"]);
+        assert_eq!(args.options.sort_mode, SortMode::Rg);
     }
 
     #[test]
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use crossterm::style::Stylize;
+
+use crate::rg::de::{RgMessage, RgMessageKind};
+use crate::ui::line::Item;
+
+/// Writes the number of matched lines in each file (in the order `rg` returned them) followed by
+/// the total, like `rg --count`. If `color` is set, the paths are colored like ripgrep colors them.
+pub fn write_counts<W: Write>(mut out: W, rg_messages: Vec<RgMessage>, color: bool) -> Result<()> {
+    let mut counts: Vec<(PathBuf, usize)> = vec![];
+    let mut positions: HashMap<PathBuf, usize> = HashMap::new();
+
+    for (i, rg_message) in rg_messages.into_iter().enumerate() {
+        let item = Item::new(i, rg_message);
+        if !matches!(item.kind, RgMessageKind::Match) {
+            continue;
+        }
+
+        if let Some(path) = item.path_buf() {
+            let pos = *positions.entry(path.clone()).or_insert_with(|| {
+                counts.push((path, 0));
+                counts.len() - 1
+            });
+
+            counts[pos].1 += 1;
+        }
+    }
+
+    for (path, count) in &counts {
+        let path = path.display().to_string();
+        if color {
+            writeln!(out, "{}:{}", path.magenta(), count)?;
+        } else {
+            writeln!(out, "{}:{}", path, count)?;
+        }
+    }
+
+    writeln!(
+        out,
+        "Total: {} matched line(s) in {} file(s)",
+        counts.iter().map(|(_, count)| count).sum::<usize>(),
+        counts.len()
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::count::write_counts;
+    use crate::rg::de::test_utilities::*;
+    use crate::rg::de::*;
+
+    fn counts(rg_messages: Vec<RgMessage>) -> String {
+        let mut out = vec![];
+        write_counts(&mut out, rg_messages, false).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn message(kind: RgMessageKind, path: &str) -> RgMessage {
+        RgMessageBuilder::new(kind)
+            .with_path_text(path)
+            .with_lines_text("foo\n")
+            .with_submatches(vec![SubMatch::new_text("foo", 0..3)])
+            .with_offset(0)
+            .build()
+    }
+
+    #[test]
+    fn it_counts_matches_per_file() {
+        let rg_messages = vec![
+            message(RgMessageKind::Begin, "b.txt"),
+            message(RgMessageKind::Match, "b.txt"),
+            message(RgMessageKind::Context, "b.txt"),
+            message(RgMessageKind::Match, "b.txt"),
+            message(RgMessageKind::Match, "b.txt"),
+            RgMessage::from_str(RG_JSON_END),
+            message(RgMessageKind::Begin, "a.txt"),
+            message(RgMessageKind::Match, "a.txt"),
+            RgMessage::from_str(RG_JSON_END),
+            RgMessage::from_str(RG_JSON_SUMMARY),
+        ];
+
+        assert_eq!(
+            counts(rg_messages),
+            "b.txt:3\na.txt:1\nTotal: 4 matched line(s) in 2 file(s)\n"
+        );
+    }
+
+    #[test]
+    fn it_counts_no_matches() {
+        assert_eq!(
+            counts(vec![RgMessage::from_str(RG_JSON_SUMMARY)]),
+            "Total: 0 matched line(s) in 0 file(s)\n"
+        );
+    }
+
+    #[test]
+    fn it_colors_paths() {
+        let mut out = vec![];
+        let rg_messages = vec![
+            message(RgMessageKind::Begin, "a.txt"),
+            message(RgMessageKind::Match, "a.txt"),
+            RgMessage::from_str(RG_JSON_END),
+        ];
+        write_counts(&mut out, rg_messages, true).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\u{1b}[38;5;13ma.txt\u{1b}[39m:1\nTotal: 1 matched line(s) in 1 file(s)\n"
+        );
+    }
+}
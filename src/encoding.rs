@@ -5,31 +5,47 @@ use encoding::EncodingRef;
 use crate::rg::RgEncoding;
 
 /// Returns a tuple of a BOM (if one exists) and an encoding.
-pub fn get_encoder(bytes: &[u8], rg_encoding: &RgEncoding) -> (Option<Bom>, EncodingRef) {
+///
+/// A BOM's encoding is preferred over the encoding the user passed, unless `override_bom` is set (for files
+/// where the BOM is wrong). The BOM is returned either way, so it can be stripped and written back.
+pub fn get_encoder(
+    bytes: &[u8],
+    rg_encoding: &RgEncoding,
+    override_bom: bool,
+) -> (Option<Bom>, EncodingRef) {
     // Check if this file has a BOM (Byte Order Mark).
     let bom = Bom::from_slice(bytes);
 
-    // Try to detect the encoding of the file.
-    let encoder = bom
-        // if we found a BOM then use that encoding
-        .map(|b| {
+    // if we found a BOM then use that encoding
+    let bom_encoder = || {
+        bom.map(|b| {
             let encoder = b.encoder();
             log::debug!("Found BOM: {:?}, using encoder: {}", b, encoder.name());
             encoder
         })
-        // otherwise if the user passed an encoding use that
-        .or_else(|| {
-            let encoder = rg_encoding.encoder();
-            if encoder.is_some() {
-                log::debug!(
-                    "Found user encoding: {:?}, using encoder: {}",
-                    rg_encoding,
-                    encoder.unwrap().name()
-                );
-            }
+    };
 
-            encoder
-        })
+    // if the user passed an encoding use that
+    let user_encoder = || {
+        let encoder = rg_encoding.encoder();
+        if let Some(encoder) = encoder {
+            log::debug!(
+                "Found user encoding: {:?}, using encoder: {}",
+                rg_encoding,
+                encoder.name()
+            );
+        }
+
+        encoder
+    };
+
+    // Try to detect the encoding of the file.
+    let encoder = if override_bom {
+        user_encoder().or_else(bom_encoder)
+    } else {
+        bom_encoder().or_else(user_encoder)
+    };
+    let encoder = encoder
         // nothing so far, try detecting the encoding
         .or_else(|| {
             let (encoding, confidence, _) = chardet::detect(bytes);
@@ -116,6 +132,7 @@ impl Bom {
         }
     }
 
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(self) -> usize {
         self.bytes().len()
     }
@@ -189,7 +206,10 @@ mod tests {
 
     macro_rules! assert_encoder {
         ($bytes:expr, $rg_enc:expr, $expected:expr) => {
-            let (bom, enc) = get_encoder($bytes, $rg_enc);
+            assert_encoder!($bytes, $rg_enc, false, $expected);
+        };
+        ($bytes:expr, $rg_enc:expr, $override_bom:expr, $expected:expr) => {
+            let (bom, enc) = get_encoder($bytes, $rg_enc, $override_bom);
             assert_eq!((bom, enc.name()), $expected);
         };
     }
@@ -243,4 +263,37 @@ mod tests {
             (None, "ascii")
         );
     }
+
+    #[test]
+    fn test_get_encoder_override_bom() {
+        // the user's encoding takes preference, but the BOM is still returned
+        assert_encoder!(
+            &[0xFF, 0xFE, 0x66, 0x6f, 0x6f],
+            &RgEncoding::Some(encoding::all::UTF_8),
+            true,
+            (Some(Bom::Utf16le), "utf-8")
+        );
+        assert_encoder!(
+            &Bom::BOM_UTF16BE,
+            &RgEncoding::Some(encoding::all::ASCII),
+            true,
+            (Some(Bom::Utf16be), "ascii")
+        );
+
+        // without a user encoding, the BOM is still used
+        assert_encoder!(
+            &Bom::BOM_UTF16LE,
+            &RgEncoding::None,
+            true,
+            (Some(Bom::Utf16le), "utf-16le")
+        );
+
+        // files without BOMs are unaffected
+        assert_encoder!(
+            &[0x1, 0x2, 0x3, 0x4],
+            &RgEncoding::Some(encoding::all::EUC_JP),
+            true,
+            (None, "euc-jp")
+        );
+    }
 }
@@ -1,114 +1,19 @@
-//! _An interactive replacer for `ripgrep`._
-//!
-//! This is an interactive command line tool to make find and replacement easy.
-//! It uses [`ripgrep`] to find, and then provides you with a simple interface to see
-//! the replacements in real-time and conditionally replace matches.
-//!
-//! Some features:
-//!
-//! * ⚡ Super fast search results
-//! * ✨ Interactive interface for selecting which matches should be replaced or not
-//! * 🕶️ Live preview of the replacements
-//! * 🧠 Replace using capturing groups (e.g., when using `/foo (\w+)/` replace with `bar $1`)
-//! * 🦀 and more!
-//!
-//! Supported file encodings:
-//!
-//! * ASCII
-//! * UTF8
-//! * UTF16BE
-//! * UTF16LE
-//!
-//! Other encodings are possibly supported but untested at the moment.
-//! See [this issue](https://github.com/acheronfail/repgrep/issues/12) for more information.
-//!
-//! # Usage
-//!
-//! After installing, just use `rgr` (think: `rg` + `replace`).
-//!
-//! The arguments are:
-//!
-//! ```bash
-//! rgr <rg arguments> # See `rgr --help` for more details
-//! ```
-//!
-//! Here's an example where we ran the command:
-//!
-//! ```bash
-//! rgr -C5 dreamcast
-//! ```
-//!
-//! And have entered the replacement `flycast`:
-//!
-//! ![demo using rgr](./doc/demo.png)
-//!
-//! # Installation
-//!
-//! First and foremost, make sure you've installed `ripgrep` (AKA: `rg`).
-//! To do so see the [`ripgrep` installation instructions].
-//!
-//! ### Precompiled binaries
-//!
-//! See the [releases] page for pre-compiled binaries.
-//!
-//! ### Via Cargo
-//!
-//! **NOTE**: The minimum Rust version required is `1.72.0`.
-//!
-//! ```bash
-//! cargo install repgrep
-//! ```
-//!
-//! ### Via Pacman (Arch Linux)
-//!
-//! [`repgrep`](https://archlinux.org/packages/extra/x86_64/repgrep/) can be installed
-//! from the official repositories using [Pacman](https://wiki.archlinux.org/title/Pacman).
-//!
-//! ```bash
-//! pacman -S repgrep
-//! ```
-//!
-//! ### Via MacPorts (macOS)
-//!
-//! `repgrep` can also be installed on macOS using [MacPorts](https://www.macports.org):
-//!
-//! ```bash
-//! sudo port install repgrep
-//! ```
-//!
-//! More info [here](https://ports.macports.org/port/repgrep/).
-//!
-//! ### From Source
-//!
-//! **NOTE**: The minimum Rust version required is `1.72.0`.
-//!
-//! ```bash
-//! git clone https://github.com/acheronfail/repgrep/
-//! cd repgrep
-//! cargo install --path .
-//! ```
-//!
-//! [`ripgrep`]: https://github.com/BurntSushi/ripgrep
-//! [releases]: https://github.com/acheronfail/repgrep/releases
-//! [`ripgrep` installation instructions]: https://github.com/BurntSushi/ripgrep/#installation
-
-mod cli;
-mod encoding;
-mod model;
-mod replace;
-mod rg;
-mod ui;
-mod util;
-
 use std::fs::File;
-use std::{env, process};
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::{env, io, process};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use crossterm::style::Stylize;
 use flexi_logger::{opt_format, FileSpec, Logger};
-use rg::exec::run_ripgrep;
-use ui::tui::Tui;
-
-use crate::rg::read::read_messages;
+use repgrep::cli::{exit_output, ExitMessage, JsonSource, RunMode};
+use repgrep::model::ReplacementCriteria;
+use repgrep::replace::ReplacementOutcome;
+use repgrep::rg::de::RgMessage;
+use repgrep::rg::exec::run_ripgrep;
+use repgrep::rg::read::{read_messages, read_messages_from_file};
+use repgrep::ui::tui::Tui;
+use repgrep::{cli, count, preview, replace, verify, vimgrep};
 
 fn init_logging() -> Result<::std::path::PathBuf> {
     let log_dir = env::temp_dir().join(format!(".{}", env!("CARGO_PKG_NAME")));
@@ -131,6 +36,182 @@ fn init_logging() -> Result<::std::path::PathBuf> {
     Ok(log_dir)
 }
 
+/// Prints a line reporting the result of replacing (or verifying), in green if colors are used (see `--color`).
+fn print_report(args: &cli::RgArgs, report: String) {
+    if args.color.use_color(io::stdout().is_terminal()) {
+        println!("{}", report.green());
+    } else {
+        println!("{}", report);
+    }
+}
+
+/// Configures the replacements with the options that were passed.
+fn configure_replacements(args: &cli::RgArgs, replacement_criteria: &mut ReplacementCriteria) {
+    // use an encoding if one was passed to `rg`
+    if let Some(encoding) = &args.encoding {
+        replacement_criteria.set_encoding(encoding);
+    }
+    replacement_criteria.set_encoding_override(args.options.encoding_override);
+    replacement_criteria.set_skip_symlinks(args.options.skip_symlinks);
+    replacement_criteria.set_allow_binary(args.options.allow_binary);
+    if let Some(line_ending) = args.options.normalize_eol {
+        replacement_criteria.set_normalize_eol(line_ending);
+    }
+    replacement_criteria.set_in_place(args.options.in_place);
+    replacement_criteria.set_capture_from_line(args.options.capture_from_line);
+    replacement_criteria.set_replace_empty_matches(args.options.replace_empty_matches);
+
+    // refuse to replace matches in the output of a preprocessor
+    if let Some(preprocessor) = &args.preprocessor {
+        replacement_criteria.set_preprocessor(preprocessor);
+    }
+
+    // don't replace matches in lines which rg may have truncated
+    if let Some(max_columns) = args.max_columns {
+        replacement_criteria.set_max_columns(max_columns);
+    }
+
+    // compute replacements with a shell command if one was passed
+    if let Some(command) = &args.options.replace_command {
+        replacement_criteria.set_replace_command(command);
+    }
+
+    // run a command (such as a formatter) on the files that were changed
+    if let Some(command) = &args.options.after_replace_command {
+        replacement_criteria.set_after_replace_command(command);
+    }
+
+    // write the replacements to a patch, rather than to the files
+    if let Some(path) = &args.options.output_patch {
+        replacement_criteria.set_output_patch(path);
+    }
+
+    // number the matches with `$COUNT` from these
+    if let Some(start) = args.options.count_start {
+        replacement_criteria.set_count_start(start);
+    }
+    if let Some(step) = args.options.count_step {
+        replacement_criteria.set_count_step(step);
+    }
+
+    // limit how many matches are replaced in each file
+    if let Some(max) = args.options.max_replacements_per_file {
+        replacement_criteria.set_max_replacements_per_file(max);
+    }
+
+    // if we're running in fixed strings mode (or rg used PCRE2), then we shouldn't treat the patterns as regexes
+    if args.fixed_strings || args.uses_pcre2() {
+        replacement_criteria.capture_pattern = None;
+    }
+}
+
+/// Builds the replacements of every match with the `-r/--replace` text, for when the interface isn't started.
+fn replace_all_criteria(
+    args: &cli::RgArgs,
+    rg_messages: Vec<RgMessage>,
+    replacement: &str,
+) -> Result<ReplacementCriteria> {
+    let pattern = match args.build_capture_patterns()? {
+        mut one if one.len() == 1 => one.pop(),
+        _ => None,
+    };
+    ReplacementCriteria::from_rg_messages(rg_messages, pattern, replacement)
+}
+
+/// Configures the replacements with the options that were passed, and writes them to disk. With `print_summary`, a
+/// single line describing the result is printed afterwards.
+fn apply_replacements(
+    args: &cli::RgArgs,
+    mut replacement_criteria: ReplacementCriteria,
+    print_summary: bool,
+) -> Result<ReplacementOutcome> {
+    configure_replacements(args, &mut replacement_criteria);
+
+    // the replacement text can only be searched for if it's the same for every match
+    let verify_text = (replacement_criteria.capture_pattern.is_none()
+        && replacement_criteria.replace_command.is_none()
+        && !replace::uses_count(&replacement_criteria))
+    .then(|| String::from_utf8_lossy(&replacement_criteria.user_replacement).into_owned())
+    .filter(|text| !text.is_empty());
+
+    let (outcome, stats) = replace::perform_replacements_with_stats(replacement_criteria)?;
+    if args.options.stats {
+        eprintln!("{}", stats);
+    }
+    if let Some(path) = &args.options.output_patch {
+        print_report(
+            args,
+            format!(
+                "Wrote a patch of {} match(es) in {} file(s) to {}",
+                stats.replaced, stats.files_changed, path
+            ),
+        );
+        return Ok(outcome);
+    }
+    if print_summary {
+        print_report(
+            args,
+            format!(
+                "Replaced {} match(es) in {} file(s)",
+                stats.replaced, stats.files_changed
+            ),
+        );
+    }
+
+    if args.options.verify {
+        match verify_text {
+            Some(text) => verify_replacements(args, &stats.files, &text)?,
+            None => eprintln!(
+                "Can't verify the replacements, since the replacement text is empty or differs for each match"
+            ),
+        }
+    }
+
+    Ok(outcome)
+}
+
+/// Searches the files again for the replacement text, and fails if it's found fewer times than the number of
+/// matches that were replaced in any of them.
+fn verify_replacements(
+    args: &cli::RgArgs,
+    replaced: &[(PathBuf, usize)],
+    text: &str,
+) -> Result<()> {
+    let verifications =
+        verify::verify_replacements(replaced, text, args.encoding.as_deref(), run_ripgrep)?;
+
+    let low = verifications
+        .iter()
+        .filter(|v| v.is_low())
+        .collect::<Vec<_>>();
+    for v in &low {
+        eprintln!(
+            "Expected to find the replacement {} time(s) in {}, but found it {} time(s)",
+            v.expected,
+            v.path.display(),
+            v.found
+        );
+    }
+
+    if !low.is_empty() {
+        bail!(
+            "The replacement wasn't found as many times as expected in {} file(s)",
+            low.len()
+        );
+    }
+
+    print_report(
+        args,
+        format!(
+            "Verified: found the replacement {} time(s) in {} file(s)",
+            verifications.iter().map(|v| v.found).sum::<usize>(),
+            verifications.len()
+        ),
+    );
+
+    Ok(())
+}
+
 fn main() {
     let log_dir = match init_logging() {
         Ok(dir) => dir,
@@ -140,21 +221,45 @@ fn main() {
         }
     };
 
+    // set once the arguments are parsed, see `--quiet`
+    let mut quiet = false;
+    let logging = log::log_enabled!(log::Level::Error);
+
     macro_rules! exit_with_error {
         ($( $eprintln_arg:expr ),*) => {
             log::error!($( $eprintln_arg ),*);
             eprintln!($( $eprintln_arg ),*);
-            if log::log_enabled!(log::Level::Error) {
+            if exit_output(ExitMessage::Error, quiet, logging).log_path {
                 eprintln!("Logs available at: {}", log_dir.display());
             }
             process::exit(1);
         };
     }
 
-    let (args, rg_json) = {
-        match env::var_os(cli::ENV_JSON_FILE) {
+    // exits with a nonzero code if any replacements were skipped, so it can be distinguished in scripts
+    macro_rules! exit_with_outcome {
+        ($outcome:expr) => {
+            let outcome = $outcome;
+            if let Err(err) = &outcome {
+                exit_with_error!("An error occurred during replacement: {}", err);
+            }
+            if let Ok(ReplacementOutcome::Partial) = &outcome {
+                let output = exit_output(ExitMessage::Partial, quiet, logging);
+                if output.message {
+                    eprintln!("Failed to perform all replacements, see log");
+                }
+                if output.log_path {
+                    eprintln!("Logs available at: {}", log_dir.display());
+                }
+            }
+            process::exit(ReplacementOutcome::exit_code(&outcome));
+        };
+    }
+
+    let (mut args, rg_json) = {
+        match cli::json_source() {
             // check if JSON is being passed as an environment file
-            Some(path) => {
+            Some(JsonSource::File(path)) => {
                 log::debug!(
                     "{} set to {}; Reading messages from file",
                     cli::ENV_JSON_FILE,
@@ -169,13 +274,32 @@ fn main() {
                             }
                         };
 
-                        (args, read_messages(json_file))
+                        let rg_messages = read_messages_from_file(json_file, args.threads);
+                        (args, rg_messages)
                     }
                     Err(e) => {
                         exit_with_error!("Failed to open {}: {}", path.to_string_lossy(), e);
                     }
                 }
             }
+            // check if JSON is being piped to us
+            Some(JsonSource::Stdin) => {
+                log::debug!(
+                    "{} passed; Reading messages from stdin",
+                    cli::FLAG_JSON_STDIN
+                );
+                let args = match cli::RgArgs::parse_pattern() {
+                    Ok(args) => args,
+                    Err(e) => {
+                        exit_with_error!("Failed to parse arguments: {}", e);
+                    }
+                };
+
+                // NOTE: the interface doesn't need stdin to be the terminal, since crossterm reads the keyboard
+                // from `/dev/tty` when it isn't
+                let rg_messages = read_messages(io::stdin().lock());
+                (args, rg_messages)
+            }
             // normal execution, parse rg arguments and call it ourselves
             None => {
                 let args = match cli::RgArgs::parse_rg_args() {
@@ -185,50 +309,110 @@ fn main() {
                     }
                 };
 
+                // print what ripgrep would be run with instead of running it
+                if args.options.dump_args {
+                    println!("{}", args.dump_args());
+                    return;
+                }
+
                 let rg_args = args.rg_args();
                 (args, run_ripgrep(rg_args))
             }
         }
     };
 
+    quiet = args.options.quiet;
+
     match rg_json {
-        Ok(rg_messages) => {
-            let result = Tui::new()
-                .and_then(|tui| tui.start(args.rg_cmdline(), rg_messages, &args.patterns));
-
-            // Restore terminal.
-            if let Err(err) = Tui::restore_terminal() {
-                log::warn!("Failed to restore terminal state: {}", err);
-                eprintln!(
-                    "Failed to restore terminal state, consider running the `reset` command. Error: {}",
-                    err
-                );
+        Ok(rg_messages) if args.options.count => {
+            let color = args.color.use_color(io::stdout().is_terminal());
+            if let Err(err) = count::write_counts(io::stdout().lock(), rg_messages, color) {
+                exit_with_error!("Failed to write counts: {}", err);
+            }
+        }
+        Ok(rg_messages) if args.options.vimgrep => {
+            if let Err(err) = vimgrep::write_vimgrep(io::stdout().lock(), rg_messages) {
+                exit_with_error!("Failed to write matches: {}", err);
             }
+        }
+        Ok(mut rg_messages) => {
+            args.trim_messages(&mut rg_messages);
 
-            // Handle application result.
-            match result {
-                Ok(Some(mut replacement_criteria)) => {
-                    // use an encoding if one was passed to `rg`
-                    if let Some(encoding) = args.encoding {
-                        replacement_criteria.set_encoding(encoding);
+            // print the replacements as JSON for other tools, rather than starting the interface
+            if args.options.json_preview {
+                let replacement = match (&args.replacement, &args.options.replace_command) {
+                    (Some(replacement), _) => replacement.as_str(),
+                    (None, Some(_)) => "",
+                    (None, None) => {
+                        exit_with_error!(
+                            "--json-preview needs the replacement text (-r/--replace <TEXT>), or --replace-command"
+                        );
                     }
+                };
+
+                let result = replace_all_criteria(&args, rg_messages, replacement).and_then(
+                    |mut replacement_criteria| {
+                        configure_replacements(&args, &mut replacement_criteria);
+                        preview::write_json_preview(io::stdout().lock(), &replacement_criteria)
+                    },
+                );
+                if let Err(err) = result {
+                    exit_with_error!("Failed to write the preview: {}", err);
+                }
 
-                    // if we're running in fixed strings mode, then we shouldn't treat the patterns as regexes
-                    if args.fixed_strings {
-                        replacement_criteria.capture_pattern = None;
+                return;
+            }
+
+            match cli::run_mode(io::stdout().is_terminal(), args.replacement.as_deref()) {
+                RunMode::NoTerminal => {
+                    exit_with_error!(
+                        "{}'s interface needs a terminal, but stdout isn't one. Pass --replace <TEXT> to replace every match without it, or --count to only count them.",
+                        env!("CARGO_PKG_NAME")
+                    );
+                }
+                RunMode::ReplaceAll(replacement) => {
+                    let result = replace_all_criteria(&args, rg_messages, replacement).and_then(
+                        |mut replacement_criteria| {
+                            replacement_criteria.quiet = args.options.quiet;
+                            apply_replacements(&args, replacement_criteria, true)
+                        },
+                    );
+
+                    exit_with_outcome!(result);
+                }
+                RunMode::Interactive => {
+                    let result = Tui::new().and_then(|tui| tui.start(&mut args, rg_messages));
+
+                    // Restore terminal.
+                    if let Err(err) = Tui::restore_terminal() {
+                        log::warn!("Failed to restore terminal state: {}", err);
+                        eprintln!(
+                            "Failed to restore terminal state, consider running the `reset` command. Error: {}",
+                            err
+                        );
                     }
 
-                    match replace::perform_replacements(replacement_criteria) {
-                        Ok(_) => {}
+                    // Handle application result.
+                    match result {
+                        Ok(Some(replacement_criteria)) => {
+                            // when quitting quietly only a single line describing the result is printed
+                            let print_summary = replacement_criteria.quiet;
+                            exit_with_outcome!(apply_replacements(
+                                &args,
+                                replacement_criteria,
+                                print_summary
+                            ));
+                        }
+                        Ok(None) => {
+                            if exit_output(ExitMessage::Cancelled, quiet, logging).message {
+                                eprintln!("Cancelled");
+                            }
+                        }
                         Err(err) => {
-                            exit_with_error!("An error occurred during replacement: {}", err);
+                            exit_with_error!("An app error occurred: {}", err);
                         }
                     }
                 }
-                Ok(None) => eprintln!("Cancelled"),
-                Err(err) => {
-                    exit_with_error!("An app error occurred: {}", err);
-                }
             }
         }
         Err(e) => {
@@ -0,0 +1,73 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+
+/// The line endings a file's lines are normalized to when it's written (`--normalize-eol`).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    Crlf,
+}
+
+impl Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LineEnding::Lf => "lf",
+            LineEnding::Crlf => "crlf",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "lf" => LineEnding::Lf,
+            "crlf" => LineEnding::Crlf,
+            other => bail!(
+                "invalid line ending \"{}\", expected one of: lf, crlf",
+                other
+            ),
+        })
+    }
+}
+
+impl LineEnding {
+    /// Converts every line ending in `text` (whether `\n` or `\r\n`) to this one. Lone `\r`s are left as-is.
+    pub fn normalize(self, text: &str) -> String {
+        let text = text.replace("\r\n", "\n");
+        match self {
+            LineEnding::Lf => text,
+            LineEnding::Crlf => text.replace('\n', "\r\n"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::model::LineEnding;
+
+    #[test]
+    fn it_round_trips_through_strings() {
+        for eol in [LineEnding::Lf, LineEnding::Crlf] {
+            assert_eq!(eol.to_string().parse::<LineEnding>().unwrap(), eol);
+        }
+
+        assert!("cr".parse::<LineEnding>().is_err());
+    }
+
+    #[test]
+    fn it_normalizes_mixed_line_endings() {
+        let text = "a\r\nb\nc\rd\r\n\n";
+        assert_eq!(LineEnding::Lf.normalize(text), "a\nb\nc\rd\n\n");
+        assert_eq!(LineEnding::Crlf.normalize(text), "a\r\nb\r\nc\rd\r\n\r\n");
+    }
+}
@@ -1,7 +1,13 @@
+pub mod line_ending;
 pub mod movement;
 pub mod printable;
 pub mod replacement;
+pub mod sort_mode;
+pub mod wrap_mode;
 
+pub use line_ending::*;
 pub use movement::*;
 pub use printable::*;
 pub use replacement::*;
+pub use sort_mode::*;
+pub use wrap_mode::*;
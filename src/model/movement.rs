@@ -23,6 +23,10 @@ pub enum Movement {
     Forward(u16),
     /// Move backward `n` items.
     Backward(u16),
+    /// Move to the first item.
+    First,
+    /// Move to the last item.
+    Last,
 }
 
 impl Movement {
@@ -32,12 +36,16 @@ impl Movement {
 
     pub fn direction(&self) -> Direction {
         match self {
-            Movement::Prev | Movement::PrevLine | Movement::PrevFile | Movement::Backward(_) => {
-                Direction::Backward
-            }
-            Movement::Next | Movement::NextLine | Movement::NextFile | Movement::Forward(_) => {
-                Direction::Forward
-            }
+            Movement::Prev
+            | Movement::PrevLine
+            | Movement::PrevFile
+            | Movement::Backward(_)
+            | Movement::First => Direction::Backward,
+            Movement::Next
+            | Movement::NextLine
+            | Movement::NextFile
+            | Movement::Forward(_)
+            | Movement::Last => Direction::Forward,
         }
     }
 }
@@ -1,23 +1,52 @@
 use std::borrow::Cow;
 use std::fmt::{self, Display};
+use std::iter;
+
+use serde::{Deserialize, Serialize};
+use unicode_width::UnicodeWidthChar;
 
 use crate::rg::de::ArbitraryData;
 
 type OneLine = bool;
 
-#[derive(Debug, Copy, Clone)]
+/// How many columns apart the tab stops are by default, see `expand_tabs`.
+pub const DEFAULT_TAB_WIDTH: usize = 8;
+
+/// Expands each tab in the text to spaces, up to the next tab stop (every `tab_width` columns). `column` is the
+/// column the text starts at, and is moved to the column it ends at (a newline moves it back to the start).
+pub fn expand_tabs(s: &str, tab_width: usize, column: &mut usize) -> String {
+    let tab_width = tab_width.max(1);
+    let mut expanded = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '\t' => {
+                let spaces = tab_width - *column % tab_width;
+                expanded.extend(iter::repeat(' ').take(spaces));
+                *column += spaces;
+            }
+            '\n' => {
+                expanded.push(ch);
+                *column = 0;
+            }
+            c => {
+                expanded.push(c);
+                *column += c.width().unwrap_or(0);
+            }
+        }
+    }
+
+    expanded
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PrintableStyle {
+    #[default]
     Hidden,
     Common(OneLine),
     All(OneLine),
 }
 
-impl Default for PrintableStyle {
-    fn default() -> Self {
-        PrintableStyle::Hidden
-    }
-}
-
 impl Display for PrintableStyle {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.symbol())
@@ -79,7 +108,10 @@ impl Printable for &str {
                         | '\x08' | '\x0B' | '\x0C' | '\x0E' | '\x0F' | '\x10' | '\x11' | '\x12'
                         | '\x13' | '\x14' | '\x15' | '\x16' | '\x17' | '\x18' | '\x19' | '\x1A'
                         | '\x1B' | '\x1C' | '\x1D' | '\x1E' | '\x1F' | '\x7F' => {}
-                        '\x09' | '\x0D' => s.push(' '),
+                        // tabs are kept, so they can be expanded to the next tab stop once their column is known
+                        // (see `expand_tabs`)
+                        '\x09' => s.push('\t'),
+                        '\x0D' => s.push(' '),
                         _ => s.push(ch),
                     }
                 }
@@ -189,7 +221,7 @@ impl Printable for Vec<u8> {
 mod tests {
     use base64_simd::STANDARD as base64;
 
-    use crate::model::{Printable, PrintableStyle};
+    use crate::model::{expand_tabs, Printable, PrintableStyle};
     use crate::rg::de::ArbitraryData;
 
     const NON_PRINTABLE_WHITESPACE: &str = "\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0A\x0B\x0C\x0D\x0E\x0F\x10\x11\x12\x13\x14\x15\x16\x17\x18\x19\x1A\x1B\x1C\x1D\x1E\x1F\x20\x7F";
@@ -198,7 +230,7 @@ mod tests {
     fn test_printable() {
         assert_eq!(
             NON_PRINTABLE_WHITESPACE.to_printable(PrintableStyle::Hidden),
-            " \n  "
+            "\t\n  "
         );
         assert_eq!(
             NON_PRINTABLE_WHITESPACE.to_printable(PrintableStyle::All(true)),
@@ -230,7 +262,7 @@ mod tests {
     #[test]
     fn test_printable_text() {
         let data = ArbitraryData::new_with_text(NON_PRINTABLE_WHITESPACE.to_string());
-        assert_eq!(data.to_printable(PrintableStyle::Hidden), " \n  ");
+        assert_eq!(data.to_printable(PrintableStyle::Hidden), "\t\n  ");
         assert_eq!(
             data.to_printable(PrintableStyle::All(true)),
             "␀␁␂␃␄␅␆␇␈␉␊␋␌␍␎␏␐␑␒␓␔␕␖␗␘␙␚␛␜␝␞␟␠␡"
@@ -245,7 +277,7 @@ mod tests {
     fn test_printable_base64() {
         let data =
             ArbitraryData::new_with_base64(base64.encode_to_string(NON_PRINTABLE_WHITESPACE));
-        assert_eq!(data.to_printable(PrintableStyle::Hidden), " \n  ");
+        assert_eq!(data.to_printable(PrintableStyle::Hidden), "\t\n  ");
         assert_eq!(
             data.to_printable(PrintableStyle::All(true)),
             "␀␁␂␃␄␅␆␇␈␉␊␋␌␍␎␏␐␑␒␓␔␕␖␗␘␙␚␛␜␝␞␟␠␡"
@@ -255,4 +287,26 @@ mod tests {
             "•••••••••→¬••¤••••••••••••••••••␣•"
         );
     }
+
+    #[test]
+    fn test_expand_tabs() {
+        let expand = |s, column| {
+            let mut column = column;
+            let expanded = expand_tabs(s, 4, &mut column);
+            (expanded, column)
+        };
+
+        assert_eq!(expand("\tfoo", 0), (String::from("    foo"), 7));
+        assert_eq!(expand("a\tb", 0), (String::from("a   b"), 5));
+        assert_eq!(expand("abc\tb", 0), (String::from("abc b"), 5));
+        assert_eq!(expand("abcd\tb", 0), (String::from("abcd    b"), 9));
+        assert_eq!(expand("\t\t", 2), (String::from("      "), 8));
+        // the column is counted in columns, not characters
+        assert_eq!(expand("日本\tx", 0), (String::from("日本    x"), 9));
+        // a newline starts from the first column again
+        assert_eq!(expand("ab\n\t", 0), (String::from("ab\n    "), 4));
+        // a tab width of 0 is treated as 1
+        let mut column = 0;
+        assert_eq!(expand_tabs("\t", 0, &mut column), " ");
+    }
 }
@@ -1,17 +1,71 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
+use anyhow::Result;
 use regex::bytes::Regex;
 
-use crate::rg::de::{ArbitraryData, RgMessageKind};
+use crate::model::LineEnding;
+use crate::rg::de::{ArbitraryData, RgMessage, RgMessageKind};
+use crate::rg::read::read_messages;
 use crate::ui::line::Item;
 
+/// Describes which matches should be replaced, and what they should be replaced with.
+/// See [`crate::replace::perform_replacements`].
 #[derive(Debug)]
 pub struct ReplacementCriteria {
+    /// If set, `user_replacement` is expanded with this pattern's capturing groups for each match. Groups are
+    /// referred to by their index (`$1`) or, for named groups like `(?P<word>\w+)`, by their name (`$word`).
+    /// Use braces (`${word}`) when the reference is followed by other letters, digits or underscores.
+    ///
+    /// Whether or not this is set, `$COUNT` (or `${COUNT}`) in `user_replacement` is expanded to the number of
+    /// each match in its file (see `count_start` and `count_step`).
     pub capture_pattern: Option<Regex>,
+    /// The items from `rg`'s output. Only `Match` items (and submatches with `should_replace`) are replaced.
     pub items: Vec<Item>,
+    /// The text to replace each match with.
     pub user_replacement: Vec<u8>,
+    /// The encoding that was passed to `rg`, if any.
     pub encoding: Option<String>,
+    /// If set, `encoding` is used even for files with a BOM for a different encoding.
+    pub encoding_override: bool,
+    /// If set, each match is piped through this shell command and its output used as the replacement.
+    pub replace_command: Option<String>,
+    /// If set, this shell command is run once the replacements are written, with the paths of the files that were
+    /// changed appended to it (e.g. to format them). The replacements are kept even if it fails.
+    pub after_replace_command: Option<String>,
+    /// If set, the files aren't written. Instead, a unified diff of the replacements (which `git apply` can apply) is
+    /// written to this path.
+    pub output_patch: Option<PathBuf>,
+    /// If set, problems with individual files are only logged rather than also printed to stderr.
+    pub quiet: bool,
+    /// The preprocessor command that was passed to `rg` (`--pre`), if any. When set, the matches are in the
+    /// preprocessor's output rather than in the files, so replacements are refused.
+    pub preprocessor: Option<String>,
+    /// If set, only the first (by their position in the file) of this many matches are replaced in each file.
+    pub max_replacements_per_file: Option<usize>,
+    /// If set, files which are symlinks are skipped. Otherwise, the files they link to are written (and the
+    /// symlinks are kept).
+    pub skip_symlinks: bool,
+    /// If set, files are truncated and written in place, rather than replaced with a temporary file. This
+    /// isn't atomic: if writing fails part way through (or the file is read meanwhile) it may be incomplete.
+    pub in_place: bool,
+    /// If set, files which ripgrep found binary data in are written too. Otherwise, they're skipped.
+    pub allow_binary: bool,
+    /// If set, the line endings of each file that's written are converted to these. Otherwise, they're kept.
+    pub normalize_eol: Option<LineEnding>,
+    /// What `$COUNT` in `user_replacement` is expanded to for the first match in each file.
+    pub count_start: i64,
+    /// How much `$COUNT` is incremented by for each following match in the file.
+    pub count_step: i64,
+    /// If set, `capture_pattern` is matched against the whole line of each match rather than just its text,
+    /// so its groups can capture the text around the match. The match found at the submatch is used.
+    pub capture_from_line: bool,
+    /// If set, zero-length matches (e.g. `^` or `\b`) are treated as insertion points, and the replacement is
+    /// inserted at them. Otherwise they're skipped, since there's nothing to replace.
+    pub replace_empty_matches: bool,
 }
 
 impl ReplacementCriteria {
@@ -25,28 +79,378 @@ impl ReplacementCriteria {
             user_replacement: user_replacement.as_ref().as_bytes().to_vec(),
             items,
             encoding: None,
+            encoding_override: false,
+            replace_command: None,
+            after_replace_command: None,
+            output_patch: None,
+            quiet: false,
+            preprocessor: None,
+            max_replacements_per_file: None,
+            skip_symlinks: false,
+            in_place: false,
+            allow_binary: false,
+            normalize_eol: None,
+            count_start: 1,
+            count_step: 1,
+            capture_from_line: false,
+            replace_empty_matches: false,
         }
     }
 
+    /// Builds `ReplacementCriteria` from the output of `rg --json`, with every match marked for replacement.
+    ///
+    /// If `pattern` is given and has capturing groups, then `user_replacement` is expanded with them for
+    /// each match (e.g. `$1`), otherwise `user_replacement` is used as-is.
+    ///
+    /// Edge cases:
+    ///
+    /// * `rg`'s `summary` message isn't included in the resulting items, since it doesn't belong to any file
+    /// * if `rg` didn't report any matches, then an `Err` is returned
+    /// * an `Err` is also returned if any line isn't a valid `rg` JSON message, or `pattern` isn't a valid regex
+    pub fn from_rg_json<R: Read, S: AsRef<str>>(
+        rdr: R,
+        pattern: Option<&str>,
+        user_replacement: S,
+    ) -> Result<ReplacementCriteria> {
+        let pattern = pattern.map(Regex::new).transpose()?;
+        ReplacementCriteria::from_rg_messages(read_messages(rdr)?, pattern, user_replacement)
+    }
+
+    /// Builds `ReplacementCriteria` from messages which have already been read from `rg`, and a pattern
+    /// which has already been compiled (see `from_rg_json`).
+    pub fn from_rg_messages<S: AsRef<str>>(
+        rg_messages: Vec<RgMessage>,
+        pattern: Option<Regex>,
+        user_replacement: S,
+    ) -> Result<ReplacementCriteria> {
+        // all regex's have at least one capturing group, see: https://docs.rs/regex/1.8.4/regex/struct.Captures.html#method.len
+        let capture_pattern = pattern.filter(|re| re.captures_len() > 1);
+
+        let items = rg_messages
+            .into_iter()
+            .filter(|rg_message| !matches!(rg_message, RgMessage::Summary { .. }))
+            .enumerate()
+            .map(|(i, rg_message)| {
+                let mut item = Item::new(i, rg_message);
+                item.set_should_replace_all(true);
+                item
+            })
+            .collect();
+
+        Ok(ReplacementCriteria::new(
+            capture_pattern,
+            user_replacement,
+            items,
+        ))
+    }
+
     pub fn set_encoding(&mut self, encoding: impl AsRef<str>) {
         self.encoding = Some(encoding.as_ref().to_owned());
     }
 
+    pub fn set_encoding_override(&mut self, encoding_override: bool) {
+        self.encoding_override = encoding_override;
+    }
+
+    pub fn set_replace_command(&mut self, command: impl AsRef<str>) {
+        self.replace_command = Some(command.as_ref().to_owned());
+    }
+
+    pub fn set_after_replace_command(&mut self, command: impl AsRef<str>) {
+        self.after_replace_command = Some(command.as_ref().to_owned());
+    }
+
+    pub fn set_output_patch(&mut self, path: impl AsRef<Path>) {
+        self.output_patch = Some(path.as_ref().to_path_buf());
+    }
+
+    pub fn set_max_replacements_per_file(&mut self, max: usize) {
+        self.max_replacements_per_file = Some(max);
+    }
+
+    pub fn set_allow_binary(&mut self, allow_binary: bool) {
+        self.allow_binary = allow_binary;
+    }
+
+    pub fn set_normalize_eol(&mut self, line_ending: LineEnding) {
+        self.normalize_eol = Some(line_ending);
+    }
+
+    pub fn set_count_start(&mut self, start: i64) {
+        self.count_start = start;
+    }
+
+    pub fn set_count_step(&mut self, step: i64) {
+        self.count_step = step;
+    }
+
+    pub fn set_capture_from_line(&mut self, capture_from_line: bool) {
+        self.capture_from_line = capture_from_line;
+    }
+
+    pub fn set_replace_empty_matches(&mut self, replace_empty_matches: bool) {
+        self.replace_empty_matches = replace_empty_matches;
+    }
+
+    pub fn set_skip_symlinks(&mut self, skip_symlinks: bool) {
+        self.skip_symlinks = skip_symlinks;
+    }
+
+    pub fn set_in_place(&mut self, in_place: bool) {
+        self.in_place = in_place;
+    }
+
+    pub fn set_preprocessor(&mut self, preprocessor: impl AsRef<str>) {
+        self.preprocessor = Some(preprocessor.as_ref().to_owned());
+    }
+
+    /// Marks the matches whose lines are longer than `max_columns` (see `rg --max-columns`) as truncated, so
+    /// they aren't replaced.
+    pub fn set_max_columns(&mut self, max_columns: u64) {
+        for item in &mut self.items {
+            item.set_truncated(item.exceeds_max_columns(max_columns));
+        }
+    }
+
+    /// Groups the `Match` items by the file they're in, so each file is only read and written once.
+    ///
+    /// `rg` may report the same file with different paths (e.g. `./foo` and `foo`, or through a symlink), so the
+    /// paths are canonicalized (falling back to the path as `rg` reported it) and the items of every path to the
+    /// same file are grouped under the first path it was reported with. If a line was reported with more than one
+    /// of them, only its last item with a selected match is kept, so it isn't replaced twice.
     pub fn as_map(&self) -> HashMap<&ArbitraryData, Vec<&Item>> {
-        self.items
+        // the first path each file was reported with, by the file it refers to
+        let mut files: HashMap<PathBuf, &ArbitraryData> = HashMap::new();
+        // the path each of `rg`'s paths is grouped under
+        let mut spellings: HashMap<&ArbitraryData, &ArbitraryData> = HashMap::new();
+
+        let mut map: HashMap<&ArbitraryData, Vec<&Item>> = HashMap::new();
+        // The only item kind we replace is the Match kind.
+        for item in self
+            .items
             .iter()
-            // The only item kind we replace is the Match kind.
             .filter(|item| matches!(item.kind, RgMessageKind::Match))
-            // Collect into a map of paths -> matches.
-            .fold(HashMap::new(), |mut map, item| {
-                match map.entry(item.path().unwrap()) {
-                    Entry::Occupied(e) => e.into_mut().push(item),
-                    Entry::Vacant(e) => {
-                        e.insert(vec![item]);
+        {
+            let path = item.path().unwrap();
+            let key = *spellings.entry(path).or_insert_with(|| {
+                let file = match path.to_path_buf() {
+                    Ok(path_buf) => fs::canonicalize(&path_buf).unwrap_or(path_buf),
+                    Err(_) => return path,
+                };
+                let key = *files.entry(file).or_insert(path);
+                if key != path {
+                    log::warn!(
+                        "The same file was reported as both \"{}\" and \"{}\", so their matches are grouped together",
+                        key,
+                        path
+                    );
+                }
+                key
+            });
+
+            match map.entry(key) {
+                Entry::Occupied(e) => {
+                    let items = e.into_mut();
+                    let existing = items
+                        .iter_mut()
+                        .find(|i| key != path && i.offset() == item.offset());
+                    if let Some(existing) = existing {
+                        log::warn!(
+                            "Only keeping one of the matches which were reported as being in \"{}\"",
+                            key
+                        );
+                        log::warn!("\tFile: \"{}\"", path);
+                        log::warn!("\tOffset: {:?}", item.offset());
+                        // the later item is the one selected most recently, unless nothing is selected in it
+                        if item.replace_count() > 0 {
+                            *existing = item;
+                        }
+                        continue;
                     }
+                    items.push(item);
                 }
+                Entry::Vacant(e) => {
+                    e.insert(vec![item]);
+                }
+            }
+        }
+
+        map
+    }
+}
 
-                map
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::model::ReplacementCriteria;
+    use crate::replace::{perform_replacements, ReplacementOutcome};
+    use crate::rg::de::test_utilities::*;
+    use crate::rg::de::RgMessageKind;
+    use crate::temp_file;
+
+    fn rg_json(path: &Path, lines: &str, submatches: &[(&str, usize, usize)]) -> String {
+        let path = serde_json::to_string(&path.to_string_lossy()).unwrap();
+        let lines = serde_json::to_string(lines).unwrap();
+        let submatches = submatches
+            .iter()
+            .map(|(text, start, end)| {
+                format!(
+                    r#"{{"match":{{"text":{:?}}},"start":{},"end":{}}}"#,
+                    text, start, end
+                )
             })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        [
+            format!(r#"{{"type":"begin","data":{{"path":{{"text":{}}}}}}}"#, path),
+            format!(
+                r#"{{"type":"match","data":{{"path":{{"text":{}}},"lines":{{"text":{}}},"line_number":1,"absolute_offset":0,"submatches":[{}]}}}}"#,
+                path, lines, submatches
+            ),
+            RG_JSON_END.replace(r#""src/model/item.rs""#, &path),
+            RG_JSON_SUMMARY.to_string(),
+        ]
+        .join("\n")
+    }
+
+    #[test]
+    fn from_rg_json() {
+        let p = temp_file!("foo bar foo");
+        let json = rg_json(&p, "foo bar foo", &[("foo", 0, 3), ("foo", 8, 11)]);
+
+        let criteria = ReplacementCriteria::from_rg_json(json.as_bytes(), None, "baz").unwrap();
+        assert!(criteria.capture_pattern.is_none());
+        assert_eq!(
+            criteria
+                .items
+                .iter()
+                .map(|i| i.kind.clone())
+                .collect::<Vec<_>>(),
+            [
+                RgMessageKind::Begin,
+                RgMessageKind::Match,
+                RgMessageKind::End
+            ]
+        );
+        assert!(criteria.items[1].get_should_replace_all());
+
+        perform_replacements(criteria).unwrap();
+        assert_eq!(fs::read_to_string(p).unwrap(), "baz bar baz");
+    }
+
+    #[test]
+    fn from_rg_json_capture_pattern() {
+        let p = temp_file!("foo bar baz");
+        let json = rg_json(&p, "foo bar baz", &[("foo bar", 0, 7)]);
+
+        // patterns without capturing groups are ignored
+        let criteria =
+            ReplacementCriteria::from_rg_json(json.as_bytes(), Some("foo bar"), "$1").unwrap();
+        assert!(criteria.capture_pattern.is_none());
+
+        let criteria =
+            ReplacementCriteria::from_rg_json(json.as_bytes(), Some("(foo) (bar)"), "$2 $1")
+                .unwrap();
+        perform_replacements(criteria).unwrap();
+        assert_eq!(fs::read_to_string(p).unwrap(), "bar foo baz");
+    }
+
+    #[test]
+    fn from_rg_json_named_capture_pattern() {
+        let p = temp_file!("foo bar baz");
+        let json = rg_json(&p, "foo bar baz", &[("foo", 0, 3)]);
+
+        let criteria =
+            ReplacementCriteria::from_rg_json(json.as_bytes(), Some("(?P<word>foo)"), "[${word}]")
+                .unwrap();
+        assert!(criteria.capture_pattern.is_some());
+        perform_replacements(criteria).unwrap();
+        assert_eq!(fs::read_to_string(p).unwrap(), "[foo] bar baz");
+    }
+
+    #[test]
+    fn as_map_groups_paths_to_the_same_file() {
+        let p = temp_file!("foo bar");
+        let alias = p.parent().unwrap().join(".").join(p.file_name().unwrap());
+        let json = [
+            rg_json(&p, "foo bar", &[("foo", 0, 3)]),
+            rg_json(&alias, "foo bar", &[("foo", 0, 3)]),
+        ]
+        .join("\n");
+
+        let criteria = ReplacementCriteria::from_rg_json(json.as_bytes(), None, "foobar").unwrap();
+        let map = criteria.as_map();
+        assert_eq!(map.len(), 1);
+        let (path, items) = map.iter().next().unwrap();
+        assert_eq!(path.to_path_buf().unwrap(), p);
+        assert_eq!(items.len(), 1);
+
+        // the match is only replaced once, rather than once for each path
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "foobar bar");
+    }
+
+    #[test]
+    fn as_map_keeps_the_last_selected_item_of_paths_to_the_same_file() {
+        let p = temp_file!("foo bar");
+        let alias = p.parent().unwrap().join(".").join(p.file_name().unwrap());
+        let json = [
+            rg_json(&p, "foo bar", &[("foo", 0, 3), ("bar", 4, 7)]),
+            rg_json(&alias, "foo bar", &[("foo", 0, 3), ("bar", 4, 7)]),
+        ]
+        .join("\n");
+
+        // only "bar" is selected through the first path, and `alias_selection` through the second one
+        let criteria = |alias_selection: [bool; 2]| {
+            let mut criteria =
+                ReplacementCriteria::from_rg_json(json.as_bytes(), None, "X").unwrap();
+            let mut matches = criteria
+                .items
+                .iter_mut()
+                .filter(|i| matches!(i.kind, RgMessageKind::Match));
+            matches.next().unwrap().set_should_replace(0, false);
+            let alias_item = matches.next().unwrap();
+            for (idx, &should_replace) in alias_selection.iter().enumerate() {
+                alias_item.set_should_replace(idx, should_replace);
+            }
+            criteria
+        };
+
+        // the selection made through the last path is kept
+        let selected = criteria([true, false]);
+        let map = selected.as_map();
+        let (path, items) = map.iter().next().unwrap();
+        assert_eq!(path.to_path_buf().unwrap(), p);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].path().unwrap().to_path_buf().unwrap(), alias);
+        perform_replacements(selected).unwrap();
+        assert_eq!(fs::read_to_string(&p).unwrap(), "X bar");
+
+        // unless nothing is selected through it
+        fs::write(&p, "foo bar").unwrap();
+        perform_replacements(criteria([false, false])).unwrap();
+        assert_eq!(fs::read_to_string(&p).unwrap(), "foo X");
+    }
+
+    #[test]
+    fn from_rg_json_errors() {
+        // no matches
+        let json = [RG_JSON_BEGIN, RG_JSON_END, RG_JSON_SUMMARY].join("\n");
+        assert!(ReplacementCriteria::from_rg_json(json.as_bytes(), None, "").is_err());
+
+        // invalid json
+        assert!(ReplacementCriteria::from_rg_json("{".as_bytes(), None, "").is_err());
+
+        // invalid pattern
+        let json = [RG_JSON_BEGIN, RG_JSON_MATCH, RG_JSON_END].join("\n");
+        assert!(ReplacementCriteria::from_rg_json(json.as_bytes(), Some("("), "").is_err());
     }
 }
@@ -0,0 +1,86 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// Defines the order in which files are displayed in the main matches list.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SortMode {
+    /// The order in which `rg` returned the files.
+    #[default]
+    #[serde(rename = "rg")]
+    Rg,
+    /// Sorted by file path.
+    #[serde(rename = "path")]
+    Path,
+    /// Files with the most matches first.
+    #[serde(rename = "count")]
+    MatchCount,
+    /// Files with the longest matched lines first.
+    #[serde(rename = "length")]
+    LineLength,
+}
+
+impl Display for SortMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SortMode::Rg => "rg",
+            SortMode::Path => "path",
+            SortMode::MatchCount => "count",
+            SortMode::LineLength => "length",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for SortMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "rg" => SortMode::Rg,
+            "path" => SortMode::Path,
+            "count" => SortMode::MatchCount,
+            "length" => SortMode::LineLength,
+            other => bail!(
+                "invalid sort mode \"{}\", expected one of: rg, path, count, length",
+                other
+            ),
+        })
+    }
+}
+
+impl SortMode {
+    /// Cycles through each possible value of a `SortMode`.
+    pub fn cycle(self) -> Self {
+        match self {
+            SortMode::Rg => SortMode::Path,
+            SortMode::Path => SortMode::MatchCount,
+            SortMode::MatchCount => SortMode::LineLength,
+            SortMode::LineLength => SortMode::Rg,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::model::SortMode;
+
+    #[test]
+    fn it_round_trips_through_strings() {
+        let mut mode = SortMode::default();
+        loop {
+            assert_eq!(mode.to_string().parse::<SortMode>().unwrap(), mode);
+            mode = mode.cycle();
+            if mode == SortMode::default() {
+                break;
+            }
+        }
+
+        assert!("foo".parse::<SortMode>().is_err());
+    }
+}
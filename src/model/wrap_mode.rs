@@ -0,0 +1,11 @@
+/// Defines how lines which are too long to fit in the main matches list are rendered.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum WrapMode {
+    /// Wrap long lines onto the next row.
+    #[default]
+    Wrap,
+    /// Wrap long lines onto the next row, and prefix each continuation row with a marker.
+    WrapIndent,
+    /// Don't wrap long lines, instead truncate them and allow scrolling horizontally.
+    NoWrap,
+}
@@ -0,0 +1,432 @@
+//! Unified diffs of replacements, which can be applied later with `git apply` (or `patch -p1`).
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::model::LineEnding;
+
+/// How many unchanged lines are shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// A replacement of the bytes in `range` of the original text with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+/// A run of lines in the original text which were changed by one or more edits.
+struct Change {
+    /// The (0-based) lines of the original text which were replaced.
+    old: Range<usize>,
+    /// The lines which replaced them.
+    new: Vec<String>,
+}
+
+/// The lines of `text`, including their line endings, along with the offset each one starts at.
+fn lines_with_offsets(text: &str) -> Vec<(usize, &str)> {
+    let mut offset = 0;
+    text.split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, line)
+        })
+        .collect()
+}
+
+/// The index of the line which contains the byte at `pos`. A position at the very end of the text is in the last
+/// line.
+fn line_of(lines: &[(usize, &str)], pos: usize) -> usize {
+    lines
+        .partition_point(|(start, _)| *start <= pos)
+        .saturating_sub(1)
+}
+
+/// Groups the `edits` (which must be sorted and not overlap) into the lines they change.
+fn changes(original: &str, edits: &[Edit]) -> Vec<Change> {
+    let lines = lines_with_offsets(original);
+    if lines.is_empty() {
+        let new = edits.iter().map(|e| e.text.as_str()).collect::<String>();
+        return vec![Change {
+            old: 0..0,
+            new: new.split_inclusive('\n').map(String::from).collect(),
+        }];
+    }
+
+    // the lines each edit spans, merging the edits which share a line
+    let mut groups: Vec<(Range<usize>, Vec<&Edit>)> = vec![];
+    for edit in edits {
+        let first = line_of(&lines, edit.range.start);
+        // the end of a range is exclusive, so a range which ends with a line ending doesn't include the next line
+        let last = line_of(
+            &lines,
+            edit.range.end.saturating_sub(1).max(edit.range.start),
+        );
+        match groups.last_mut() {
+            Some((span, group)) if first < span.end => {
+                span.end = span.end.max(last + 1);
+                group.push(edit);
+            }
+            _ => groups.push((first..last + 1, vec![edit])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(old, group)| {
+            let start = lines[old.start].0;
+            let (end_start, end_line) = lines[old.end - 1];
+            let end = end_start + end_line.len();
+
+            let mut new = String::new();
+            let mut pos = start;
+            for edit in group {
+                new.push_str(&original[pos..edit.range.start]);
+                new.push_str(&edit.text);
+                pos = edit.range.end;
+            }
+            new.push_str(&original[pos..end]);
+
+            Change {
+                old,
+                new: new.split_inclusive('\n').map(String::from).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Writes a line of a hunk, noting when it doesn't end with a newline (as the last line of a file may not).
+fn push_line(out: &mut String, prefix: char, line: &str) {
+    out.push(prefix);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// The range of a hunk's header, which is 1-based unless it's empty.
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Returns a unified diff of the `edits` made to the `original` text of the file at `path`, or an empty string if
+/// there aren't any. The `edits` must be sorted by their position and not overlap.
+pub fn unified_diff(path: &str, original: &str, edits: &[Edit]) -> String {
+    if edits.is_empty() {
+        return String::new();
+    }
+
+    diff_changes(path, original, &changes(original, edits))
+}
+
+/// The same as [`unified_diff`], but every line ending in the file is also converted to `line_ending` (see
+/// `--normalize-eol`), as it is when the replacements are written.
+pub fn unified_diff_with_line_ending(
+    path: &str,
+    original: &str,
+    edits: &[Edit],
+    line_ending: LineEnding,
+) -> String {
+    let lines = lines_with_offsets(original);
+    let mut changes = if edits.is_empty() {
+        vec![]
+    } else {
+        changes(original, edits)
+    };
+
+    // the changes span whole lines, so their line endings can be converted on their own
+    let mut is_edited = vec![false; lines.len()];
+    for change in changes.iter_mut() {
+        is_edited[change.old.clone()].fill(true);
+        change.new = line_ending
+            .normalize(&change.new.concat())
+            .split_inclusive('\n')
+            .map(String::from)
+            .collect();
+    }
+
+    // the lines which weren't edited, but whose line endings are converted
+    for (i, (_, line)) in lines.iter().enumerate() {
+        let normalized = line_ending.normalize(line);
+        if !is_edited[i] && normalized != *line {
+            changes.push(Change {
+                old: i..i + 1,
+                new: vec![normalized],
+            });
+        }
+    }
+
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // join the changes to adjacent lines, so their removed lines are listed before their added lines
+    changes.sort_by_key(|change| change.old.start);
+    let mut joined: Vec<Change> = vec![];
+    for change in changes {
+        match joined.last_mut() {
+            Some(last) if last.old.end == change.old.start => {
+                last.old.end = change.old.end;
+                last.new.extend(change.new);
+            }
+            _ => joined.push(change),
+        }
+    }
+
+    diff_changes(path, original, &joined)
+}
+
+/// Writes the unified diff of the `changes` (which must be sorted and not overlap) made to the `original` text.
+fn diff_changes(path: &str, original: &str, changes: &[Change]) -> String {
+    let lines = lines_with_offsets(original)
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect::<Vec<_>>();
+
+    // the changes whose context lines touch are shown in the same hunk
+    let mut hunks: Vec<Vec<&Change>> = vec![];
+    for change in changes {
+        match hunks.last_mut() {
+            Some(hunk)
+                if change.old.start.saturating_sub(CONTEXT_LINES)
+                    <= hunk.last().unwrap().old.end + CONTEXT_LINES =>
+            {
+                hunk.push(change)
+            }
+            _ => hunks.push(vec![change]),
+        }
+    }
+
+    let path = path.strip_prefix("./").unwrap_or(path);
+    let mut out = String::new();
+    let _ = writeln!(out, "--- a/{}", path);
+    let _ = writeln!(out, "+++ b/{}", path);
+
+    // how many more lines the new text has than the original, before the current hunk
+    let mut offset = 0isize;
+    for hunk in hunks {
+        let old_start = hunk[0].old.start.saturating_sub(CONTEXT_LINES);
+        let old_end = (hunk.last().unwrap().old.end + CONTEXT_LINES).min(lines.len());
+
+        let mut body = String::new();
+        let mut pos = old_start;
+        let mut new_len = 0;
+        for change in &hunk {
+            for line in &lines[pos..change.old.start] {
+                push_line(&mut body, ' ', line);
+            }
+            for line in &lines[change.old.clone()] {
+                push_line(&mut body, '-', line);
+            }
+            for line in &change.new {
+                push_line(&mut body, '+', line);
+            }
+            new_len += change.old.start - pos + change.new.len();
+            pos = change.old.end;
+        }
+        for line in &lines[pos..old_end] {
+            push_line(&mut body, ' ', line);
+        }
+        new_len += old_end - pos;
+
+        let old_len = old_end - old_start;
+        let new_start = (old_start as isize + offset) as usize;
+        let _ = writeln!(
+            out,
+            "@@ -{} +{} @@",
+            hunk_range(old_start, old_len),
+            hunk_range(new_start, new_len)
+        );
+        out.push_str(&body);
+        offset += new_len as isize - old_len as isize;
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::model::LineEnding;
+    use crate::patch::{unified_diff, unified_diff_with_line_ending, Edit};
+
+    fn edit(range: std::ops::Range<usize>, text: &str) -> Edit {
+        Edit {
+            range,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn it_diffs_a_single_replacement() {
+        let original = "one\ntwo foo\nthree\n";
+        assert_eq!(
+            unified_diff("./src/a.txt", original, &[edit(8..11, "bar")]),
+            "\
+--- a/src/a.txt
++++ b/src/a.txt
+@@ -1,3 +1,3 @@
+ one
+-two foo
++two bar
+ three
+"
+        );
+    }
+
+    #[test]
+    fn it_has_no_diff_without_edits() {
+        assert_eq!(unified_diff("a.txt", "foo\n", &[]), "");
+    }
+
+    #[test]
+    fn it_merges_edits_in_the_same_line() {
+        let original = "foo foo\n";
+        assert_eq!(
+            unified_diff("a.txt", original, &[edit(0..3, "x"), edit(4..7, "y")]),
+            "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,1 +1,1 @@
+-foo foo
++x y
+"
+        );
+    }
+
+    #[test]
+    fn it_splits_distant_edits_into_hunks() {
+        let original = (1..=20)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        let offset = |line: usize| original.match_indices("line").nth(line - 1).unwrap().0;
+        let edits = [
+            edit(offset(2)..offset(2) + 4, "LINE"),
+            // this replacement adds a line
+            edit(offset(15)..offset(15) + 4, "new\nLINE"),
+            edit(offset(17)..offset(17) + 4, "LINE"),
+        ];
+
+        assert_eq!(
+            unified_diff("a.txt", &original, &edits),
+            "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,5 +1,5 @@
+ line 1
+-line 2
++LINE 2
+ line 3
+ line 4
+ line 5
+@@ -12,9 +12,10 @@
+ line 12
+ line 13
+ line 14
+-line 15
++new
++LINE 15
+ line 16
+-line 17
++LINE 17
+ line 18
+ line 19
+ line 20
+"
+        );
+    }
+
+    #[test]
+    fn it_diffs_lines_without_a_trailing_newline() {
+        assert_eq!(
+            unified_diff("a.txt", "foo\nbar", &[edit(4..7, "baz")]),
+            "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+ foo
+-bar
+\\ No newline at end of file
++baz
+\\ No newline at end of file
+"
+        );
+    }
+
+    #[test]
+    fn it_diffs_edits_which_remove_lines() {
+        assert_eq!(
+            unified_diff("a.txt", "a\nfoo\nbar\nb\n", &[edit(2..10, "")]),
+            "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,4 +1,2 @@
+ a
+-foo
+-bar
+ b
+"
+        );
+    }
+
+    #[test]
+    fn it_converts_line_endings() {
+        let original = "a\r\nfoo\r\nb\nc\n";
+        let diff = |line_ending| {
+            unified_diff_with_line_ending("a.txt", original, &[edit(3..6, "x\ny")], line_ending)
+        };
+        assert_eq!(
+            diff(LineEnding::Lf),
+            "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,4 +1,5 @@
+-a\r
+-foo\r
++a
++x
++y
+ b
+ c
+"
+        );
+        assert_eq!(
+            diff(LineEnding::Crlf),
+            "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,4 +1,5 @@
+ a\r
+-foo\r
+-b
+-c
++x\r
++y\r
++b\r
++c\r
+"
+        );
+
+        // the line endings are converted even without any edits
+        assert_eq!(
+            unified_diff_with_line_ending("a.txt", "a\nb\r\n", &[], LineEnding::Lf),
+            "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+ a
+-b\r
++b
+"
+        );
+        assert_eq!(
+            unified_diff_with_line_ending("a.txt", "a\nb\n", &[], LineEnding::Lf),
+            ""
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::model::ReplacementCriteria;
+use crate::replace::replacement_for;
+use crate::rg::de::{ArbitraryData, RgMessageKind, SubMatch};
+
+/// A submatch from `rg`'s output, along with what it would be replaced with.
+#[derive(Serialize)]
+struct PreviewSubMatch<'a> {
+    #[serde(flatten)]
+    sub_match: &'a SubMatch,
+    /// `null` if the submatch won't be replaced.
+    replacement: Option<ArbitraryData>,
+}
+
+/// Writes each match as a line of JSON, in the same format as `rg --json`, except each submatch also has a
+/// `replacement` field with the text it would be replaced with. Nothing is written to disk.
+///
+/// Only `Match` messages are written, so tools (such as editors) can show the replacements without having to
+/// run the interface. `$COUNT` is numbered as if every selected match in the file will be replaced.
+pub fn write_json_preview<W: Write>(mut out: W, criteria: &ReplacementCriteria) -> Result<()> {
+    let mut byte_buf = vec![];
+    // how many matches have been replaced in each file so far, for `$COUNT`
+    let mut replaced_counts: HashMap<&ArbitraryData, usize> = HashMap::new();
+    for item in &criteria.items {
+        if !matches!(item.kind, RgMessageKind::Match) {
+            continue;
+        }
+
+        let line_bytes = item.lines().map(|lines| lines.to_vec());
+        let mut submatches = vec![];
+        for sub_item in item.sub_items() {
+            let is_replaced = sub_item.should_replace
+                && !item.is_truncated()
+                && (criteria.replace_empty_matches || !sub_item.sub_match.range.is_empty());
+            let replacement = if is_replaced {
+                let index = item.path().map_or(0, |path| {
+                    let count = replaced_counts.entry(path).or_default();
+                    *count += 1;
+                    *count - 1
+                });
+                let matched_bytes = sub_item.sub_match.text.to_vec();
+                let replacement = match &sub_item.replacement_override {
+                    Some(replacement) => replacement.as_slice(),
+                    None => replacement_for(
+                        criteria,
+                        &matched_bytes,
+                        line_bytes
+                            .as_deref()
+                            .map(|line| (line, &sub_item.sub_match.range)),
+                        index,
+                        &mut byte_buf,
+                    )?,
+                };
+                Some(ArbitraryData::from_bytes(replacement))
+            } else {
+                None
+            };
+
+            submatches.push(PreviewSubMatch {
+                sub_match: &sub_item.sub_match,
+                replacement,
+            });
+        }
+
+        let mut message = serde_json::to_value(item.rg_message())?;
+        message["data"]["submatches"] = serde_json::to_value(submatches)?;
+        serde_json::to_writer(&mut out, &message)?;
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use regex::bytes::Regex;
+
+    use crate::model::ReplacementCriteria;
+    use crate::preview::write_json_preview;
+    use crate::rg::de::test_utilities::*;
+    use crate::rg::de::*;
+
+    fn preview(criteria: &ReplacementCriteria) -> String {
+        let mut out = vec![];
+        write_json_preview(&mut out, criteria).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn messages() -> Vec<RgMessage> {
+        vec![
+            RgMessageBuilder::new(RgMessageKind::Begin)
+                .with_path_text("a.txt")
+                .build(),
+            RgMessageBuilder::new(RgMessageKind::Match)
+                .with_path_text("a.txt")
+                .with_lines_text("foo bar\n")
+                .with_line_number(1)
+                .with_offset(0)
+                .with_submatches(vec![SubMatch::new_text("foo bar", 0..7)])
+                .build(),
+            RgMessageBuilder::new(RgMessageKind::Match)
+                .with_path_text("a.txt")
+                .with_lines_text("fox baz foo qux\n")
+                .with_line_number(2)
+                .with_offset(8)
+                .with_submatches(vec![
+                    SubMatch::new_text("fox baz", 0..7),
+                    SubMatch::new_text("foo qux", 8..15),
+                ])
+                .build(),
+            RgMessageBuilder::new(RgMessageKind::End)
+                .with_path_text("a.txt")
+                .with_stats(Stats::new())
+                .build(),
+        ]
+    }
+
+    #[test]
+    fn it_previews_replacements_with_capturing_groups() {
+        let pattern = Regex::new(r"(?P<first>\w+) (\w+)").unwrap();
+        let criteria =
+            ReplacementCriteria::from_rg_messages(messages(), Some(pattern), "$2 ${first}")
+                .unwrap();
+
+        assert_eq!(
+            preview(&criteria),
+            [
+                r#"{"data":{"absolute_offset":0,"line_number":1,"lines":{"text":"foo bar\n"},"path":{"text":"a.txt"},"submatches":[{"end":7,"match":{"text":"foo bar"},"replacement":{"text":"bar foo"},"start":0}]},"type":"match"}"#,
+                r#"{"data":{"absolute_offset":8,"line_number":2,"lines":{"text":"fox baz foo qux\n"},"path":{"text":"a.txt"},"submatches":[{"end":7,"match":{"text":"fox baz"},"replacement":{"text":"baz fox"},"start":0},{"end":15,"match":{"text":"foo qux"},"replacement":{"text":"qux foo"},"start":8}]},"type":"match"}"#,
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_previews_replacements_with_count() {
+        let mut criteria =
+            ReplacementCriteria::from_rg_messages(messages(), None, "n$COUNT").unwrap();
+        criteria.items[2].set_should_replace(0, false);
+        criteria.set_count_start(0);
+
+        let lines = preview(&criteria);
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert!(lines[0].contains(r#""replacement":{"text":"n0"}"#));
+        assert!(lines[1].contains(r#""replacement":null"#));
+        assert!(lines[1].contains(r#""replacement":{"text":"n1"}"#));
+    }
+
+    #[test]
+    fn it_previews_submatches_which_are_not_replaced() {
+        let mut criteria = ReplacementCriteria::from_rg_messages(messages(), None, "x").unwrap();
+        criteria.items[2].set_should_replace(0, false);
+
+        let lines = preview(&criteria);
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains(r#""replacement":{"text":"x"}"#));
+        assert!(lines[1].contains(
+            r#"{"end":7,"match":{"text":"fox baz"},"replacement":null,"start":0},{"end":15,"match":{"text":"foo qux"},"replacement":{"text":"x"},"start":8}"#
+        ));
+    }
+}
@@ -1,24 +1,422 @@
-use std::fs::{File, OpenOptions};
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use encoding::{DecoderTrap, EncoderTrap};
+use regex::bytes::{Captures, Regex};
 use tempfile::NamedTempFile;
 
 use crate::encoding::{get_encoder, Bom};
 use crate::model::ReplacementCriteria;
+use crate::patch::{unified_diff, unified_diff_with_line_ending, Edit};
 use crate::rg::de::{ArbitraryData, SubMatch};
 use crate::rg::RgEncoding;
-use crate::ui::line::Item;
+use crate::ui::line::{Item, SubItem};
 
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+/// Like `shell_command`, but `args` are passed to the command as arguments after it, without being interpreted by
+/// the shell.
+#[cfg(not(windows))]
+fn shell_command_with_args(command: &str, args: &[PathBuf]) -> Command {
+    let mut cmd = Command::new("sh");
+    // the first argument after the script is `$0`, so the others are `"$@"`
+    cmd.arg("-c")
+        .arg(format!("{} \"$@\"", command))
+        .arg("sh")
+        .args(args);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell_command_with_args(command: &str, args: &[PathBuf]) -> Command {
+    let mut cmd = shell_command(command);
+    cmd.args(args);
+    cmd
+}
+
+/// Runs the user's after replace command with the paths of the files that were changed appended to it. Its output
+/// is logged, and the replacements are kept even if it fails.
+fn run_after_replace_command(command: &str, paths: &[PathBuf]) -> Result<()> {
+    log::debug!("Running after replace command: {} {:?}", command, paths);
+    let output = shell_command_with_args(command, paths)
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("Failed to run after replace command: {}", command))?;
+
+    log::debug!(
+        "After replace command stdout: {}",
+        String::from_utf8_lossy(&output.stdout).trim()
+    );
+    log::debug!(
+        "After replace command stderr: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+
+    if !output.status.success() {
+        bail!(
+            "After replace command \"{}\" failed ({}): {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether the file at `path` can't be written to, and will be skipped when performing replacements.
+/// Files which can't be read at all aren't considered read-only.
+pub(crate) fn is_read_only(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            metadata.permissions().readonly() || OpenOptions::new().write(true).open(path).is_err()
+        }
+        Err(_) => false,
+    }
+}
+
+/// The paths of the files which ripgrep found binary data in (see `Item::has_binary_data`). These are skipped
+/// when performing replacements, unless `--allow-binary` was passed.
+pub(crate) fn binary_paths(items: &[Item]) -> HashSet<&ArbitraryData> {
+    items
+        .iter()
+        .filter(|item| item.has_binary_data())
+        .filter_map(|item| item.path())
+        .collect()
+}
+
+/// Runs the user's replace command with the matched text on its stdin, and returns its stdout.
+/// If the command added a trailing newline that wasn't in the match, it's removed.
+fn run_replace_command(command: &str, matched_bytes: &[u8]) -> Result<Vec<u8>> {
+    log::debug!("Running replace command: {}", command);
+    let mut child = shell_command(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run replace command: {}", command))?;
+
+    // Write stdin from another thread, so a command that writes lots of output can't deadlock us.
+    let mut stdin = child.stdin.take().unwrap();
+    let output = thread::scope(|s| {
+        s.spawn(move || {
+            // NOTE: errors are ignored here since the command isn't required to read its stdin
+            let _ = stdin.write_all(matched_bytes);
+        });
+
+        child.wait_with_output()
+    })?;
+
+    if !output.status.success() {
+        bail!(
+            "Replace command \"{}\" failed ({}): {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut replacement = output.stdout;
+    if !matched_bytes.ends_with(b"\n") && replacement.ends_with(b"\n") {
+        replacement.pop();
+        if replacement.ends_with(b"\r") {
+            replacement.pop();
+        }
+    }
+
+    Ok(replacement)
+}
+
+/// The token in the replacement text which is expanded to the number of each match in its file.
+const COUNT_TOKEN: &[u8] = b"$COUNT";
+/// The same as `COUNT_TOKEN`, but may be followed by letters, digits or underscores.
+const COUNT_TOKEN_BRACED: &[u8] = b"${COUNT}";
+
+/// Splits the replacement text at each `$COUNT` (or `${COUNT}`) token. Like capturing group references, the
+/// unbraced token isn't one if it's followed by a letter, digit or underscore, and `$$COUNT` is left as it is.
+fn split_count_tokens(replacement: &[u8]) -> Vec<&[u8]> {
+    let mut segments = vec![];
+    let mut segment_start = 0;
+    let mut i = 0;
+    while i < replacement.len() {
+        let rest = &replacement[i..];
+        let token_len = if rest.starts_with(b"$$") {
+            i += 2;
+            continue;
+        } else if rest.starts_with(COUNT_TOKEN_BRACED) {
+            COUNT_TOKEN_BRACED.len()
+        } else if rest.starts_with(COUNT_TOKEN)
+            && !rest
+                .get(COUNT_TOKEN.len())
+                .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_')
+        {
+            COUNT_TOKEN.len()
+        } else {
+            i += 1;
+            continue;
+        };
+
+        segments.push(&replacement[segment_start..i]);
+        i += token_len;
+        segment_start = i;
+    }
+    segments.push(&replacement[segment_start..]);
+
+    segments
+}
+
+/// Whether the replacement text contains a `$COUNT` token (which doesn't apply to a replace command), so each
+/// match is replaced with different text.
+pub fn uses_count(criteria: &ReplacementCriteria) -> bool {
+    criteria.replace_command.is_none() && split_count_tokens(&criteria.user_replacement).len() > 1
+}
+
+/// Computes what `matched_bytes` should be replaced with: the output of the replace command, the replacement
+/// text expanded with the capture pattern's groups, or just the replacement text. `byte_buf` is used to hold
+/// the replacement when it has to be computed.
+///
+/// `index` is the position of the match among those replaced in its file (in the order they're in the file),
+/// which `$COUNT` is computed from.
+///
+/// `line` is the lines the match was found in, and the range of the match within them. It's only used when
+/// `capture_from_line` is set.
+pub(crate) fn replacement_for<'a>(
+    criteria: &'a ReplacementCriteria,
+    matched_bytes: &[u8],
+    line: Option<(&[u8], &Range<usize>)>,
+    index: usize,
+    byte_buf: &'a mut Vec<u8>,
+) -> Result<&'a [u8]> {
+    if let Some(command) = &criteria.replace_command {
+        // user passed a command to compute the replacement
+        *byte_buf = run_replace_command(command, matched_bytes)?;
+        return Ok(byte_buf.as_slice());
+    }
+
+    let captures = criteria.capture_pattern.as_ref().and_then(|re| {
+        match line.filter(|_| criteria.capture_from_line) {
+            Some((line, range)) => line_captures(re, line, range),
+            None => re.captures(matched_bytes),
+        }
+    });
+
+    // expand each segment between the `$COUNT` tokens on its own, so the count can't be mistaken for part of a
+    // capturing group reference (e.g. `$1$COUNT`)
+    let segments = split_count_tokens(&criteria.user_replacement);
+    if segments.len() > 1 {
+        let count = criteria.count_start + criteria.count_step * index as i64;
+        byte_buf.clear();
+        for (i, segment) in segments.iter().enumerate() {
+            if i > 0 {
+                byte_buf.extend_from_slice(count.to_string().as_bytes());
+            }
+            match &captures {
+                Some(captures) => captures.expand(segment, byte_buf),
+                None => byte_buf.extend_from_slice(segment),
+            }
+        }
+
+        return Ok(byte_buf.as_slice());
+    }
+
+    match captures {
+        // user passed a capturing group
+        Some(captures) => {
+            // empty buf without changing capacity
+            byte_buf.clear();
+            captures.expand(&criteria.user_replacement, byte_buf);
+            Ok(byte_buf.as_slice())
+        }
+        // just use raw replacement
+        None => Ok(criteria.user_replacement.as_slice()),
+    }
+}
+
+/// Returns the captures of the first match of `re` in `line` which contains the submatch at `range`, so the
+/// capturing groups may refer to the text around the submatch. If there's none, the pattern is matched against
+/// just the submatch's text instead.
+fn line_captures<'l>(re: &Regex, line: &'l [u8], range: &Range<usize>) -> Option<Captures<'l>> {
+    re.captures_iter(line)
+        .find(|captures| {
+            let m = captures.get(0).unwrap();
+            m.start() <= range.start && range.end <= m.end()
+        })
+        .or_else(|| line.get(range.clone()).and_then(|text| re.captures(text)))
+}
+
+/// Expands the capturing groups in the `replacement` text with those the capture pattern finds in
+/// `matched_bytes`, like `replacement_for` does when the replacements are written. `$COUNT` tokens are kept as
+/// they are, since they depend on which other matches are replaced. Returns `None` if the pattern doesn't match.
+pub fn expand_replacement(
+    capture_pattern: &Regex,
+    replacement: &str,
+    matched_bytes: &[u8],
+) -> Option<Vec<u8>> {
+    let captures = capture_pattern.captures(matched_bytes)?;
+    let mut expanded = vec![];
+    for (i, segment) in split_count_tokens(replacement.as_bytes())
+        .iter()
+        .enumerate()
+    {
+        if i > 0 {
+            expanded.extend_from_slice(COUNT_TOKEN);
+        }
+        captures.expand(segment, &mut expanded);
+    }
+
+    Some(expanded)
+}
+
+/// Whether the lines of `item` are still the same as when `rg` reported them.
+fn line_is_unchanged(file_as_str: &str, item: &Item) -> bool {
+    let offset = item.offset().unwrap_or_default();
+    item.lines().is_some_and(|lines| {
+        let lines = lines.to_vec();
+        file_as_str
+            .get(offset..offset + lines.len())
+            .is_some_and(|s| s.as_bytes() == lines.as_slice())
+    })
+}
+
+/// Whether the submatch (in a line at `offset`) can be replaced, because the file still contains the bytes `rg`
+/// reported. Zero-length matches (e.g. `^` or lookarounds) have no bytes of their own to check, so their whole
+/// line must be unchanged instead.
+fn is_replaceable(
+    file_as_str: &str,
+    offset: usize,
+    sub_match: &SubMatch,
+    line_is_unchanged: bool,
+) -> bool {
+    let range = (offset + sub_match.range.start)..(offset + sub_match.range.end);
+    if range.is_empty() {
+        return line_is_unchanged && file_as_str.is_char_boundary(range.start);
+    }
+
+    file_as_str
+        .get(range)
+        .is_some_and(|s| s.as_bytes() == sub_match.text.to_vec().as_slice())
+}
+
+/// Returns the submatches of `item` which were selected for replacement, sorted by their position. ripgrep shouldn't
+/// report submatches which overlap, but if it does then replacing both would corrupt the text between them, so any
+/// submatch which overlaps an earlier one is left out. Also returns whether any were left out.
+///
+/// None are returned if ripgrep may have truncated the item's line (`--max-columns`), since its offsets may not
+/// match the file.
+fn selected_sub_items<'a>(path_data: &ArbitraryData, item: &'a Item) -> (Vec<&'a SubItem>, bool) {
+    if item.is_truncated() {
+        let did_skip = item.replace_count() > 0;
+        if did_skip {
+            log::warn!("Skipping match in a line which rg truncated (--max-columns)!");
+            log::warn!("\tFile: \"{}\"", path_data);
+            log::warn!("\tOffset: {:?}", item.offset());
+        }
+        return (vec![], did_skip);
+    }
+
+    let mut sub_items = item
+        .sub_items()
+        .iter()
+        .filter(|s| s.should_replace)
+        .collect::<Vec<_>>();
+    sub_items.sort_by_key(|s| (s.sub_match.range.start, s.sub_match.range.end));
+
+    let mut selected: Vec<&SubItem> = Vec::with_capacity(sub_items.len());
+    let mut did_skip = false;
+    for sub_item in sub_items {
+        let range = &sub_item.sub_match.range;
+        match selected.last() {
+            Some(prev) if range.start < prev.sub_match.range.end => {
+                log::warn!("Skipping submatch which overlaps another!");
+                log::warn!("\tFile: \"{}\"", path_data);
+                log::warn!("\tOffset: {:?}", item.offset());
+                log::warn!("\tRange: {:?} overlaps {:?}", range, prev.sub_match.range);
+                did_skip = true;
+            }
+            _ => selected.push(sub_item),
+        }
+    }
+
+    (selected, did_skip)
+}
+
+/// What [`perform_replacements_in_file`] did to a file.
+#[derive(Debug, Default)]
+struct FileReplacements {
+    /// How many matches were replaced.
+    replaced: usize,
+    /// How many matches were left because the file already had `--max-replacements-per-file` replacements.
+    over_limit: usize,
+    /// Whether any replacement was skipped.
+    did_skip: bool,
+    /// Whether the replacements changed the file, so it was written (or added to the patch).
+    is_changed: bool,
+}
+
+/// Makes the replacements in a single file.
+///
+/// If `patch` is passed, then the file isn't written. Instead, a diff of the replacements is written to `patch`.
 fn perform_replacements_in_file(
     criteria: &ReplacementCriteria,
     rg_encoding: &RgEncoding,
     (path_data, mut items): (&ArbitraryData, Vec<&Item>),
-) -> Result<bool> {
+    patch: Option<&mut String>,
+) -> Result<FileReplacements> {
     log::debug!("File: {} (item count: {})", path_data, items.len());
     let path_buf = path_data.to_path_buf()?;
 
+    // The file may have been (re)moved since rg searched it.
+    if !path_buf.exists() {
+        log::warn!("Skipping file which no longer exists: {}", path_data);
+        if !criteria.quiet {
+            eprintln!("Skipping file which no longer exists: {}", path_data);
+        }
+        return Ok(FileReplacements {
+            did_skip: true,
+            ..Default::default()
+        });
+    }
+
+    // Replacing a symlink with the temporary file would break the link, so write the file it links to instead.
+    let target_path = if fs::symlink_metadata(&path_buf)?.file_type().is_symlink() {
+        if criteria.skip_symlinks {
+            log::warn!("Skipping symlink: {}", path_data);
+            if !criteria.quiet {
+                eprintln!("Skipping symlink: {}", path_data);
+            }
+            return Ok(FileReplacements {
+                did_skip: true,
+                ..Default::default()
+            });
+        }
+
+        let target_path = fs::canonicalize(&path_buf)?;
+        log::debug!("Following symlink to: {}", target_path.display());
+        target_path
+    } else {
+        path_buf.clone()
+    };
+
     // Check the file for a BOM, detect its encoding and then decode it into a string.
     let (bom, encoder, mut file_as_str) = {
         let mut file_contents = vec![];
@@ -28,7 +426,7 @@ fn perform_replacements_in_file(
             .read_to_end(&mut file_contents)?;
 
         // Search for a BOM and attempt to detect file encoding.
-        let (bom, encoder) = get_encoder(&file_contents, rg_encoding);
+        let (bom, encoder) = get_encoder(&file_contents, rg_encoding, criteria.encoding_override);
         log::debug!("BOM: {:?}", bom);
         log::debug!("Encoder: {}", encoder.name());
 
@@ -56,58 +454,150 @@ fn perform_replacements_in_file(
 
     // Sort the items so they're in order - ripgrep should give them to us in order anyway but we sort them here to
     // future-proof against any changes.
+    // NOTE: this only affects the order the replacements are made in, not the order the matches are displayed in
+    // (see `App::sort_list`), so it's needed even with `rg --sort=none`.
     // NOTE: we're sorting by the offset here with the assumption that no two Match items within one file will have
     // the same offset.
     items.sort_unstable_by_key(|i| i.offset());
 
+    // The submatches to replace in each item, without any which overlap.
+    let mut did_skip_replacement = false;
+    let selected = items
+        .iter()
+        .map(|item| {
+            let (mut sub_items, did_skip) = selected_sub_items(path_data, item);
+            did_skip_replacement |= did_skip;
+
+            // Zero-length matches (e.g. `^`) have nothing to remove, so they're only used as insertion points when
+            // the user asked for that.
+            if !criteria.replace_empty_matches {
+                let len = sub_items.len();
+                sub_items.retain(|s| !s.sub_match.range.is_empty());
+                if sub_items.len() < len {
+                    log::warn!("Skipping zero-length matches (see --replace-empty-matches)!");
+                    log::warn!("\tFile: \"{}\"", path_data);
+                    log::warn!("\tOffset: {:?}", item.offset());
+                    did_skip_replacement = true;
+                }
+            }
+
+            sub_items
+        })
+        .collect::<Vec<_>>();
+
+    // When the number of replacements is limited, then only the first matches in the file are replaced. Since the
+    // matches are replaced in reverse, count how many can be replaced so we know when we've reached them. This is
+    // also how each match's number is known when `$COUNT` is used.
+    let needs_index = criteria.max_replacements_per_file.is_some() || uses_count(criteria);
+    let mut replaceable_count = if needs_index {
+        items
+            .iter()
+            .zip(&selected)
+            .map(|(item, sub_items)| {
+                let offset = item.offset().unwrap();
+                let line_is_unchanged = line_is_unchanged(&file_as_str, item);
+                sub_items
+                    .iter()
+                    .filter(|s| {
+                        is_replaceable(&file_as_str, offset, &s.sub_match, line_is_unchanged)
+                    })
+                    .count()
+            })
+            .sum()
+    } else {
+        0
+    };
+    let mut over_limit_count = 0;
+
+    // the replacements are made in reverse, so their ranges are still those in the original file
+    let original = patch.is_some().then(|| file_as_str.clone());
+    let mut edits = vec![];
+
     // Iterate over the items in _reverse_ order -> this is so offsets can stay the same even though we're making
     // changes to the string.
-    let mut did_skip_replacement = false;
-    for (i, item) in items.iter().rev().enumerate() {
+    let mut replaced_count = 0;
+    // whether any replacement differs from the text it replaced, so the file has to be written
+    let mut is_changed = false;
+    for (i, (item, sub_items)) in items.iter().zip(&selected).rev().enumerate() {
         let offset = item.offset().unwrap();
         log::debug!("Item[{}] offset: {}", i, offset);
 
         let mut byte_buf = Vec::new();
+        // the capture pattern may be matched against the whole line, but only the submatch is replaced
+        let line_bytes = item.lines().map(|lines| lines.to_vec());
+
+        // Zero-length matches (e.g. `^` or lookarounds) have no bytes of their own to check, so check that the
+        // whole line is unchanged instead. This must be done before any of the line's submatches are replaced.
+        let line_is_unchanged = line_is_unchanged(&file_as_str, item);
 
         // Iterate backwards so the offset doesn't change as we make replacements.
-        for (i, sub_item) in item
-            .sub_items()
-            .iter()
-            .rev()
-            .filter(|s| s.should_replace)
-            .enumerate()
-        {
+        for (i, sub_item) in sub_items.iter().rev().enumerate() {
             let SubMatch { range, text } = &sub_item.sub_match;
             log::debug!("SubMatch[{}] range: {:?}, data: \"{}\"", i, range, text);
 
             let normalised_range = (offset + range.start)..(offset + range.end);
+
+            // Slicing or replacing a range that doesn't fall on char boundaries would panic, so skip it instead.
+            if !file_as_str.is_char_boundary(normalised_range.start)
+                || !file_as_str.is_char_boundary(normalised_range.end)
+            {
+                log::warn!("Replacement range does not fall on char boundaries!");
+                log::warn!("\tFile: \"{}\"", path_buf.display());
+                log::warn!("\tMatch: data=\"{}\"", text);
+                log::warn!("\tRange: {:?}", normalised_range);
+                did_skip_replacement = true;
+                continue;
+            }
+
             let str_to_remove = &file_as_str[normalised_range.clone()];
             let matched_bytes = text.to_vec();
 
-            if str_to_remove.as_bytes() == matched_bytes.as_slice() {
-                // compute replacement
-                let replacement = match criteria
-                    .capture_pattern
-                    .as_ref()
-                    .and_then(|re| re.captures(&matched_bytes))
-                {
-                    // user passed a capturing group
-                    Some(captures) => {
-                        // empty buf without changing capacity
-                        byte_buf.clear();
-                        captures.expand(&criteria.user_replacement, &mut byte_buf);
-                        byte_buf.as_slice()
+            let is_unchanged = if normalised_range.is_empty() {
+                line_is_unchanged
+            } else {
+                str_to_remove.as_bytes() == matched_bytes.as_slice()
+            };
+
+            if is_unchanged {
+                // this is now the position of the match among the replaceable matches in the file
+                if needs_index {
+                    replaceable_count -= 1;
+                }
+
+                // skip the match if it's not one of the first matches in the file
+                if let Some(max) = criteria.max_replacements_per_file {
+                    if replaceable_count >= max {
+                        over_limit_count += 1;
+                        continue;
                     }
-                    // just use raw replacement
-                    None => criteria.user_replacement.as_slice(),
+                }
+
+                // compute replacement, unless the user chose the text for this match on its own
+                let replacement = match &sub_item.replacement_override {
+                    Some(replacement) => replacement.as_slice(),
+                    None => replacement_for(
+                        criteria,
+                        &matched_bytes,
+                        line_bytes.as_deref().map(|line| (line, range)),
+                        replaceable_count,
+                        &mut byte_buf,
+                    )?,
                 };
 
                 // have to save this because it will be invalid after the replacement
                 let removed_str = str_to_remove.to_string();
                 // must convert to strings since due to encoding support we perform replacements as strings
-                let replacement = std::str::from_utf8(&replacement)?;
+                let replacement = std::str::from_utf8(replacement)?;
+                is_changed |= removed_str != replacement;
+                if original.is_some() {
+                    edits.push(Edit {
+                        range: normalised_range.clone(),
+                        text: replacement.to_string(),
+                    });
+                }
                 // performance replacement
                 file_as_str.replace_range(normalised_range, replacement);
+                replaced_count += 1;
 
                 log::debug!(
                     "Replacement - reported line: {:?}, removed: \"{}\", added: \"{}\"",
@@ -130,17 +620,90 @@ fn perform_replacements_in_file(
         }
     }
 
+    if over_limit_count > 0 {
+        log::info!(
+            "Left {} match(es) in {} (more than --max-replacements-per-file)",
+            over_limit_count,
+            path_buf.display()
+        );
+        if !criteria.quiet {
+            eprintln!(
+                "Left {} match(es) in {} (more than --max-replacements-per-file)",
+                over_limit_count,
+                path_buf.display()
+            );
+        }
+    }
+
+    let mut replacements = FileReplacements {
+        replaced: replaced_count,
+        over_limit: over_limit_count,
+        did_skip: did_skip_replacement,
+        is_changed,
+    };
+
+    // Convert the line endings, now that the replacements (which may contain line endings too) are made.
+    if let Some(line_ending) = criteria.normalize_eol {
+        log::debug!("Normalizing line endings to: {}", line_ending);
+        let normalized = line_ending.normalize(&file_as_str);
+        replacements.is_changed |= normalized != file_as_str;
+        file_as_str = normalized;
+    }
+
+    if let (Some(patch), Some(original)) = (patch, original) {
+        edits.reverse();
+        let path = path_buf.to_string_lossy();
+        *patch = match criteria.normalize_eol {
+            Some(line_ending) => {
+                unified_diff_with_line_ending(&path, &original, &edits, line_ending)
+            }
+            None => unified_diff(&path, &original, &edits),
+        };
+        return Ok(replacements);
+    }
+
+    // Leave the file (and its modification time) untouched if the replacements didn't change it, e.g. when the
+    // matches already equal their replacements because the same replacements were made before.
+    if !replacements.is_changed {
+        log::debug!("Skipping unchanged file: {}", path_buf.display());
+        return Ok(replacements);
+    }
+
     // Convert back into the detected encoding.
     log::trace!("Re-encoding file");
     let replaced_contents = encoder
         .encode(&file_as_str, EncoderTrap::Strict)
         .map_err(|e| anyhow!("Failed to encode replaced string: {}", e))?;
 
+    // Prepend a BOM if one existed beforehand.
+    let replaced_contents = match bom {
+        // NOTE: we don't strip a UTF8 BOM, because ripgrep doesn't either therefore no need to re-write one
+        // See: https://github.com/BurntSushi/ripgrep/issues/1638
+        Some(bom) if !matches!(bom, Bom::Utf8) => {
+            let bom_bytes = bom.bytes();
+            log::debug!("Writing BOM: {:?}", bom_bytes);
+            [bom_bytes, &replaced_contents].concat()
+        }
+        _ => replaced_contents,
+    };
+
+    // Overwrite the original file directly, for filesystems where renaming the temp file over it fails (or
+    // loses its ACLs). This isn't atomic, since the file is truncated before it's written.
+    if criteria.in_place {
+        log::debug!("Writing in place: {}", target_path.display());
+        let mut file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&target_path)?;
+        file.write_all(&replaced_contents)?;
+        return Ok(replacements);
+    }
+
     // Create a temporary file.
-    let parent_dir = path_buf.parent().with_context(|| {
+    let parent_dir = target_path.parent().with_context(|| {
         anyhow!(
             "Failed to get parent directory for file: {}",
-            path_buf.display()
+            target_path.display()
         )
     })?;
     let mut temp_file = NamedTempFile::new_in(parent_dir)?;
@@ -150,34 +713,123 @@ fn perform_replacements_in_file(
     // Adjust permissions of the file to match the target file's permissions
     // Related: https://github.com/Stebalien/tempfile/issues/157
     temp_file.as_file_mut().set_permissions({
-        let file = File::open(&path_buf)?;
+        let file = File::open(&target_path)?;
         file.metadata()?.permissions()
     })?;
 
-    // Write a BOM if one existed beforehand.
-    if let Some(bom) = bom {
-        // NOTE: we don't strip a UTF8 BOM, because ripgrep doesn't either therefore no need to re-write one
-        // See: https://github.com/BurntSushi/ripgrep/issues/1638
-        if !matches!(bom, Bom::Utf8) {
-            let bom_bytes = bom.bytes();
-            log::debug!("Writing BOM: {:?}", bom_bytes);
-            temp_file.write_all(bom_bytes)?;
-        }
-    }
-
     // Write the replaced contents.
     log::debug!("Writing: {}", temp_file_path);
     temp_file.write_all(&replaced_contents)?;
 
     // Overwrite the original file with the patched temp file.
-    log::debug!("Moving {} to {}", temp_file_path, path_buf.display());
-    temp_file.into_temp_path().persist(&path_buf)?;
+    log::debug!("Moving {} to {}", temp_file_path, target_path.display());
+    temp_file.into_temp_path().persist(&target_path)?;
+
+    Ok(replacements)
+}
+
+/// Whether every replacement was written, see [`perform_replacements`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplacementOutcome {
+    /// Every replacement was written.
+    Complete,
+    /// Some replacements were skipped (or a file couldn't be written), but the others were written.
+    Partial,
+}
+
+impl ReplacementOutcome {
+    /// The code `rgr` exits with after performing replacements: `0` if every replacement was written, `2` if
+    /// some of them were skipped, and `1` if they couldn't be performed at all (an `Err`).
+    pub fn exit_code(outcome: &Result<ReplacementOutcome>) -> i32 {
+        match outcome {
+            Ok(ReplacementOutcome::Complete) => 0,
+            Ok(ReplacementOutcome::Partial) => 2,
+            Err(_) => 1,
+        }
+    }
+}
 
-    Ok(did_skip_replacement)
+/// Counts of what `perform_replacements_with_stats` did, which are printed with `--stats`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReplacementStats {
+    /// How many files the replacements changed.
+    pub files_changed: usize,
+    /// How many matches were replaced in the files that were changed.
+    pub replaced: usize,
+    /// How many selected matches weren't replaced, e.g. because their file was skipped or had changed since
+    /// `rg` searched it. Matches left by `--max-replacements-per-file` aren't counted.
+    pub skipped: usize,
+    /// How long it took to write the replacements.
+    pub elapsed: Duration,
+    /// The files the replacements changed, and how many matches were replaced in each of them.
+    pub files: Vec<(PathBuf, usize)>,
 }
 
-pub fn perform_replacements(criteria: ReplacementCriteria) -> Result<()> {
+impl Display for ReplacementStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Changed {} file(s): made {} replacement(s) and skipped {}, in {:.2?}",
+            self.files_changed, self.replaced, self.skipped, self.elapsed
+        )
+    }
+}
+
+/// Writes the replacements described by `criteria` to disk.
+///
+/// Each file is only written if the bytes at each match's offset are still the bytes that `rg` reported.
+/// If any replacement was skipped, or a file couldn't be written, then `ReplacementOutcome::Partial` is
+/// returned after all other files have been processed. An `Err` is only returned if no replacements could be
+/// performed at all.
+///
+/// ```
+/// use std::fs;
+///
+/// use repgrep::model::ReplacementCriteria;
+/// use repgrep::replace::{perform_replacements, ReplacementOutcome};
+/// use repgrep::rg::de::RgMessage;
+/// use repgrep::Item;
+///
+/// let file = tempfile::NamedTempFile::new().unwrap();
+/// fs::write(file.path(), "foo bar baz").unwrap();
+/// let path = file.into_temp_path();
+///
+/// // a match, as reported by `rg --json foo`
+/// let message: RgMessage = serde_json::from_value(serde_json::json!({
+///     "type": "match",
+///     "data": {
+///         "path": { "text": path.to_str().unwrap() },
+///         "lines": { "text": "foo bar baz" },
+///         "line_number": 1,
+///         "absolute_offset": 0,
+///         "submatches": [{ "match": { "text": "foo" }, "start": 0, "end": 3 }]
+///     }
+/// }))
+/// .unwrap();
+///
+/// let criteria = ReplacementCriteria::new(None, "qux", vec![Item::new(0, message)]);
+/// let outcome = perform_replacements(criteria).unwrap();
+/// assert_eq!(outcome, ReplacementOutcome::Complete);
+/// assert_eq!(fs::read_to_string(&path).unwrap(), "qux bar baz");
+/// ```
+pub fn perform_replacements(criteria: ReplacementCriteria) -> Result<ReplacementOutcome> {
+    perform_replacements_with_stats(criteria).map(|(outcome, _)| outcome)
+}
+
+/// The same as [`perform_replacements`], but also counts how many matches were replaced and skipped.
+pub fn perform_replacements_with_stats(
+    criteria: ReplacementCriteria,
+) -> Result<(ReplacementOutcome, ReplacementStats)> {
     log::trace!("--- PERFORM REPLACEMENTS ---");
+    let start = Instant::now();
+    let mut stats = ReplacementStats::default();
+    if let Some(preprocessor) = &criteria.preprocessor {
+        bail!(
+            "Refusing to perform replacements, since rg searched the output of the preprocessor \"{}\" (--pre) rather than the files themselves",
+            preprocessor
+        );
+    }
+
     log::debug!(
         "Replacement text: \"{}\"",
         String::from_utf8_lossy(&criteria.user_replacement)
@@ -186,31 +838,116 @@ pub fn perform_replacements(criteria: ReplacementCriteria) -> Result<()> {
     let rg_encoding = RgEncoding::from(&criteria.encoding);
     log::debug!("User passed encoding: {:?}", rg_encoding);
 
+    let binary_paths = if criteria.allow_binary {
+        HashSet::new()
+    } else {
+        binary_paths(&criteria.items)
+    };
+
     // Group items by their file so we only open each file once.
     let mut did_skip_replacement = false;
+    let mut changed_paths = vec![];
+    let mut patches = vec![];
 
-    // TODO: consider concurrent replacements here - make it configurable - we don't want to read in multiple large files at once
     for meta in criteria.as_map() {
-        match perform_replacements_in_file(&criteria, &rg_encoding, meta) {
-            Ok(did_skip) => {
+        let (path_data, items) = &meta;
+        let selected_count = items.iter().map(|i| i.replace_count()).sum::<usize>();
+        // a patch doesn't write to the file, so it doesn't matter if it's read-only
+        if criteria.output_patch.is_none()
+            && path_data.to_path_buf().is_ok_and(|p| is_read_only(&p))
+        {
+            did_skip_replacement = true;
+            log::warn!("Skipping read-only file: {}", path_data);
+            if !criteria.quiet {
+                eprintln!("Skipping read-only file: {}", path_data);
+            }
+            stats.skipped += selected_count;
+            continue;
+        }
+        if binary_paths.contains(path_data) {
+            did_skip_replacement = true;
+            log::warn!("Skipping binary file: {}", path_data);
+            if !criteria.quiet {
+                eprintln!(
+                    "Skipping binary file (pass --allow-binary to replace in it): {}",
+                    path_data
+                );
+            }
+            stats.skipped += selected_count;
+            continue;
+        }
+
+        let path_data = *path_data;
+        let mut file_patch = String::new();
+        let patch = criteria.output_patch.is_some().then_some(&mut file_patch);
+        match perform_replacements_in_file(&criteria, &rg_encoding, meta, patch) {
+            Ok(FileReplacements {
+                replaced,
+                over_limit,
+                did_skip,
+                is_changed,
+            }) => {
                 if did_skip {
                     did_skip_replacement = true
                 }
+                // the matches in a file the replacements didn't change weren't replaced, nor skipped
+                if is_changed {
+                    stats.files_changed += 1;
+                    stats.replaced += replaced;
+                    stats
+                        .files
+                        .extend(path_data.to_path_buf().ok().map(|p| (p, replaced)));
+                    if criteria.output_patch.is_some() {
+                        patches.push((path_data.to_vec(), file_patch));
+                    } else {
+                        changed_paths.extend(path_data.to_path_buf().ok());
+                    }
+                }
+                // the matches over the limit were left on purpose, so they weren't skipped
+                stats.skipped += selected_count - replaced - over_limit;
             }
             Err(e) => {
                 did_skip_replacement = true;
+                stats.skipped += selected_count;
                 log::warn!("Failed to make all replacements: {}", e);
-                eprintln!("Failed to make all replacements: {}", e);
+                if !criteria.quiet {
+                    eprintln!("Failed to make all replacements: {}", e);
+                }
                 continue;
             }
         }
     }
 
+    // the files are in the patch in order, so it's the same for the same replacements
+    if let Some(patch_path) = &criteria.output_patch {
+        patches.sort();
+        let patch = patches
+            .into_iter()
+            .map(|(_, patch)| patch)
+            .collect::<String>();
+        fs::write(patch_path, patch)
+            .with_context(|| format!("Failed to write patch to {}", patch_path.display()))?;
+    }
+
+    if let Some(command) = &criteria.after_replace_command {
+        if !changed_paths.is_empty() {
+            changed_paths.sort();
+            if let Err(e) = run_after_replace_command(command, &changed_paths) {
+                log::warn!("{}", e);
+                if !criteria.quiet {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+    }
+
+    stats.files.sort();
+    stats.elapsed = start.elapsed();
     if did_skip_replacement {
         log::warn!("Failed to perform all replacements");
-        Err(anyhow!("Failed to perform all replacements, see log"))
+        Ok((ReplacementOutcome::Partial, stats))
     } else {
-        Ok(())
+        Ok((ReplacementOutcome::Complete, stats))
     }
 }
 
@@ -218,15 +955,19 @@ pub fn perform_replacements(criteria: ReplacementCriteria) -> Result<()> {
 mod tests {
     use std::fs::{self, OpenOptions};
     use std::io::Read;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
     #[cfg(not(any(target_os = "macos", target_os = "windows")))]
     use base64_simd::STANDARD as base64;
     use pretty_assertions::assert_eq;
     use regex::bytes::Regex;
 
+    use crate::encoding::Bom;
     use crate::model::*;
-    use crate::replace::perform_replacements;
+    use crate::replace::{
+        expand_replacement, is_read_only, perform_replacements, perform_replacements_with_stats,
+        split_count_tokens, ReplacementOutcome, ReplacementStats,
+    };
     use crate::rg::de::test_utilities::RgMessageBuilder;
     use crate::rg::de::{Duration, RgMessageKind, Stats, SubMatch};
     use crate::ui::line::*;
@@ -291,6 +1032,45 @@ mod tests {
             (s("foo", 0..3), r"$1 $1", re!("(foo)"), r"foo foo bar baz"),
             (s("foo", 0..3), r"bar$1", re!("(f)oo"), r"barf bar baz"),
             (s("foo", 0..3), r"$2🦀$2", re!("(f)(o+)"), r"oo🦀oo bar baz"),
+            // named capture groups
+            (
+                s("foo", 0..3),
+                r"[${word}]",
+                re!("(?P<word>foo)"),
+                r"[foo] bar baz",
+            ),
+            (
+                s("foo", 0..3),
+                r"$word!",
+                re!("(?P<word>foo)"),
+                r"foo! bar baz",
+            ),
+            (
+                s("foo", 0..3),
+                r"${o}${f}",
+                re!("(?P<f>f)(?P<o>o+)"),
+                r"oof bar baz",
+            ),
+            (
+                s("foo", 0..3),
+                r"${f}-$2",
+                re!("(?P<f>f)(o+)"),
+                r"f-oo bar baz",
+            ),
+            // unknown names are replaced with nothing, and `$word_` is the name "word_"
+            (
+                s("foo", 0..3),
+                r"[${nope}]",
+                re!("(?P<word>foo)"),
+                r"[] bar baz",
+            ),
+            (s("foo", 0..3), r"$word_", re!("(?P<word>foo)"), r" bar baz"),
+            (
+                s("foo", 0..3),
+                r"$$word",
+                re!("(?P<word>foo)"),
+                r"$word bar baz",
+            ),
             (
                 s("foo bar baz", 0..11),
                 r"¯\_(ツ)_/¯$3$4$1 ${2}f",
@@ -302,11 +1082,122 @@ mod tests {
         for (submatch, replacement, capture_pattern, expected) in test_cases {
             let (item1, p1) = temp_item!(0, file_text, vec![submatch]);
             let criteria = ReplacementCriteria::new(capture_pattern, replacement, vec![item1]);
-            perform_replacements(criteria).unwrap();
+            assert_eq!(
+                perform_replacements(criteria).unwrap(),
+                ReplacementOutcome::Complete
+            );
             assert_eq!(fs::read_to_string(p1).unwrap(), expected);
         }
     }
 
+    #[test]
+    fn it_expands_captures_of_whole_line_matches() {
+        // with `-x/--line-regexp` ripgrep matches the whole line, so the pattern is anchored (see
+        // `RgArgs::build_regex`) and the captures are taken from the whole line
+        let (item, p) = temp_item!(0, "foo bar\n", vec![SubMatch::new_text("foo bar", 0..7)]);
+        let criteria =
+            ReplacementCriteria::new(re!(r"^(?:(\w+)|(\w+) (\w+))$"), "$3 $2", vec![item]);
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "bar foo\n");
+    }
+
+    #[test]
+    fn it_expands_replacement_previews() {
+        let re = Regex::new(r"(?P<first>\w)(\w+)").unwrap();
+        let expand = |replacement, matched: &str| {
+            expand_replacement(&re, replacement, matched.as_bytes())
+                .map(|bytes| String::from_utf8(bytes).unwrap())
+        };
+
+        assert_eq!(expand("${1}bar", "foo").as_deref(), Some("fbar"));
+        // this is the group named "1bar", which is why the preview is useful
+        assert_eq!(expand("$1bar", "foo").as_deref(), Some(""));
+        assert_eq!(expand("${2}-${first}", "foo").as_deref(), Some("oo-f"));
+        assert_eq!(expand("bar", "foo").as_deref(), Some("bar"));
+        assert_eq!(expand("$$1", "foo").as_deref(), Some("$1"));
+        // unknown groups are expanded to nothing
+        assert_eq!(expand("<$3>", "foo").as_deref(), Some("<>"));
+        // `$COUNT` is kept, since it depends on the other matches
+        assert_eq!(expand("$1$COUNT", "foo").as_deref(), Some("f$COUNT"));
+        assert_eq!(expand("${COUNT}_$2", "foo").as_deref(), Some("$COUNT_oo"));
+        // the pattern doesn't match
+        assert_eq!(expand("$1", "!"), None);
+    }
+
+    #[test]
+    fn it_swaps_capturing_groups() {
+        // groups may be referred to in any order, since they're all captured from the whole match
+        let (item, p) = temp_item!(
+            0,
+            "foo bar\nbaz qux\n",
+            vec![SubMatch::new_text("foo bar", 0..7)]
+        );
+        let criteria = ReplacementCriteria::new(re!(r"(\w+) (\w+)"), "$2 $1", vec![item]);
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "bar foo\nbaz qux\n");
+
+        let re = Regex::new(r"(\w+) (\w+)").unwrap();
+        let expand = |replacement| {
+            expand_replacement(&re, replacement, b"foo bar").map(|b| String::from_utf8(b).unwrap())
+        };
+        assert_eq!(expand("$2 $1").as_deref(), Some("bar foo"));
+        assert_eq!(expand("${2}${1}$2").as_deref(), Some("barfoobar"));
+    }
+
+    #[test]
+    fn it_expands_captures_with_inline_flags() {
+        let text = "FOO bar Foo fOo\nfoo";
+        let s = |a, b| SubMatch::new_text(a, b);
+        for (capture_pattern, replacement, expected) in [
+            (r"(?i)(f)(o+)", "$2$1", "OOF bar ooF Oof\noof"),
+            (
+                r"(?i)(?P<f>F)(?P<o>O+)",
+                "${o}-${f}",
+                "OO-F bar oo-F Oo-f\noo-f",
+            ),
+        ] {
+            let p = temp_file!(text);
+            let items = vec![
+                Item::new(
+                    0,
+                    RgMessageBuilder::new(RgMessageKind::Match)
+                        .with_path_text(p.to_string_lossy())
+                        .with_lines_text("FOO bar Foo fOo\n")
+                        .with_submatches(vec![s("FOO", 0..3), s("Foo", 8..11), s("fOo", 12..15)])
+                        .with_offset(0)
+                        .build(),
+                ),
+                Item::new(
+                    1,
+                    RgMessageBuilder::new(RgMessageKind::Match)
+                        .with_path_text(p.to_string_lossy())
+                        .with_lines_text("foo")
+                        .with_submatches(vec![s("foo", 0..3)])
+                        .with_offset(16)
+                        .build(),
+                ),
+            ];
+
+            let criteria = ReplacementCriteria::new(re!(capture_pattern), replacement, items);
+            assert_eq!(
+                perform_replacements(criteria).unwrap(),
+                ReplacementOutcome::Complete
+            );
+            assert_eq!(
+                fs::read_to_string(p).unwrap(),
+                expected,
+                "{}",
+                capture_pattern
+            );
+        }
+    }
+
     #[test]
     fn it_performs_replacements_only_on_match_items() {
         let text = "foo bar baz";
@@ -338,7 +1229,10 @@ mod tests {
             build_item(RgMessageKind::Summary, &p5),
         ];
 
-        perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", items)).unwrap();
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", items)).unwrap(),
+            ReplacementOutcome::Complete
+        );
         assert_eq!(fs::read_to_string(p1).unwrap(), text);
         assert_eq!(fs::read_to_string(p2).unwrap(), text);
         assert_eq!(fs::read_to_string(p3).unwrap(), "NEW_VALUE bar baz");
@@ -347,31 +1241,119 @@ mod tests {
     }
 
     #[test]
-    #[cfg(not(target_os = "windows"))]
-    fn it_performs_replacements_and_keeps_permissions() {
-        use std::os::unix::fs::PermissionsExt;
-        // create temporary item
-        let (item, path) = temp_item!(0, "foo bar baz", vec![SubMatch::new_text("foo", 0..3)]);
+    fn it_keeps_references_literal_without_a_capture_pattern() {
+        // as with `-F`, where the pattern isn't compiled so no capturing groups are expanded
+        let (item, p) = temp_item!(0, "total: 10\n", vec![SubMatch::new_text("total:", 0..6)]);
 
-        // get permissions of file
-        let perms = || fs::metadata(&path).unwrap().permissions();
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "price: $1", vec![item])).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "price: $1 10\n");
+    }
 
-        // set permissions to 777
-        let mut p = perms();
-        p.set_mode(0o777);
-        fs::set_permissions(&path, p).unwrap();
+    #[test]
+    fn it_does_not_replace_matches_in_truncated_lines() {
+        let (short_item, short_path) =
+            temp_item!(0, "foo bar\n", vec![SubMatch::new_text("foo", 0..3)]);
+        let (long_item, long_path) = temp_item!(
+            0,
+            "foo bar baz qux\n",
+            vec![SubMatch::new_text("foo", 0..3)]
+        );
 
-        // sanity check perms were set
-        assert_eq!(perms().mode(), 0o100777);
+        let mut criteria = ReplacementCriteria::new(None, "NEW", vec![short_item, long_item]);
+        criteria.set_max_columns(10);
+        assert!(!criteria.items[0].is_truncated());
+        assert!(criteria.items[1].is_truncated());
 
-        // perform replacement
-        perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", vec![item])).unwrap();
-        assert_eq!(fs::read_to_string(&path).unwrap(), "NEW_VALUE bar baz");
+        let (outcome, stats) = perform_replacements_with_stats(criteria).unwrap();
+        assert_eq!(outcome, ReplacementOutcome::Partial);
+        assert_eq!((stats.replaced, stats.skipped), (1, 1));
+        assert_eq!(fs::read_to_string(short_path).unwrap(), "NEW bar\n");
+        assert_eq!(fs::read_to_string(long_path).unwrap(), "foo bar baz qux\n");
+    }
+
+    #[test]
+    fn it_performs_different_replacements_per_submatch() {
+        let (mut item, p) = temp_item!(
+            0,
+            "foo bar baz",
+            vec![
+                SubMatch::new_text("foo", 0..3),
+                SubMatch::new_text("bar", 4..7),
+                SubMatch::new_text("baz", 8..11),
+            ]
+        );
+        item.set_replacement_override(0, b"A".to_vec());
+        item.set_replacement_override(1, b"B".to_vec());
+
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "X", vec![item])).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "A B X");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn it_performs_replacements_and_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        // create temporary item
+        let (item, path) = temp_item!(0, "foo bar baz", vec![SubMatch::new_text("foo", 0..3)]);
+
+        // get permissions of file
+        let perms = || fs::metadata(&path).unwrap().permissions();
+
+        // set permissions to 777
+        let mut p = perms();
+        p.set_mode(0o777);
+        fs::set_permissions(&path, p).unwrap();
+
+        // sanity check perms were set
+        assert_eq!(perms().mode(), 0o100777);
+
+        // perform replacement
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", vec![item])).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "NEW_VALUE bar baz");
 
         // now check permissions are what we expect
         assert_eq!(perms().mode(), 0o100777);
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn it_does_not_write_files_the_replacements_do_not_change() {
+        use std::os::unix::fs::MetadataExt;
+
+        let (item, path) = temp_item!(0, "foo bar baz", vec![SubMatch::new_text("foo", 0..3)]);
+        let metadata = fs::metadata(&path).unwrap();
+        // wait a little, so a write would change the modification time
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        // the match is replaced with the same text, e.g. when the replacements are made again
+        let (outcome, stats) =
+            perform_replacements_with_stats(ReplacementCriteria::new(None, "foo", vec![item]))
+                .unwrap();
+        assert_eq!(outcome, ReplacementOutcome::Complete);
+        assert_eq!(
+            (stats.files_changed, stats.replaced, stats.skipped),
+            (0, 0, 0)
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "foo bar baz");
+
+        // the file wasn't written, nor replaced with a new one
+        let new_metadata = fs::metadata(&path).unwrap();
+        assert_eq!(
+            new_metadata.modified().unwrap(),
+            metadata.modified().unwrap()
+        );
+        assert_eq!(new_metadata.ino(), metadata.ino());
+    }
+
     #[test]
     fn it_performs_replacements_in_separate_files() {
         let (item1, p1) = temp_item!(0, "foo bar baz", vec![SubMatch::new_text("foo", 0..3)]);
@@ -379,7 +1361,10 @@ mod tests {
         let (item3, p3) = temp_item!(0, "bar baz foo", vec![SubMatch::new_text("foo", 8..11)]);
 
         let items = vec![item1, item2, item3];
-        perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", items)).unwrap();
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", items)).unwrap(),
+            ReplacementOutcome::Complete
+        );
         assert_eq!(fs::read_to_string(p1).unwrap(), "NEW_VALUE bar baz");
         assert_eq!(fs::read_to_string(p2).unwrap(), "baz NEW_VALUE bar");
         assert_eq!(fs::read_to_string(p3).unwrap(), "bar baz NEW_VALUE");
@@ -397,7 +1382,10 @@ mod tests {
         items[1].set_should_replace(0, true);
         items[2].set_should_replace(0, false);
 
-        perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", items)).unwrap();
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", items)).unwrap(),
+            ReplacementOutcome::Complete
+        );
         assert_eq!(fs::read_to_string(p1).unwrap(), "foo bar baz");
         assert_eq!(fs::read_to_string(p2).unwrap(), "baz NEW_VALUE bar");
         assert_eq!(fs::read_to_string(p3).unwrap(), "bar baz foo");
@@ -415,7 +1403,10 @@ mod tests {
             ]
         );
 
-        perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", vec![item])).unwrap();
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", vec![item])).unwrap(),
+            ReplacementOutcome::Complete
+        );
         assert_eq!(
             fs::read_to_string(p).unwrap(),
             "NEW_VALUE NEW_VALUE NEW_VALUE"
@@ -457,7 +1448,10 @@ mod tests {
             ),
         ];
 
-        perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", items)).unwrap();
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", items)).unwrap(),
+            ReplacementOutcome::Complete
+        );
         assert_eq!(
             fs::read_to_string(p).unwrap(),
             "NEW_VALUE bar baz\n...\nbaz NEW_VALUE bar\n...\nbar baz NEW_VALUE"
@@ -490,13 +1484,917 @@ mod tests {
             ),
         ];
 
-        perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", items)).unwrap();
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "NEW_VALUE", items)).unwrap(),
+            ReplacementOutcome::Complete
+        );
         assert_eq!(
             fs::read_to_string(p).unwrap(),
             "foo bar baz\n...\nbaz NEW_VALUE bar\n...\nbar NEW_VALUE foo"
         );
     }
 
+    #[test]
+    fn it_skips_replacements_not_on_char_boundaries() {
+        // the range starts in the middle of the (4 byte) crab emoji
+        let text = "🦀 bar baz";
+        let (item1, p1) = temp_item!(0, text, vec![SubMatch::new_text("🦀", 1..5)]);
+        let (item2, p2) = temp_item!(0, "foo bar baz", vec![SubMatch::new_text("foo", 0..3)]);
+
+        let result =
+            perform_replacements(ReplacementCriteria::new(None, "NEW", vec![item1, item2]));
+        assert_eq!(result.unwrap(), ReplacementOutcome::Partial);
+        assert_eq!(fs::read_to_string(p1).unwrap(), text);
+        assert_eq!(fs::read_to_string(p2).unwrap(), "NEW bar baz");
+    }
+
+    macro_rules! replace_command_criteria {
+        ($command:expr, $items:expr) => {{
+            let mut criteria = ReplacementCriteria::new(None, "IGNORED", $items);
+            criteria.set_replace_command($command);
+            criteria
+        }};
+    }
+
+    #[test]
+    fn it_performs_replacements_with_trimmed_lines() {
+        let text = "    foo bar\n\t\tbaz foo\n";
+        let p = temp_file!(text);
+        let items = vec![(0, "    foo bar\n", 4..7), (12, "\t\tbaz foo\n", 6..9)]
+            .into_iter()
+            .map(|(offset, lines, range)| {
+                let mut rg_message = RgMessageBuilder::new(RgMessageKind::Match)
+                    .with_path_text(p.to_string_lossy())
+                    .with_lines_text(lines)
+                    .with_submatches(vec![SubMatch::new_text("foo", range)])
+                    .with_offset(offset)
+                    .build();
+                rg_message.trim_leading_whitespace();
+                Item::new(0, rg_message)
+            })
+            .collect();
+
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "qux", items)).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "    qux bar\n\t\tbaz qux\n");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn it_performs_replacements_with_replace_command() {
+        let (item1, p1) = temp_item!(0, "foo bar baz", vec![SubMatch::new_text("foo", 0..3)]);
+        let (item2, p2) = temp_item!(0, "foo bar baz", vec![SubMatch::new_text("bar", 4..7)]);
+
+        // identity
+        assert_eq!(
+            perform_replacements(replace_command_criteria!("cat", vec![item1])).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p1).unwrap(), "foo bar baz");
+
+        // transform
+        assert_eq!(
+            perform_replacements(replace_command_criteria!("tr a-z A-Z", vec![item2])).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p2).unwrap(), "foo BAR baz");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn it_runs_the_after_replace_command_with_the_changed_paths() {
+        let (item1, p1) = temp_item!(0, "foo bar", vec![SubMatch::new_text("foo", 0..3)]);
+        let (item2, p2) = temp_item!(0, "bar foo", vec![SubMatch::new_text("foo", 4..7)]);
+        let (mut item3, p3) = temp_item!(0, "foo baz", vec![SubMatch::new_text("foo", 0..3)]);
+        // nothing is replaced in this file
+        item3.set_should_replace_all(false);
+
+        let log = temp_file!("");
+        let mut criteria = ReplacementCriteria::new(None, "NEW", vec![item1, item2, item3]);
+        criteria.set_after_replace_command(format!("echo >> '{}'", log.display()));
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+
+        let mut expected = [p1.display().to_string(), p2.display().to_string()];
+        expected.sort();
+        assert_eq!(fs::read_to_string(log).unwrap(), expected.join(" ") + "\n");
+        assert_eq!(fs::read_to_string(p3).unwrap(), "foo baz");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn it_does_not_run_the_after_replace_command_without_changes() {
+        let (mut item, _) = temp_item!(0, "foo bar", vec![SubMatch::new_text("foo", 0..3)]);
+        item.set_should_replace_all(false);
+
+        let log = temp_file!("");
+        let mut criteria = ReplacementCriteria::new(None, "NEW", vec![item]);
+        criteria.set_after_replace_command(format!("echo >> '{}'", log.display()));
+        perform_replacements(criteria).unwrap();
+        assert_eq!(fs::read_to_string(log).unwrap(), "");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn it_keeps_replacements_when_the_after_replace_command_fails() {
+        let (item, p) = temp_item!(0, "foo bar", vec![SubMatch::new_text("foo", 0..3)]);
+        let mut criteria = ReplacementCriteria::new(None, "NEW", vec![item]);
+        criteria.set_after_replace_command("false");
+        criteria.quiet = true;
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "NEW bar");
+    }
+
+    #[test]
+    fn it_writes_a_patch_instead_of_the_files() {
+        let (item, p) = temp_item!(
+            4,
+            "foo bar\n",
+            vec![
+                SubMatch::new_text("foo", 0..3),
+                SubMatch::new_text("bar", 4..7)
+            ]
+        );
+        fs::write(&p, "one\nfoo bar\nthree\n").unwrap();
+        let patch_path = temp_file!("");
+
+        let mut criteria = ReplacementCriteria::new(None, "NEW", vec![item]);
+        criteria.set_output_patch(&patch_path);
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+
+        // the file is unchanged
+        assert_eq!(fs::read_to_string(&p).unwrap(), "one\nfoo bar\nthree\n");
+        let path = p.to_string_lossy();
+        assert_eq!(
+            fs::read_to_string(patch_path).unwrap(),
+            format!(
+                "--- a/{0}\n+++ b/{0}\n@@ -1,3 +1,3 @@\n one\n-foo bar\n+NEW NEW\n three\n",
+                path
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_writes_a_patch_of_read_only_files_with_normalized_line_endings() {
+        let (item, p) = temp_item!(5, "foo\r\n", vec![SubMatch::new_text("foo", 0..3)]);
+        fs::write(&p, "one\r\nfoo\r\nthree\r\n").unwrap();
+        set_read_only(&p);
+        let patch_path = temp_file!("");
+
+        let mut criteria = ReplacementCriteria::new(None, "NEW", vec![item]);
+        criteria.set_output_patch(&patch_path);
+        criteria.set_normalize_eol(LineEnding::Lf);
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+
+        let path = p.to_string_lossy();
+        assert_eq!(
+            fs::read_to_string(patch_path).unwrap(),
+            format!(
+                "--- a/{0}\n+++ b/{0}\n@@ -1,3 +1,3 @@\n-one\r\n-foo\r\n-three\r\n+one\n+NEW\n+three\n",
+                path
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn it_performs_replacements_with_replace_command_trailing_newline() {
+        let (item, p) = temp_item!(0, "foo bar baz", vec![SubMatch::new_text("foo", 0..3)]);
+
+        // `echo` adds a trailing newline which wasn't in the match, so it should be removed
+        assert_eq!(
+            perform_replacements(replace_command_criteria!("echo qux", vec![item])).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "qux bar baz");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn it_skips_files_when_replace_command_fails() {
+        let text = "foo bar baz";
+        let (item, p) = temp_item!(0, text, vec![SubMatch::new_text("foo", 0..3)]);
+
+        let result = perform_replacements(replace_command_criteria!("exit 1", vec![item]));
+        assert_eq!(result.unwrap(), ReplacementOutcome::Partial);
+        assert_eq!(fs::read_to_string(p).unwrap(), text);
+    }
+
+    #[cfg(unix)]
+    fn set_read_only(path: &std::path::Path) {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, fs::Permissions::from_mode(0o444)).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_flags_read_only_files() {
+        let p = temp_file!("foo bar baz");
+        assert!(!is_read_only(&p));
+
+        set_read_only(&p);
+        assert!(is_read_only(&p));
+
+        // files which don't exist aren't flagged
+        assert!(!is_read_only(&p.with_extension("missing")));
+    }
+
+    fn line_item(p: &Path, lines: &str, offset: usize, submatches: Vec<SubMatch>) -> Item {
+        Item::new(
+            0,
+            RgMessageBuilder::new(RgMessageKind::Match)
+                .with_path_text(p.to_string_lossy())
+                .with_lines_text(lines)
+                .with_submatches(submatches)
+                .with_offset(offset)
+                .build(),
+        )
+    }
+
+    #[test]
+    fn it_inserts_text_at_zero_length_matches() {
+        // as reported by `rg '^'`
+        let p = temp_file!("foo\nbar\n\nbaz");
+        let empty = || vec![SubMatch::new_text("", 0..0)];
+        let items = vec![
+            line_item(&p, "foo\n", 0, empty()),
+            line_item(&p, "bar\n", 4, empty()),
+            line_item(&p, "\n", 8, empty()),
+            line_item(&p, "baz", 9, empty()),
+        ];
+
+        let mut criteria = ReplacementCriteria::new(None, "// ", items);
+        criteria.set_replace_empty_matches(true);
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(
+            fs::read_to_string(p).unwrap(),
+            "// foo\n// bar\n// \n// baz"
+        );
+    }
+
+    #[test]
+    fn it_skips_zero_length_matches_by_default() {
+        // as reported by `rg '^'`
+        let p = temp_file!("foo\nbar\n");
+        let empty = || vec![SubMatch::new_text("", 0..0)];
+        let items = vec![
+            line_item(&p, "foo\n", 0, empty()),
+            line_item(&p, "bar\n", 4, empty()),
+        ];
+
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "// ", items)).unwrap(),
+            ReplacementOutcome::Partial
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "foo\nbar\n");
+    }
+
+    #[test]
+    fn it_inserts_text_at_many_zero_length_matches_in_a_line() {
+        // as reported by `rg '\b'`
+        let s = |range| SubMatch::new_text("", range);
+        let p = temp_file!("foo bar\n");
+        let item = line_item(&p, "foo bar\n", 0, vec![s(0..0), s(3..3), s(4..4), s(7..7)]);
+
+        let mut criteria = ReplacementCriteria::new(None, "|", vec![item]);
+        criteria.set_replace_empty_matches(true);
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "|foo| |bar|\n");
+    }
+
+    #[test]
+    fn it_skips_overlapping_submatches() {
+        let s = |text, range| SubMatch::new_text(text, range);
+        let p = temp_file!("foobar baz\n");
+        // the submatches aren't in order, and the second overlaps the first
+        let item = line_item(
+            &p,
+            "foobar baz\n",
+            0,
+            vec![s("baz", 7..10), s("foob", 0..4), s("obar", 2..6)],
+        );
+
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "X", vec![item])).unwrap(),
+            ReplacementOutcome::Partial
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "Xar X\n");
+    }
+
+    #[test]
+    fn it_inserts_text_at_zero_length_and_other_matches() {
+        // as reported by `rg '$|o'`, with a capturing group
+        let s = |text, range| SubMatch::new_text(text, range);
+        let p = temp_file!("foo\n");
+        let item = line_item(
+            &p,
+            "foo\n",
+            0,
+            vec![s("o", 1..2), s("o", 2..3), s("", 3..3)],
+        );
+
+        let mut criteria =
+            ReplacementCriteria::new(Some(Regex::new("(o)?").unwrap()), "<$1>", vec![item]);
+        criteria.set_replace_empty_matches(true);
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "f<o><o><>\n");
+    }
+
+    #[test]
+    fn it_skips_zero_length_matches_in_changed_lines() {
+        let p = temp_file!("foo\nbar\n");
+        let items = vec![
+            // the line has changed since `rg` searched the file
+            line_item(&p, "fob\n", 0, vec![SubMatch::new_text("", 0..0)]),
+            line_item(&p, "bar\n", 4, vec![SubMatch::new_text("", 0..0)]),
+        ];
+
+        let mut criteria = ReplacementCriteria::new(None, "// ", items);
+        criteria.set_replace_empty_matches(true);
+        let result = perform_replacements(criteria);
+        assert_eq!(result.unwrap(), ReplacementOutcome::Partial);
+        assert_eq!(fs::read_to_string(p).unwrap(), "foo\n// bar\n");
+    }
+
+    fn limited_criteria(p: &Path, max: usize, deselect: &[usize]) -> ReplacementCriteria {
+        let s = |text, range| SubMatch::new_text(text, range);
+        let mut first = line_item(
+            p,
+            "foo foo foo\n",
+            0,
+            vec![s("foo", 0..3), s("foo", 4..7), s("foo", 8..11)],
+        );
+        for idx in deselect {
+            first.set_should_replace(*idx, false);
+        }
+        let items = vec![first, line_item(p, "foo\n", 12, vec![s("foo", 0..3)])];
+
+        let mut criteria = ReplacementCriteria::new(None, "bar", items);
+        criteria.set_max_replacements_per_file(max);
+        criteria
+    }
+
+    #[test]
+    fn it_only_replaces_the_first_matches_in_each_file() {
+        let text = "foo foo foo\nfoo\n";
+        let expected = [
+            "foo foo foo\nfoo\n",
+            "bar foo foo\nfoo\n",
+            "bar bar foo\nfoo\n",
+            "bar bar bar\nfoo\n",
+            "bar bar bar\nbar\n",
+            "bar bar bar\nbar\n",
+        ];
+        for (max, expected) in expected.iter().enumerate() {
+            let p = temp_file!(text);
+            assert_eq!(
+                perform_replacements(limited_criteria(&p, max, &[])).unwrap(),
+                ReplacementOutcome::Complete
+            );
+            assert_eq!(fs::read_to_string(p).unwrap(), *expected, "max: {}", max);
+        }
+    }
+
+    #[test]
+    fn it_only_counts_selected_matches_towards_the_limit() {
+        let p = temp_file!("foo foo foo\nfoo\n");
+        assert_eq!(
+            perform_replacements(limited_criteria(&p, 2, &[0])).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "foo bar bar\nfoo\n");
+    }
+
+    #[test]
+    fn it_does_not_count_matches_over_the_limit_as_skipped() {
+        let p = temp_file!("foo foo foo\nfoo\n");
+        let (outcome, stats) =
+            perform_replacements_with_stats(limited_criteria(&p, 2, &[])).unwrap();
+        assert_eq!(outcome, ReplacementOutcome::Complete);
+        assert_eq!((stats.replaced, stats.skipped), (2, 0));
+        assert_eq!(fs::read_to_string(p).unwrap(), "bar bar foo\nfoo\n");
+    }
+
+    #[test]
+    fn it_only_counts_replaceable_matches_towards_the_limit() {
+        // the first match has changed since `rg` searched the file, so it can't be replaced
+        let p = temp_file!("fox foo foo\nfoo\n");
+        let result = perform_replacements(limited_criteria(&p, 2, &[]));
+        assert_eq!(result.unwrap(), ReplacementOutcome::Partial);
+        assert_eq!(fs::read_to_string(p).unwrap(), "fox bar bar\nfoo\n");
+    }
+
+    #[test]
+    fn it_splits_count_tokens() {
+        let split = |replacement: &str| {
+            split_count_tokens(replacement.as_bytes())
+                .into_iter()
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(split("item"), ["item"]);
+        assert_eq!(split("item_$COUNT"), ["item_", ""]);
+        assert_eq!(split("$COUNT-${COUNT}x"), ["", "-", "x"]);
+        assert_eq!(split("$1$COUNT"), ["$1", ""]);
+        // not a token, like capturing group references
+        assert_eq!(
+            split("$COUNTS $COUNT_1 $$COUNT"),
+            ["$COUNTS $COUNT_1 $$COUNT"]
+        );
+        assert_eq!(split("$$$COUNT"), ["$$", ""]);
+    }
+
+    fn counted_criteria(p: &Path, replacement: &str) -> ReplacementCriteria {
+        let s = |text, range| SubMatch::new_text(text, range);
+        let items = vec![
+            line_item(p, "foo foo\n", 0, vec![s("foo", 0..3), s("foo", 4..7)]),
+            line_item(p, "bar\n", 8, vec![]),
+            line_item(p, "foo\n", 12, vec![s("foo", 0..3)]),
+        ];
+
+        ReplacementCriteria::new(None, replacement, items)
+    }
+
+    #[test]
+    fn it_numbers_matches_with_count() {
+        let p = temp_file!("foo foo\nbar\nfoo\n");
+        let criteria = counted_criteria(&p, "item_$COUNT");
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(
+            fs::read_to_string(p).unwrap(),
+            "item_1 item_2\nbar\nitem_3\n"
+        );
+
+        let p = temp_file!("foo foo\nbar\nfoo\n");
+        let mut criteria = counted_criteria(&p, "${COUNT}0");
+        criteria.set_count_start(10);
+        criteria.set_count_step(-5);
+        perform_replacements(criteria).unwrap();
+        assert_eq!(fs::read_to_string(p).unwrap(), "100 50\nbar\n00\n");
+    }
+
+    #[test]
+    fn it_numbers_matches_with_count_and_captures() {
+        let p = temp_file!("foo foo\nbar\nfoo\n");
+        let mut criteria = counted_criteria(&p, "$1$COUNT");
+        criteria.capture_pattern = Some(Regex::new("(f)oo").unwrap());
+        perform_replacements(criteria).unwrap();
+        assert_eq!(fs::read_to_string(p).unwrap(), "f1 f2\nbar\nf3\n");
+    }
+
+    fn line_context_criteria(p: &Path) -> ReplacementCriteria {
+        // as reported by `rg foo`, so only `foo` is matched but the pattern captures the key before it
+        let s = |text, range| SubMatch::new_text(text, range);
+        let items = vec![
+            line_item(
+                p,
+                "key=foo other=foo\n",
+                0,
+                vec![s("foo", 4..7), s("foo", 14..17)],
+            ),
+            line_item(p, "foo\n", 18, vec![s("foo", 0..3)]),
+        ];
+
+        ReplacementCriteria::new(re!(r"(?:(\w+)=)?(foo)"), "${1}_$2", items)
+    }
+
+    #[test]
+    fn it_expands_captures_from_the_line() {
+        let p = temp_file!("key=foo other=foo\nfoo\n");
+        let mut criteria = line_context_criteria(&p);
+        criteria.set_capture_from_line(true);
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(
+            fs::read_to_string(p).unwrap(),
+            "key=key_foo other=other_foo\n_foo\n"
+        );
+
+        // otherwise, the pattern only sees the matched text
+        let p = temp_file!("key=foo other=foo\nfoo\n");
+        perform_replacements(line_context_criteria(&p)).unwrap();
+        assert_eq!(
+            fs::read_to_string(p).unwrap(),
+            "key=_foo other=_foo\n_foo\n"
+        );
+    }
+
+    #[test]
+    fn it_falls_back_to_the_matched_text_when_the_line_does_not_contain_it() {
+        // in the line, the pattern matches `ab cd` and `ef`, neither of which contain the submatch
+        let p = temp_file!("ab cd ef\n");
+        let items = vec![line_item(
+            &p,
+            "ab cd ef\n",
+            0,
+            vec![SubMatch::new_text("cd ef", 3..8)],
+        )];
+        let mut criteria = ReplacementCriteria::new(re!(r"(\w+) ?(\w*)"), "$2 $1", items);
+        criteria.set_capture_from_line(true);
+        perform_replacements(criteria).unwrap();
+        assert_eq!(fs::read_to_string(p).unwrap(), "ab ef cd\n");
+    }
+
+    #[test]
+    fn it_only_numbers_replaced_matches_with_count() {
+        // deselected matches aren't numbered
+        let p = temp_file!("foo foo\nbar\nfoo\n");
+        let mut criteria = counted_criteria(&p, "$COUNT");
+        criteria.items[0].set_should_replace(0, false);
+        perform_replacements(criteria).unwrap();
+        assert_eq!(fs::read_to_string(p).unwrap(), "foo 1\nbar\n2\n");
+
+        // nor are matches over the limit
+        let p = temp_file!("foo foo\nbar\nfoo\n");
+        let mut criteria = counted_criteria(&p, "$COUNT");
+        criteria.set_max_replacements_per_file(2);
+        perform_replacements(criteria).unwrap();
+        assert_eq!(fs::read_to_string(p).unwrap(), "1 2\nbar\nfoo\n");
+
+        // each file is numbered separately
+        let p1 = temp_file!("foo foo\nbar\nfoo\n");
+        let p2 = temp_file!("foo foo\nbar\nfoo\n");
+        let mut criteria = counted_criteria(&p1, "$COUNT");
+        criteria.items.extend(counted_criteria(&p2, "$COUNT").items);
+        perform_replacements(criteria).unwrap();
+        assert_eq!(fs::read_to_string(p1).unwrap(), "1 2\nbar\n3\n");
+        assert_eq!(fs::read_to_string(p2).unwrap(), "1 2\nbar\n3\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_skips_read_only_files() {
+        let text = "foo bar baz";
+        let (read_only_item, read_only_path) =
+            temp_item!(0, text, vec![SubMatch::new_text("foo", 0..3)]);
+        let (item, p) = temp_item!(0, text, vec![SubMatch::new_text("foo", 0..3)]);
+        set_read_only(&read_only_path);
+
+        let result = perform_replacements(ReplacementCriteria::new(
+            None,
+            "qux",
+            vec![read_only_item, item],
+        ));
+        assert_eq!(result.unwrap(), ReplacementOutcome::Partial);
+        assert_eq!(fs::read_to_string(read_only_path).unwrap(), text);
+        assert_eq!(fs::read_to_string(p).unwrap(), "qux bar baz");
+    }
+
+    #[test]
+    fn it_skips_binary_files() {
+        use crate::rg::de::test_utilities::RG_B64_JSON_MATCH;
+        use crate::rg::de::RgMessage;
+
+        // the lines of `RG_B64_JSON_MATCH`, which aren't valid UTF-8
+        let bytes = b"    \xffItem::\xffnew(rg_msg)\n";
+        let p = temp_file!(bytes, bytes);
+        let mut message = serde_json::to_value(RgMessage::from_str(RG_B64_JSON_MATCH)).unwrap();
+        message["data"]["path"]["text"] = p.to_string_lossy().into();
+        message["data"]["absolute_offset"] = 0.into();
+        let b64_item = Item::new(0, serde_json::from_value(message).unwrap());
+
+        // ripgrep found binary data in this file, even though the matched line is valid UTF-8
+        let text = "foo bar baz";
+        let (item, binary_offset_path) = temp_item!(0, text, vec![SubMatch::new_text("foo", 0..3)]);
+        let end_item = Item::new(
+            1,
+            RgMessageBuilder::new(RgMessageKind::End)
+                .with_path_text(binary_offset_path.to_string_lossy())
+                .with_offset(8)
+                .with_stats(Stats::new())
+                .build(),
+        );
+
+        let criteria = |allow_binary| {
+            let mut criteria = ReplacementCriteria::new(
+                None,
+                "qux",
+                vec![b64_item.clone(), item.clone(), end_item.clone()],
+            );
+            criteria.set_allow_binary(allow_binary);
+            criteria
+        };
+
+        let result = perform_replacements(criteria(false));
+        assert_eq!(result.unwrap(), ReplacementOutcome::Partial);
+        assert_eq!(fs::read(&p).unwrap(), bytes);
+        assert_eq!(fs::read_to_string(&binary_offset_path).unwrap(), text);
+
+        // once they're allowed, the file with invalid UTF-8 still can't be decoded (without --encoding)
+        let result = perform_replacements(criteria(true));
+        assert_eq!(result.unwrap(), ReplacementOutcome::Partial);
+        assert_eq!(fs::read(&p).unwrap(), bytes);
+        assert_eq!(
+            fs::read_to_string(&binary_offset_path).unwrap(),
+            "qux bar baz"
+        );
+    }
+
+    #[test]
+    fn it_normalizes_line_endings() {
+        for (contents, line_ending, expected) in [
+            ("foo\r\nbar\r\nfoo\r\n", LineEnding::Lf, "qux\nbar\nqux\n"),
+            ("foo\nbar\r\nfoo", LineEnding::Lf, "qux\nbar\nqux"),
+            ("foo\nbar\nfoo\n", LineEnding::Crlf, "qux\r\nbar\r\nqux\r\n"),
+            ("foo\r\nbar\nfoo", LineEnding::Crlf, "qux\r\nbar\r\nqux"),
+        ] {
+            let p = temp_file!(contents);
+            // every line starting with "foo" is a match
+            let mut offset = 0;
+            let mut items = vec![];
+            for line in contents.split_inclusive('\n') {
+                if line.starts_with("foo") {
+                    items.push(Item::new(
+                        items.len(),
+                        RgMessageBuilder::new(RgMessageKind::Match)
+                            .with_path_text(p.to_string_lossy())
+                            .with_lines_text(line)
+                            .with_submatches(vec![SubMatch::new_text("foo", 0..3)])
+                            .with_offset(offset)
+                            .build(),
+                    ));
+                }
+                offset += line.len();
+            }
+
+            let mut criteria = ReplacementCriteria::new(None, "qux", items);
+            criteria.set_normalize_eol(line_ending);
+            let result = perform_replacements(criteria);
+            assert_eq!(result.unwrap(), ReplacementOutcome::Complete);
+            assert_eq!(fs::read(&p).unwrap(), expected.as_bytes(), "{:?}", contents);
+        }
+
+        // without it, the line endings are kept
+        let (item, p) = temp_item!(0, "foo\r\nbar\n", vec![SubMatch::new_text("foo", 0..3)]);
+        let result = perform_replacements(ReplacementCriteria::new(None, "qux", vec![item]));
+        assert_eq!(result.unwrap(), ReplacementOutcome::Complete);
+        assert_eq!(fs::read(&p).unwrap(), b"qux\r\nbar\n");
+    }
+
+    #[test]
+    fn it_counts_replacements() {
+        let text = "foo bar foo";
+        let submatches = || {
+            vec![
+                SubMatch::new_text("foo", 0..3),
+                SubMatch::new_text("foo", 8..11),
+            ]
+        };
+        let (item, p) = temp_item!(0, text, submatches());
+        let (mut other_item, other_p) = temp_item!(0, text, submatches());
+        // one match has changed since rg searched the file, and one isn't selected
+        let (changed_item, changed_p) = temp_item!(
+            0,
+            text,
+            vec![
+                SubMatch::new_text("foo", 0..3),
+                SubMatch::new_text("bar", 8..11),
+                SubMatch::new_text("bar", 4..7),
+            ]
+        );
+        let (missing_item, missing_p) = temp_item!(0, text, submatches());
+        fs::remove_file(&missing_p).unwrap();
+        other_item.set_should_replace(1, false);
+
+        let mut items = vec![item, other_item, changed_item, missing_item];
+        items[2].set_should_replace(2, false);
+        let (outcome, stats) =
+            perform_replacements_with_stats(ReplacementCriteria::new(None, "qux", items)).unwrap();
+        assert_eq!(outcome, ReplacementOutcome::Partial);
+        let mut files = vec![(p.clone(), 2), (other_p.clone(), 1), (changed_p.clone(), 1)];
+        files.sort();
+        assert_eq!(
+            stats,
+            ReplacementStats {
+                files_changed: 3,
+                replaced: 4,
+                skipped: 3,
+                elapsed: stats.elapsed,
+                files,
+            }
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), "qux bar qux");
+        assert_eq!(fs::read_to_string(other_p).unwrap(), "qux bar foo");
+        assert_eq!(fs::read_to_string(changed_p).unwrap(), "qux bar foo");
+        assert!(stats
+            .to_string()
+            .starts_with("Changed 3 file(s): made 4 replacement(s) and skipped 3, in "));
+    }
+
+    #[test]
+    fn it_skips_missing_files() {
+        let text = "foo bar baz";
+        let (missing_item, missing_path) =
+            temp_item!(0, text, vec![SubMatch::new_text("foo", 0..3)]);
+        let (item, p) = temp_item!(0, text, vec![SubMatch::new_text("foo", 0..3)]);
+        fs::remove_file(&missing_path).unwrap();
+
+        let result = perform_replacements(ReplacementCriteria::new(
+            None,
+            "qux",
+            vec![missing_item, item],
+        ));
+        assert_eq!(result.unwrap(), ReplacementOutcome::Partial);
+        assert!(!missing_path.exists());
+        assert_eq!(fs::read_to_string(p).unwrap(), "qux bar baz");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_keeps_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.txt");
+        let link = dir.path().join("link.txt");
+        fs::write(&target, "foo bar baz").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let item = line_item(
+            &link,
+            "foo bar baz",
+            0,
+            vec![SubMatch::new_text("foo", 0..3)],
+        );
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, "qux", vec![item])).unwrap(),
+            ReplacementOutcome::Complete
+        );
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), target);
+        assert_eq!(fs::read_to_string(&target).unwrap(), "qux bar baz");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_skips_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.txt");
+        let link = dir.path().join("link.txt");
+        fs::write(&target, "foo bar baz").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let item = line_item(
+            &link,
+            "foo bar baz",
+            0,
+            vec![SubMatch::new_text("foo", 0..3)],
+        );
+        let mut criteria = ReplacementCriteria::new(None, "qux", vec![item]);
+        criteria.set_skip_symlinks(true);
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Partial
+        );
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "foo bar baz");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_writes_in_place() {
+        use std::os::unix::fs::MetadataExt;
+
+        // a UTF-16LE file with a BOM
+        let p = temp_file!(bytes, &hex::decode(UTF16LE_FOO).unwrap());
+        let inode = fs::metadata(&p).unwrap().ino();
+
+        let item = line_item(
+            &p,
+            "foo bar baz\n",
+            0,
+            vec![SubMatch::new_text("foo", 0..3)],
+        );
+        let mut criteria = ReplacementCriteria::new(None, "A", vec![item]);
+        criteria.set_in_place(true);
+        assert_eq!(
+            perform_replacements(criteria).unwrap(),
+            ReplacementOutcome::Complete
+        );
+
+        // the same file was written (rather than replaced), the BOM was kept and the shorter contents truncated it
+        assert_eq!(fs::metadata(&p).unwrap().ino(), inode);
+        let mut expected = hex::decode(UTF16LE_FOO).unwrap();
+        expected.splice(2..8, hex::decode("4100").unwrap());
+        assert_eq!(fs::read(&p).unwrap(), expected);
+    }
+
+    #[test]
+    fn it_maps_outcomes_to_exit_codes() {
+        assert_eq!(
+            ReplacementOutcome::exit_code(&Ok(ReplacementOutcome::Complete)),
+            0
+        );
+        assert_eq!(
+            ReplacementOutcome::exit_code(&Ok(ReplacementOutcome::Partial)),
+            2
+        );
+        assert_eq!(
+            ReplacementOutcome::exit_code(&Err(anyhow::anyhow!("failed"))),
+            1
+        );
+
+        // skipping a replacement is a partial success, while refusing to perform any is an error
+        let (item, _) = temp_item!(0, "foo bar baz", vec![SubMatch::new_text("fox", 0..3)]);
+        let outcome = perform_replacements(ReplacementCriteria::new(None, "qux", vec![item]));
+        assert_eq!(ReplacementOutcome::exit_code(&outcome), 2);
+
+        let (item, _) = temp_item!(0, "foo bar baz", vec![SubMatch::new_text("foo", 0..3)]);
+        let mut criteria = ReplacementCriteria::new(None, "qux", vec![item]);
+        criteria.set_preprocessor("gunzip");
+        assert_eq!(
+            ReplacementOutcome::exit_code(&perform_replacements(criteria)),
+            1
+        );
+
+        let (item, _) = temp_item!(0, "foo bar baz", vec![SubMatch::new_text("foo", 0..3)]);
+        let outcome = perform_replacements(ReplacementCriteria::new(None, "qux", vec![item]));
+        assert_eq!(ReplacementOutcome::exit_code(&outcome), 0);
+    }
+
+    #[test]
+    fn it_refuses_to_replace_preprocessed_files() {
+        let text = "foo bar baz";
+        let (item, p) = temp_item!(0, text, vec![SubMatch::new_text("foo", 0..3)]);
+
+        let mut criteria = ReplacementCriteria::new(None, "qux", vec![item]);
+        criteria.set_preprocessor("gunzip");
+
+        let err = perform_replacements(criteria).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Refusing to perform replacements, since rg searched the output of the preprocessor \"gunzip\" (--pre) rather than the files themselves"
+        );
+        assert_eq!(fs::read_to_string(p).unwrap(), text);
+    }
+
+    #[test]
+    fn it_overrides_the_encoding_of_a_bom() {
+        // a UTF-8 file with a UTF-16LE BOM
+        let bytes = [Bom::Utf16le.bytes(), b"foo bar"].concat();
+        let criteria = |p: &Path, encoding_override| {
+            let item = line_item(p, "foo bar", 0, vec![SubMatch::new_text("foo", 0..3)]);
+            let mut criteria = ReplacementCriteria::new(None, "qux", vec![item]);
+            criteria.set_encoding("utf-8");
+            criteria.set_encoding_override(encoding_override);
+            criteria
+        };
+
+        // the BOM's encoding is used by default, so the match isn't found
+        let p = temp_file!(bytes, &bytes);
+        assert_eq!(
+            perform_replacements(criteria(&p, false)).unwrap(),
+            ReplacementOutcome::Partial
+        );
+        assert_eq!(fs::read(&p).unwrap(), bytes);
+
+        // the BOM is kept when the encoding is overridden
+        assert_eq!(
+            perform_replacements(criteria(&p, true)).unwrap(),
+            ReplacementOutcome::Complete
+        );
+        assert_eq!(
+            fs::read(&p).unwrap(),
+            [Bom::Utf16le.bytes(), b"qux bar"].concat()
+        );
+    }
+
     // TODO: write a similar test for Windows/macOS systems
     #[test]
     #[cfg(not(any(target_os = "macos", target_os = "windows")))]
@@ -522,13 +2420,16 @@ mod tests {
             0,
             RgMessageBuilder::new(RgMessageKind::Match)
                 .with_path_base64(base64.encode_to_string(p.as_os_str().as_bytes()))
-                .with_lines_text(lines.to_string())
+                .with_lines_text(lines)
                 .with_submatches(vec![SubMatch::new_text("o", 4..5)])
                 .with_offset(0)
                 .build(),
         );
 
-        perform_replacements(ReplacementCriteria::new(None, " on", vec![item])).unwrap();
+        assert_eq!(
+            perform_replacements(ReplacementCriteria::new(None, " on", vec![item])).unwrap(),
+            ReplacementOutcome::Complete
+        );
         assert_eq!(fs::read_to_string(p).unwrap(), "hell on earth");
     }
 
@@ -536,6 +2437,10 @@ mod tests {
 
     macro_rules! simple_test {
         ($name:ident, $src:expr, $dst:expr, ($needle:expr, $replace:expr), $submatches:expr) => {
+            simple_test!($name, $src, $dst, ($needle, $replace), $submatches, false);
+        };
+
+        ($name:ident, $src:expr, $dst:expr, ($needle:expr, $replace:expr), $submatches:expr, $in_place:expr) => {
             #[test]
             fn $name() {
                 let src_bytes = hex::decode($src).unwrap();
@@ -557,7 +2462,12 @@ mod tests {
                     })
                     .collect();
 
-                perform_replacements(ReplacementCriteria::new(None, $replace, items)).unwrap();
+                let mut criteria = ReplacementCriteria::new(None, $replace, items);
+                criteria.set_in_place($in_place);
+                assert_eq!(
+                    perform_replacements(criteria).unwrap(),
+                    ReplacementOutcome::Complete
+                );
 
                 // Read file bytes.
                 let mut file_bytes = vec![];
@@ -623,10 +2533,16 @@ mod tests {
         };
 
         (@ [$($enc:ident$(,)?)+], $name:ident, $left:ident, $right:ident, $info:expr, $submatches:expr) => {
+            simple_test_batch!(@ multiline [$($enc,)+], $name, $left, $right, $info, $submatches, false);
+            // the same again, but writing the files in place rather than with a temporary file
+            simple_test_batch!(@ in_place [$($enc,)+], $name, $left, $right, $info, $submatches, true);
+        };
+
+        (@ $prefix:ident [$($enc:ident$(,)?)+], $name:ident, $left:ident, $right:ident, $info:expr, $submatches:expr, $in_place:expr) => {
             paste::paste! {
                 $(
                     simple_test!(
-                        [<multiline_ $name _ $enc:lower>],
+                        [<$prefix _ $name _ $enc:lower>],
                         [<$enc _ $left:upper>],
                         [<$enc _ $right:upper>],
                         $info,
@@ -638,7 +2554,8 @@ mod tests {
                                 .collect::<Vec<_>>()
                         } else {
                             $submatches
-                        }
+                        },
+                        $in_place
                     );
                 )+
             }
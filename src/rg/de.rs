@@ -56,6 +56,57 @@ pub enum RgMessage {
     },
 }
 
+impl RgMessage {
+    /// Removes leading whitespace from the lines of `Match` and `Context` messages, as ripgrep does when passed
+    /// `--trim` (it doesn't trim lines when printing JSON). The offsets are adjusted so each submatch still refers
+    /// to the same bytes in the file, and whitespace that's part of a submatch is never removed.
+    pub fn trim_leading_whitespace(&mut self) {
+        match self {
+            RgMessage::Match {
+                lines,
+                absolute_offset,
+                submatches,
+                ..
+            }
+            | RgMessage::Context {
+                lines,
+                absolute_offset,
+                submatches,
+                ..
+            } => {
+                let bytes = lines.to_vec();
+                let whitespace_len = bytes
+                    .iter()
+                    .take_while(|b| b.is_ascii_whitespace() && **b != b'\n')
+                    .count();
+                let trim_len = submatches
+                    .iter()
+                    .map(|s| s.range.start)
+                    .fold(whitespace_len, usize::min);
+                if trim_len == 0 {
+                    return;
+                }
+
+                // NOTE: only ASCII bytes are trimmed, so the remaining text is still valid UTF-8
+                *lines = match lines {
+                    ArbitraryData::Text { text } => ArbitraryData::Text {
+                        text: text[trim_len..].to_string(),
+                    },
+                    ArbitraryData::Base64 { .. } => ArbitraryData::Base64 {
+                        bytes: base64.encode_to_string(&bytes[trim_len..]),
+                    },
+                };
+                *absolute_offset += trim_len;
+                for submatch in submatches {
+                    submatch.range =
+                        (submatch.range.start - trim_len)..(submatch.range.end - trim_len);
+                }
+            }
+            RgMessage::Begin { .. } | RgMessage::End { .. } | RgMessage::Summary { .. } => {}
+        }
+    }
+}
+
 /// As specified in: [object-arbitrary-data](https://docs.rs/grep-printer/0.1.5/grep_printer/struct.JSON.html#object-arbitrary-data).
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Hash)]
 #[serde(untagged)]
@@ -65,6 +116,18 @@ pub enum ArbitraryData {
 }
 
 impl ArbitraryData {
+    /// Text if `bytes` are valid UTF-8, otherwise Base64 encoded bytes (as `rg` does).
+    pub fn from_bytes(bytes: &[u8]) -> ArbitraryData {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => ArbitraryData::Text {
+                text: text.to_owned(),
+            },
+            Err(_) => ArbitraryData::Base64 {
+                bytes: base64.encode_to_string(bytes),
+            },
+        }
+    }
+
     pub fn to_vec(&self) -> Vec<u8> {
         match self {
             ArbitraryData::Text { text } => text.as_bytes().to_vec(),
@@ -326,11 +389,95 @@ mod tests {
             serde_json::from_str(json).unwrap()
         )
     }
+
+    fn trimmed(json: &str) -> RgMessage {
+        let mut rg_message: RgMessage = serde_json::from_str(json).unwrap();
+        rg_message.trim_leading_whitespace();
+        rg_message
+    }
+
+    #[test]
+    fn trim_leading_whitespace() {
+        let json = r#"{"type":"match","data":{"path":{"text":"foo"},"lines":{"text":" \t foo bar\n"},"line_number":1,"absolute_offset":10,"submatches":[{"match":{"text":"foo"},"start":3,"end":6},{"match":{"text":"bar"},"start":7,"end":10}]}}"#;
+        assert_eq!(
+            trimmed(json),
+            Match {
+                path: Text {
+                    text: "foo".to_owned()
+                },
+                lines: Text {
+                    text: "foo bar\n".to_owned()
+                },
+                line_number: Some(1),
+                absolute_offset: 13,
+                submatches: vec![
+                    SubMatch {
+                        text: Text {
+                            text: "foo".to_owned()
+                        },
+                        range: 0..3
+                    },
+                    SubMatch {
+                        text: Text {
+                            text: "bar".to_owned()
+                        },
+                        range: 4..7
+                    }
+                ]
+            }
+        );
+
+        // whitespace only lines
+        let json = r#"{"type":"context","data":{"path":{"text":"foo"},"lines":{"text":"   \n"},"line_number":1,"absolute_offset":0,"submatches":[]}}"#;
+        match trimmed(json) {
+            Context {
+                lines,
+                absolute_offset,
+                ..
+            } => assert_eq!((lines.to_vec(), absolute_offset), (b"\n".to_vec(), 3)),
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn trim_leading_whitespace_base64() {
+        let json = format!(
+            r#"{{"type":"context","data":{{"path":{{"text":"foo"}},"lines":{{"bytes":"{}"}},"line_number":1,"absolute_offset":0,"submatches":[]}}}}"#,
+            base64.encode_to_string(b"  f\x80o\n")
+        );
+        match trimmed(&json) {
+            Context {
+                lines,
+                absolute_offset,
+                ..
+            } => assert_eq!((lines.to_vec(), absolute_offset), (b"f\x80o\n".to_vec(), 2)),
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn trim_leading_whitespace_keeps_submatches() {
+        // the submatch starts with whitespace, so only the whitespace before it is trimmed
+        let json = r#"{"type":"match","data":{"path":{"text":"foo"},"lines":{"text":"    foo\n"},"line_number":1,"absolute_offset":0,"submatches":[{"match":{"text":"  foo"},"start":2,"end":7}]}}"#;
+        match trimmed(json) {
+            Match {
+                lines,
+                absolute_offset,
+                submatches,
+                ..
+            } => {
+                assert_eq!(lines.to_vec(), b"  foo\n");
+                assert_eq!(absolute_offset, 2);
+                assert_eq!(submatches[0].range, 0..5);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
 }
 
 /// Utilities for tests.
 #[cfg(test)]
-#[allow(dead_code)]
+#[allow(dead_code, clippy::new_without_default, clippy::should_implement_trait)]
 pub mod test_utilities {
     use super::*;
 
@@ -346,6 +493,8 @@ pub mod test_utilities {
 
     pub const RG_JSON_MATCH_MULTILINE: &str = r#"{"type":"match","data":{"path":{"text":"./foo/baz"},"lines":{"text":"baz 1\n22\n333 bar 4444\n"},"line_number":3,"absolute_offset":16,"submatches":[{"match":{"text":"1\n22\n333"},"start":4,"end":12},{"match":{"text":"4444"},"start":17,"end":21}]}}"#;
 
+    pub const RG_JSON_MATCH_MULTILINE_GAP: &str = r#"{"type":"match","data":{"path":{"text":"./foo/baz"},"lines":{"text":"baz 1\n22\n333 bar 4444\n"},"line_number":3,"absolute_offset":16,"submatches":[{"match":{"text":"1"},"start":4,"end":5},{"match":{"text":"4444"},"start":17,"end":21}]}}"#;
+
     pub const RG_JSON_MATCH_LINE_WRAP: &str = r#"{"type":"match","data":{"path":{"text":"./foo/baz"},"lines":{"text":"123456789!123456789@123456789#123456789$123456789%123456789^123456789&123456789*123456789(123456789_one_hundred_characters_wowzers\n"},"line_number":3,"absolute_offset":16,"submatches":[{"match":{"text":"one_hundred"},"start":100,"end":111}]}}"#;
     pub const RG_JSON_MATCH_LINE_WRAP_MULTI: &str = r#"{"type":"match","data":{"path":{"text":"./foo/baz"},"lines":{"text":"foo foo foo foo foo bar foo foo foo foo foo bar foo foo foo foo foo bar foo foo foo foo foo bar foo foo foo foo foo bar foo foo foo foo foo bar foo foo foo foo foo bar\n"},"line_number":1,"absolute_offset":0,"submatches":[{"match":{"text":"bar"},"start":20,"end":23},{"match":{"text":"bar"},"start":44,"end":47},{"match":{"text":"bar"},"start":68,"end":71},{"match":{"text":"bar"},"start":92,"end":95},{"match":{"text":"bar"},"start":116,"end":119},{"match":{"text":"bar"},"start":140,"end":143},{"match":{"text":"bar"},"start":164,"end":167}]}}"#;
     pub const RG_JSON_CONTEXT_LINE_WRAP: &str = r#"{"type":"context","data":{"path":{"text":"./foo/baz"},"lines":{"text":"123456789!123456789@123456789#123456789$123456789%123456789^123456789&123456789*123456789(123456789_a_context_line\n"},"line_number":4,"absolute_offset":131,"submatches":[]}}"#;
@@ -33,7 +33,7 @@ impl Debug for RgEncoding {
 
 impl From<&str> for RgEncoding {
     fn from(s: &str) -> Self {
-        encoding_from_whatwg_label(s).map_or_else(|| RgEncoding::None, |e| RgEncoding::Some(e))
+        encoding_from_whatwg_label(s).map_or_else(|| RgEncoding::None, RgEncoding::Some)
     }
 }
 
@@ -2,6 +2,7 @@ use std::ffi::OsStr;
 use std::fmt::Display;
 use std::io::{ErrorKind, Read};
 use std::process::{Command, Stdio};
+use std::thread;
 
 use anyhow::{anyhow, Error, Result};
 
@@ -11,12 +12,61 @@ fn rg_run_error(msg: impl Display) -> Error {
     anyhow!("An error occurred when running `rg`:\n\n{}", msg)
 }
 
+/// The error for when `rg` couldn't be started.
+fn rg_spawn_error(e: std::io::Error) -> Error {
+    if let ErrorKind::NotFound = e.kind() {
+        anyhow!("Failed to find `rg`! Please make sure it's installed and available in PATH.")
+    } else {
+        rg_run_error(e)
+    }
+}
+
+/// Returns the first line of `rg --version` (e.g. `ripgrep 14.1.0`), to see which `rg` is on PATH.
+pub fn ripgrep_version() -> Result<String> {
+    program_version("rg", ["--version"])
+}
+
+fn program_version<P, I, S>(program: P, args: I) -> Result<String>
+where
+    P: AsRef<OsStr>,
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(rg_spawn_error)?;
+
+    if !output.status.success() {
+        return Err(rg_run_error(
+            String::from_utf8_lossy(&output.stderr).trim_end(),
+        ));
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_owned())
+        .filter(|line| !line.is_empty())
+        .ok_or_else(|| anyhow!("`rg --version` didn't print a version"))
+}
+
 pub fn run_ripgrep<I, S>(args: I) -> Result<Vec<RgMessage>>
 where
     I: IntoIterator<Item = S>,
     S: AsRef<OsStr>,
 {
-    let mut child = match Command::new("rg")
+    run_program("rg", args)
+}
+
+fn run_program<P, I, S>(program: P, args: I) -> Result<Vec<RgMessage>>
+where
+    P: AsRef<OsStr>,
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut child = match Command::new(program)
         .args(args)
         // We use the JSON output
         .arg("--json")
@@ -24,46 +74,104 @@ where
         .arg("--no-binary")
         .arg("--no-text")
         .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
     {
         Ok(child) => child,
-        Err(e) => {
-            if let ErrorKind::NotFound = e.kind() {
-                return Err(anyhow!(
-                    "Failed to find `rg`! Please make sure it's installed and available in PATH."
-                ));
-            } else {
-                return Err(rg_run_error(e));
-            }
-        }
+        Err(e) => return Err(rg_spawn_error(e)),
     };
 
-    // Read messages from child process.
-    let rg_messages = super::read::read_messages(child.stdout.as_mut().unwrap())?;
+    // Read messages from child process, while reading its stderr from another thread so a full stderr
+    // pipe can't block ripgrep.
+    let mut stderr = child.stderr.take().unwrap();
+    let (rg_messages, rg_stderr) = thread::scope(|s| {
+        let stderr_handle = s.spawn(move || {
+            let mut buf = vec![];
+            stderr.read_to_end(&mut buf).map(|_| buf)
+        });
+
+        let rg_messages = super::read::read_messages(child.stdout.as_mut().unwrap());
+        (rg_messages, stderr_handle.join())
+    });
 
     // Wait for ripgrep to finish before returning.
     match child.wait() {
-        Ok(exit_status) if exit_status.success() => Ok(rg_messages),
-        Ok(_) => {
-            let mut rg_stderr = String::new();
-            Err(
-                match child
-                    .stderr
-                    .as_mut()
-                    .unwrap()
-                    .read_to_string(&mut rg_stderr)
-                {
-                    Ok(_) => {
-                        if rg_stderr.is_empty() {
-                            anyhow!("No matches found")
-                        } else {
-                            rg_run_error(rg_stderr)
-                        }
-                    }
-                    Err(e) => anyhow!("failed to read rg's stderr: {}", e),
-                },
-            )
-        }
+        Ok(exit_status) if exit_status.success() => rg_messages,
+        Ok(_) => match rg_stderr {
+            Ok(Ok(rg_stderr)) => {
+                let rg_stderr = String::from_utf8_lossy(&rg_stderr);
+                if rg_stderr.trim().is_empty() {
+                    rg_messages.and(Err(anyhow!("No matches found")))
+                } else {
+                    // prefer ripgrep's own error, since it explains why no (valid) JSON was returned
+                    Err(rg_run_error(rg_stderr.trim_end()))
+                }
+            }
+            Ok(Err(e)) => Err(anyhow!("failed to read rg's stderr: {}", e)),
+            Err(_) => Err(anyhow!("failed to read rg's stderr")),
+        },
         Err(e) => Err(anyhow!("failed to wait for rg to end: {}", e)),
     }
 }
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::rg::exec::{program_version, run_program};
+
+    #[test]
+    fn it_surfaces_rg_errors() {
+        let err = run_program(
+            "sh",
+            [
+                "-c",
+                "echo 'rg: unsupported encoding: foo' >&2; exit 2",
+                "rg",
+            ],
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "An error occurred when running `rg`:\n\nrg: unsupported encoding: foo"
+        );
+    }
+
+    #[test]
+    fn it_reports_no_matches() {
+        let err = run_program("sh", ["-c", "exit 1", "rg"]).unwrap_err();
+        assert_eq!(err.to_string(), "No matches returned from rg!");
+    }
+
+    #[test]
+    fn it_reports_missing_program() {
+        let err = run_program("rgr-missing-program", ["foo"]).unwrap_err();
+        assert!(err.to_string().starts_with("Failed to find `rg`!"));
+    }
+
+    #[test]
+    fn it_reads_the_version() {
+        let version = program_version(
+            "sh",
+            [
+                "-c",
+                "echo 'ripgrep 14.1.0'; echo; echo 'features:+pcre2'",
+                "rg",
+            ],
+        )
+        .unwrap();
+        assert_eq!(version, "ripgrep 14.1.0");
+        assert!(crate::cli::version_text(Some(Ok(version))).ends_with("\nrg: ripgrep 14.1.0"));
+
+        let err = program_version("sh", ["-c", "echo 'rg: oops' >&2; exit 2", "rg"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "An error occurred when running `rg`:\n\nrg: oops"
+        );
+
+        let err = program_version("rgr-missing-program", ["--version"]).unwrap_err();
+        assert!(err.to_string().starts_with("Failed to find `rg`!"));
+    }
+}
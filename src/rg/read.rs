@@ -1,35 +1,228 @@
-use std::io::{self, BufRead, BufReader, Read, Write};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::thread;
 
 use anyhow::{anyhow, Result};
 
 use crate::rg::de::RgMessage;
 
-pub fn read_messages<R: Read>(rdr: R) -> Result<Vec<RgMessage>> {
-    let mut saw_match_message = false;
+/// The error returned when `rg` didn't report any matches. Check for it with `Error::is::<NoMatches>()`.
+#[derive(Debug)]
+pub struct NoMatches;
+
+impl fmt::Display for NoMatches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No matches returned from rg!")
+    }
+}
+
+impl std::error::Error for NoMatches {}
+
+/// Files smaller than this are parsed on a single thread, since starting more threads would take longer.
+const PARALLEL_THRESHOLD: u64 = 1024 * 1024;
+
+fn parse_line(line: &str) -> Result<RgMessage> {
+    serde_json::from_str(line).map_err(|e| anyhow!("Failed to parse JSON: {}", e))
+}
+
+/// Splits `text` into at most `n` chunks of about the same size, which each end at the end of a line.
+fn split_lines(text: &str, n: usize) -> Vec<&str> {
+    let chunk_size = text.len() / n.max(1) + 1;
+    let mut chunks = vec![];
+    let mut rest = text;
+    while !rest.is_empty() {
+        // a newline is never part of a multi-byte character, so this is always a char boundary
+        let end = rest
+            .bytes()
+            .skip(chunk_size)
+            .position(|b| b == b'\n')
+            .map_or(rest.len(), |i| chunk_size + i + 1);
+        let (chunk, remaining) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = remaining;
+    }
+
+    chunks
+}
+
+/// Parses each line of `text` as a message, with the lines split among `threads` threads. The chunks are joined
+/// in the order they're in, so the messages are in the same order as `rg` printed them (and the `Summary` is last).
+fn parse_lines_parallel(text: &str, threads: usize) -> Result<Vec<RgMessage>> {
+    thread::scope(|s| {
+        let handles = split_lines(text, threads)
+            .into_iter()
+            .map(|chunk| s.spawn(move || chunk.lines().map(parse_line).collect::<Result<Vec<_>>>()))
+            .collect::<Vec<_>>();
+
+        let mut rg_messages = vec![];
+        for handle in handles {
+            let chunk_messages = handle
+                .join()
+                .map_err(|_| anyhow!("Failed to parse JSON: a thread panicked"))??;
+            rg_messages.extend(chunk_messages);
+        }
+
+        Ok(rg_messages)
+    })
+}
+
+/// We expect at least one match, otherwise there's nothing to replace.
+fn expect_matches(rg_messages: Vec<RgMessage>) -> Result<Vec<RgMessage>> {
+    if rg_messages
+        .iter()
+        .any(|rg_msg| matches!(rg_msg, RgMessage::Match { .. }))
+    {
+        Ok(rg_messages)
+    } else {
+        Err(NoMatches.into())
+    }
+}
+
+/// Reads the messages from a file of `rg --json` output (see `ENV_JSON_FILE`). Parsing the JSON is the slowest
+/// part of reading them (see `benches/parsing_json.rs`), so large files are parsed on `threads` threads, or one
+/// for each CPU if that's not set.
+pub fn read_messages_from_file(mut file: File, threads: Option<usize>) -> Result<Vec<RgMessage>> {
+    let threads = threads
+        .or_else(|| thread::available_parallelism().ok().map(usize::from))
+        .unwrap_or(1);
+    if threads <= 1 || file.metadata()?.len() < PARALLEL_THRESHOLD {
+        return read_messages(file);
+    }
+
+    log::debug!("Parsing JSON with {} threads", threads);
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    expect_matches(parse_lines_parallel(&text, threads)?)
+}
 
+pub fn read_messages<R: Read>(rdr: R) -> Result<Vec<RgMessage>> {
     let mut rg_messages: Vec<RgMessage> = vec![];
     let reader = BufReader::new(rdr);
+    let show_progress = io::stdout().is_terminal();
     for (i, line) in reader.lines().enumerate() {
         // For large result lists show some progress in the terminal.
-        if i > 0 && i % 1000 == 0 {
+        if show_progress && i > 0 && i % 1000 == 0 {
             let _ = io::stdout().write_all(format!("\rMatches found: ~{}", i).as_bytes());
             let _ = io::stdout().flush();
         }
 
-        let rg_msg: RgMessage =
-            serde_json::from_str(&line?).map_err(|e| anyhow!("Failed to parse JSON: {}", e))?;
+        rg_messages.push(parse_line(&line?)?);
+    }
+
+    expect_matches(rg_messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+
+    use pretty_assertions::assert_eq;
 
-        if !saw_match_message && matches!(rg_msg, RgMessage::Match { .. }) {
-            saw_match_message = true;
+    use crate::rg::de::test_utilities::*;
+    use crate::rg::de::RgMessage;
+    use crate::rg::read::{
+        parse_lines_parallel, read_messages, read_messages_from_file, split_lines, NoMatches,
+        PARALLEL_THRESHOLD,
+    };
+
+    /// The output of `rg --json` for `files` files with a few matches each.
+    fn rg_json(files: usize) -> String {
+        let mut json = String::new();
+        for _ in 0..files {
+            for line in [
+                RG_JSON_BEGIN,
+                RG_JSON_MATCH,
+                RG_JSON_CONTEXT,
+                RG_JSON_MATCH_MULTILINE,
+                RG_B64_JSON_MATCH,
+                RG_JSON_END,
+            ] {
+                json.push_str(line);
+                json.push('\n');
+            }
         }
+        json.push_str(RG_JSON_SUMMARY);
+        json.push('\n');
+        json
+    }
 
-        rg_messages.push(rg_msg);
+    #[test]
+    fn it_splits_text_at_the_end_of_lines() {
+        assert_eq!(split_lines("", 4), Vec::<&str>::new());
+        assert_eq!(split_lines("a\nb\nc\nd\n", 2), ["a\nb\nc\n", "d\n"]);
+        assert_eq!(split_lines("a\nb\nc\nd", 4), ["a\nb\n", "c\nd"]);
+        assert_eq!(split_lines("aaaaaa\nb\n", 4), ["aaaaaa\n", "b\n"]);
+        assert_eq!(split_lines("a\nb\n", 1), ["a\nb\n"]);
     }
 
-    // We expect at least one message.
-    if !saw_match_message {
-        Err(anyhow!("No matches returned from rg!"))
-    } else {
-        Ok(rg_messages)
+    #[test]
+    fn it_parses_in_parallel_like_it_does_serially() {
+        let json = rg_json(100);
+        let serial = read_messages(json.as_bytes()).unwrap();
+        for threads in [1, 2, 3, 8, 1000] {
+            let parallel = parse_lines_parallel(&json, threads).unwrap();
+            assert_eq!(parallel, serial);
+            assert!(matches!(parallel.last(), Some(RgMessage::Summary { .. })));
+        }
+    }
+
+    #[test]
+    fn it_reads_large_files_in_parallel() {
+        let json = rg_json(1000);
+        assert!(json.len() as u64 > PARALLEL_THRESHOLD);
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(json.as_bytes()).unwrap();
+
+        let serial = read_messages(File::open(file.path()).unwrap()).unwrap();
+        let parallel = read_messages_from_file(File::open(file.path()).unwrap(), Some(4)).unwrap();
+        assert_eq!(parallel.len(), 1000 * 6 + 1);
+        assert_eq!(parallel, serial);
+        assert!(matches!(parallel.last(), Some(RgMessage::Summary { .. })));
+    }
+
+    /// Reads at most `chunk_size` bytes at a time, like a pipe (such as stdin) which is being written to.
+    struct ChunkedReader<'a> {
+        bytes: &'a [u8],
+        chunk_size: usize,
+    }
+
+    impl Read for ChunkedReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.bytes.len().min(self.chunk_size).min(buf.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn it_reads_messages_from_a_stream() {
+        let json = rg_json(10);
+        let expected = read_messages(json.as_bytes()).unwrap();
+        for chunk_size in [1, 7, 4096] {
+            let rdr = ChunkedReader {
+                bytes: json.as_bytes(),
+                chunk_size,
+            };
+            assert_eq!(read_messages(rdr).unwrap(), expected);
+        }
+        assert_eq!(expected.len(), 10 * 6 + 1);
+
+        // a stream without any matches is an error, like a file
+        let json = format!("{}\n", RG_JSON_SUMMARY);
+        let rdr = ChunkedReader {
+            bytes: json.as_bytes(),
+            chunk_size: 3,
+        };
+        assert!(read_messages(rdr).unwrap_err().is::<NoMatches>());
+    }
+
+    #[test]
+    fn it_fails_to_parse_invalid_lines_in_parallel() {
+        let json = rg_json(10) + "{\n";
+        let err = parse_lines_parallel(&json, 4).unwrap_err();
+        assert!(err.to_string().starts_with("Failed to parse JSON:"));
     }
 }
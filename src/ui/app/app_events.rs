@@ -1,26 +1,155 @@
 /// Event handling for `App`.
+use std::collections::HashSet;
+use std::time::Instant;
+
 use anyhow::Result;
-use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers, MouseEventKind};
 use either::Either;
 use ratatui::layout::Rect;
+use regex::bytes::Regex;
 
-use crate::model::Movement;
+use crate::model::{Movement, WrapMode};
 use crate::rg::de::RgMessageKind;
-use crate::ui::app::{App, AppState, AppUiState};
+use crate::ui::app::duplicates::DuplicateGroups;
+use crate::ui::app::preview::{line_highlights, FilePreview};
+use crate::ui::app::{App, AppListState, AppState, AppUiState};
+use crate::ui::keymap::Action;
 use crate::util::{byte_pos_from_char_pos, clamp};
 
+/// How many lines the mouse wheel scrolls at a time.
+const MOUSE_SCROLL_LINES: usize = 3;
+
+/// Where the visible window of a list which is `total_lines` long (and shows `height` lines at a time) starts,
+/// after scrolling it by `delta` lines from `window_start`. It's never scrolled past either end of the list.
+fn scroll_window_start(
+    window_start: usize,
+    delta: isize,
+    total_lines: usize,
+    height: usize,
+) -> usize {
+    let window_start = if delta < 0 {
+        window_start.saturating_sub(delta.unsigned_abs())
+    } else {
+        window_start.saturating_add(delta.unsigned_abs())
+    };
+
+    window_start.min(total_lines.saturating_sub(height))
+}
+
+/// The actions available in the SELECT mode, in order of precedence.
+const SELECT_ACTIONS: &[Action] = &[
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::PrevFile,
+    Action::NextFile,
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::MoveToFirst,
+    Action::MoveToLast,
+    Action::Toggle,
+    Action::ToggleLine,
+    Action::ToggleAll,
+    Action::DeselectAll,
+    Action::Invert,
+    Action::InvertMatch,
+    Action::InvertAll,
+    Action::VisualSelect,
+    Action::SelectRegex,
+    Action::CollapseDuplicates,
+    Action::ToggleLineNumbers,
+    Action::CycleSort,
+    Action::ReverseFiles,
+    Action::OpenEditor,
+    Action::Preview,
+    Action::FocusMatch,
+    Action::SearchStats,
+    Action::CopyCmdline,
+    Action::ChangeContext,
+    Action::Quit,
+    Action::Help,
+    Action::Filter,
+    Action::ScrollLines,
+    Action::EnterReplace,
+];
+
+/// The actions available in the VISUAL mode, in order of precedence.
+const VISUAL_ACTIONS: &[Action] = &[
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::PrevFile,
+    Action::NextFile,
+    Action::Toggle,
+    Action::Back,
+    Action::VisualSelect,
+];
+
+/// The actions available in the FILES mode, in order of precedence.
+const PICK_FILES_ACTIONS: &[Action] = &[
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::Toggle,
+    Action::ToggleAll,
+    Action::Confirm,
+    Action::Quit,
+];
+
+/// The actions available in the SCROLL mode, in order of precedence.
+const SCROLL_ACTIONS: &[Action] = &[
+    Action::ScrollLeft,
+    Action::ScrollRight,
+    Action::ScrollLeftHalfPage,
+    Action::ScrollRightHalfPage,
+    Action::ScrollStart,
+    Action::Back,
+    Action::ScrollLines,
+];
+
 impl App {
     pub fn on_event(&mut self, term_size: Rect, event: Event) -> Result<()> {
+        self.on_tick(Instant::now());
         match event {
             Event::Resize(w, h) => {
                 let new_size = Rect::new(term_size.x, term_size.y, w, h);
                 self.update_indicator(new_size);
             }
-            Event::Key(key) if self.is_frame_too_small(term_size) => {
-                match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => self.state = AppState::Cancelled,
+            Event::Mouse(mouse) if !self.is_frame_too_small(term_size) => {
+                let delta = match mouse.kind {
+                    MouseEventKind::ScrollUp => -(MOUSE_SCROLL_LINES as isize),
+                    MouseEventKind::ScrollDown => MOUSE_SCROLL_LINES as isize,
+                    _ => return Ok(()),
+                };
+
+                match self.ui_state {
+                    AppUiState::SelectMatches
+                    | AppUiState::ScrollLines
+                    | AppUiState::VisualSelect(_)
+                    | AppUiState::FilterMatches => self.scroll_viewport(delta, term_size),
+                    AppUiState::Help if delta < 0 => {
+                        self.help_text_state.decr_by(MOUSE_SCROLL_LINES)
+                    }
+                    AppUiState::Help => self.help_text_state.incr_by(MOUSE_SCROLL_LINES),
+                    AppUiState::FocusMatch(scroll) => {
+                        self.ui_state = AppUiState::FocusMatch(scroll.saturating_add_signed(delta))
+                    }
                     _ => {}
                 }
+            }
+            // pasted text is inserted at the cursor as a whole, rather than arriving as separate key presses (where
+            // each newline would be an `Enter`)
+            Event::Paste(text) => {
+                if let AppUiState::InputReplacement(input, pos) = &self.ui_state {
+                    // terminals may send carriage returns for the newlines in pasted text
+                    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+                    let mut new_input = input.clone();
+                    new_input.insert_str(byte_pos_from_char_pos(input, *pos), &text);
+                    self.ui_state =
+                        AppUiState::InputReplacement(new_input, pos + text.chars().count());
+                }
+            }
+            Event::Key(key) if self.is_frame_too_small(term_size) => {
+                if self.keymap.is(Action::Quit, &key) {
+                    self.state = AppState::Cancelled;
+                }
                 return Ok(());
             }
             Event::Key(key) => {
@@ -30,132 +159,443 @@ impl App {
                     return Ok(());
                 }
 
-                // Common Ctrl+Key scroll keybindings that apply to multiple modes.
-                let control_pressed = key.modifiers.contains(KeyModifiers::CONTROL);
-                if control_pressed {
-                    let did_handle_key = match &self.ui_state {
-                        AppUiState::SelectMatches
-                        | AppUiState::InputReplacement(_, _)
-                        | AppUiState::ConfirmReplacement(_, _) => match key.code {
-                            // Page movements
-                            KeyCode::Char('b') => {
-                                self.move_pos(
-                                    Movement::Backward(self.main_view_list_rect(term_size).height),
-                                    term_size,
-                                );
-                                true
-                            }
-                            KeyCode::Char('f') => {
-                                self.move_pos(
-                                    Movement::Forward(self.main_view_list_rect(term_size).height),
-                                    term_size,
-                                );
-                                true
-                            }
+                // Any key other than the second of a sequence (such as `gg`) cancels it.
+                let pending_key = self.pending_key.take();
+
+                // Common keybindings that apply to multiple modes.
+                let did_handle_key = match &self.ui_state {
+                    AppUiState::SelectMatches
+                    | AppUiState::ScrollLines
+                    | AppUiState::VisualSelect(_)
+                    | AppUiState::FilterMatches
+                    | AppUiState::InputReplacement(_, _)
+                    | AppUiState::ConfirmReplacement(_, _) => match self.keymap.find(
+                        &[
+                            Action::PageUp,
+                            Action::PageDown,
+                            Action::CyclePrintableStyle,
+                        ],
+                        &key,
+                    ) {
+                        // Page movements
+                        Some(Action::PageUp) => {
+                            self.move_pos(
+                                Movement::Backward(self.main_view_list_rect(term_size).height),
+                                term_size,
+                            );
+                            true
+                        }
+                        Some(Action::PageDown) => {
+                            self.move_pos(
+                                Movement::Forward(self.main_view_list_rect(term_size).height),
+                                term_size,
+                            );
+                            true
+                        }
 
-                            // Toggle whitespace style
-                            KeyCode::Char('v') => {
-                                self.printable_style = self.printable_style.cycle();
-                                self.update_indicator(term_size);
-                                true
-                            }
-                            _ => false,
-                        },
+                        // Toggle whitespace style
+                        Some(Action::CyclePrintableStyle) => {
+                            self.printable_style = self.printable_style.cycle();
+                            self.update_indicator(term_size);
+                            true
+                        }
                         _ => false,
-                    };
+                    },
+                    _ => false,
+                };
 
-                    // If a key was handled then stop processing any other events.
-                    if did_handle_key {
-                        return Ok(());
-                    }
+                // If a key was handled then stop processing any other events.
+                if did_handle_key {
+                    return Ok(());
                 }
 
                 match &self.ui_state {
-                    AppUiState::ConfirmReplacement(replacement, pos) => match key.code {
-                        KeyCode::Esc | KeyCode::Char('q') => {
+                    AppUiState::InputReplacement(_, _) | AppUiState::ConfirmReplacement(_, _)
+                        if self.keymap.is(Action::CopyReplacedLine, &key) =>
+                    {
+                        self.copy_replaced_line()
+                    }
+                    // many files will be changed, so "yes" has to be typed to confirm it
+                    AppUiState::ConfirmReplacement(_, _) if self.typed_confirmation.is_some() => {
+                        let typed = self.typed_confirmation.get_or_insert_with(String::new);
+                        match key.code {
+                            KeyCode::Char(c) => typed.push(c),
+                            KeyCode::Backspace => {
+                                typed.pop();
+                            }
+                            KeyCode::Enter if typed.eq_ignore_ascii_case("yes") => {
+                                self.typed_confirmation = None;
+                                self.state = AppState::Complete;
+                                return Ok(());
+                            }
+                            KeyCode::Enter => self.set_toast("Type \"yes\" to write the changes"),
+                            KeyCode::Esc => {
+                                self.typed_confirmation = None;
+                                self.quiet_exit = false;
+                            }
+                            _ => {}
+                        }
+                    }
+                    AppUiState::ConfirmReplacement(replacement, pos) => match self.keymap.find(
+                        &[
+                            Action::Confirm,
+                            Action::ConfirmQuietly,
+                            Action::ShowOnlyChanges,
+                            Action::Back,
+                        ],
+                        &key,
+                    ) {
+                        Some(Action::Back) => {
                             self.ui_state =
-                                AppUiState::InputReplacement(replacement.to_owned(), *pos)
+                                AppUiState::InputReplacement(replacement.to_owned(), *pos);
+                            self.update_indicator(term_size);
                         }
-                        KeyCode::Enter => {
-                            self.state = AppState::Complete;
-                            return Ok(());
+                        Some(Action::ShowOnlyChanges) => {
+                            self.only_changes = !self.only_changes;
+                            self.select_visible_item();
+                            self.update_indicator(term_size);
+                        }
+                        Some(action @ (Action::Confirm | Action::ConfirmQuietly)) => {
+                            self.quiet_exit = action == Action::ConfirmQuietly;
+                            if self.affected_file_count() > self.confirm_threshold {
+                                self.typed_confirmation = Some(String::new());
+                            } else {
+                                self.state = AppState::Complete;
+                                return Ok(());
+                            }
                         }
                         _ => {}
                     },
-                    AppUiState::Help => match key.code {
-                        KeyCode::Esc | KeyCode::Char('q') => {
-                            self.ui_state = AppUiState::SelectMatches
+                    AppUiState::Help if self.help_text_state.is_editing_query() => match key.code {
+                        KeyCode::Char(c) => {
+                            let mut query =
+                                self.help_text_state.query().unwrap_or_default().to_owned();
+                            query.push(c);
+                            self.help_text_state.find(&query);
+                        }
+                        KeyCode::Backspace => {
+                            let mut query =
+                                self.help_text_state.query().unwrap_or_default().to_owned();
+                            query.pop();
+                            self.help_text_state.find(&query);
                         }
-                        KeyCode::Char('k') | KeyCode::Up => self.help_text_state.decr(),
-                        KeyCode::Char('j') | KeyCode::Down => self.help_text_state.incr(),
+                        KeyCode::Enter => self.help_text_state.accept_search(),
+                        KeyCode::Esc => self.help_text_state.clear_search(),
                         _ => {}
                     },
-                    AppUiState::SelectMatches => {
-                        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+                    // cycle through the occurrences of the query, like in `less` or `vim`
+                    AppUiState::Help
+                        if self.help_text_state.query().is_some()
+                            && matches!(key.code, KeyCode::Char('n' | 'N')) =>
+                    {
                         match key.code {
-                            KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('K') => self.move_pos(
-                                if shift {
-                                    Movement::PrevFile
-                                } else {
-                                    Movement::PrevLine
-                                },
-                                term_size,
-                            ),
-                            KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('J') => self
-                                .move_pos(
-                                    if shift {
-                                        Movement::NextFile
-                                    } else {
-                                        Movement::NextLine
-                                    },
-                                    term_size,
-                                ),
-                            KeyCode::Left | KeyCode::Char('h') | KeyCode::Char('H') => {
-                                self.move_pos(Movement::Prev, term_size)
+                            KeyCode::Char('n') => self.help_text_state.next_hit(),
+                            _ => self.help_text_state.prev_hit(),
+                        }
+                    }
+                    AppUiState::Help => match self.keymap.find(
+                        &[
+                            Action::Back,
+                            Action::MoveUp,
+                            Action::MoveDown,
+                            Action::PageUp,
+                            Action::PageDown,
+                            Action::Filter,
+                        ],
+                        &key,
+                    ) {
+                        Some(Action::Back) => self.ui_state = AppUiState::SelectMatches,
+                        Some(Action::Filter) => self.help_text_state.start_search(),
+                        Some(Action::MoveUp) => self.help_text_state.decr(),
+                        Some(Action::MoveDown) => self.help_text_state.incr(),
+                        Some(Action::PageUp) => self
+                            .help_text_state
+                            .decr_by(self.help_page_height(term_size)),
+                        Some(Action::PageDown) => self
+                            .help_text_state
+                            .incr_by(self.help_page_height(term_size)),
+                        // the page keys scroll the help too, even though they aren't bound by default
+                        _ => match key.code {
+                            KeyCode::PageUp => self
+                                .help_text_state
+                                .decr_by(self.help_page_height(term_size)),
+                            KeyCode::PageDown => self
+                                .help_text_state
+                                .incr_by(self.help_page_height(term_size)),
+                            _ => {}
+                        },
+                    },
+                    AppUiState::SelectMatches => match self.keymap.find(SELECT_ACTIONS, &key) {
+                        Some(Action::MoveUp) => self.move_pos(Movement::PrevLine, term_size),
+                        Some(Action::MoveDown) => self.move_pos(Movement::NextLine, term_size),
+                        Some(Action::PrevFile) => self.move_pos(Movement::PrevFile, term_size),
+                        Some(Action::NextFile) => self.move_pos(Movement::NextFile, term_size),
+                        Some(Action::MoveLeft) => self.move_pos(Movement::Prev, term_size),
+                        Some(Action::MoveRight) => self.move_pos(Movement::Next, term_size),
+                        Some(Action::MoveToFirst) => {
+                            if pending_key == Some(Action::MoveToFirst) {
+                                self.move_pos(Movement::First, term_size)
+                            } else {
+                                self.pending_key = Some(Action::MoveToFirst)
+                            }
+                        }
+                        Some(Action::MoveToLast) => self.move_pos(Movement::Last, term_size),
+                        Some(Action::Toggle) => self.toggle_item(false),
+                        Some(Action::ToggleLine) => self.toggle_item(true),
+                        Some(Action::ToggleAll) => self.toggle_all_items(),
+                        Some(Action::DeselectAll) => self.deselect_all_items(),
+                        Some(Action::Invert) => self.invert_selection_current(),
+                        Some(Action::InvertMatch) => self.invert_selection_submatch(),
+                        Some(Action::InvertAll) => self.invert_selection_all(),
+                        Some(Action::VisualSelect) => {
+                            self.ui_state =
+                                AppUiState::VisualSelect(self.list_state.selected_item())
+                        }
+                        Some(Action::SelectRegex) => {
+                            self.ui_state = AppUiState::InputSelectRegex(String::new())
+                        }
+                        Some(Action::CollapseDuplicates) => {
+                            self.toggle_collapse_duplicates(term_size)
+                        }
+                        Some(Action::ToggleLineNumbers) => {
+                            self.show_line_numbers = !self.show_line_numbers;
+                            self.update_indicator(term_size);
+                        }
+                        Some(Action::CycleSort) => self.cycle_sort_mode(term_size),
+                        Some(Action::ReverseFiles) => self.reverse_files(term_size),
+                        Some(Action::OpenEditor) => self.open_selected_in_editor(),
+                        Some(Action::Preview) => self.open_preview(),
+                        Some(Action::FocusMatch) => self.focus_match(),
+                        Some(Action::SearchStats) => self.ui_state = AppUiState::SearchStats,
+                        Some(Action::CopyCmdline) => self.copy_rg_cmdline(),
+                        Some(Action::ChangeContext) => {
+                            self.ui_state = AppUiState::InputContext(String::new())
+                        }
+                        Some(Action::Quit) => self.state = AppState::Cancelled,
+                        Some(Action::Help) => self.ui_state = AppUiState::Help,
+                        Some(Action::Filter) => {
+                            if self.fuzzy_query.is_none() {
+                                self.fuzzy_query = Some(String::new());
+                            }
+                            self.ui_state = AppUiState::FilterMatches
+                        }
+                        Some(Action::ScrollLines) if self.wrap_mode == WrapMode::NoWrap => {
+                            self.ui_state = AppUiState::ScrollLines
+                        }
+                        Some(Action::EnterReplace) => {
+                            self.ui_state = AppUiState::InputReplacement(
+                                self.initial_replacement.clone(),
+                                self.initial_replacement.chars().count(),
+                            )
+                        }
+                        _ => {
+                            if let KeyCode::Char(c @ '1'..='9') = key.code {
+                                self.toggle_submatch(c as usize - '1' as usize)
+                            }
+                        }
+                    },
+                    AppUiState::Preview => match self
+                        .keymap
+                        .find(&[Action::Back, Action::MoveUp, Action::MoveDown], &key)
+                    {
+                        Some(Action::Back) => {
+                            self.preview = None;
+                            self.ui_state = AppUiState::SelectMatches;
+                        }
+                        Some(Action::MoveUp) => self.preview.iter_mut().for_each(|p| p.scroll_up()),
+                        Some(Action::MoveDown) => {
+                            self.preview.iter_mut().for_each(|p| p.scroll_down())
+                        }
+                        _ => {}
+                    },
+                    AppUiState::FocusMatch(scroll) => {
+                        let scroll = *scroll;
+                        let page = self.main_view_list_rect(term_size).height as usize;
+                        match self.keymap.find(
+                            &[
+                                Action::Back,
+                                Action::FocusMatch,
+                                Action::MoveUp,
+                                Action::MoveDown,
+                                Action::PageUp,
+                                Action::PageDown,
+                            ],
+                            &key,
+                        ) {
+                            Some(Action::Back | Action::FocusMatch) => {
+                                self.ui_state = AppUiState::SelectMatches
+                            }
+                            Some(Action::MoveUp) => {
+                                self.ui_state = AppUiState::FocusMatch(scroll.saturating_sub(1))
+                            }
+                            Some(Action::MoveDown) => {
+                                self.ui_state = AppUiState::FocusMatch(scroll.saturating_add(1))
+                            }
+                            Some(Action::PageUp) => {
+                                self.ui_state = AppUiState::FocusMatch(scroll.saturating_sub(page))
+                            }
+                            Some(Action::PageDown) => {
+                                self.ui_state = AppUiState::FocusMatch(scroll.saturating_add(page))
+                            }
+                            _ => {}
+                        }
+                    }
+                    AppUiState::VisualSelect(anchor) => {
+                        let anchor = *anchor;
+                        match self.keymap.find(VISUAL_ACTIONS, &key) {
+                            Some(Action::MoveUp) => self.move_pos(Movement::PrevLine, term_size),
+                            Some(Action::MoveDown) => self.move_pos(Movement::NextLine, term_size),
+                            Some(Action::PrevFile) => self.move_pos(Movement::PrevFile, term_size),
+                            Some(Action::NextFile) => self.move_pos(Movement::NextFile, term_size),
+                            Some(Action::Toggle) => {
+                                self.toggle_range(anchor, self.list_state.selected_item());
+                                self.ui_state = AppUiState::SelectMatches;
+                            }
+                            Some(Action::Back) | Some(Action::VisualSelect) => {
+                                self.ui_state = AppUiState::SelectMatches
+                            }
+                            _ => {}
+                        }
+                    }
+                    AppUiState::PickFiles => {
+                        let action = self.keymap.find(PICK_FILES_ACTIONS, &key);
+                        if let Some(picker) = &mut self.file_picker {
+                            match action {
+                                Some(Action::MoveUp) => picker.move_up(),
+                                Some(Action::MoveDown) => picker.move_down(),
+                                Some(Action::Toggle) => picker.toggle(),
+                                Some(Action::ToggleAll) => picker.toggle_all(),
+                                Some(Action::Confirm) => self.show_picked_files(term_size),
+                                Some(Action::Quit) => self.state = AppState::Cancelled,
+                                _ => {}
+                            }
+                        }
+                    }
+                    AppUiState::SearchStats => {
+                        if self
+                            .keymap
+                            .find(&[Action::Back, Action::SearchStats], &key)
+                            .is_some()
+                        {
+                            self.ui_state = AppUiState::SelectMatches;
+                        }
+                    }
+                    AppUiState::FilterMatches => match key.code {
+                        KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            self.fuzzy_case_sensitive = Some(!self.is_fuzzy_case_sensitive());
+                            self.set_fuzzy_query(self.fuzzy_query.clone(), term_size);
+                        }
+                        KeyCode::Char(c) => {
+                            let mut query = self.fuzzy_query.clone().unwrap_or_default();
+                            query.push(c);
+                            self.set_fuzzy_query(Some(query), term_size);
+                        }
+                        KeyCode::Backspace => {
+                            let mut query = self.fuzzy_query.clone().unwrap_or_default();
+                            query.pop();
+                            self.set_fuzzy_query(Some(query), term_size);
+                        }
+                        KeyCode::Enter => {
+                            if self.active_fuzzy_query().is_none() {
+                                self.fuzzy_query = None;
+                            }
+                            self.ui_state = AppUiState::SelectMatches;
+                        }
+                        KeyCode::Esc => {
+                            self.fuzzy_case_sensitive = None;
+                            self.set_fuzzy_query(None, term_size);
+                            self.ui_state = AppUiState::SelectMatches;
+                        }
+                        _ => {}
+                    },
+                    AppUiState::InputContext(input) => match key.code {
+                        // at most 4 digits, which is far more context than fits on the screen
+                        KeyCode::Char(c @ '0'..='9') if input.len() < 4 => {
+                            self.ui_state = AppUiState::InputContext(format!("{}{}", input, c))
+                        }
+                        KeyCode::Backspace => {
+                            let mut input = input.clone();
+                            input.pop();
+                            self.ui_state = AppUiState::InputContext(input);
+                        }
+                        KeyCode::Enter => {
+                            if let Ok(lines) = input.parse() {
+                                self.state = AppState::Rerun(lines);
+                            }
+                            self.ui_state = AppUiState::SelectMatches;
+                        }
+                        KeyCode::Esc => self.ui_state = AppUiState::SelectMatches,
+                        _ => {}
+                    },
+                    AppUiState::InputSelectRegex(input) => match key.code {
+                        KeyCode::Char(c) => {
+                            self.ui_state = AppUiState::InputSelectRegex(format!("{}{}", input, c))
+                        }
+                        KeyCode::Backspace => {
+                            let mut input = input.clone();
+                            input.pop();
+                            self.ui_state = AppUiState::InputSelectRegex(input);
+                        }
+                        KeyCode::Enter => {
+                            let input = input.clone();
+                            self.select_lines_matching(&input);
+                        }
+                        KeyCode::Esc => self.ui_state = AppUiState::SelectMatches,
+                        _ => {}
+                    },
+                    AppUiState::ScrollLines => {
+                        let half_page = (self.main_view_list_rect(term_size).width / 2) as usize;
+                        match self.keymap.find(SCROLL_ACTIONS, &key) {
+                            Some(Action::ScrollLeft) => {
+                                self.h_scroll = self.h_scroll.saturating_sub(1)
                             }
-                            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char('L') => {
-                                self.move_pos(Movement::Next, term_size)
+                            Some(Action::ScrollRight) => self.h_scroll += 1,
+                            Some(Action::ScrollLeftHalfPage) => {
+                                self.h_scroll = self.h_scroll.saturating_sub(half_page)
                             }
-                            KeyCode::Char(' ') => self.toggle_item(false),
-                            KeyCode::Char('s') | KeyCode::Char('S') => self.toggle_item(true),
-                            KeyCode::Char('a') | KeyCode::Char('A') => self.toggle_all_items(),
-                            KeyCode::Char('v') => self.invert_selection_current(),
-                            KeyCode::Char('V') => self.invert_selection_all(),
-                            KeyCode::Esc | KeyCode::Char('q') => self.state = AppState::Cancelled,
-                            KeyCode::Char('?') => self.ui_state = AppUiState::Help,
-                            KeyCode::Enter | KeyCode::Char('r') | KeyCode::Char('R') => {
-                                self.ui_state = AppUiState::InputReplacement(String::new(), 0)
+                            Some(Action::ScrollRightHalfPage) => self.h_scroll += half_page,
+                            Some(Action::ScrollStart) => self.h_scroll = 0,
+                            Some(Action::Back) | Some(Action::ScrollLines) => {
+                                self.ui_state = AppUiState::SelectMatches
                             }
                             _ => {}
                         }
                     }
+                    // the matches can't be replaced when rg searched the output of a preprocessor
+                    AppUiState::InputReplacement(_, _)
+                        if self.preprocessor.is_some()
+                            && self.keymap.is(Action::AcceptReplacement, &key) => {}
+                    AppUiState::InputReplacement(input, pos)
+                        if self.keymap.is(Action::AcceptReplacement, &key) =>
+                    {
+                        self.ui_state = AppUiState::ConfirmReplacement(input.to_owned(), *pos);
+                        self.select_visible_item();
+                        self.update_indicator(term_size);
+                    }
+                    AppUiState::InputReplacement(_, _)
+                        if self.keymap.is(Action::ReplaceSelectedOnly, &key) =>
+                    {
+                        self.replace_selected_only();
+                        self.update_indicator(term_size);
+                    }
                     AppUiState::InputReplacement(ref input, pos) => match key.code {
                         // input char, or detect changing to next mode
                         KeyCode::Char(ch) => {
-                            if control_pressed && ch == 's' {
-                                self.ui_state =
-                                    AppUiState::ConfirmReplacement(input.to_owned(), *pos);
-                            } else {
-                                let mut new_input = input.clone();
-                                new_input.insert(byte_pos_from_char_pos(input, *pos), ch);
-                                self.ui_state = AppUiState::InputReplacement(new_input, pos + 1);
-                            }
+                            let mut new_input = input.clone();
+                            new_input.insert(byte_pos_from_char_pos(input, *pos), ch);
+                            self.ui_state = AppUiState::InputReplacement(new_input, pos + 1);
                         }
                         // remove character behind cursor
-                        KeyCode::Backspace => {
-                            if !input.is_empty() && *pos > 0 {
-                                let mut new_input = input.clone();
-                                new_input.remove(byte_pos_from_char_pos(input, *pos - 1));
-                                self.ui_state = AppUiState::InputReplacement(new_input, pos - 1);
-                            }
+                        KeyCode::Backspace if !input.is_empty() && *pos > 0 => {
+                            let mut new_input = input.clone();
+                            new_input.remove(byte_pos_from_char_pos(input, *pos - 1));
+                            self.ui_state = AppUiState::InputReplacement(new_input, pos - 1);
                         }
                         // remove character at cursor
-                        KeyCode::Delete => {
-                            if !input.is_empty() && *pos < input.chars().count() {
-                                let mut new_input = input.clone();
-                                new_input.remove(byte_pos_from_char_pos(input, *pos));
-                                self.ui_state = AppUiState::InputReplacement(new_input, *pos);
-                            }
+                        KeyCode::Delete if !input.is_empty() && *pos < input.chars().count() => {
+                            let mut new_input = input.clone();
+                            new_input.remove(byte_pos_from_char_pos(input, *pos));
+                            self.ui_state = AppUiState::InputReplacement(new_input, *pos);
                         }
                         // leave mode
                         KeyCode::Esc => self.ui_state = AppUiState::SelectMatches,
@@ -215,10 +655,16 @@ impl App {
     }
 
     fn move_vertically(&mut self, movement: &Movement) {
-        // Reverse the iterator depending on movement direction.
+        // Reverse the iterator depending on movement direction. Moving to either end of the list searches from
+        // that end instead, for the first item that can be selected.
+        let iterate_forward = match movement {
+            Movement::First => true,
+            Movement::Last => false,
+            _ => movement.is_forward(),
+        };
         let iterator = {
             let iter = self.list.iter().enumerate();
-            if movement.is_forward() {
+            if iterate_forward {
                 Either::Right(iter)
             } else {
                 Either::Left(iter.rev())
@@ -242,6 +688,8 @@ impl App {
                 (selected_item, self.list.len() - 1)
             }
             Movement::Forward(n) => (selected_item + (*n as usize), self.list.len() - 1),
+            Movement::First => (0, 0),
+            Movement::Last => (0, self.list.len() - 1),
         };
 
         // Find the new position.
@@ -259,9 +707,10 @@ impl App {
                         i < selected_item
                     }
                     Movement::Next | Movement::NextLine | Movement::Forward(_) => i > selected_item,
+                    Movement::First | Movement::Last => true,
                 };
 
-                if is_valid_next && item.is_selectable() {
+                if is_valid_next && item.is_selectable() && !self.is_item_hidden(i) {
                     if matches!(movement, Movement::Prev) {
                         Some((i, item.sub_items().len().saturating_sub(1)))
                     } else {
@@ -271,7 +720,11 @@ impl App {
                     None
                 }
             })
-            .unwrap_or((default_item_idx, 0));
+            // when the end of the list is hidden (e.g.: by a filter), then stay where we are
+            .unwrap_or_else(|| match self.is_item_hidden(default_item_idx) {
+                true => (selected_item, self.list_state.selected_submatch()),
+                false => (default_item_idx, 0),
+            });
 
         let item_idx = clamp(item_idx, 0, self.list.len() - 1);
         self.list_state.set_selected_item(item_idx);
@@ -281,22 +734,9 @@ impl App {
     /// Update the UI's indicator position to point to the start of the selected item, and in the case of
     /// a match which spans multiple lines and has multiple submatches, the start of the selected submatch.
     /// Note that this is also the mechanism which scrolls ratatui's list interface.
-    fn update_indicator(&mut self, term_size: Rect) {
-        let item_idx = self.list_state.selected_item();
-        let match_idx = self.list_state.selected_submatch();
+    pub(crate) fn update_indicator(&mut self, term_size: Rect) {
         let main_view_list_rect = self.main_view_list_rect(term_size);
-
-        let mut indicator_idx = 0;
-        for item in &mut self.list.as_mut_slice()[0..item_idx] {
-            let item_height = item.line_count(main_view_list_rect.width, self.printable_style);
-            indicator_idx += item_height;
-        }
-
-        let height_to_sub_item = self.list[item_idx]
-            .line_count_at(match_idx, main_view_list_rect.width, self.printable_style)
-            // sub 1 here because the indicator starts at position 1 of the item
-            .saturating_sub(1);
-        indicator_idx += height_to_sub_item;
+        let indicator_idx = self.indicator_line(main_view_list_rect.width);
 
         // update visible window region is required
         {
@@ -319,11 +759,93 @@ impl App {
             .set_indicator_pos(indicator_idx - self.list_state.window_start());
     }
 
+    /// Scroll the visible window of the main list by `delta` lines (up if it's negative), without changing the
+    /// selection. The indicator is hidden while the selection is outside of the window, and moving the selection
+    /// scrolls back to it (see `update_indicator`).
+    pub(crate) fn scroll_viewport(&mut self, delta: isize, term_size: Rect) {
+        let main_view_list_rect = self.main_view_list_rect(term_size);
+        let height = main_view_list_rect.height as usize;
+        let total_lines = self.list_line_count(main_view_list_rect.width);
+
+        let window_start =
+            scroll_window_start(self.list_state.window_start(), delta, total_lines, height);
+        self.list_state.set_window_start(window_start);
+
+        let indicator_idx = self.indicator_line(main_view_list_rect.width);
+        if (window_start..window_start + height).contains(&indicator_idx) {
+            self.list_state
+                .set_indicator_pos(indicator_idx - window_start);
+        } else {
+            self.list_state.hide_indicator();
+        }
+    }
+
+    /// How many lines the (visible items of the) main list take up in total.
+    pub(crate) fn list_line_count(&mut self, list_width: u16) -> usize {
+        let mut total_lines = 0;
+        for i in 0..self.list.len() {
+            if !self.is_item_hidden(i) {
+                total_lines += self.list[i].line_count(
+                    list_width,
+                    self.printable_style,
+                    self.wrap_mode,
+                    self.show_line_numbers,
+                    self.tab_width,
+                );
+            }
+        }
+
+        total_lines
+    }
+
+    /// The line of the main list (counting from the start of the list, not the visible window) which the
+    /// indicator points to.
+    fn indicator_line(&mut self, list_width: u16) -> usize {
+        let item_idx = self.list_state.selected_item();
+        let match_idx = self.list_state.selected_submatch();
+
+        let hidden = (0..item_idx)
+            .map(|i| self.is_item_hidden(i))
+            .collect::<Vec<_>>();
+        let mut indicator_idx = 0;
+        for (i, item) in self.list[0..item_idx].iter_mut().enumerate() {
+            if hidden[i] {
+                continue;
+            }
+
+            let item_height = item.line_count(
+                list_width,
+                self.printable_style,
+                self.wrap_mode,
+                self.show_line_numbers,
+                self.tab_width,
+            );
+            indicator_idx += item_height;
+        }
+
+        let height_to_sub_item = self.list[item_idx]
+            .line_count_at(
+                match_idx,
+                list_width,
+                self.printable_style,
+                self.wrap_mode,
+                self.show_line_numbers,
+                self.tab_width,
+            )
+            // sub 1 here because the indicator starts at position 1 of the item
+            .saturating_sub(1);
+        indicator_idx += height_to_sub_item;
+
+        indicator_idx
+    }
+
     pub(crate) fn move_pos(&mut self, movement: Movement, term_size: Rect) {
         if !self.move_horizontally(&movement) {
             self.move_vertically(&movement);
         }
 
+        self.selection_anchor = Some(self.selected_position());
+
         self.update_indicator(term_size);
     }
 
@@ -331,27 +853,212 @@ impl App {
         let selected_item = self.list_state.selected_item();
         let selected_match = self.list_state.selected_submatch();
 
-        // If Match item, toggle replace.
+        // If Match item, toggle replace (for it and any duplicates collapsed into it).
         if matches!(self.list[selected_item].kind, RgMessageKind::Match) {
-            let selected_item = &mut self.list[selected_item];
             if all_sub_items {
-                let should_replace = !selected_item.get_should_replace_all();
-                selected_item.set_should_replace_all(should_replace);
+                let should_replace = !self.list[selected_item].get_should_replace_all();
+                for i in self.item_group(selected_item) {
+                    self.list[i].set_should_replace_all(should_replace);
+                }
             } else {
-                selected_item.set_should_replace(
-                    selected_match,
-                    !selected_item.get_should_replace(selected_match),
-                );
+                self.toggle_submatch(selected_match);
             }
         }
 
         // If Begin item, toggle all matches in it.
         if matches!(self.list[selected_item].kind, RgMessageKind::Begin) {
-            let mut items_to_toggle = self.get_all_items_in_file(selected_item);
-            let should_replace = items_to_toggle.iter().all(|i| !i.get_should_replace_all());
-            for item in items_to_toggle.iter_mut() {
-                item.set_should_replace_all(should_replace);
+            let items_to_toggle = self.get_all_items_in_file(selected_item);
+            let should_replace = items_to_toggle
+                .iter()
+                .all(|i| !self.list[*i].get_should_replace_all());
+            for i in items_to_toggle {
+                self.list[i].set_should_replace_all(should_replace);
+            }
+        }
+    }
+
+    /// Toggle the given submatch of the selected item (and any duplicates collapsed into it). Nothing
+    /// happens if the selected item isn't a `Match`, or it doesn't have that many submatches.
+    pub(crate) fn toggle_submatch(&mut self, submatch_idx: usize) {
+        let selected_item = self.list_state.selected_item();
+        let item = &self.list[selected_item];
+        if !matches!(item.kind, RgMessageKind::Match) || submatch_idx >= item.sub_items().len() {
+            return;
+        }
+
+        let should_replace = !item.get_should_replace(submatch_idx);
+        for i in self.item_group(selected_item) {
+            if submatch_idx < self.list[i].sub_items().len() {
+                self.list[i].set_should_replace(submatch_idx, should_replace);
+            }
+        }
+    }
+
+    /// Collapse (or expand) identical matches into a single row.
+    pub(crate) fn toggle_collapse_duplicates(&mut self, term_size: Rect) {
+        self.remember_selection();
+        self.collapsed_duplicates = match self.collapsed_duplicates {
+            Some(_) => None,
+            None => Some(DuplicateGroups::new(&self.list)),
+        };
+
+        self.restore_selection();
+        self.update_indicator(term_size);
+    }
+
+    /// Fuzzy filter the list with the given query, hiding the lines which don't match it and ranking
+    /// the files with the best matches first.
+    pub(crate) fn set_fuzzy_query(&mut self, query: Option<String>, term_size: Rect) {
+        self.fuzzy_query = query;
+        self.sort_list();
+        self.update_indicator(term_size);
+    }
+
+    /// Sort the files in the list by the next `SortMode`.
+    fn cycle_sort_mode(&mut self, term_size: Rect) {
+        self.sort_mode = self.sort_mode.cycle();
+        self.sort_list();
+        self.update_indicator(term_size);
+    }
+
+    /// Reverse the order of the files in the list, keeping the order of the items within each of them.
+    fn reverse_files(&mut self, term_size: Rect) {
+        self.reverse_files = !self.reverse_files;
+        self.sort_list();
+        self.update_indicator(term_size);
+    }
+
+    /// Leave the file picker, and only show the matches in the files that were chosen.
+    fn show_picked_files(&mut self, term_size: Rect) {
+        let chosen = match &self.file_picker {
+            Some(picker) => picker
+                .chosen_paths()
+                .into_iter()
+                .cloned()
+                .collect::<HashSet<_>>(),
+            None => return,
+        };
+        if chosen.is_empty() {
+            self.set_toast("Choose at least one file with <space>");
+            return;
+        }
+
+        self.list
+            .retain(|item| item.path().is_some_and(|path| chosen.contains(path)));
+        for (i, item) in self.list.iter_mut().enumerate() {
+            item.index = i;
+        }
+
+        self.file_picker = None;
+        self.ui_state = AppUiState::SelectMatches;
+        self.list_state = AppListState::new();
+        if self.collapsed_duplicates.is_some() {
+            self.collapsed_duplicates = Some(DuplicateGroups::new(&self.list));
+        }
+        self.fuzzy_hidden = self.fuzzy_hidden_items();
+        self.update_indicator(term_size);
+    }
+
+    /// Copy `rg`'s command line, so it can be run again outside of `rgr`.
+    fn copy_rg_cmdline(&mut self) {
+        let toast = match self.clipboard.copy(&self.rg_cmdline) {
+            Ok(message) => format!("rg's command line: {}", message),
+            Err(e) => format!("Failed to copy rg's command line: {}", e),
+        };
+        self.set_toast(toast);
+    }
+
+    /// Copy the selected line as it will be once its matches are replaced.
+    fn copy_replaced_line(&mut self) {
+        let replacement = self.ui_state.user_replacement_text().unwrap_or_default();
+        let item = &self.list[self.list_state.selected_item()];
+        let text = match item.replaced_text(self.capture_pattern.as_ref(), replacement) {
+            Some(text) => text,
+            None => {
+                self.set_toast("Select a match to copy its replaced line");
+                return;
+            }
+        };
+
+        let toast = match self.clipboard.copy(&text) {
+            Ok(message) => format!("Replaced line: {}", message),
+            Err(e) => format!("Failed to copy the replaced line: {}", e),
+        };
+        self.set_toast(toast);
+    }
+
+    /// Replace only the selected match with the replacement text that was typed, and go back to selecting
+    /// matches. The other matches are still replaced with whatever text is accepted in the end.
+    fn replace_selected_only(&mut self) {
+        let replacement = self.ui_state.user_replacement_text().unwrap_or_default();
+        let selected_submatch = self.list_state.selected_submatch();
+        let item = &mut self.list[self.list_state.selected_item()];
+        if !matches!(item.kind, RgMessageKind::Match) {
+            self.set_toast("Select a match to replace only it");
+            return;
+        }
+
+        let toast = format!("Only this match will be replaced with: {}", replacement);
+        item.set_replacement_override(selected_submatch, replacement.as_bytes().to_vec());
+        self.ui_state = AppUiState::SelectMatches;
+        self.set_toast(toast);
+    }
+
+    /// Request that the file of the selected item be opened in the user's editor.
+    fn open_selected_in_editor(&mut self) {
+        let item = &self.list[self.list_state.selected_item()];
+        if let Some(path) = item.path_buf() {
+            self.state = AppState::OpenEditor(path, item.line_number().cloned());
+        }
+    }
+
+    /// Show the lines of the selected match on their own, using the whole terminal.
+    fn focus_match(&mut self) {
+        match self.list.get(self.list_state.selected_item()) {
+            Some(item) if matches!(item.kind, RgMessageKind::Match) => {
+                self.ui_state = AppUiState::FocusMatch(0)
+            }
+            _ => self.set_toast("Select a match to focus it"),
+        }
+    }
+
+    /// Show a preview of the whole file of the selected item, around the selected item.
+    fn open_preview(&mut self) {
+        let item = &self.list[self.list_state.selected_item()];
+        let path = match item.path_buf() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let (match_lines, highlights) = match (item.line_number(), item.lines()) {
+            (Some(n), Some(lines)) => {
+                let text = lines.lossy_utf8();
+                let first_line = n.saturating_sub(1);
+                let line_count = text.trim_end_matches('\n').split('\n').count();
+                let ranges = item
+                    .sub_items()
+                    .iter()
+                    .map(|s| s.sub_match.range.clone())
+                    .collect::<Vec<_>>();
+
+                (
+                    first_line..first_line + line_count,
+                    line_highlights(&text, first_line, &ranges),
+                )
             }
+            // files are previewed from their start
+            _ => (0..0, vec![]),
+        };
+
+        self.preview = Some(FilePreview::new(path, match_lines, highlights));
+        self.ui_state = AppUiState::Preview;
+    }
+
+    /// Returns the indices of the given item and any duplicates which have been collapsed into it.
+    fn item_group(&self, item_idx: usize) -> Vec<usize> {
+        match &self.collapsed_duplicates {
+            Some(duplicates) => duplicates.members(item_idx),
+            None => vec![item_idx],
         }
     }
 
@@ -363,26 +1070,86 @@ impl App {
         }
     }
 
+    /// Select every match in the lines which `pattern` matches, and go back to selecting matches. The other
+    /// matches are left as they are. If `pattern` isn't a valid regex, then it's shown and nothing is selected.
+    pub(crate) fn select_lines_matching(&mut self, pattern: &str) {
+        let re = match Regex::new(pattern) {
+            Ok(re) => re,
+            Err(e) => {
+                self.set_toast(format!("Invalid regex: {}", e));
+                return;
+            }
+        };
+
+        let mut count = 0;
+        for item in self.list.iter_mut() {
+            let is_match = matches!(item.kind, RgMessageKind::Match)
+                && item
+                    .lines()
+                    .is_some_and(|lines| re.is_match(&lines.to_vec()));
+            if is_match {
+                item.set_should_replace_all(true);
+                count += 1;
+            }
+        }
+
+        self.ui_state = AppUiState::SelectMatches;
+        self.set_toast(format!("Selected the matches in {} line(s)", count));
+    }
+
+    /// Deselect every item, regardless of how many are already selected (unlike `toggle_all_items`).
+    pub(crate) fn deselect_all_items(&mut self) {
+        for item in self.list.iter_mut() {
+            item.set_should_replace_all(false);
+        }
+    }
+
+    /// Toggle all the visible `Match` items between the two items (inclusive), along with any duplicates which
+    /// have been collapsed into them. If they're all selected then they're deselected, otherwise they're all
+    /// selected.
+    pub(crate) fn toggle_range(&mut self, from: usize, to: usize) {
+        let items_to_toggle = (from.min(to)..=from.max(to))
+            .filter(|i| self.list[*i].kind == RgMessageKind::Match && !self.is_item_hidden(*i))
+            .flat_map(|i| self.item_group(i))
+            .collect::<Vec<_>>();
+        let should_replace = !items_to_toggle
+            .iter()
+            .all(|i| self.list[*i].get_should_replace_all());
+        for i in items_to_toggle {
+            self.list[i].set_should_replace_all(should_replace);
+        }
+    }
+
     fn invert_selection_current(&mut self) {
         let selected_item = self.list_state.selected_item();
 
-        match self.list[selected_item].kind {
-            RgMessageKind::Match => self.list[selected_item].invert_selection(),
-            RgMessageKind::Begin => {
-                for item in self.get_all_items_in_file(selected_item) {
-                    item.invert_selection();
-                }
-            }
-            _ => {}
+        let items_to_invert = match self.list[selected_item].kind {
+            RgMessageKind::Match => self.item_group(selected_item),
+            RgMessageKind::Begin => self.get_all_items_in_file(selected_item),
+            _ => vec![],
+        };
+
+        for i in items_to_invert {
+            self.list[i].invert_selection();
         }
     }
 
-    fn get_all_items_in_file(&mut self, selected_item: usize) -> Vec<&mut crate::ui::line::Item> {
+    /// Invert only the selected submatch of the selected item (and of any duplicates collapsed into it).
+    /// Unlike toggling, nothing happens if the selected item isn't a `Match` (rather than toggling the file).
+    fn invert_selection_submatch(&mut self) {
+        self.toggle_submatch(self.list_state.selected_submatch());
+    }
+
+    /// Returns the indices of all the visible `Match` items in the file, along with any duplicates which
+    /// have been collapsed into them.
+    fn get_all_items_in_file(&self, selected_item: usize) -> Vec<usize> {
         self.list
-            .iter_mut()
+            .iter()
+            .enumerate()
             .skip(selected_item)
-            .take_while(|i| i.kind != RgMessageKind::End)
-            .filter(|i| i.kind == RgMessageKind::Match)
+            .take_while(|(_, i)| i.kind != RgMessageKind::End)
+            .filter(|(idx, i)| i.kind == RgMessageKind::Match && !self.is_item_hidden(*idx))
+            .flat_map(|(idx, _)| self.item_group(idx))
             .collect()
     }
 
@@ -395,14 +1162,25 @@ impl App {
 
 #[cfg(test)]
 mod tests {
-    use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    use anyhow::{bail, Result};
+    use crossterm::event::{
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent, MouseEventKind,
+    };
     use pretty_assertions::assert_eq;
     use ratatui::layout::Rect;
 
-    use crate::model::Movement;
+    use crate::model::{Movement, SortMode, WrapMode};
     use crate::rg::de::test_utilities::*;
     use crate::rg::de::*;
+    use crate::temp_file;
+    use crate::ui::app::app_events::scroll_window_start;
     use crate::ui::app::*;
+    use crate::ui::clipboard::Clipboard;
+    use crate::ui::keymap::KeyMap;
 
     impl App {
         fn current_item(&mut self) -> &mut Item {
@@ -410,8 +1188,26 @@ mod tests {
         }
     }
 
-    fn app_list_to_match_replace(app: &App) -> Vec<bool> {
-        app.list
+    /// Records everything that's copied to it, or fails if there's nothing to record to.
+    struct TestClipboard(Option<Rc<RefCell<Vec<String>>>>);
+
+    impl Clipboard for TestClipboard {
+        fn copy(&mut self, text: &str) -> Result<String> {
+            match &self.0 {
+                Some(copied) => copied.borrow_mut().push(text.to_string()),
+                None => bail!("no clipboard"),
+            }
+
+            Ok(String::from("Copied"))
+        }
+    }
+
+    fn toast(app: &App) -> Option<&str> {
+        app.toast.as_ref().map(|(msg, _)| msg.as_str())
+    }
+
+    fn app_list_to_match_replace(app: &App) -> Vec<bool> {
+        app.list
             .iter()
             .filter(|i| matches!(i.kind, RgMessageKind::Match))
             .map(|i| i.get_should_replace_all())
@@ -522,6 +1318,164 @@ mod tests {
         assert_eq!(app.list, expected_items);
     }
 
+    #[test]
+    fn it_flags_binary_files() {
+        let app = App::new(
+            None,
+            "TESTS".to_string(),
+            vec![
+                // the lines of this file aren't valid UTF-8
+                RgMessage::from_str(RG_JSON_BEGIN),
+                RgMessage::from_str(RG_B64_JSON_MATCH),
+                RgMessage::from_str(RG_B64_JSON_CONTEXT),
+                RgMessage::from_str(RG_JSON_END),
+                // this file's path isn't valid UTF-8, which doesn't make it binary
+                RgMessage::from_str(RG_B64_JSON_BEGIN),
+                RgMessage::from_str(RG_B64_JSON_END),
+                RgMessage::from_str(RG_JSON_SUMMARY),
+            ],
+        );
+
+        assert_eq!(app.list[0].kind, RgMessageKind::Begin);
+        assert!(app.list[0].is_binary());
+        assert_eq!(app.list[4].kind, RgMessageKind::Begin);
+        assert!(!app.list[4].is_binary());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn it_flags_read_only_files() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::temp_file;
+
+        let p = temp_file!("foo");
+        fs::set_permissions(&p, fs::Permissions::from_mode(0o444)).unwrap();
+
+        let app = App::new(
+            None,
+            "TESTS".to_string(),
+            vec![
+                RgMessage::from_str(RG_JSON_BEGIN),
+                RgMessageBuilder::new(RgMessageKind::Begin)
+                    .with_path_text(p.to_string_lossy())
+                    .build(),
+                RgMessage::from_str(RG_JSON_SUMMARY),
+            ],
+        );
+
+        assert!(!app.list[0].is_read_only());
+        assert!(app.list[1].is_read_only());
+    }
+
+    #[test]
+    fn it_marks_context_separators() {
+        let message = |kind, path: &str, line_number, lines: &str| {
+            RgMessageBuilder::new(kind)
+                .with_path_text(path)
+                .with_line_number(line_number)
+                .with_lines_text(lines)
+                .with_offset(0)
+                .build()
+        };
+
+        let app = App::new(
+            None,
+            "TESTS".to_string(),
+            vec![
+                RgMessage::from_str(RG_JSON_BEGIN),
+                message(RgMessageKind::Context, "a", 1, "foo\n"),
+                message(RgMessageKind::Match, "a", 2, "foo\nbar\n"),
+                message(RgMessageKind::Context, "a", 4, "foo\n"),
+                message(RgMessageKind::Context, "a", 10, "foo\n"),
+                message(RgMessageKind::Match, "a", 11, "foo\n"),
+                message(RgMessageKind::Match, "a", 20, "foo\n"),
+                RgMessage::from_str(RG_JSON_END),
+                RgMessage::from_str(RG_JSON_BEGIN),
+                message(RgMessageKind::Match, "b", 30, "foo\n"),
+                RgMessage::from_str(RG_JSON_END),
+                RgMessage::from_str(RG_JSON_SUMMARY),
+            ],
+        );
+
+        let separators = app
+            .list
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.has_context_separator())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        assert_eq!(separators, [4, 6]);
+    }
+
+    #[test]
+    fn it_only_moves_to_matches_with_passthru() {
+        // with `--passthru` every line which doesn't match is a context line
+        let context = |line_number| {
+            RgMessageBuilder::new(RgMessageKind::Context)
+                .with_path_text("src/model/item.rs")
+                .with_line_number(line_number)
+                .with_lines_text("foo\n")
+                .with_offset(0)
+                .build()
+        };
+
+        let mut app = App::new(
+            None,
+            "TESTS".to_string(),
+            vec![
+                RgMessage::from_str(RG_JSON_BEGIN),
+                context(195),
+                context(196),
+                RgMessage::from_str(RG_JSON_MATCH),
+                context(198),
+                context(199),
+                context(200),
+                RgMessage::from_str(RG_JSON_MATCH),
+                context(202),
+                RgMessage::from_str(RG_JSON_END),
+                RgMessage::from_str(RG_JSON_SUMMARY),
+            ],
+        );
+        app.set_passthru(true);
+
+        let selectable = app
+            .list
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_selectable())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        assert_eq!(selectable, [0, 3, 7]);
+
+        let term_size = Rect::new(0, 0, 80, 24);
+        let mut visited = vec![app.list_state.selected_item()];
+        for _ in 0..4 {
+            app.move_pos(Movement::NextLine, term_size);
+            visited.push(app.list_state.selected_item());
+        }
+        assert_eq!(visited, [0, 3, 7, 9, 9]);
+
+        for _ in 0..4 {
+            app.move_pos(Movement::PrevLine, term_size);
+            visited.push(app.list_state.selected_item());
+        }
+        assert_eq!(visited[5..], [7, 3, 0, 0]);
+
+        // moving by submatch also skips the context lines
+        app.move_pos(Movement::Next, term_size);
+        app.move_pos(Movement::Next, term_size);
+        app.move_pos(Movement::Next, term_size);
+        assert_eq!(app.list_state.selected_item(), 7);
+    }
+
+    #[test]
+    fn it_does_not_mark_context_separators_without_context() {
+        let app = new_app_multiple_files();
+        assert!(app.list.iter().all(|item| !item.has_context_separator()));
+    }
+
     #[test]
     fn it_toggles_item_sub_item() {
         let mut app = new_app();
@@ -881,6 +1835,70 @@ mod tests {
         move_and_assert_list_state!(app, Movement::Backward(100), POS_1_BEGIN);
     }
 
+    #[test]
+    fn movement_first_and_last() {
+        let mut app = new_app_multiple_files();
+        assert_list_state!(app, POS_1_BEGIN);
+        // the end item isn't selectable, so the last item is the last match
+        move_and_assert_list_state!(app, Movement::Last, POS_4_MATCH_MULTILINE_0_0);
+        move_and_assert_list_state!(app, Movement::Last, POS_4_MATCH_MULTILINE_0_0);
+        move_and_assert_list_state!(app, Movement::First, POS_1_BEGIN);
+        move_and_assert_list_state!(app, Movement::First, POS_1_BEGIN);
+
+        // from the middle of the list, and from a submatch other than the first
+        move_and_assert_list_state!(app, Movement::NextFile, POS_2_BEGIN);
+        move_and_assert_list_state!(app, Movement::Next, POS_2_MATCH_MULTILINE_0_0);
+        move_and_assert_list_state!(app, Movement::Next, POS_2_MATCH_MULTILINE_0_1);
+        move_and_assert_list_state!(app, Movement::First, POS_1_BEGIN);
+        move_and_assert_list_state!(app, Movement::Next, POS_1_MATCH_0_0);
+        move_and_assert_list_state!(app, Movement::Next, POS_1_MATCH_0_1);
+        move_and_assert_list_state!(app, Movement::Last, POS_4_MATCH_MULTILINE_0_0);
+    }
+
+    // Match position
+
+    #[test]
+    fn match_ordinal() {
+        let app = new_app_multiple_files();
+        let ordinal = |(item, submatch, _): PosTriple| app.match_ordinal(item, submatch);
+
+        assert_eq!(app.match_total(), 12);
+        assert_eq!(ordinal(POS_1_BEGIN), None);
+        assert_eq!(ordinal(POS_1_MATCH_0_0), Some(1));
+        assert_eq!(ordinal(POS_1_MATCH_0_1), Some(2));
+        assert_eq!(ordinal(POS_1_MATCH_1_0), Some(3));
+        assert_eq!(ordinal(POS_1_MATCH_1_1), Some(4));
+        assert_eq!(ordinal(POS_2_BEGIN), None);
+        assert_eq!(ordinal(POS_2_MATCH_MULTILINE_0_0), Some(5));
+        assert_eq!(ordinal(POS_2_MATCH_MULTILINE_0_1), Some(6));
+        assert_eq!(ordinal(POS_3_MATCH_0_0), Some(7));
+        assert_eq!(ordinal(POS_3_MATCH_1_1), Some(10));
+        assert_eq!(ordinal(POS_4_MATCH_MULTILINE_0_1), Some(12));
+        assert_eq!(ordinal(POS_4_END), None);
+    }
+
+    #[test]
+    fn match_ordinal_follows_movement() {
+        let mut app = new_app_multiple_files();
+        let term_size = Rect::new(0, 0, 80, 24);
+        let ordinal = |app: &App| {
+            app.match_ordinal(
+                app.list_state.selected_item(),
+                app.list_state.selected_submatch(),
+            )
+        };
+
+        assert_eq!(ordinal(&app), None);
+        app.move_pos(Movement::Next, term_size);
+        assert_eq!(ordinal(&app), Some(1));
+        app.move_pos(Movement::NextFile, term_size);
+        app.move_pos(Movement::Next, term_size);
+        app.move_pos(Movement::Next, term_size);
+        assert_eq!(ordinal(&app), Some(6));
+        app.move_pos(Movement::PrevLine, term_size);
+        assert_eq!(ordinal(&app), None);
+    }
+
     // cursor position when inputting replacement text
 
     use KeyCode::*;
@@ -917,6 +1935,1347 @@ mod tests {
         };
     }
 
+    #[test]
+    fn toggle_submatch_with_number_keys() {
+        let mut app = new_app();
+        let mut expected_items = items();
+        app.list_state.set_selected_item(1);
+
+        send_key!(app, key!(Char('2')));
+        expected_items[1].set_should_replace(1, false);
+        assert_eq!(app.list, expected_items);
+
+        // numbers beyond the submatch count are ignored
+        send_key!(app, key!(Char('3')));
+        send_key!(app, key!(Char('9')));
+        assert_eq!(app.list, expected_items);
+
+        send_key!(app, key!(Char('2')));
+        expected_items[1].set_should_replace(1, true);
+        assert_eq!(app.list, expected_items);
+
+        // nothing happens on items that aren't matches
+        app.list_state.set_selected_item(0);
+        send_key!(app, key!(Char('1')));
+        assert_eq!(app.list, items());
+    }
+
+    #[test]
+    fn it_inverts_a_submatch() {
+        let mut app = new_app();
+        let mut expected_items = items();
+
+        app.list_state.set_selected_item(1);
+        app.list_state.set_selected_submatch(1);
+        send_key!(app, key!(Char('c')));
+        expected_items[1].set_should_replace(1, false);
+        assert_eq!(app.list, expected_items);
+        assert_eq!(app.list[1].get_should_replace(0), true);
+
+        send_key!(app, key!(Char('c')));
+        assert_eq!(app.list, items());
+
+        // nothing happens on items that aren't matches, even though toggling them toggles the whole file
+        app.list_state.set_selected_item(0);
+        app.list_state.set_selected_submatch(0);
+        send_key!(app, key!(Char('c')));
+        assert_eq!(app.list, items());
+    }
+
+    #[test]
+    fn help_scrolls_by_pages() {
+        let mut app = new_app();
+        send_key!(app, key!(Char('?')));
+        assert_eq!(app.ui_state, AppUiState::Help);
+        let max = app.help_text_state.max;
+
+        // the help text is 22 rows high (without the stats line), less its borders
+        send_key!(app, key!(PageDown));
+        assert_eq!(app.help_text_state.pos, 20.min(max));
+        send_key!(app, key!(Char('f'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(app.help_text_state.pos, 40.min(max));
+        for _ in 0..max / 20 + 1 {
+            send_key!(app, key!(PageDown));
+        }
+        assert_eq!(app.help_text_state.pos, max);
+
+        send_key!(app, key!(Char('b'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(app.help_text_state.pos, max.saturating_sub(20));
+        for _ in 0..max / 20 + 1 {
+            send_key!(app, key!(PageUp));
+        }
+        assert_eq!(app.help_text_state.pos, 0);
+        assert_eq!(app.ui_state, AppUiState::Help);
+    }
+
+    #[test]
+    fn help_can_be_searched() {
+        let mut app = new_app();
+        send_key!(app, key!(Char('?')));
+        send_key!(app, key!(Char('/')));
+        assert!(app.help_text_state.is_editing_query());
+
+        // keys are typed into the query while it's being edited
+        for c in "qUIt".chars() {
+            send_key!(app, key!(Char(c)));
+        }
+        assert_eq!(app.help_text_state.query(), Some("qUIt"));
+        let hits = app.help_text_state.hits().len();
+        assert!(hits > 1);
+        send_key!(app, key!(Enter));
+        assert!(!app.help_text_state.is_editing_query());
+
+        let first = app.help_text_state.current_hit().cloned();
+        send_key!(app, key!(Char('n')));
+        assert_ne!(app.help_text_state.current_hit().cloned(), first);
+        send_key!(app, key!(Char('N')));
+        assert_eq!(app.help_text_state.current_hit().cloned(), first);
+
+        // the search is cleared first, then the help is closed
+        send_key!(app, key!(Char('/')));
+        send_key!(app, key!(Esc));
+        assert_eq!(app.help_text_state.query(), None);
+        assert_eq!(app.ui_state, AppUiState::Help);
+        send_key!(app, key!(Esc));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+    }
+
+    #[test]
+    fn it_clamps_the_scrolled_window() {
+        assert_eq!(scroll_window_start(0, -3, 100, 10), 0);
+        assert_eq!(scroll_window_start(0, 3, 100, 10), 3);
+        assert_eq!(scroll_window_start(88, 3, 100, 10), 90);
+        assert_eq!(scroll_window_start(90, -3, 100, 10), 87);
+        // the list fits in the window
+        assert_eq!(scroll_window_start(5, 3, 8, 10), 0);
+    }
+
+    #[test]
+    fn it_scrolls_with_the_mouse_wheel() {
+        let mut app = new_app_multiple_files();
+        let term_size = Rect::new(0, 0, 80, 20);
+        let scroll = |app: &mut App, kind| {
+            let mouse = MouseEvent {
+                kind,
+                column: 0,
+                row: 0,
+                modifiers: KeyModifiers::empty(),
+            };
+            app.on_event(term_size, Event::Mouse(mouse)).unwrap();
+        };
+
+        let height = app.main_view_list_rect(term_size).height as usize;
+        let max_start = POS_4_END.2 + 1 - height;
+        assert!(max_start > 0);
+
+        // scrolling doesn't change the selection, and hides the indicator when it's scrolled out of view
+        scroll(&mut app, MouseEventKind::ScrollDown);
+        assert_eq!(app.list_state.window_start(), max_start.min(3));
+        assert_eq!(app.list_state.selected_item(), 0);
+        assert!(app.list_state.is_indicator_hidden());
+
+        for _ in 0..10 {
+            scroll(&mut app, MouseEventKind::ScrollDown);
+        }
+        assert_eq!(app.list_state.window_start(), max_start);
+
+        for _ in 0..10 {
+            scroll(&mut app, MouseEventKind::ScrollUp);
+        }
+        assert_eq!(app.list_state.window_start(), 0);
+        assert_eq!(app.list_state.selected_item(), 0);
+        assert!(!app.list_state.is_indicator_hidden());
+
+        // moving the selection brings the indicator back into view
+        scroll(&mut app, MouseEventKind::ScrollDown);
+        app.on_event(term_size, Event::Key(KeyEvent::from(KeyCode::Down)))
+            .unwrap();
+        assert_eq!(app.list_state.selected_item(), 1);
+        assert!(!app.list_state.is_indicator_hidden());
+    }
+
+    /// The messages for `rg foo` in `a.txt` and `b.txt`, and with `context`, also the lines around the matches
+    /// (and `c.txt`, which wasn't searched before).
+    fn reload_messages(context: bool) -> Vec<RgMessage> {
+        let begin = |path| {
+            RgMessageBuilder::new(RgMessageKind::Begin)
+                .with_path_text(path)
+                .build()
+        };
+        let line = |kind, path, number, offset, submatches| {
+            RgMessageBuilder::new(kind)
+                .with_path_text(path)
+                .with_lines_text("foo\n")
+                .with_line_number(number)
+                .with_offset(offset)
+                .with_submatches(submatches)
+                .build()
+        };
+        let found = |path, number, offset| {
+            line(
+                RgMessageKind::Match,
+                path,
+                number,
+                offset,
+                vec![SubMatch::new_text("foo", 0..3)],
+            )
+        };
+        let around =
+            |path, number, offset| line(RgMessageKind::Context, path, number, offset, vec![]);
+        let end = |path| {
+            RgMessageBuilder::new(RgMessageKind::End)
+                .with_path_text(path)
+                .with_stats(Stats::new())
+                .build()
+        };
+
+        let mut messages = vec![begin("a.txt"), found("a.txt", 1, 0)];
+        if context {
+            messages.push(around("a.txt", 2, 4));
+        }
+        messages.extend([found("a.txt", 3, 8), end("a.txt"), begin("b.txt")]);
+        if context {
+            messages.push(around("b.txt", 1, 0));
+        }
+        messages.extend([found("b.txt", 2, 4), end("b.txt")]);
+        if context {
+            messages.extend([begin("c.txt"), found("c.txt", 1, 0), end("c.txt")]);
+        }
+        messages.push(RgMessage::from_str(RG_JSON_SUMMARY));
+
+        messages
+    }
+
+    #[test]
+    fn it_reloads_the_list() {
+        let mut app = App::new(None, "rg foo".into(), reload_messages(false));
+        let term_size = Rect::new(0, 0, 80, 24);
+        app.list[2].set_should_replace(0, false);
+        app.list_state.set_selected_item(5);
+        let selected = app.selected_position();
+
+        app.reload(
+            "rg foo --context=1".into(),
+            reload_messages(true),
+            term_size,
+        );
+        assert_eq!(app.rg_cmdline, "rg foo --context=1");
+        assert_eq!(
+            app.list.iter().map(|i| i.kind.clone()).collect::<Vec<_>>(),
+            [
+                RgMessageKind::Begin,
+                RgMessageKind::Match,
+                RgMessageKind::Context,
+                RgMessageKind::Match,
+                RgMessageKind::End,
+                RgMessageKind::Begin,
+                RgMessageKind::Context,
+                RgMessageKind::Match,
+                RgMessageKind::End,
+            ]
+        );
+        // files which weren't in the list aren't added
+        assert!(app
+            .list
+            .iter()
+            .all(|i| i.path().unwrap().lossy_utf8() != "c.txt"));
+        // the same match is still deselected, and the same item is selected
+        assert_eq!(app_list_to_match_replace(&app), [true, false, true]);
+        assert_eq!(app.list_state.selected_item(), 7);
+        assert_eq!(app.selected_position(), selected);
+    }
+
+    #[test]
+    fn it_deselects_all_items() {
+        let mut app = new_app();
+        assert_eq!(app_list_to_match_replace(&app), [true, true]);
+
+        send_key!(app, key!(Char('a'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(app_list_to_match_replace(&app), [false, false]);
+
+        // unlike toggling, pressing it again leaves them deselected
+        send_key!(app, key!(Char('a'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(app_list_to_match_replace(&app), [false, false]);
+
+        // and so does pressing it when only some are selected
+        app.list[1].set_should_replace(0, true);
+        send_key!(app, key!(Char('a'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(app_list_to_match_replace(&app), [false, false]);
+        assert!(!app.list[1].get_should_replace(0));
+    }
+
+    #[test]
+    fn it_prompts_for_context_lines() {
+        let mut app = new_app();
+        send_key!(app, key!(Char('C')));
+        assert_eq!(app.ui_state, AppUiState::InputContext(String::new()));
+
+        // only digits are entered
+        send_key!(app, key!(Char('1')));
+        send_key!(app, key!(Char('x')));
+        send_key!(app, key!(Char('2')));
+        send_key!(app, key!(Char('3')));
+        send_key!(app, key!(Backspace));
+        assert_eq!(app.ui_state, AppUiState::InputContext("12".into()));
+        send_key!(app, key!(Enter));
+        assert!(matches!(app.state, AppState::Rerun(12)));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+
+        // nothing is searched without a number, or when cancelled
+        let mut app = new_app();
+        send_key!(app, key!(Char('C')));
+        send_key!(app, key!(Enter));
+        assert!(matches!(app.state, AppState::Running));
+        send_key!(app, key!(Char('C')));
+        send_key!(app, key!(Char('3')));
+        send_key!(app, key!(Esc));
+        assert!(matches!(app.state, AppState::Running));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+    }
+
+    #[test]
+    fn it_toggles_line_numbers() {
+        let mut app = new_app();
+        assert!(app.show_line_numbers);
+        send_key!(app, key!(Char('n')));
+        assert!(!app.show_line_numbers);
+        send_key!(app, key!(Char('n')));
+        assert!(app.show_line_numbers);
+    }
+
+    #[test]
+    fn it_moves_to_the_first_and_last_items() {
+        let mut app = new_app_multiple_files();
+        send_key!(app, key!(Char('G')));
+        assert_list_state!(app, POS_4_MATCH_MULTILINE_0_0);
+
+        // `g` has to be pressed twice
+        send_key!(app, key!(Char('g')));
+        assert_list_state!(app, POS_4_MATCH_MULTILINE_0_0);
+        send_key!(app, key!(Char('g')));
+        assert_list_state!(app, POS_1_BEGIN);
+
+        // another key in between cancels it
+        send_key!(app, key!(Char('G')));
+        send_key!(app, key!(Char('g')));
+        send_key!(app, key!(Char('k')));
+        send_key!(app, key!(Char('g')));
+        assert_list_state!(app, POS_4_BEGIN);
+        send_key!(app, key!(Char('g')));
+        assert_list_state!(app, POS_1_BEGIN);
+    }
+
+    #[test]
+    fn custom_keymap() {
+        let mut app = new_app();
+        let mut expected_items = items();
+        app.set_keymap(KeyMap::from_toml("toggle = \"t\"\nquit = [\"x\"]").unwrap());
+        app.list_state.set_selected_item(1);
+
+        // the old key no longer toggles
+        send_key!(app, key!(Char(' ')));
+        assert_eq!(app.list, expected_items);
+
+        send_key!(app, key!(Char('t')));
+        expected_items[1].set_should_replace(0, false);
+        assert_eq!(app.list, expected_items);
+
+        // other keys are unchanged
+        send_key!(app, key!(Char('s')));
+        expected_items[1].set_should_replace_all(true);
+        assert_eq!(app.list, expected_items);
+
+        send_key!(app, key!(Char('q')));
+        assert!(matches!(app.state, AppState::Running));
+        send_key!(app, key!(Char('x')));
+        assert!(matches!(app.state, AppState::Cancelled));
+    }
+
+    #[test]
+    fn preview_file() {
+        let path = temp_file!("one\ntwo foo\nthree\n");
+        let path = path.to_string_lossy();
+        let messages = vec![
+            RgMessageBuilder::new(RgMessageKind::Begin)
+                .with_path_text(&path)
+                .build(),
+            RgMessageBuilder::new(RgMessageKind::Match)
+                .with_path_text(&path)
+                .with_lines_text("two foo\n")
+                .with_line_number(2)
+                .with_offset(4)
+                .with_submatches(vec![SubMatch::new_text("foo", 4..7)])
+                .build(),
+            RgMessage::from_str(RG_JSON_END),
+            RgMessage::from_str(RG_JSON_SUMMARY),
+        ];
+        let mut app = App::new(None, "TESTS".to_string(), messages);
+        app.list_state.set_selected_item(1);
+
+        send_key!(app, key!(Char('p')));
+        assert_eq!(app.ui_state, AppUiState::Preview);
+        let preview = app.preview.as_ref().unwrap();
+        assert_eq!(
+            preview.lines.as_ref().unwrap(),
+            &["one", "two foo", "three"]
+        );
+        assert_eq!(preview.match_lines, 1..2);
+        assert_eq!(preview.highlights, [(1, 4..7)]);
+
+        // the list isn't moved while previewing
+        send_key!(app, key!(Char('j')));
+        assert_eq!(app.list_state.selected_item(), 1);
+
+        send_key!(app, key!(Esc));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+        assert!(app.preview.is_none());
+
+        // files are previewed from their start
+        app.list_state.set_selected_item(0);
+        send_key!(app, key!(Char('p')));
+        assert_eq!(app.preview.as_ref().unwrap().match_lines, 0..0);
+    }
+
+    #[test]
+    fn focus_match() {
+        let mut app = new_app();
+
+        // only matches can be focused
+        app.list_state.set_selected_item(0);
+        send_key!(app, key!(Char('f')));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+        assert_eq!(toast(&app), Some("Select a match to focus it"));
+
+        app.list_state.set_selected_item(1);
+        send_key!(app, key!(Char('f')));
+        assert_eq!(app.ui_state, AppUiState::FocusMatch(0));
+
+        // scrolling doesn't move the list
+        send_key!(app, key!(Char('j')));
+        send_key!(app, key!(Char('j')));
+        send_key!(app, key!(Char('k')));
+        assert_eq!(app.ui_state, AppUiState::FocusMatch(1));
+        send_key!(app, key!(Char('k')));
+        send_key!(app, key!(Char('k')));
+        assert_eq!(app.ui_state, AppUiState::FocusMatch(0));
+        assert_eq!(app.list_state.selected_item(), 1);
+
+        send_key!(app, key!(Esc));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+        send_key!(app, key!(Char('f')));
+        send_key!(app, key!(Char('f')));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+    }
+
+    #[test]
+    fn search_stats() {
+        let mut app = new_app();
+        send_key!(app, key!(Char('i')));
+        assert_eq!(app.ui_state, AppUiState::SearchStats);
+
+        // other keys are ignored
+        send_key!(app, key!(Char('j')));
+        send_key!(app, key!(Enter));
+        assert_eq!(app.ui_state, AppUiState::SearchStats);
+        assert_list_state!(app, (0, 0, 0));
+
+        send_key!(app, key!(Char('i')));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+        send_key!(app, key!(Char('i')));
+        send_key!(app, key!(Esc));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+    }
+
+    #[test]
+    fn copy_rg_cmdline() {
+        let copied = Rc::new(RefCell::new(vec![]));
+        let mut app = new_app();
+        app.set_clipboard(Box::new(TestClipboard(Some(copied.clone()))));
+
+        send_key!(app, key!(Char('y')));
+        assert_eq!(*copied.borrow(), [app.rg_cmdline.clone()]);
+        assert_eq!(toast(&app), Some("rg's command line: Copied"));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+
+        app.set_clipboard(Box::new(TestClipboard(None)));
+        send_key!(app, key!(Char('y')));
+        assert_eq!(
+            toast(&app),
+            Some("Failed to copy rg's command line: no clipboard")
+        );
+    }
+
+    #[test]
+    fn copy_replaced_line() {
+        let copied = Rc::new(RefCell::new(vec![]));
+        let mut app = new_app();
+        app.set_clipboard(Box::new(TestClipboard(Some(copied.clone()))));
+
+        // the file is selected
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('X')));
+        send_key!(app, key!(Char('y'), modifiers = KeyModifiers::CONTROL));
+        assert!(copied.borrow().is_empty());
+        assert_eq!(
+            toast(&app),
+            Some("Select a match to copy its replaced line")
+        );
+        assert_eq!(app.ui_state, AppUiState::InputReplacement("X".into(), 1));
+
+        // only the second match on the line is selected
+        send_key!(app, key!(Esc));
+        send_key!(app, key!(Char('j')));
+        send_key!(app, key!(Char('l')));
+        send_key!(app, key!(Char('v')));
+        send_key!(app, key!(Char('c')));
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('X')));
+        send_key!(app, key!(Char('y'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(*copied.borrow(), ["    Item::new(X)"]);
+        assert_eq!(toast(&app), Some("Replaced line: Copied"));
+
+        send_key!(app, key!(Char('s'), modifiers = KeyModifiers::CONTROL));
+        send_key!(app, key!(Char('y'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(copied.borrow().len(), 2);
+        assert_eq!(app.ui_state, AppUiState::ConfirmReplacement("X".into(), 1));
+    }
+
+    #[test]
+    fn replace_selected_only() {
+        let mut app = new_app();
+
+        // the file is selected
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('X')));
+        send_key!(app, key!(Char('o'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(toast(&app), Some("Select a match to replace only it"));
+        assert_eq!(app.ui_state, AppUiState::InputReplacement("X".into(), 1));
+
+        // give both matches on the line their own text
+        send_key!(app, key!(Esc));
+        send_key!(app, key!(Char('j')));
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('A')));
+        send_key!(app, key!(Char('o'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+        assert_eq!(
+            toast(&app),
+            Some("Only this match will be replaced with: A")
+        );
+
+        send_key!(app, key!(Char('l')));
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('B')));
+        send_key!(app, key!(Char('o'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+
+        let overrides = app.list[1]
+            .sub_items()
+            .iter()
+            .map(|s| s.replacement_override.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(overrides, [Some(b"A".to_vec()), Some(b"B".to_vec())]);
+        assert_eq!(
+            app.list[1].replaced_text(None, "C").as_deref(),
+            Some("    A::new(B)")
+        );
+    }
+
+    #[test]
+    fn pick_files_then_show_their_matches() {
+        let mut app = new_app_sortable();
+        app.pick_files();
+        assert_eq!(app.ui_state, AppUiState::PickFiles);
+
+        // at least one file must be chosen
+        send_key!(app, key!(Enter));
+        assert_eq!(app.ui_state, AppUiState::PickFiles);
+        assert!(toast(&app).is_some());
+
+        // choose c.txt and b.txt
+        send_key!(app, key!(Char(' ')));
+        send_key!(app, key!(Char('j')));
+        send_key!(app, key!(Char('j')));
+        send_key!(app, key!(Char(' ')));
+        send_key!(app, key!(Enter));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+        assert!(app.file_picker.is_none());
+        assert_eq!(file_order(&app), ["b.txt", "c.txt"]);
+        assert!(app.list.iter().enumerate().all(|(i, item)| item.index == i));
+        assert_list_state!(app, (0, 0, 0));
+
+        // only the chosen files' matches are replaced
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('x')));
+        send_key!(app, key!(Char('s'), modifiers = KeyModifiers::CONTROL));
+        send_key!(app, key!(Enter));
+        let criteria = app.get_replacement_criteria().unwrap();
+        let mut paths = criteria
+            .as_map()
+            .keys()
+            .map(|p| p.lossy_utf8())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(paths, ["b.txt", "c.txt"]);
+    }
+
+    #[test]
+    fn pick_files_and_quit() {
+        let mut app = new_app_sortable();
+        app.pick_files();
+        send_key!(app, key!(Char('q')));
+        assert!(matches!(app.state, AppState::Cancelled));
+    }
+
+    #[test]
+    fn toast_expires() {
+        let mut app = new_app();
+        assert!(!app.on_tick(Instant::now()));
+
+        app.set_toast("hello");
+        let shown_at = app.toast.as_ref().unwrap().1;
+        assert!(!app.on_tick(shown_at));
+        assert!(!app.on_tick(shown_at + Duration::from_secs(1)));
+        assert_eq!(toast(&app), Some("hello"));
+
+        // it's redrawn while it fades, and once more after it's hidden
+        assert!(app.on_tick(shown_at + TOAST_DURATION - Duration::from_millis(100)));
+        assert_eq!(toast(&app), Some("hello"));
+        assert!(app.on_tick(shown_at + TOAST_DURATION));
+        assert_eq!(toast(&app), None);
+        assert!(!app.on_tick(shown_at + TOAST_DURATION * 2));
+    }
+
+    #[test]
+    fn toast_expires_on_event() {
+        let mut app = new_app();
+        app.toast = Some(("hello".into(), Instant::now() - TOAST_DURATION));
+        send_key!(app, key!(Char('j')));
+        assert_eq!(toast(&app), None);
+
+        // but not before it has been shown long enough
+        app.set_toast("hello");
+        send_key!(app, key!(Char('j')));
+        assert_eq!(toast(&app), Some("hello"));
+    }
+
+    #[test]
+    fn affected_file_count() {
+        // the same two files are listed twice
+        let mut app = new_app_multiple_files();
+        assert_eq!(app.affected_file_count(), 2);
+
+        let path = app.list[0].path().cloned();
+        for item in app.list.iter_mut().filter(|i| i.path() == path.as_ref()) {
+            item.set_should_replace_all(false);
+        }
+        assert_eq!(app.affected_file_count(), 1);
+
+        // files at or below the threshold are replaced without asking
+        app.set_confirm_threshold(1);
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('x')));
+        send_key!(app, key!(Char('s'), modifiers = KeyModifiers::CONTROL));
+        send_key!(app, key!(Enter));
+        assert_eq!(app.typed_confirmation, None);
+        assert!(matches!(app.state, AppState::Complete));
+    }
+
+    #[test]
+    fn confirm_many_files() {
+        let mut app = new_app_multiple_files();
+        app.set_confirm_threshold(1);
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('x')));
+        send_key!(app, key!(Char('s'), modifiers = KeyModifiers::CONTROL));
+        send_key!(app, key!(Char('w')));
+        assert!(matches!(app.state, AppState::Running));
+        assert_eq!(app.typed_confirmation.as_deref(), Some(""));
+        assert!(app.quiet_exit);
+
+        // it may be cancelled
+        send_key!(app, key!(Esc));
+        assert_eq!(app.typed_confirmation, None);
+        assert!(!app.quiet_exit);
+        assert_eq!(app.ui_state, AppUiState::ConfirmReplacement("x".into(), 1));
+
+        // anything but "yes" isn't accepted
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('n')));
+        send_key!(app, key!(Char('o')));
+        send_key!(app, key!(Enter));
+        assert!(matches!(app.state, AppState::Running));
+        assert_eq!(app.typed_confirmation.as_deref(), Some("no"));
+        assert_eq!(toast(&app), Some("Type \"yes\" to write the changes"));
+
+        send_key!(app, key!(Backspace));
+        send_key!(app, key!(Backspace));
+        for c in "yes".chars() {
+            send_key!(app, key!(Char(c)));
+        }
+        send_key!(app, key!(Enter));
+        assert_eq!(app.typed_confirmation, None);
+        assert!(matches!(app.state, AppState::Complete));
+        assert!(!app.get_replacement_criteria().unwrap().quiet);
+    }
+
+    #[test]
+    fn confirm_and_quit_quietly() {
+        let mut app = new_app();
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('x')));
+        send_key!(app, key!(Char('s'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(app.ui_state, AppUiState::ConfirmReplacement("x".into(), 1));
+        assert!(!app.quiet_exit);
+
+        send_key!(app, key!(Char('w')));
+        assert!(app.quiet_exit);
+        assert!(matches!(app.state, AppState::Complete));
+
+        let criteria = app.get_replacement_criteria().unwrap();
+        assert!(criteria.quiet);
+        assert_eq!(criteria.user_replacement, b"x");
+    }
+
+    #[test]
+    fn confirm_shows_only_changes() {
+        let mut app = new_app();
+        let hidden = |app: &App| {
+            (0..app.list.len())
+                .map(|i| app.is_item_hidden(i))
+                .collect::<Vec<_>>()
+        };
+
+        // deselect the first match
+        send_key!(app, key!(Char('j')));
+        send_key!(app, key!(Char('s')));
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('x')));
+        send_key!(app, key!(Char('s'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(hidden(&app), [false; 6]);
+        assert_eq!(app.list_line_count(80), 6);
+
+        // only the second match is shown, and it's selected
+        send_key!(app, key!(Char('c')));
+        assert_eq!(hidden(&app), [true, true, true, false, true, true]);
+        assert_eq!(app.list_line_count(80), 1);
+        assert_eq!(app.list_state.selected_item(), 3);
+
+        send_key!(app, key!(Char('c')));
+        assert_eq!(hidden(&app), [false; 6]);
+
+        // it only applies to the CONFIRM mode
+        send_key!(app, key!(Char('c')));
+        send_key!(app, key!(Esc));
+        assert_eq!(app.ui_state, AppUiState::InputReplacement("x".into(), 1));
+        assert_eq!(hidden(&app), [false; 6]);
+    }
+
+    #[test]
+    fn preprocessor_disables_confirm() {
+        let mut app = new_app();
+        app.set_preprocessor(Some("gunzip".into()));
+        send_key!(app, key!(Enter));
+        send_key!(app, key!(Char('x')));
+        send_key!(app, key!(Char('s'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(app.ui_state, AppUiState::InputReplacement("x".into(), 1));
+        assert!(matches!(app.state, AppState::Running));
+    }
+
+    #[test]
+    fn collapse_duplicates_skips_hidden_items() {
+        let mut app = new_app();
+        send_key!(app, key!(Char('d')));
+        assert!(app.is_item_hidden(3));
+
+        move_and_assert_list_state!(app, Movement::NextLine, (1, 0, 1));
+        move_and_assert_list_state!(app, Movement::NextLine, (5, 0, 4));
+        move_and_assert_list_state!(app, Movement::PrevLine, (1, 0, 1));
+
+        // expanding shows the duplicate again
+        send_key!(app, key!(Char('d')));
+        assert!(!app.is_item_hidden(3));
+        move_and_assert_list_state!(app, Movement::NextLine, (3, 0, 3));
+
+        // collapsing while a duplicate is selected selects the item it was collapsed into
+        send_key!(app, key!(Char('d')));
+        assert_list_state!(app, (1, 0, 1));
+    }
+
+    #[test]
+    fn collapse_duplicates_toggles_all_duplicates() {
+        let mut app = new_app();
+        let mut expected_items = items();
+        app.toggle_collapse_duplicates(Rect::new(0, 0, 80, 24));
+
+        // toggling a sub item toggles it in every duplicate
+        app.list_state.set_selected_item(1);
+        app.list_state.set_selected_submatch(1);
+        app.toggle_item(false);
+        expected_items[1].set_should_replace(1, false);
+        expected_items[3].set_should_replace(1, false);
+        assert_eq!(app.list, expected_items);
+
+        // as does toggling the whole line
+        app.toggle_item(true);
+        expected_items[1].set_should_replace_all(true);
+        expected_items[3].set_should_replace_all(true);
+        assert_eq!(app.list, expected_items);
+
+        // as does inverting the selection
+        app.invert_selection_current();
+        expected_items[1].set_should_replace_all(false);
+        expected_items[3].set_should_replace_all(false);
+        assert_eq!(app.list, expected_items);
+
+        // and toggling the whole file
+        app.list_state.set_selected_item(0);
+        app.toggle_item(false);
+        expected_items[1].set_should_replace_all(true);
+        expected_items[3].set_should_replace_all(true);
+        assert_eq!(app.list, expected_items);
+    }
+
+    fn file(path: &str, lines: &[(&str, Vec<SubMatch>)]) -> Vec<RgMessage> {
+        let mut messages = vec![RgMessageBuilder::new(RgMessageKind::Begin)
+            .with_path_text(path)
+            .build()];
+        let mut offset = 0;
+        for (i, (text, submatches)) in lines.iter().enumerate() {
+            messages.push(
+                RgMessageBuilder::new(RgMessageKind::Match)
+                    .with_path_text(path)
+                    .with_lines_text(text)
+                    .with_line_number(i + 1)
+                    .with_offset(offset)
+                    .with_submatches(submatches.clone())
+                    .build(),
+            );
+            offset += text.len() + 1;
+        }
+        messages.push(
+            RgMessageBuilder::new(RgMessageKind::End)
+                .with_path_text(path)
+                .with_stats(Stats::new())
+                .build(),
+        );
+        messages
+    }
+
+    fn new_app_sortable() -> App {
+        let s = |a, b| SubMatch::new_text(a, b);
+
+        let mut messages = vec![];
+        messages.extend(file("b.txt", &[("foo", vec![s("foo", 0..3)])]));
+        messages.extend(file(
+            "a.txt",
+            &[(
+                "foo foo foo",
+                vec![s("foo", 0..3), s("foo", 4..7), s("foo", 8..11)],
+            )],
+        ));
+        messages.extend(file(
+            "c.txt",
+            &[
+                ("a much longer foo line here", vec![s("foo", 14..17)]),
+                ("foo", vec![s("foo", 0..3)]),
+            ],
+        ));
+        messages.push(RgMessage::from_str(RG_JSON_SUMMARY));
+
+        App::new(None, "TESTS".to_string(), messages)
+    }
+
+    fn file_order(app: &App) -> Vec<String> {
+        app.list
+            .iter()
+            .filter(|i| matches!(i.kind, RgMessageKind::Begin))
+            .map(|i| i.path().unwrap().lossy_utf8())
+            .collect()
+    }
+
+    #[test]
+    fn sort_list() {
+        let mut app = new_app_sortable();
+        assert_eq!(file_order(&app), ["b.txt", "a.txt", "c.txt"]);
+
+        app.set_sort_mode(SortMode::Path);
+        assert_eq!(file_order(&app), ["a.txt", "b.txt", "c.txt"]);
+
+        app.set_sort_mode(SortMode::MatchCount);
+        assert_eq!(file_order(&app), ["a.txt", "c.txt", "b.txt"]);
+
+        app.set_sort_mode(SortMode::LineLength);
+        assert_eq!(file_order(&app), ["c.txt", "a.txt", "b.txt"]);
+
+        app.set_sort_mode(SortMode::Rg);
+        assert_eq!(file_order(&app), ["b.txt", "a.txt", "c.txt"]);
+    }
+
+    #[test]
+    fn sort_list_keeps_the_order_rg_printed() {
+        // e.g. with `rg --sort=none`, the files are in the order they were streamed from `rg`, not by path
+        let mut app = new_app_sortable();
+        let ingested = app
+            .list
+            .iter()
+            .map(|i| (i.kind.clone(), i.path().map(|p| p.lossy_utf8())))
+            .collect::<Vec<_>>();
+
+        // as the interface is set up (see `tui.rs`)
+        app.set_sort_mode(SortMode::Rg);
+        app.set_search_roots(&[".".into()]);
+        assert_eq!(file_order(&app), ["b.txt", "a.txt", "c.txt"]);
+        assert_eq!(
+            app.list
+                .iter()
+                .map(|i| (i.kind.clone(), i.path().map(|p| p.lossy_utf8())))
+                .collect::<Vec<_>>(),
+            ingested
+        );
+        assert!(app.list.iter().enumerate().all(|(i, item)| item.index == i));
+
+        // and after sorting by something else
+        app.set_sort_mode(SortMode::Path);
+        app.set_sort_mode(SortMode::Rg);
+        assert_eq!(file_order(&app), ["b.txt", "a.txt", "c.txt"]);
+    }
+
+    #[test]
+    fn sort_list_groups_files_by_root() {
+        let mut app = new_app_sortable();
+        let headers = |app: &App| {
+            app.list
+                .iter()
+                .filter(|i| matches!(i.kind, RgMessageKind::Begin))
+                .map(|i| i.root_header().map(String::from))
+                .collect::<Vec<_>>()
+        };
+
+        app.set_search_roots(&["a.txt".into(), ".".into()]);
+        assert_eq!(file_order(&app), ["a.txt", "b.txt", "c.txt"]);
+        assert_eq!(
+            headers(&app),
+            [Some("a.txt".into()), Some(".".into()), None]
+        );
+
+        // files are sorted within their group
+        app.set_sort_mode(SortMode::MatchCount);
+        assert_eq!(file_order(&app), ["a.txt", "c.txt", "b.txt"]);
+        assert_eq!(
+            headers(&app),
+            [Some("a.txt".into()), Some(".".into()), None]
+        );
+
+        // files which aren't in any of the roots are last, without a header
+        app.set_search_roots(&["c.txt".into(), "b.txt".into()]);
+        assert_eq!(file_order(&app), ["c.txt", "b.txt", "a.txt"]);
+        assert_eq!(
+            headers(&app),
+            [Some("c.txt".into()), Some("b.txt".into()), None]
+        );
+
+        // a single root isn't shown
+        app.set_search_roots(&["c.txt".into()]);
+        assert_eq!(file_order(&app), ["a.txt", "c.txt", "b.txt"]);
+        assert_eq!(headers(&app), [None, None, None]);
+    }
+
+    #[test]
+    fn sort_list_keeps_files_together() {
+        let mut app = new_app_sortable();
+        app.set_sort_mode(SortMode::LineLength);
+
+        let kinds = app.list.iter().map(|i| i.kind.clone()).collect::<Vec<_>>();
+        use RgMessageKind::*;
+        assert_eq!(
+            kinds,
+            [Begin, Match, Match, End, Begin, Match, End, Begin, Match, End]
+        );
+
+        // the items within a file keep their order
+        assert_eq!(app.list[1].line_number(), Some(&1));
+        assert_eq!(app.list[2].line_number(), Some(&2));
+
+        // indices are kept up to date
+        for (i, item) in app.list.iter().enumerate() {
+            assert_eq!(item.index, i);
+        }
+    }
+
+    #[test]
+    fn reverse_files() {
+        let mut app = new_app_sortable();
+        let lines = |app: &App| {
+            app.list
+                .iter()
+                .map(|i| (i.kind.clone(), i.line_number().copied()))
+                .collect::<Vec<_>>()
+        };
+        let before = lines(&app);
+
+        send_key!(app, key!(Char('O')));
+        assert_eq!(file_order(&app), ["c.txt", "a.txt", "b.txt"]);
+
+        // the items within each file keep their order
+        use RgMessageKind::*;
+        assert_eq!(
+            lines(&app),
+            [
+                (Begin, None),
+                (Match, Some(1)),
+                (Match, Some(2)),
+                (End, None),
+                (Begin, None),
+                (Match, Some(1)),
+                (End, None),
+                (Begin, None),
+                (Match, Some(1)),
+                (End, None),
+            ]
+        );
+        assert!(app.list.iter().enumerate().all(|(i, item)| item.index == i));
+
+        // the reversal is kept when the files are sorted again
+        app.set_sort_mode(SortMode::Path);
+        assert_eq!(file_order(&app), ["c.txt", "b.txt", "a.txt"]);
+
+        send_key!(app, key!(Char('O')));
+        app.set_sort_mode(SortMode::Rg);
+        assert_eq!(file_order(&app), ["b.txt", "a.txt", "c.txt"]);
+        assert_eq!(lines(&app), before);
+    }
+
+    #[test]
+    fn sort_list_keeps_selection() {
+        let mut app = new_app_sortable();
+        let rect = Rect::new(0, 0, 80, 24);
+
+        // select the match in "a.txt"
+        move_and_assert_list_state!(app, Movement::NextFile, (3, 0, 3));
+        move_and_assert_list_state!(app, Movement::NextLine, (4, 0, 4));
+
+        send_key!(app, key!(Char('o')));
+        assert_eq!(app.sort_mode, SortMode::Path);
+        assert_list_state!(app, (1, 0, 1));
+
+        app.cycle_sort_mode(rect);
+        assert_eq!(app.sort_mode, SortMode::MatchCount);
+        assert_list_state!(app, (1, 0, 1));
+    }
+
+    #[test]
+    fn sort_list_tracks_selected_match() {
+        let mut app = new_app_sortable();
+
+        // select the second line in "c.txt"
+        app.list_state.set_selected_item(8);
+        app.selection_anchor = Some(app.selected_position());
+        app.set_sort_mode(SortMode::LineLength);
+        assert_eq!(file_order(&app), ["c.txt", "a.txt", "b.txt"]);
+        assert_eq!(app.list_state.selected_item(), 2);
+
+        // select the last submatch in "a.txt"
+        move_and_assert_list_state!(app, Movement::NextFile, (4, 0, 4));
+        move_and_assert_list_state!(app, Movement::NextLine, (5, 0, 5));
+        move_and_assert_list_state!(app, Movement::Next, (5, 1, 5));
+        move_and_assert_list_state!(app, Movement::Next, (5, 2, 5));
+        let position = app.selected_position();
+
+        for sort_mode in [SortMode::Path, SortMode::MatchCount, SortMode::Rg] {
+            app.set_sort_mode(sort_mode);
+            let (item_idx, submatch_idx) = app.locate_item(&position).unwrap();
+            assert_eq!(app.list[item_idx].path().unwrap().lossy_utf8(), "a.txt");
+            assert_eq!(app.list_state.selected_item(), item_idx);
+            assert_eq!(app.list_state.selected_submatch(), submatch_idx);
+            assert_eq!(submatch_idx, 2);
+        }
+    }
+
+    #[test]
+    fn fuzzy_filter_restores_selected_match() {
+        let mut app = new_app_sortable();
+        let rect = Rect::new(0, 0, 80, 24);
+
+        // select the second submatch in "a.txt"
+        move_and_assert_list_state!(app, Movement::NextFile, (3, 0, 3));
+        move_and_assert_list_state!(app, Movement::NextLine, (4, 0, 4));
+        move_and_assert_list_state!(app, Movement::Next, (4, 1, 4));
+
+        // it's hidden by the filter, so the closest visible match is selected
+        app.set_fuzzy_query(Some("mlh".into()), rect);
+        assert_eq!(file_order(&app), ["c.txt", "b.txt", "a.txt"]);
+        assert_eq!(app.list_state.selected_item(), 1);
+        assert!(!app.is_item_hidden(1));
+
+        // and it's selected again once the filter is cleared
+        app.set_fuzzy_query(None, rect);
+        assert_eq!(file_order(&app), ["b.txt", "a.txt", "c.txt"]);
+        assert_eq!(app.list_state.selected_item(), 4);
+        assert_eq!(app.list_state.selected_submatch(), 1);
+
+        // unless another match was selected in the meantime
+        app.set_fuzzy_query(Some("mlh".into()), rect);
+        move_and_assert_list_state!(app, Movement::NextLine, (1, 0, 1));
+        app.set_fuzzy_query(None, rect);
+        assert_eq!(app.list_state.selected_item(), 7);
+        assert_eq!(app.list_state.selected_submatch(), 0);
+    }
+
+    fn selected_matches(app: &App) -> Vec<usize> {
+        app.list
+            .iter()
+            .filter(|i| i.kind == RgMessageKind::Match && i.get_should_replace_all())
+            .map(|i| i.index)
+            .collect()
+    }
+
+    #[test]
+    fn visual_select_toggles_range() {
+        let mut app = new_app_sortable();
+        assert_eq!(selected_matches(&app), [1, 4, 7, 8]);
+
+        // select from the match in "b.txt" to the first match in "c.txt"
+        move_and_assert_list_state!(app, Movement::NextLine, (1, 0, 1));
+        send_key!(app, key!(Char('m')));
+        assert_eq!(app.ui_state, AppUiState::VisualSelect(1));
+        for _ in 0..4 {
+            send_key!(app, key!(Char('j')));
+        }
+        assert_eq!(app.list_state.selected_item(), 7);
+        send_key!(app, key!(Char(' ')));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+        assert_eq!(selected_matches(&app), [8]);
+
+        // select backwards from the last match, across the end of "a.txt"
+        send_key!(app, key!(Char('j')));
+        send_key!(app, key!(Char('m')));
+        assert_eq!(app.ui_state, AppUiState::VisualSelect(8));
+        send_key!(app, key!(Char('K')));
+        send_key!(app, key!(Char('K')));
+        assert_eq!(app.list_state.selected_item(), 3);
+        send_key!(app, key!(Char(' ')));
+        assert_eq!(selected_matches(&app), [4, 7, 8]);
+
+        // the range is deselected when all of it is already selected
+        send_key!(app, key!(Char('m')));
+        send_key!(app, key!(Char('J')));
+        send_key!(app, key!(Char('j')));
+        assert_eq!(app.list_state.selected_item(), 7);
+        send_key!(app, key!(Char(' ')));
+        assert_eq!(selected_matches(&app), [8]);
+    }
+
+    #[test]
+    fn visual_select_cancel() {
+        let mut app = new_app_sortable();
+        move_and_assert_list_state!(app, Movement::NextLine, (1, 0, 1));
+
+        for cancel in [key!(Esc), key!(Char('q')), key!(Char('m'))] {
+            send_key!(app, key!(Char('m')));
+            send_key!(app, key!(Char('j')));
+            send_key!(app, cancel);
+            assert_eq!(app.ui_state, AppUiState::SelectMatches);
+            assert!(matches!(app.state, AppState::Running));
+        }
+
+        // nothing was toggled, but the selection moved
+        assert_eq!(selected_matches(&app), [1, 4, 7, 8]);
+        assert_eq!(app.list_state.selected_item(), 6);
+    }
+
+    fn hidden_items(app: &App) -> Vec<bool> {
+        (0..app.list.len()).map(|i| app.is_item_hidden(i)).collect()
+    }
+
+    #[test]
+    fn fuzzy_filter() {
+        let mut app = new_app_sortable();
+        let (f, t) = (false, true);
+
+        send_key!(app, key!(Char('/')));
+        assert_eq!(app.ui_state, AppUiState::FilterMatches);
+        for c in "mlh".chars() {
+            send_key!(app, key!(Char(c)));
+        }
+
+        // only the long line in "c.txt" matches, so its file is shown first and everything else is hidden
+        assert_eq!(app.fuzzy_query.as_deref(), Some("mlh"));
+        assert_eq!(file_order(&app), ["c.txt", "b.txt", "a.txt"]);
+        assert_eq!(hidden_items(&app), [f, f, t, f, t, t, t, t, t, t]);
+        assert_eq!(app.list_state.selected_item(), 1);
+
+        // the filter is kept when returning to the select mode
+        send_key!(app, key!(Enter));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+        assert_eq!(app.fuzzy_query.as_deref(), Some("mlh"));
+        move_and_assert_list_state!(app, Movement::NextLine, (1, 0, 1));
+
+        // editing the query updates the filter
+        send_key!(app, key!(Char('/')));
+        send_key!(app, key!(Backspace));
+        send_key!(app, key!(Backspace));
+        send_key!(app, key!(Char('o')));
+        assert_eq!(app.fuzzy_query.as_deref(), Some("mo"));
+        assert_eq!(hidden_items(&app), [f, f, t, f, t, t, t, t, t, t]);
+
+        // and it's cleared with escape
+        send_key!(app, key!(Esc));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+        assert_eq!(app.fuzzy_query, None);
+        assert_eq!(file_order(&app), ["b.txt", "a.txt", "c.txt"]);
+        assert!(hidden_items(&app).iter().all(|h| !h));
+    }
+
+    #[test]
+    fn fuzzy_filter_ranks_files() {
+        let s = |a, b| SubMatch::new_text(a, b);
+        let mut messages = vec![];
+        messages.extend(file("x.txt", &[("xfxoxo", vec![s("x", 0..1)])]));
+        messages.extend(file("y.txt", &[("f_o_o", vec![s("f", 0..1)])]));
+        messages.extend(file("z.txt", &[("foo", vec![s("f", 0..1)])]));
+        messages.push(RgMessage::from_str(RG_JSON_SUMMARY));
+        let mut app = App::new(None, "TESTS".to_string(), messages);
+
+        app.set_fuzzy_query(Some("foo".into()), Rect::new(0, 0, 80, 24));
+        assert_eq!(file_order(&app), ["z.txt", "y.txt", "x.txt"]);
+        assert!(hidden_items(&app).iter().all(|h| !h));
+
+        // an empty query doesn't filter anything
+        app.set_fuzzy_query(Some("".into()), Rect::new(0, 0, 80, 24));
+        assert_eq!(file_order(&app), ["x.txt", "y.txt", "z.txt"]);
+    }
+
+    #[test]
+    fn fuzzy_filter_case_sensitivity() {
+        let s = |a, b| SubMatch::new_text(a, b);
+        let mut messages = vec![];
+        messages.extend(file("x.txt", &[("Foo", vec![s("F", 0..1)])]));
+        messages.extend(file("y.txt", &[("foo", vec![s("f", 0..1)])]));
+        messages.push(RgMessage::from_str(RG_JSON_SUMMARY));
+        let mut app = App::new(None, "TESTS".to_string(), messages);
+        let (f, t) = (false, true);
+
+        // smart case: a lowercase query ignores case
+        send_key!(app, key!(Char('/')));
+        send_key!(app, key!(Char('f')));
+        assert!(!app.is_fuzzy_case_sensitive());
+        assert!(hidden_items(&app).iter().all(|h| !h));
+
+        // toggling it matches the case, so only "y.txt" matches (and is shown first)
+        send_key!(app, key!(Char('t'), modifiers = KeyModifiers::CONTROL));
+        assert_eq!(app.fuzzy_query.as_deref(), Some("f"));
+        assert!(app.is_fuzzy_case_sensitive());
+        assert_eq!(file_order(&app), ["y.txt", "x.txt"]);
+        assert_eq!(hidden_items(&app), [f, f, f, t, t, t]);
+
+        // and it's kept when the query changes
+        send_key!(app, key!(Backspace));
+        send_key!(app, key!(Char('F')));
+        assert_eq!(file_order(&app), ["x.txt", "y.txt"]);
+        assert_eq!(hidden_items(&app), [f, f, f, t, t, t]);
+        send_key!(app, key!(Char('t'), modifiers = KeyModifiers::CONTROL));
+        assert!(!app.is_fuzzy_case_sensitive());
+        assert!(hidden_items(&app).iter().all(|h| !h));
+
+        // clearing the filter goes back to smart case
+        send_key!(app, key!(Esc));
+        send_key!(app, key!(Char('/')));
+        send_key!(app, key!(Char('F')));
+        assert!(app.is_fuzzy_case_sensitive());
+        assert_eq!(hidden_items(&app), [f, f, f, t, t, t]);
+    }
+
+    #[test]
+    fn select_lines_matching_regex() {
+        let s = |a, b| SubMatch::new_text(a, b);
+        let mut messages = vec![];
+        messages.extend(file(
+            "a.txt",
+            &[
+                ("foo() // TODO", vec![s("foo", 0..3)]),
+                ("foo()", vec![s("foo", 0..3)]),
+                ("foo(foo) // todo", vec![s("foo", 0..3), s("foo", 4..7)]),
+            ],
+        ));
+        messages.extend(file("b.txt", &[("bar(foo) // TODO", vec![s("foo", 4..7)])]));
+        messages.push(RgMessage::from_str(RG_JSON_SUMMARY));
+        let mut app = App::new(None, "TESTS".to_string(), messages);
+        let (f, t) = (false, true);
+
+        app.deselect_all_items();
+        app.list[2].set_should_replace_all(true);
+        send_key!(app, key!(Char('T')));
+        for c in "TODO$".chars() {
+            send_key!(app, key!(Char(c)));
+        }
+        assert_eq!(app.ui_state, AppUiState::InputSelectRegex("TODO$".into()));
+
+        // the lines which match are selected, and the others are left as they were
+        send_key!(app, key!(Enter));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+        assert_eq!(app_list_to_match_replace(&app), [t, t, f, t]);
+        assert_eq!(toast(&app), Some("Selected the matches in 2 line(s)"));
+
+        // every match in the line is selected
+        send_key!(app, key!(Char('T')));
+        for c in "(?i)todo".chars() {
+            send_key!(app, key!(Char(c)));
+        }
+        send_key!(app, key!(Enter));
+        assert_eq!(app_list_to_match_replace(&app), [t, t, t, t]);
+        assert!(app.list[3].get_should_replace(1));
+    }
+
+    #[test]
+    fn select_lines_matching_invalid_regex() {
+        let mut app = new_app();
+        app.deselect_all_items();
+
+        send_key!(app, key!(Char('T')));
+        send_key!(app, key!(Char('(')));
+        send_key!(app, key!(Enter));
+        assert_eq!(app.ui_state, AppUiState::InputSelectRegex("(".into()));
+        assert!(toast(&app).unwrap().starts_with("Invalid regex: "));
+        assert_eq!(app_list_to_match_replace(&app), [false, false]);
+
+        send_key!(app, key!(Esc));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+    }
+
+    #[test]
+    fn scroll_lines() {
+        let mut app = new_app_line_wrapping();
+
+        // only available when not wrapping lines
+        send_key!(app, key!(Char('z')));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+
+        app.set_wrap_mode(WrapMode::NoWrap);
+        send_key!(app, key!(Char('z')));
+        assert_eq!(app.ui_state, AppUiState::ScrollLines);
+
+        send_key!(app, key!(Left));
+        assert_eq!(app.h_scroll, 0);
+        send_key!(app, key!(Right));
+        send_key!(app, key!(Char('l')));
+        assert_eq!(app.h_scroll, 2);
+        send_key!(app, key!(Char('L'), modifiers = KeyModifiers::SHIFT));
+        assert_eq!(app.h_scroll, 40);
+        send_key!(app, key!(Char('h')));
+        assert_eq!(app.h_scroll, 39);
+        send_key!(app, key!(Left, modifiers = KeyModifiers::SHIFT));
+        assert_eq!(app.h_scroll, 1);
+        send_key!(app, key!(Char('0')));
+        assert_eq!(app.h_scroll, 0);
+
+        // scrolling doesn't move the selected match
+        assert_list_state!(app, POS_WRAP_BEGIN);
+
+        send_key!(app, key!(Esc));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+    }
+
     #[test]
     fn works_with_other_key_event_kinds() {
         let mut app = new_app();
@@ -932,6 +3291,43 @@ mod tests {
         send_key_assert!(app, key!(Char('a'), kind = KeyEventKind::Release), "a", 1);
     }
 
+    #[test]
+    fn input_replacement_paste() {
+        let mut app = new_app();
+        send_key_assert!(app, key!(Enter), "", 0);
+        send_key_assert!(app, key!(Char('a')), "a", 1);
+        send_key_assert!(app, key!(Char('🎉')), "a🎉", 2);
+        send_key_assert!(app, key!(Left), "a🎉", 1);
+
+        // the text is inserted at the cursor, and the cursor moves to the end of it
+        send_key_assert!(
+            app,
+            Event::Paste("foo\nbär\r\nbaz\r".into()),
+            "afoo\nbär\nbaz\n🎉",
+            13
+        );
+        send_key_assert!(app, key!(Char('!')), "afoo\nbär\nbaz\n!🎉", 14);
+
+        // pasting does nothing outside of the input
+        send_key!(app, key!(Esc));
+        send_key!(app, Event::Paste("foo".into()));
+        assert_eq!(app.ui_state, AppUiState::SelectMatches);
+    }
+
+    #[test]
+    fn input_replacement_initial() {
+        let mut app = new_app();
+        app.set_initial_replacement("fö\no");
+
+        // the replacement is pre-filled, with the cursor at its end
+        send_key_assert!(app, key!(Enter), "fö\no", 4);
+        send_key_assert!(app, key!(Backspace), "fö\n", 3);
+
+        // and it's pre-filled again each time the mode is entered
+        send_key!(app, key!(Esc));
+        send_key_assert!(app, key!(Enter), "fö\no", 4);
+    }
+
     #[test]
     fn input_replacement() {
         let mut app = new_app();
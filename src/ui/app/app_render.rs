@@ -1,19 +1,27 @@
 /// Rendering for `App`.
+use std::time::Instant;
+
 use const_format::formatcp;
 use ratatui::backend::Backend;
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span, Text};
-use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Row, Table, Wrap};
+use ratatui::widgets::{
+    Block, Borders, Clear, List, ListItem, ListState, Paragraph, Row, Table, Wrap,
+};
 use ratatui::Frame;
 
-use crate::model::Printable;
+use crate::model::{Printable, SortMode, WrapMode};
+use crate::replace::expand_replacement;
 use crate::rg::de::RgMessageKind;
-use crate::ui::app::{App, AppUiState};
+use crate::ui::app::focus::focus_window;
+use crate::ui::app::{scrollbar, search_stats, App, AppUiState};
+use crate::ui::keymap::Action;
+use crate::ui::line::Item;
 use crate::ui::render::UiItemContext;
+use crate::ui::theme::Theme;
 use crate::util::byte_pos_from_char_pos;
 
-const LIST_HIGHLIGHT_SYMBOL: &str = "-> ";
 const MINIMUM_WIDTH: u16 = 70;
 const MINIMUM_HEIGHT: u16 = 20;
 const TOO_SMALL_MESSAGE: &str = formatcp!(
@@ -42,13 +50,49 @@ impl App {
         let (root_split, stats_and_input_split) = self.get_layouts(frame);
         if matches!(self.ui_state, AppUiState::Help) {
             self.draw_help_view(f, root_split[0]);
+        } else if matches!(self.ui_state, AppUiState::PickFiles) {
+            self.draw_file_picker_view(f, root_split[0]);
         } else {
             self.draw_main_view(f, root_split[0]);
         }
+        if matches!(self.ui_state, AppUiState::Preview) {
+            self.draw_preview_view(f, root_split[0]);
+        }
+        if let AppUiState::FocusMatch(scroll) = self.ui_state {
+            self.draw_focus_view(f, root_split[0], scroll);
+        }
+        if matches!(self.ui_state, AppUiState::SearchStats) {
+            self.draw_search_stats_view(f, root_split[0]);
+        }
+        self.draw_toast(f, root_split[0]);
         self.draw_stats_line(f, stats_and_input_split[0]);
         self.draw_input_line(f, stats_and_input_split[1]);
     }
 
+    /// Draws the toast (if any) on the bottom line of `r`, aligned to the right.
+    fn draw_toast<B: Backend>(&self, f: &mut Frame<B>, r: Rect) {
+        let msg = match &self.toast {
+            Some((msg, _)) => format!(" {} ", msg),
+            None => return,
+        };
+
+        let style = if self.is_toast_fading(Instant::now()) {
+            Style::default().bg(Color::DarkGray).fg(Color::Black)
+        } else {
+            Style::default().bg(Color::Yellow).fg(Color::Black)
+        };
+        let span = Span::styled(msg, style);
+        let width = (span.width() as u16).min(r.width);
+        let area = Rect::new(
+            r.x + r.width - width,
+            r.y + r.height.saturating_sub(1),
+            width,
+            1,
+        );
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(Line::from(span)), area);
+    }
+
     fn get_layouts(&self, r: Rect) -> (Vec<Rect>, Vec<Rect>) {
         let root_split = Layout::default()
             .direction(Direction::Vertical)
@@ -72,13 +116,57 @@ impl App {
         f.render_widget(p, r);
     }
 
+    /// The replacement `input` expanded with the capturing groups of the selected match, if it changes the text.
+    fn replacement_preview(&self, input: &str) -> Option<String> {
+        let capture_pattern = self.capture_pattern.as_ref()?;
+        let sub_item = self
+            .list
+            .get(self.list_state.selected_item())?
+            .sub_items()
+            .get(self.list_state.selected_submatch())?;
+        let expanded =
+            expand_replacement(capture_pattern, input, &sub_item.sub_match.text.to_vec())?;
+        if expanded == input.as_bytes() {
+            return None;
+        }
+
+        Some(expanded.to_printable(self.printable_style.as_one_line()))
+    }
+
     fn draw_input_line<B: Backend>(&mut self, f: &mut Frame<B>, r: Rect) {
         let prefix = "Replacement: ";
+        let filter_prefix = "Filter: ";
+        let context_prefix = "Context lines: ";
+        let select_regex_prefix = "Select lines matching: ";
+        let search_prefix = "Search help: ";
+
         let mut spans = match &self.ui_state {
-            AppUiState::Help => vec![Span::from("Viewing Help. Press <esc> or <q> to return...")],
+            AppUiState::Help => match self.help_text_state.query() {
+                Some(query) => {
+                    let hits = self.help_text_state.hits();
+                    let position = match self.help_text_state.current_hit() {
+                        Some(hit) => hits.iter().position(|h| h == hit).unwrap_or(0) + 1,
+                        None => 0,
+                    };
+                    vec![
+                        Span::from(search_prefix),
+                        Span::from(query.to_owned()),
+                        Span::styled(
+                            format!("    ({}/{} found)", position, hits.len()),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                    ]
+                }
+                None => vec![Span::from(
+                    "Viewing Help. Press </> to search it, or <esc> or <q> to return...",
+                )],
+            },
             AppUiState::SelectMatches => vec![Span::from(
                 "Select (or deselect) Matches with <space> then press <Enter>. Press <?> for help.",
             )],
+            AppUiState::ScrollLines => vec![Span::from(
+                "Scroll lines with <left> and <right>. Press <esc> or <q> to return...",
+            )],
             AppUiState::InputReplacement(input, pos) => {
                 let mut spans = vec![Span::from(prefix)];
                 if input.is_empty() {
@@ -87,7 +175,7 @@ impl App {
                         Style::default().fg(Color::DarkGray),
                     ));
                 } else {
-                    let (before, after) = input.split_at(byte_pos_from_char_pos(&input, *pos));
+                    let (before, after) = input.split_at(byte_pos_from_char_pos(input, *pos));
                     let style = self.printable_style.as_one_line();
                     spans.push(Span::from(before.to_printable(style)));
                     spans.push(Span::from(after.to_printable(style)));
@@ -95,9 +183,44 @@ impl App {
 
                 spans
             }
+            AppUiState::ConfirmReplacement(_, _) if self.typed_confirmation.is_some() => vec![
+                Span::styled(
+                    format!(
+                        "Replace in {} files? Type \"yes\" to confirm: ",
+                        self.affected_file_count()
+                    ),
+                    Style::default().fg(Color::Red),
+                ),
+                Span::from(self.typed_confirmation.clone().unwrap_or_default()),
+            ],
             AppUiState::ConfirmReplacement(_, _) => vec![Span::from(
-                "Press <enter> to write changes, <esc> to cancel.",
+                "Press <enter> (or <w> to quit quietly) to write changes, <c> to show only them, <esc> to cancel.",
+            )],
+            AppUiState::Preview => vec![Span::from(
+                "Previewing file. Scroll with <j> and <k>, press <esc> or <q> to return...",
+            )],
+            AppUiState::FocusMatch(_) => vec![Span::from(
+                "Focusing match. Scroll with <j> and <k>, press <esc> or <q> to return...",
+            )],
+            AppUiState::VisualSelect(_) => vec![Span::from(
+                "Move to select a range of matches, then press <space> to toggle them. Press <esc> or <q> to cancel...",
             )],
+            AppUiState::PickFiles => vec![Span::from(
+                "Choose files with <space> (or <a> for all) then press <enter> to show their matches. Press <q> to quit.",
+            )],
+            AppUiState::SearchStats => vec![Span::from(
+                "Viewing search stats. Press <esc> or <q> to return...",
+            )],
+            AppUiState::FilterMatches => vec![
+                Span::from(filter_prefix),
+                Span::from(self.fuzzy_query.as_deref().unwrap_or_default().to_owned()),
+            ],
+            AppUiState::InputContext(input) => {
+                vec![Span::from(context_prefix), Span::from(input.to_owned())]
+            }
+            AppUiState::InputSelectRegex(input) => {
+                vec![Span::from(select_regex_prefix), Span::from(input.to_owned())]
+            }
         };
 
         let mut render_input = |spans| f.render_widget(Paragraph::new(Line::from(spans)), r);
@@ -108,16 +231,89 @@ impl App {
             let x_pos = if input.is_empty() {
                 0
             } else {
-                (&spans[spans.len() - 2]).width() as u16
+                spans[spans.len() - 2].width() as u16
+            };
+
+            // show what the selected match would be replaced with, if the text refers to capturing groups
+            if let Some(preview) = self.replacement_preview(input) {
+                spans.push(Span::styled(
+                    format!("  → {}", preview),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+
+            spans.push(match &self.preprocessor {
+                Some(_) => Span::styled(
+                    "    (replacements are disabled since rg's --pre was used)",
+                    Style::default().fg(Color::Red),
+                ),
+                None => Span::styled(
+                    "    (press <control+s> to accept replacement)",
+                    Style::default().fg(Color::DarkGray),
+                ),
+            });
+
+            render_input(spans);
+            f.set_cursor(x_start + x_pos, r.y);
+        } else if matches!(self.ui_state, AppUiState::Help)
+            && self.help_text_state.query().is_some()
+        {
+            // the cursor is after the query while it's being typed
+            let x_pos = r.x + spans[..2].iter().map(|s| s.width() as u16).sum::<u16>();
+            let hint = if self.help_text_state.is_editing_query() {
+                "    (press <enter> to accept search, <esc> to clear it)"
+            } else {
+                "    (press <n> or <N> for the next or previous match)"
             };
+            spans.push(Span::styled(hint, Style::default().fg(Color::DarkGray)));
 
+            render_input(spans);
+            if self.help_text_state.is_editing_query() {
+                f.set_cursor(x_pos, r.y);
+            }
+        } else if let AppUiState::InputContext(_) = &self.ui_state {
+            let x_pos = r.x + spans.iter().map(|s| s.width() as u16).sum::<u16>();
             spans.push(Span::styled(
-                "    (press <control+s> to accept replacement)",
+                "    (press <enter> to search again with this many lines of context, <esc> to cancel)",
                 Style::default().fg(Color::DarkGray),
             ));
 
             render_input(spans);
-            f.set_cursor(x_start + x_pos, r.y);
+            f.set_cursor(x_pos, r.y);
+        } else if let AppUiState::InputSelectRegex(_) = &self.ui_state {
+            let x_pos = r.x + spans.iter().map(|s| s.width() as u16).sum::<u16>();
+            spans.push(Span::styled(
+                "    (press <enter> to select the matches in the lines it matches, <esc> to cancel)",
+                Style::default().fg(Color::DarkGray),
+            ));
+
+            render_input(spans);
+            f.set_cursor(x_pos, r.y);
+        } else if self.typed_confirmation.is_some() {
+            let x_pos = r.x + spans.iter().map(|s| s.width() as u16).sum::<u16>();
+            spans.push(Span::styled(
+                "    (press <enter> to write the changes, <esc> to cancel)",
+                Style::default().fg(Color::DarkGray),
+            ));
+
+            render_input(spans);
+            f.set_cursor(x_pos, r.y);
+        } else if let AppUiState::FilterMatches = &self.ui_state {
+            let x_pos = r.x + spans.iter().map(|s| s.width() as u16).sum::<u16>();
+            spans.push(Span::styled(
+                format!(
+                    "    (press <enter> to accept filter, <esc> to clear it, <ctrl+t> to {} case)",
+                    if self.is_fuzzy_case_sensitive() {
+                        "ignore"
+                    } else {
+                        "match"
+                    }
+                ),
+                Style::default().fg(Color::DarkGray),
+            ));
+
+            render_input(spans);
+            f.set_cursor(x_pos, r.y);
         } else {
             render_input(spans);
         }
@@ -136,6 +332,13 @@ impl App {
             })
             .sum::<usize>();
 
+        let match_position = self
+            .match_ordinal(
+                self.list_state.selected_item(),
+                self.list_state.selected_submatch(),
+            )
+            .map_or_else(|| String::from("-"), |n| n.to_string());
+
         // Split the stats line into halves, so we can render left and right aligned portions.
         let hsplit = Layout::default()
             .direction(Direction::Horizontal)
@@ -144,7 +347,7 @@ impl App {
             .split(r);
 
         let left_side_items = vec![Line::from(self.ui_state.to_span())];
-        let right_side_items = vec![Line::from(vec![
+        let mut right_side_spans = vec![
             Span::styled(
                 format!(" {} ", self.rg_cmdline),
                 Style::default().bg(Color::Blue).fg(Color::Black),
@@ -153,11 +356,44 @@ impl App {
                 format!(" CtrlChars: {} ", self.printable_style),
                 Style::default().bg(Color::Cyan).fg(Color::Black),
             ),
+        ];
+        if let Some(scope) = self.search_scope.indicator() {
+            right_side_spans.push(Span::styled(
+                format!(" Scope: {} ", scope),
+                Style::default().bg(Color::LightRed).fg(Color::Black),
+            ));
+        }
+        if self.sort_mode != SortMode::Rg || self.reverse_files {
+            right_side_spans.push(Span::styled(
+                format!(
+                    " Sort: {}{} ",
+                    self.sort_mode,
+                    if self.reverse_files {
+                        " (reversed)"
+                    } else {
+                        ""
+                    }
+                ),
+                Style::default().bg(Color::Green).fg(Color::Black),
+            ));
+        }
+        if let Some(query) = self.active_fuzzy_query() {
+            right_side_spans.push(Span::styled(
+                format!(" Filter: {} ", query),
+                Style::default().bg(Color::White).fg(Color::Black),
+            ));
+        }
+        right_side_spans.extend([
+            Span::styled(
+                format!(" Match: {}/{} ", match_position, self.match_total()),
+                Style::default().bg(Color::Yellow).fg(Color::Black),
+            ),
             Span::styled(
                 format!(" {}/{} ", replacement_count, self.stats.matches),
                 Style::default().bg(Color::Magenta).fg(Color::Black),
             ),
-        ])];
+        ]);
+        let right_side_items = vec![Line::from(right_side_spans)];
 
         let stats_line_style = Style::default().bg(Color::DarkGray).fg(Color::White);
         f.render_widget(
@@ -181,38 +417,143 @@ impl App {
             .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
             .split(r);
 
-        let help_table = Table::new(
-            vec![
-                Row::new(vec!["MODE: ALL"]).style(title_style),
-                Row::new(vec!["control + b", "move backward one page"]),
-                Row::new(vec!["control + f", "move forward one page"]),
-                Row::new(vec![
-                    "control + v",
-                    "toggle how control characters are rendered",
-                ])
-                .bottom_margin(1),
-                Row::new(vec!["MODE: SELECT"]).style(title_style),
-                Row::new(vec!["k, up", "move to previous match"]),
-                Row::new(vec!["j, down", "move to next match"]),
-                Row::new(vec!["K, shift + up", "move to previous file"]),
-                Row::new(vec!["J, shift + down", "move to next file"]),
-                Row::new(vec!["space", "toggle selection"]),
-                Row::new(vec!["a, A", "toggle selection for all matches"]),
-                Row::new(vec!["s, S", "toggle selection for whole line"]),
-                Row::new(vec!["v", "invert section for the current item"]),
-                Row::new(vec!["V", "invert section for all items"]),
-                Row::new(vec!["enter, r, R", "accept selection"]),
-                Row::new(vec!["q, esc", "quit"]),
-                Row::new(vec!["?", "show help and keybindings"]).bottom_margin(1),
-                Row::new(vec!["MODE: REPLACE"]).style(title_style),
-                Row::new(vec!["control + s", "accept replacement text"]),
-                Row::new(vec!["esc", "previous mode"]).bottom_margin(1),
-                Row::new(vec!["MODE: CONFIRM"]).style(title_style),
-                Row::new(vec!["enter", "write replacements to disk"]),
-                Row::new(vec!["q, esc", "previous mode"]),
-            ]
-            .into_iter(),
-        )
+        // the keys are described from the keymap, since the user may have changed them
+        let keys = |action| self.keymap.describe(action);
+        let row =
+            |keys: String, description: &'static str| Row::new(vec![keys, description.into()]);
+        let help_table = Table::new(vec![
+            Row::new(vec!["MODE: ALL"]).style(title_style),
+            row(keys(Action::PageUp), "move backward one page"),
+            row(keys(Action::PageDown), "move forward one page"),
+            row(
+                keys(Action::CyclePrintableStyle),
+                "toggle how control characters are rendered",
+            )
+            .bottom_margin(1),
+            Row::new(vec!["MODE: SELECT"]).style(title_style),
+            row(keys(Action::MoveUp), "move to previous match"),
+            row(keys(Action::MoveDown), "move to next match"),
+            row(keys(Action::PrevFile), "move to previous file"),
+            row(keys(Action::NextFile), "move to next file"),
+            row(
+                keys(Action::MoveToFirst),
+                "move to the start of the list (press twice)",
+            ),
+            row(keys(Action::MoveToLast), "move to the end of the list"),
+            row(keys(Action::Toggle), "toggle selection"),
+            row("1-9".into(), "toggle the nth match of the current item"),
+            row(keys(Action::ToggleAll), "toggle selection for all matches"),
+            row(keys(Action::DeselectAll), "deselect all matches"),
+            row(keys(Action::ToggleLine), "toggle selection for whole line"),
+            row(keys(Action::Invert), "invert section for the current item"),
+            row(
+                keys(Action::InvertMatch),
+                "invert section for the current match",
+            ),
+            row(keys(Action::InvertAll), "invert section for all items"),
+            row(
+                keys(Action::VisualSelect),
+                "select a range of matches to toggle",
+            ),
+            row(
+                keys(Action::SelectRegex),
+                "select the matches in lines matching a regex",
+            ),
+            row(
+                keys(Action::CollapseDuplicates),
+                "collapse (or expand) identical matches",
+            ),
+            row(
+                keys(Action::ToggleLineNumbers),
+                "show (or hide) line numbers",
+            ),
+            row(keys(Action::CycleSort), "cycle how files are sorted"),
+            row(keys(Action::ReverseFiles), "reverse the order of the files"),
+            row(keys(Action::Filter), "fuzzy filter the matches"),
+            row(
+                keys(Action::OpenEditor),
+                "open the selected file in $EDITOR",
+            ),
+            row(keys(Action::Preview), "preview the whole file"),
+            row(
+                keys(Action::FocusMatch),
+                "show the selected match using the whole terminal",
+            ),
+            row(keys(Action::SearchStats), "show search time and stats"),
+            row(keys(Action::CopyCmdline), "copy rg's command line"),
+            row(
+                keys(Action::ChangeContext),
+                "search again with a different number of context lines",
+            ),
+            row(keys(Action::EnterReplace), "accept selection"),
+            row(
+                keys(Action::ScrollLines),
+                "scroll lines horizontally (with --no-wrap)",
+            ),
+            row(keys(Action::Quit), "quit"),
+            row(keys(Action::Help), "show help and keybindings").bottom_margin(1),
+            Row::new(vec!["MODE: SCROLL"]).style(title_style),
+            row(keys(Action::ScrollLeft), "scroll left"),
+            row(keys(Action::ScrollRight), "scroll right"),
+            row(keys(Action::ScrollLeftHalfPage), "scroll left half a page"),
+            row(
+                keys(Action::ScrollRightHalfPage),
+                "scroll right half a page",
+            ),
+            row(
+                keys(Action::ScrollStart),
+                "scroll to the start of the lines",
+            ),
+            row(
+                format!("{}, {}", keys(Action::Back), keys(Action::ScrollLines)),
+                "previous mode",
+            )
+            .bottom_margin(1),
+            Row::new(vec!["MODE: VISUAL"]).style(title_style),
+            row(keys(Action::MoveUp), "move to previous match"),
+            row(keys(Action::MoveDown), "move to next match"),
+            row(keys(Action::PrevFile), "move to previous file"),
+            row(keys(Action::NextFile), "move to next file"),
+            row(keys(Action::Toggle), "toggle the selected range"),
+            row(
+                format!("{}, {}", keys(Action::Back), keys(Action::VisualSelect)),
+                "previous mode",
+            )
+            .bottom_margin(1),
+            Row::new(vec!["MODE: PREVIEW"]).style(title_style),
+            row(keys(Action::MoveUp), "scroll up"),
+            row(keys(Action::MoveDown), "scroll down"),
+            row(keys(Action::Back), "previous mode").bottom_margin(1),
+            Row::new(vec!["MODE: FILTER"]).style(title_style),
+            row("enter".into(), "accept filter"),
+            row("esc".into(), "clear filter").bottom_margin(1),
+            Row::new(vec!["MODE: REPLACE"]).style(title_style),
+            row(keys(Action::AcceptReplacement), "accept replacement text"),
+            row(
+                keys(Action::ReplaceSelectedOnly),
+                "use the text for only the selected match",
+            ),
+            row(
+                keys(Action::CopyReplacedLine),
+                "copy the selected line with its replacements",
+            ),
+            row("esc".into(), "previous mode").bottom_margin(1),
+            Row::new(vec!["MODE: CONFIRM"]).style(title_style),
+            row(keys(Action::Confirm), "write replacements to disk"),
+            row(
+                keys(Action::ConfirmQuietly),
+                "write replacements to disk and quit quietly",
+            ),
+            row(
+                keys(Action::ShowOnlyChanges),
+                "show only the lines which will change",
+            ),
+            row(
+                keys(Action::CopyReplacedLine),
+                "copy the selected line with its replacements",
+            ),
+            row(keys(Action::Back), "previous mode"),
+        ])
         .header(
             Row::new(vec!["[Key]", "[Action]"])
                 .style(
@@ -230,11 +571,31 @@ impl App {
         .widths(&[Constraint::Length(20), Constraint::Length(50)])
         .column_spacing(1);
 
-        f.render_widget(help_table, hsplit[1]);
+        // the rg options (if any) are shown below the keybindings
+        let right_split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(1),
+                    // +2 accounting for borders
+                    Constraint::Length(match self.rg_options.len() {
+                        0 => 0,
+                        n => n as u16 + 2,
+                    }),
+                ]
+                .as_ref(),
+            )
+            .split(hsplit[1]);
+
+        f.render_widget(help_table, right_split[0]);
+        if !self.rg_options.is_empty() {
+            self.draw_rg_options(f, right_split[1], title_style);
+        }
 
         let help_title = Span::styled(format!("{} help", env!("CARGO_PKG_NAME")), title_style);
-        let help_text = self.help_text_state.text(hsplit[0].height as usize);
-        let help_text = Text::from(help_text.as_str());
+        let help_text = self
+            .help_text_state
+            .highlighted_text(hsplit[0].height as usize);
         let help_paragraph = Paragraph::new(help_text)
             .wrap(Wrap { trim: false })
             .block(Block::default().borders(Borders::ALL).title(help_title));
@@ -242,16 +603,248 @@ impl App {
         f.render_widget(help_paragraph, hsplit[0]);
     }
 
+    /// Draws the preview of a whole file in a box over the main view.
+    fn draw_preview_view<B: Backend>(&mut self, f: &mut Frame<B>, r: Rect) {
+        let style = self.printable_style.as_one_line();
+        let preview = match self.preview.as_mut() {
+            Some(preview) => preview,
+            None => return,
+        };
+
+        let area = Rect::new(
+            r.x + r.width / 10,
+            r.y + r.height / 10,
+            r.width - r.width / 5,
+            r.height - r.height / 5,
+        );
+        let block = Block::default().borders(Borders::ALL).title(Span::styled(
+            format!(" {} ", preview.path.display()),
+            Style::default().fg(Color::Magenta),
+        ));
+        let inner = block.inner(area);
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let window = preview.window(inner.height as usize);
+        let lines = match &preview.lines {
+            Ok(lines) => lines,
+            Err(e) => {
+                let p = Paragraph::new(format!("Failed to read file: {}", e))
+                    .style(Style::default().fg(Color::Red))
+                    .wrap(Wrap { trim: false });
+                return f.render_widget(p, inner);
+            }
+        };
+
+        let number_width = window.end.to_string().len();
+        let preview_lines = lines[window.clone()]
+            .iter()
+            .zip(window)
+            .map(|(line, i)| {
+                let number_style = if preview.match_lines.contains(&i) {
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                };
+                let mut spans = vec![Span::styled(
+                    format!("{:>width$} ", i + 1, width = number_width),
+                    number_style,
+                )];
+
+                // split the line at the submatches, so they can be highlighted
+                let mut offset = 0;
+                for (_, range) in preview.highlights.iter().filter(|(n, _)| *n == i) {
+                    if range.start < offset || line.get(range.clone()).is_none() {
+                        continue;
+                    }
+
+                    spans.push(Span::raw((&line[offset..range.start]).to_printable(style)));
+                    spans.push(Span::styled(
+                        (&line[range.clone()]).to_printable(style),
+                        Style::default().fg(Color::Black).bg(Color::Red),
+                    ));
+                    offset = range.end;
+                }
+                spans.push(Span::raw((&line[offset..]).to_printable(style)));
+
+                Line::from(spans)
+            })
+            .collect::<Vec<_>>();
+
+        f.render_widget(Paragraph::new(preview_lines), inner);
+    }
+
+    /// The lines of the selected match, wrapped to `width` columns (regardless of the wrap mode).
+    pub(crate) fn focused_match_lines(&self, width: u16) -> Vec<Line<'_>> {
+        let item = match self.list.get(self.list_state.selected_item()) {
+            Some(item) => item,
+            None => return vec![],
+        };
+
+        let ctx = UiItemContext {
+            capture_pattern: self.capture_pattern.as_ref(),
+            replacement_text: self.ui_state.user_replacement_text(),
+            printable_style: self.printable_style,
+            wrap_mode: match self.wrap_mode {
+                WrapMode::NoWrap => WrapMode::Wrap,
+                wrap_mode => wrap_mode,
+            },
+            show_line_numbers: self.show_line_numbers,
+            passthru: self.passthru,
+            tab_width: self.tab_width,
+            h_scroll: 0,
+            app_list_state: &self.list_state,
+            app_ui_state: &self.ui_state,
+            // only the width is used to wrap the lines (a larger area would be clamped, and shrink its width)
+            list_rect: Rect::new(0, 0, width, 1),
+            fuzzy_query: self.active_fuzzy_query(),
+            fuzzy_case_sensitive: self.is_fuzzy_case_sensitive(),
+        };
+
+        item.to_span_lines(&ctx)
+    }
+
+    /// Draws the lines of the selected match in a box over the whole main view.
+    fn draw_focus_view<B: Backend>(&mut self, f: &mut Frame<B>, r: Rect, scroll: usize) {
+        let title = match self.list.get(self.list_state.selected_item()) {
+            Some(item) => format!(
+                " {}:{} ",
+                item.path().map(|p| p.lossy_utf8()).unwrap_or_default(),
+                item.line_number().copied().unwrap_or_default()
+            ),
+            None => return,
+        };
+
+        let block = Block::default().borders(Borders::ALL).title(Span::styled(
+            title,
+            Style::default().fg(Color::LightMagenta),
+        ));
+        let inner = block.inner(r);
+        f.render_widget(Clear, r);
+        f.render_widget(block, r);
+
+        // the scroll is clamped when drawn, since that's when the number of rows is known
+        let lines = self.focused_match_lines(inner.width);
+        let window = focus_window(lines.len(), scroll, inner.height as usize);
+        f.render_widget(Paragraph::new(lines[window.clone()].to_vec()), inner);
+        self.ui_state = AppUiState::FocusMatch(window.start);
+    }
+
+    /// Draws the stats of `rg`'s search in a box over the main view.
+    fn draw_search_stats_view<B: Backend>(&mut self, f: &mut Frame<B>, r: Rect) {
+        let rows = search_stats::stats_rows(&self.stats, &self.elapsed_total);
+        let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+        let lines = rows
+            .into_iter()
+            .map(|(label, value)| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:>width$}  ", label, width = label_width),
+                        Style::default().fg(Color::LightBlue),
+                    ),
+                    Span::from(value),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        // +2 accounting for borders
+        let height = (lines.len() as u16 + 2).min(r.height);
+        let width = (r.width * 3 / 5).max(r.width.min(40));
+        let area = Rect::new(
+            r.x + (r.width - width) / 2,
+            r.y + (r.height - height) / 2,
+            width,
+            height,
+        );
+        let block = Block::default().borders(Borders::ALL).title(Span::styled(
+            " Search Stats ",
+            Style::default().fg(Color::LightBlue),
+        ));
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_file_picker_view<B: Backend>(&self, f: &mut Frame<B>, r: Rect) {
+        let picker = match &self.file_picker {
+            Some(picker) => picker,
+            None => return,
+        };
+
+        let items = picker
+            .files
+            .iter()
+            .map(|file| {
+                let (checkbox, style) = if file.chosen {
+                    ("[x] ", Style::default().fg(Color::Green))
+                } else {
+                    ("[ ] ", Style::default())
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(checkbox, style),
+                    Span::styled(file.path.lossy_utf8(), style.fg(Color::Magenta)),
+                    Span::styled(
+                        format!("  ({} match(es))", file.match_count),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]))
+            })
+            .collect::<Vec<_>>();
+
+        let mut state = ListState::default();
+        state.select(Some(picker.selected));
+        let title = format!(
+            " {} of {} file(s) chosen ",
+            picker.chosen_paths().len(),
+            picker.files.len()
+        );
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_symbol(&self.theme.indicator);
+        f.render_stateful_widget(list, r, &mut state);
+    }
+
+    fn draw_rg_options<B: Backend>(&self, f: &mut Frame<B>, r: Rect, title_style: Style) {
+        let label_width = self
+            .rg_options
+            .iter()
+            .map(|(label, _)| label.len())
+            .max()
+            .unwrap_or(0);
+        let lines = self
+            .rg_options
+            .iter()
+            .map(|(label, value)| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:>width$}  ", label, width = label_width),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    Span::from(value.as_str()),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(Span::styled("rg options", title_style));
+        f.render_widget(Paragraph::new(lines).block(block), r);
+    }
+
     fn list_indicator(&self) -> String {
-        if self.ui_state.is_replacing() {
-            " ".repeat(LIST_HIGHLIGHT_SYMBOL.len())
+        let indicator = &self.theme.indicator;
+        if self.ui_state.is_replacing() || self.list_state.is_indicator_hidden() {
+            // padded with as many columns as the indicator takes up (not its length), so the list doesn't move
+            " ".repeat(Span::from(indicator.as_str()).width())
         } else {
-            String::from(LIST_HIGHLIGHT_SYMBOL)
+            indicator.clone()
         }
     }
 
+    /// How many columns are left of the list's text: the indicator, and the selection markers (if there are any).
     fn list_indicator_width(&self) -> u16 {
-        Span::from(self.list_indicator().as_str()).width() as u16
+        (Span::from(self.list_indicator().as_str()).width() + self.theme.marker_width()) as u16
     }
 
     fn draw_main_view<B: Backend>(&mut self, f: &mut Frame<B>, r: Rect) {
@@ -271,28 +864,54 @@ impl App {
             capture_pattern: self.capture_pattern.as_ref(),
             replacement_text: self.ui_state.user_replacement_text(),
             printable_style: self.printable_style,
+            wrap_mode: self.wrap_mode,
+            show_line_numbers: self.show_line_numbers,
+            passthru: self.passthru,
+            tab_width: self.tab_width,
+            h_scroll: self.h_scroll,
             app_list_state: &self.list_state,
             app_ui_state: &self.ui_state,
             list_rect,
+            fuzzy_query: self.fuzzy_query.as_deref().filter(|q| !q.is_empty()),
+            fuzzy_case_sensitive: self.is_fuzzy_case_sensitive(),
         };
 
+        // when duplicates are collapsed, hidden items are skipped and the others show how many
+        // items they represent
+        let duplicates = self.collapsed_duplicates.as_ref();
+        let theme = &self.theme;
+        let hidden = (0..self.list.len())
+            .map(|i| self.is_item_hidden(i))
+            .collect::<Vec<_>>();
         // iterate over all our items and collect only those that will be in the visible
         // window region of the list (skipping all the others)
         let mut match_items = vec![];
         let mut curr_height = 0;
-        for item in self.list.iter_mut() {
+        for (i, item) in self.list.iter_mut().enumerate() {
             // we've passed the visible region
             if curr_height > window_end {
                 break;
             }
 
-            let line_count = item.line_count(list_rect.width, self.printable_style);
+            if hidden[i] {
+                continue;
+            }
+
+            let line_count = item.line_count(
+                list_rect.width,
+                self.printable_style,
+                self.wrap_mode,
+                self.show_line_numbers,
+                self.tab_width,
+            );
 
             // items that fall in the visible window, but don't start in the visible window
             if curr_height < window_start {
                 let gap = (curr_height + line_count).saturating_sub(window_start);
                 if gap > 0 {
-                    let lines = item.to_span_lines(ctx);
+                    let mut lines = item.to_span_lines(ctx);
+                    push_duplicate_count(&mut lines, duplicates.map_or(1, |d| d.count(i)));
+                    push_selection_marker(&mut lines, item, theme);
                     let padding = lines.len() - gap;
                     for line in lines.into_iter().skip(padding) {
                         match_items.push(ListItem::new(line));
@@ -302,7 +921,10 @@ impl App {
 
             // items that start in the visible window
             if curr_height >= window_start {
-                for line in item.to_span_lines(ctx).into_iter() {
+                let mut lines = item.to_span_lines(ctx);
+                push_duplicate_count(&mut lines, duplicates.map_or(1, |d| d.count(i)));
+                push_selection_marker(&mut lines, item, theme);
+                for line in lines.into_iter() {
                     match_items.push(ListItem::new(line));
                 }
             }
@@ -317,7 +939,26 @@ impl App {
             .style(Style::default().fg(Color::White))
             .highlight_symbol(&indicator_symbol);
 
-        f.render_stateful_widget(match_list, r, &mut self.list_state.indicator_mut());
+        f.render_stateful_widget(match_list, r, self.list_state.indicator_mut());
+
+        // the scrollbar is drawn in the last column, which the list leaves free for it (see `main_view_list_rect`)
+        let content_height = self.list_line_count(list_rect.width);
+        if let Some(thumb) = scrollbar::thumb(content_height, window_height, window_start) {
+            let lines = (0..window_height)
+                .map(|row| match thumb.contains(&row) {
+                    true => Line::styled("█", Style::default().fg(Color::Gray)),
+                    false => Line::styled("│", Style::default().fg(Color::DarkGray)),
+                })
+                .collect::<Vec<_>>();
+            let scrollbar_rect = Rect::new(r.right().saturating_sub(1), r.y, 1, r.height);
+            f.render_widget(Paragraph::new(lines), scrollbar_rect);
+        }
+    }
+
+    /// How many lines of the help text are shown at once.
+    pub(crate) fn help_page_height(&self, term_size: Rect) -> usize {
+        // -2 accounting for borders
+        self.get_layouts(term_size).0[0].height.saturating_sub(2) as usize
     }
 
     pub(crate) fn main_view_list_rect(&self, term_size: Rect) -> Rect {
@@ -328,11 +969,123 @@ impl App {
             height,
         } = self.get_layouts(term_size).0[0];
         let indicator_width = self.list_indicator_width();
+        // the last column is left for the scrollbar
         Rect::new(
             x + indicator_width,
             y,
-            width.saturating_sub(indicator_width),
+            width.saturating_sub(indicator_width + 1),
             height,
         )
     }
 }
+
+/// Appends a badge to the last line of an item, showing how many identical items it represents.
+fn push_duplicate_count(lines: &mut [Line], count: usize) {
+    if count > 1 {
+        if let Some(line) = lines.last_mut() {
+            line.spans.push(Span::styled(
+                format!(" (x{})", count),
+                Style::default().fg(Color::Black).bg(Color::Yellow),
+            ));
+        }
+    }
+}
+
+/// Prefixes the lines of an item with the selection markers of the theme: matches get the marker which shows
+/// whether they'll be replaced, and every other line is padded so they all stay aligned.
+fn push_selection_marker(lines: &mut [Line], item: &Item, theme: &Theme) {
+    if theme.marker_width() == 0 {
+        return;
+    }
+
+    let first_line = usize::from(item.has_context_separator());
+    let padding = " ".repeat(theme.marker_width());
+    for (i, line) in lines.iter_mut().enumerate() {
+        let marker = match item.kind {
+            RgMessageKind::Match if i == first_line => Span::styled(
+                theme.marker(item.replace_count() > 0),
+                Style::default().fg(Color::DarkGray),
+            ),
+            _ => Span::raw(padding.clone()),
+        };
+        line.spans.insert(0, marker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use ratatui::layout::Rect;
+
+    use crate::model::WrapMode;
+    use crate::rg::de::test_utilities::*;
+    use crate::rg::de::{RgMessage, RgMessageKind, Stats, SubMatch};
+    use crate::ui::app::{App, AppUiState};
+    use crate::ui::theme::Theme;
+
+    fn new_app(theme: Theme) -> App {
+        let messages = vec![RgMessage::from_str(RG_JSON_SUMMARY)];
+        let mut app = App::new(None, String::new(), messages);
+        app.set_theme(theme);
+        app
+    }
+
+    #[test]
+    fn list_indicator_width() {
+        assert_eq!(new_app(Theme::default()).list_indicator_width(), 3);
+
+        // wide characters take up two columns each
+        let mut app = new_app(Theme {
+            indicator: String::from("👉 "),
+            selected_marker: String::from("✅ "),
+            deselected_marker: String::from("❌"),
+        });
+        assert_eq!(app.list_indicator(), "👉 ");
+        assert_eq!(app.list_indicator_width(), 6);
+
+        // the indicator is hidden when replacing, but its columns are kept
+        app.ui_state = AppUiState::InputReplacement(String::new(), 0);
+        assert_eq!(app.list_indicator(), "   ");
+        assert_eq!(app.list_indicator_width(), 6);
+
+        let rect = app.main_view_list_rect(Rect::new(0, 0, 80, 24));
+        assert_eq!((rect.x, rect.width), (6, 80 - 6 - 1));
+    }
+
+    #[test]
+    fn focused_match_lines() {
+        let text = "0123456789".repeat(5);
+        let messages = vec![
+            RgMessageBuilder::new(RgMessageKind::Begin)
+                .with_path_text("a.txt")
+                .build(),
+            RgMessageBuilder::new(RgMessageKind::Match)
+                .with_path_text("a.txt")
+                .with_lines_text(&text)
+                .with_line_number(1)
+                .with_offset(0)
+                .with_submatches(vec![SubMatch::new_text("234", 2..5)])
+                .build(),
+            RgMessageBuilder::new(RgMessageKind::End)
+                .with_path_text("a.txt")
+                .with_stats(Stats::new())
+                .build(),
+            RgMessage::from_str(RG_JSON_SUMMARY),
+        ];
+        let mut app = App::new(None, String::new(), messages);
+        app.list_state.set_selected_item(1);
+
+        // the line is wrapped to the given width, even when lines aren't wrapped in the list
+        app.set_wrap_mode(WrapMode::NoWrap);
+        let lines = app.focused_match_lines(20);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines
+                .iter()
+                .flat_map(|line| line.spans.iter().map(|span| span.content.as_ref()))
+                .collect::<String>(),
+            format!("1:{}", text)
+        );
+        assert_eq!(app.focused_match_lines(100).len(), 1);
+    }
+}
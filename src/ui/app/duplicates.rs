@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use crate::rg::de::RgMessageKind;
+use crate::ui::line::Item;
+
+/// Groups `Match` items which have identical `lines`, so they can be collapsed into a single row
+/// in the main view. The first item of each group represents the whole group.
+#[derive(Debug)]
+pub struct DuplicateGroups {
+    /// For each item in the list, the index of the item which represents it.
+    representatives: Vec<usize>,
+    /// The indices of all the items in each group of `Match` items, keyed by the group's representative.
+    groups: HashMap<usize, Vec<usize>>,
+}
+
+impl DuplicateGroups {
+    pub fn new(items: &[Item]) -> DuplicateGroups {
+        let mut representatives = Vec::with_capacity(items.len());
+        let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut seen: HashMap<Vec<u8>, usize> = HashMap::new();
+
+        for (i, item) in items.iter().enumerate() {
+            match (&item.kind, item.lines()) {
+                (RgMessageKind::Match, Some(lines)) => {
+                    let representative = *seen.entry(lines.to_vec()).or_insert(i);
+                    representatives.push(representative);
+                    groups.entry(representative).or_default().push(i);
+                }
+                _ => representatives.push(i),
+            }
+        }
+
+        DuplicateGroups {
+            representatives,
+            groups,
+        }
+    }
+
+    /// Returns the index of the item which represents the given item.
+    pub fn representative(&self, idx: usize) -> usize {
+        self.representatives[idx]
+    }
+
+    /// Whether the given item is a duplicate, and is hidden when duplicates are collapsed.
+    pub fn is_hidden(&self, idx: usize) -> bool {
+        self.representative(idx) != idx
+    }
+
+    /// Returns the indices of all the items in the same group as the given item.
+    pub fn members(&self, idx: usize) -> Vec<usize> {
+        self.groups
+            .get(&self.representative(idx))
+            .cloned()
+            .unwrap_or_else(|| vec![idx])
+    }
+
+    /// Returns how many items are in the same group as the given item.
+    pub fn count(&self, idx: usize) -> usize {
+        self.groups
+            .get(&self.representative(idx))
+            .map_or(1, Vec::len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::rg::de::test_utilities::*;
+    use crate::rg::de::*;
+    use crate::ui::app::duplicates::DuplicateGroups;
+    use crate::ui::line::Item;
+
+    fn items(messages: &[&str]) -> Vec<Item> {
+        messages
+            .iter()
+            .enumerate()
+            .map(|(i, json)| Item::new(i, RgMessage::from_str(json)))
+            .collect()
+    }
+
+    #[test]
+    fn it_groups_identical_matches() {
+        let items = items(&[
+            RG_JSON_BEGIN,
+            RG_JSON_MATCH,
+            RG_JSON_CONTEXT,
+            RG_JSON_MATCH,
+            RG_JSON_MATCH_MULTILINE,
+            RG_JSON_END,
+            RG_JSON_BEGIN,
+            RG_JSON_MATCH,
+            RG_JSON_CONTEXT,
+            RG_JSON_END,
+        ]);
+        let groups = DuplicateGroups::new(&items);
+
+        let hidden = (0..items.len())
+            .filter(|i| groups.is_hidden(*i))
+            .collect::<Vec<_>>();
+        assert_eq!(hidden, [3, 7]);
+
+        assert_eq!(groups.members(1), [1, 3, 7]);
+        assert_eq!(groups.members(3), [1, 3, 7]);
+        assert_eq!(groups.representative(7), 1);
+        assert_eq!(groups.count(1), 3);
+
+        assert_eq!(groups.members(4), [4]);
+        assert_eq!(groups.count(4), 1);
+    }
+
+    #[test]
+    fn it_does_not_group_other_kinds() {
+        let items = items(&[
+            RG_JSON_BEGIN,
+            RG_JSON_CONTEXT,
+            RG_JSON_CONTEXT,
+            RG_JSON_END,
+            RG_JSON_BEGIN,
+            RG_JSON_END,
+        ]);
+        let groups = DuplicateGroups::new(&items);
+
+        for i in 0..items.len() {
+            assert!(!groups.is_hidden(i));
+            assert_eq!(groups.members(i), [i]);
+            assert_eq!(groups.count(i), 1);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::rg::de::{ArbitraryData, RgMessageKind};
+use crate::ui::line::Item;
+
+/// A file with matches, which may be chosen in the file picker.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PickerFile {
+    pub path: ArbitraryData,
+    /// How many matches are in the file.
+    pub match_count: usize,
+    pub chosen: bool,
+}
+
+/// Lists only the files with matches (with `-l/--files-with-matches`), so the files can be chosen before the
+/// matches in them are shown.
+#[derive(Debug)]
+pub struct FilePicker {
+    pub files: Vec<PickerFile>,
+    /// The index of the selected file.
+    pub selected: usize,
+}
+
+impl FilePicker {
+    /// Lists each file once (in the order `rg` returned them), from the `Begin` items.
+    pub fn new(items: &[Item]) -> FilePicker {
+        let mut files: Vec<PickerFile> = vec![];
+        for item in items {
+            let path = match item.path() {
+                Some(path) => path,
+                None => continue,
+            };
+
+            match item.kind {
+                RgMessageKind::Begin if !files.iter().any(|f| &f.path == path) => {
+                    files.push(PickerFile {
+                        path: path.clone(),
+                        match_count: 0,
+                        chosen: false,
+                    })
+                }
+                RgMessageKind::Match => {
+                    if let Some(file) = files.iter_mut().rev().find(|f| &f.path == path) {
+                        file.match_count += item.sub_items().len();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        FilePicker { files, selected: 0 }
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        self.selected = (self.selected + 1).min(self.files.len().saturating_sub(1));
+    }
+
+    pub fn toggle(&mut self) {
+        if let Some(file) = self.files.get_mut(self.selected) {
+            file.chosen = !file.chosen;
+        }
+    }
+
+    /// Chooses every file, or none of them if they're all already chosen.
+    pub fn toggle_all(&mut self) {
+        let chosen = !self.files.iter().all(|f| f.chosen);
+        self.files.iter_mut().for_each(|f| f.chosen = chosen);
+    }
+
+    pub fn chosen_paths(&self) -> Vec<&ArbitraryData> {
+        self.files
+            .iter()
+            .filter(|f| f.chosen)
+            .map(|f| &f.path)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::rg::de::test_utilities::RgMessageBuilder;
+    use crate::rg::de::{RgMessageKind, Stats, SubMatch};
+    use crate::ui::app::file_picker::FilePicker;
+    use crate::ui::line::Item;
+
+    fn items(files: &[(&str, usize)]) -> Vec<Item> {
+        let mut items = vec![];
+        for (path, match_count) in files {
+            let begin = RgMessageBuilder::new(RgMessageKind::Begin)
+                .with_path_text(*path)
+                .build();
+            items.push(Item::new(items.len(), begin));
+            for i in 0..*match_count {
+                let item = RgMessageBuilder::new(RgMessageKind::Match)
+                    .with_path_text(*path)
+                    .with_lines_text("foo foo\n")
+                    .with_offset(i * 8)
+                    .with_submatches(vec![
+                        SubMatch::new_text("foo", 0..3),
+                        SubMatch::new_text("foo", 4..7),
+                    ])
+                    .build();
+                items.push(Item::new(items.len(), item));
+            }
+            let end = RgMessageBuilder::new(RgMessageKind::End)
+                .with_path_text(*path)
+                .with_stats(Stats::new())
+                .build();
+            items.push(Item::new(items.len(), end));
+        }
+
+        items
+    }
+
+    fn paths(picker: &FilePicker) -> Vec<(String, usize)> {
+        picker
+            .files
+            .iter()
+            .map(|f| (f.path.lossy_utf8(), f.match_count))
+            .collect()
+    }
+
+    #[test]
+    fn it_lists_unique_files() {
+        let picker = FilePicker::new(&items(&[("b.txt", 1), ("a.txt", 3), ("c.txt", 2)]));
+        assert_eq!(
+            paths(&picker),
+            [
+                ("b.txt".into(), 2),
+                ("a.txt".into(), 6),
+                ("c.txt".into(), 4)
+            ]
+        );
+
+        // a file is only listed once, even if rg reported it more than once
+        let picker = FilePicker::new(&items(&[("a.txt", 1), ("b.txt", 1), ("a.txt", 1)]));
+        assert_eq!(paths(&picker), [("a.txt".into(), 4), ("b.txt".into(), 2)]);
+
+        assert!(FilePicker::new(&[]).files.is_empty());
+    }
+
+    #[test]
+    fn it_chooses_files() {
+        let mut picker = FilePicker::new(&items(&[("a.txt", 1), ("b.txt", 1), ("c.txt", 1)]));
+        assert!(picker.chosen_paths().is_empty());
+
+        picker.move_up();
+        picker.toggle();
+        picker.move_down();
+        picker.move_down();
+        picker.move_down();
+        picker.toggle();
+        assert_eq!(picker.selected, 2);
+        let chosen = |picker: &FilePicker| {
+            picker
+                .chosen_paths()
+                .into_iter()
+                .map(|p| p.lossy_utf8())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(chosen(&picker), ["a.txt", "c.txt"]);
+
+        picker.toggle_all();
+        assert_eq!(chosen(&picker), ["a.txt", "b.txt", "c.txt"]);
+        picker.toggle_all();
+        assert!(chosen(&picker).is_empty());
+    }
+}
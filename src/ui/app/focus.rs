@@ -0,0 +1,31 @@
+use std::ops::Range;
+
+/// Returns the rows of the focused match which fit in a view `height` rows tall, when it's scrolled `scroll`
+/// rows down. It can't be scrolled any further once its last row is at the bottom of the view.
+pub fn focus_window(row_count: usize, scroll: usize, height: usize) -> Range<usize> {
+    let start = scroll.min(row_count.saturating_sub(height));
+    start..(start + height).min(row_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::ui::app::focus::focus_window;
+
+    #[test]
+    fn it_scrolls_the_window_of_rows() {
+        assert_eq!(focus_window(100, 0, 10), 0..10);
+        assert_eq!(focus_window(100, 42, 10), 42..52);
+        assert_eq!(focus_window(100, 90, 10), 90..100);
+    }
+
+    #[test]
+    fn it_stops_scrolling_at_the_last_row() {
+        assert_eq!(focus_window(100, 95, 10), 90..100);
+        assert_eq!(focus_window(100, usize::MAX, 10), 90..100);
+        // everything fits, so there's nothing to scroll
+        assert_eq!(focus_window(3, 2, 10), 0..3);
+        assert_eq!(focus_window(0, 1, 10), 0..0);
+    }
+}
@@ -1,18 +1,51 @@
 mod app_events;
 mod app_render;
+mod duplicates;
+mod file_picker;
+mod focus;
+mod preview;
+mod scrollbar;
+mod search_roots;
+mod search_stats;
+mod session;
 mod state;
 
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::time::Instant;
+
 use anyhow::{bail, Result};
+use duplicates::DuplicateGroups;
+use file_picker::FilePicker;
+use preview::FilePreview;
+use ratatui::layout::Rect;
 use regex::bytes::Regex;
+use search_roots::SearchRoots;
+pub use session::Session;
 use state::HelpTextState;
 pub use state::{AppListState, AppState, AppUiState};
 
-use crate::model::{PrintableStyle, ReplacementCriteria};
-use crate::rg::de::{RgMessage, Stats};
+use crate::cli::SearchScope;
+use crate::model::{PrintableStyle, ReplacementCriteria, SortMode, WrapMode, DEFAULT_TAB_WIDTH};
+use crate::replace::{binary_paths, is_read_only};
+use crate::rg::de::{ArbitraryData, Duration, RgMessage, RgMessageKind, Stats};
+use crate::ui::clipboard::{default_clipboard, Clipboard};
+use crate::ui::fuzzy::{fuzzy_match, is_smart_case_sensitive};
+use crate::ui::keymap::{Action, KeyMap};
 use crate::ui::line::Item;
+use crate::ui::theme::Theme;
 
 const HELP_TEXT: &str = include_str!("../../../doc/rgr.1.template");
 
+/// How many files may be changed before "yes" has to be typed to confirm the replacements, by default.
+pub const DEFAULT_CONFIRM_THRESHOLD: usize = 100;
+
+/// How long a toast is shown for.
+pub const TOAST_DURATION: std::time::Duration = std::time::Duration::from_secs(3);
+/// How long before a toast is hidden that it starts to fade.
+const TOAST_FADE: std::time::Duration = std::time::Duration::from_millis(500);
+
 pub struct App {
     pub state: AppState,
 
@@ -22,8 +55,12 @@ pub struct App {
 
     /// Raw args passed to `ripgrep`.
     rg_cmdline: String,
+    /// The arguments forwarded to `rg`, described for the help view (see `describe_rg_args`).
+    rg_options: Vec<(&'static str, String)>,
     /// Stats from `ripgrep`'s JSON output
     stats: Stats,
+    /// How long `ripgrep` took to search, from its JSON output.
+    elapsed_total: Duration,
     /// A list that represents all matches and holds each match's state.
     list: Vec<Item>,
     /// State for where the user is inside the list.
@@ -35,6 +72,115 @@ pub struct App {
 
     /// The current printable style used to render text.
     printable_style: PrintableStyle,
+    /// How lines too long to fit in the main view are rendered.
+    wrap_mode: WrapMode,
+    /// How many columns apart the tab stops are, which tabs are expanded to (with `PrintableStyle::Hidden`).
+    tab_width: usize,
+    /// Whether lines in the main view are prefixed with their line numbers.
+    show_line_numbers: bool,
+    /// Whether `rg` printed every line of the files (`--passthru`), so the lines which don't match are dimmed.
+    passthru: bool,
+    /// Whether only the matches which will be replaced are shown in the CONFIRM mode.
+    only_changes: bool,
+    /// How many columns the main view is scrolled horizontally (only used with `WrapMode::NoWrap`).
+    h_scroll: usize,
+    /// Set when identical matches are collapsed into a single row.
+    collapsed_duplicates: Option<DuplicateGroups>,
+    /// The order in which files are displayed in the list.
+    sort_mode: SortMode,
+    /// Whether the files are displayed in the reverse of the order given by `sort_mode`.
+    reverse_files: bool,
+    /// The paths `rg` searched, which the files are grouped under when there's more than one.
+    search_roots: SearchRoots,
+    /// The position of each file in `ripgrep`'s output, used to restore its original order.
+    file_order: HashMap<ArbitraryData, usize>,
+    /// Set when the user confirmed the replacements with the key that also asks for a quiet exit.
+    quiet_exit: bool,
+    /// How many files may be changed before "yes" has to be typed to confirm the replacements.
+    confirm_threshold: usize,
+    /// The text the replacement is pre-filled with when the REPLACE mode is entered (see `-r/--replace`).
+    initial_replacement: String,
+    /// The text typed to confirm the replacements, while it's being asked for (in the CONFIRM mode).
+    typed_confirmation: Option<String>,
+    /// The first key of a sequence of keys (e.g. `gg`), while waiting for the next one.
+    pending_key: Option<Action>,
+    /// The query used to fuzzy filter (and rank) the files and lines in the list.
+    fuzzy_query: Option<String>,
+    /// Whether the fuzzy query was toggled to be matched case sensitively (or not). When `None`, it's decided
+    /// by whether the query contains an uppercase character.
+    fuzzy_case_sensitive: Option<bool>,
+    /// For each item in the list, whether it's hidden because it doesn't match `fuzzy_query`.
+    fuzzy_hidden: Vec<bool>,
+    /// The keys bound to each action in the interface.
+    keymap: KeyMap,
+    /// The symbols drawn next to the list of matches.
+    theme: Theme,
+    /// The preview of the selected item's file, when in the PREVIEW mode.
+    preview: Option<FilePreview>,
+    /// The item the user last selected, which is selected again after the list is sorted or filtered.
+    selection_anchor: Option<ItemPosition>,
+    /// The preprocessor `rg` searched with (`--pre`), if any. Replacements can't be confirmed when set.
+    preprocessor: Option<String>,
+    /// The longest line `rg` prints in full (`--max-columns`), if any. Matches in longer lines aren't replaced.
+    max_columns: Option<u64>,
+    /// Which of `rg`'s filters on the files it searches were turned off (e.g. `--no-ignore`).
+    search_scope: SearchScope,
+    /// The files to choose from, before the matches are shown (with `-l/--files-with-matches`).
+    file_picker: Option<FilePicker>,
+    /// Where text is copied to (e.g.: `rg`'s command line).
+    clipboard: Box<dyn Clipboard>,
+    /// A brief message (and when it was shown), which is hidden once it's been shown for `TOAST_DURATION`.
+    toast: Option<(String, Instant)>,
+}
+
+/// Identifies an item (and a submatch in it), even after the list has been reordered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ItemPosition {
+    kind: RgMessageKind,
+    path: Option<Vec<u8>>,
+    offset: Option<usize>,
+    /// The range of the selected submatch, if the item has any.
+    submatch: Option<Range<usize>>,
+}
+
+/// Marks each `Match` or `Context` item which doesn't directly follow the previous line in its file, so a
+/// separator can be rendered between them (like ripgrep's `--context-separator`).
+fn mark_context_separators(list: &mut [Item]) {
+    let mut prev_line_end = None;
+    for item in list {
+        match item.kind {
+            RgMessageKind::Begin => prev_line_end = None,
+            RgMessageKind::Match | RgMessageKind::Context => {
+                let line_number = item.line_number().cloned();
+                let line_count = item.lines().map_or(1, |lines| {
+                    lines
+                        .to_vec()
+                        .iter()
+                        .filter(|b| **b == b'\n')
+                        .count()
+                        .max(1)
+                });
+
+                if let (Some(prev_end), Some(n)) = (prev_line_end, line_number) {
+                    item.set_context_separator(n > prev_end + 1);
+                }
+
+                prev_line_end = line_number.map(|n| n + line_count - 1);
+            }
+            RgMessageKind::End | RgMessageKind::Summary => {}
+        }
+    }
+}
+
+/// Returns how well the lines of the item match the fuzzy query, or `None` if they don't match at all.
+fn fuzzy_score(query: &str, case_sensitive: bool, item: &Item) -> Option<i64> {
+    match item.kind {
+        RgMessageKind::Match | RgMessageKind::Context => item
+            .lines()
+            .and_then(|lines| fuzzy_match(query, &lines.lossy_utf8(), case_sensitive))
+            .map(|m| m.score),
+        _ => None,
+    }
 }
 
 impl App {
@@ -45,30 +191,535 @@ impl App {
     ) -> App {
         let mut list = vec![];
         let mut maybe_stats = None;
+        let mut elapsed_total = None;
 
         for (i, rg_message) in rg_messages.into_iter().enumerate() {
             match rg_message {
-                RgMessage::Summary { stats, .. } => {
+                RgMessage::Summary {
+                    stats,
+                    elapsed_total: elapsed,
+                } => {
                     maybe_stats = Some(stats);
+                    elapsed_total = Some(elapsed);
                     // NOTE: there should only be one RgMessage::Summary, and it should be the last item.
                     break;
                 }
-                other => list.push(Item::new(i, other)),
+                other => {
+                    let mut item = Item::new(i, other);
+                    if matches!(item.kind, RgMessageKind::Begin) {
+                        item.set_read_only(item.path_buf().is_some_and(|p| is_read_only(&p)));
+                    }
+
+                    list.push(item);
+                }
             }
         }
 
+        let binary_paths = binary_paths(&list).into_iter().cloned().collect::<Vec<_>>();
+        for item in list.iter_mut() {
+            if matches!(item.kind, RgMessageKind::Begin) {
+                item.set_binary(item.path().is_some_and(|p| binary_paths.contains(p)));
+            }
+        }
+
+        // ripgrep only separates non-adjacent lines when it was asked to print context lines
+        if list
+            .iter()
+            .any(|item| matches!(item.kind, RgMessageKind::Context))
+        {
+            mark_context_separators(&mut list);
+        }
+
+        let file_order = list
+            .iter()
+            .filter(|item| matches!(item.kind, RgMessageKind::Begin))
+            .filter_map(|item| item.path().cloned())
+            .enumerate()
+            .map(|(i, path)| (path, i))
+            .collect();
+
         App {
             state: AppState::Running,
 
             capture_pattern,
             rg_cmdline,
+            rg_options: vec![],
             stats: maybe_stats.expect("failed to find RgMessage::Summary from rg!"),
+            elapsed_total: elapsed_total.expect("failed to find RgMessage::Summary from rg!"),
             list_state: AppListState::new(),
             list,
             ui_state: AppUiState::SelectMatches,
             help_text_state: HelpTextState::new(HELP_TEXT),
             printable_style: PrintableStyle::default(),
+            wrap_mode: WrapMode::default(),
+            tab_width: DEFAULT_TAB_WIDTH,
+            show_line_numbers: true,
+            passthru: false,
+            only_changes: false,
+            h_scroll: 0,
+            collapsed_duplicates: None,
+            sort_mode: SortMode::default(),
+            reverse_files: false,
+            search_roots: SearchRoots::default(),
+            file_order,
+            quiet_exit: false,
+            confirm_threshold: DEFAULT_CONFIRM_THRESHOLD,
+            initial_replacement: String::new(),
+            typed_confirmation: None,
+            pending_key: None,
+            fuzzy_query: None,
+            fuzzy_case_sensitive: None,
+            fuzzy_hidden: vec![],
+            keymap: KeyMap::default(),
+            theme: Theme::default(),
+            preview: None,
+            selection_anchor: None,
+            preprocessor: None,
+            max_columns: None,
+            search_scope: SearchScope::default(),
+            file_picker: None,
+            clipboard: default_clipboard(),
+            toast: None,
+        }
+    }
+
+    pub fn set_wrap_mode(&mut self, wrap_mode: WrapMode) {
+        self.wrap_mode = wrap_mode;
+    }
+
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width;
+    }
+
+    pub fn set_passthru(&mut self, passthru: bool) {
+        self.passthru = passthru;
+    }
+
+    pub fn set_rg_options(&mut self, rg_options: Vec<(&'static str, String)>) {
+        self.rg_options = rg_options;
+    }
+
+    pub fn set_keymap(&mut self, keymap: KeyMap) {
+        self.keymap = keymap;
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn set_confirm_threshold(&mut self, confirm_threshold: usize) {
+        self.confirm_threshold = confirm_threshold;
+    }
+
+    pub fn set_initial_replacement(&mut self, initial_replacement: impl Into<String>) {
+        self.initial_replacement = initial_replacement.into();
+    }
+
+    pub fn set_preprocessor(&mut self, preprocessor: Option<String>) {
+        self.preprocessor = preprocessor;
+    }
+
+    /// Sets the longest line `rg` prints in full, and marks the matches in longer lines as truncated.
+    pub fn set_max_columns(&mut self, max_columns: Option<u64>) {
+        self.max_columns = max_columns;
+        for item in self.list.iter_mut() {
+            item.set_truncated(max_columns.is_some_and(|max| item.exceeds_max_columns(max)));
+        }
+    }
+
+    pub fn set_search_scope(&mut self, search_scope: SearchScope) {
+        self.search_scope = search_scope;
+    }
+
+    pub fn set_clipboard(&mut self, clipboard: Box<dyn Clipboard>) {
+        self.clipboard = clipboard;
+    }
+
+    /// Shows a brief message, e.g. the result of an action.
+    pub fn set_toast(&mut self, msg: impl Into<String>) {
+        self.toast = Some((msg.into(), Instant::now()));
+    }
+
+    /// Called periodically (and before each event) with the current time. Returns `true` if the interface
+    /// should be redrawn.
+    pub fn on_tick(&mut self, now: Instant) -> bool {
+        let expired = self.toast.as_ref().is_some_and(|(_, shown_at)| {
+            now.saturating_duration_since(*shown_at) >= TOAST_DURATION
+        });
+        if expired {
+            self.toast = None;
+            return true;
+        }
+
+        // keep redrawing while it fades
+        self.is_toast_fading(now)
+    }
+
+    /// Whether the toast (if any) is fading, since it's about to be hidden.
+    pub(crate) fn is_toast_fading(&self, now: Instant) -> bool {
+        self.toast.as_ref().is_some_and(|(_, shown_at)| {
+            now.saturating_duration_since(*shown_at) + TOAST_FADE >= TOAST_DURATION
+        })
+    }
+
+    /// Lists only the files with matches, so the user can choose which ones to show the matches of.
+    pub fn pick_files(&mut self) {
+        self.file_picker = Some(FilePicker::new(&self.list));
+        self.ui_state = AppUiState::PickFiles;
+    }
+
+    /// Replaces the list with the results of searching again (e.g. with a different number of context lines).
+    /// Only the files which were already in the list are kept, matches which were deselected stay deselected,
+    /// and the same item stays selected if it's still in the list.
+    pub fn reload(&mut self, rg_cmdline: String, rg_messages: Vec<RgMessage>, term_size: Rect) {
+        let reloaded = App::new(self.capture_pattern.clone(), rg_cmdline, rg_messages);
+
+        self.remember_selection();
+        let paths = self
+            .list
+            .iter()
+            .filter_map(|item| item.path().map(ArbitraryData::to_vec))
+            .collect::<HashSet<_>>();
+        let deselected = self
+            .list
+            .iter()
+            .flat_map(|item| {
+                item.sub_items()
+                    .iter()
+                    .filter(|sub_item| !sub_item.should_replace)
+                    .map(move |sub_item| {
+                        (
+                            item.path().map(ArbitraryData::to_vec),
+                            item.offset(),
+                            sub_item.sub_match.range.clone(),
+                        )
+                    })
+            })
+            .collect::<HashSet<_>>();
+
+        self.list = reloaded.list;
+        self.list.retain(|item| {
+            item.path()
+                .is_some_and(|path| paths.contains(&path.to_vec()))
+        });
+        self.set_max_columns(self.max_columns);
+        for (i, item) in self.list.iter_mut().enumerate() {
+            item.index = i;
+            let (path, offset) = (item.path().map(ArbitraryData::to_vec), item.offset());
+            for idx in 0..item.sub_items().len() {
+                let range = item.sub_items()[idx].sub_match.range.clone();
+                if deselected.contains(&(path.clone(), offset, range)) {
+                    item.set_should_replace(idx, false);
+                }
+            }
+        }
+
+        self.rg_cmdline = reloaded.rg_cmdline;
+        self.stats = reloaded.stats;
+        self.elapsed_total = reloaded.elapsed_total;
+        self.file_order = reloaded.file_order;
+        self.list_state = AppListState::new();
+        self.h_scroll = 0;
+
+        // sorting also collapses duplicates and filters the list again, and restores the selection
+        self.sort_list();
+        self.update_indicator(term_size);
+    }
+
+    /// Sets the paths `rg` searched. The list is only reordered when its files are (or were) grouped by them.
+    pub fn set_search_roots(&mut self, roots: &[String]) {
+        let search_roots = SearchRoots::new(roots);
+        let was_grouped = self.search_roots.is_grouped();
+        self.search_roots = search_roots;
+        if was_grouped || self.search_roots.is_grouped() {
+            self.sort_list();
+        }
+    }
+
+    /// Sets the order of the files in the list, and reorders it if that changed. Since the list starts in the order
+    /// the files were read from `rg` (e.g. with `rg --sort=none`), it isn't rebuilt for the default `SortMode::Rg`.
+    pub fn set_sort_mode(&mut self, sort_mode: SortMode) {
+        if self.sort_mode != sort_mode {
+            self.sort_mode = sort_mode;
+            self.sort_list();
+        }
+    }
+
+    /// Reorders the list according to the current `SortMode`. Each file's items (from its `Begin` to its
+    /// `End`) are kept together and in order, only the order of the files themselves changes.
+    pub(crate) fn sort_list(&mut self) {
+        self.remember_selection();
+        let mut files: Vec<Vec<Item>> = vec![];
+        for item in self.list.drain(..) {
+            match files.last_mut() {
+                Some(file) if !matches!(item.kind, RgMessageKind::Begin) => file.push(item),
+                _ => files.push(vec![item]),
+            }
+        }
+
+        let path = |file: &[Item]| file[0].path().map(ArbitraryData::to_vec);
+        let match_count = |file: &[Item]| {
+            file.iter()
+                .filter(|item| matches!(item.kind, RgMessageKind::Match))
+                .map(|item| item.sub_items().len())
+                .sum::<usize>()
+        };
+        let line_length = |file: &[Item]| {
+            file.iter()
+                .filter(|item| matches!(item.kind, RgMessageKind::Match))
+                .filter_map(|item| item.lines())
+                .filter_map(|lines| lines.lossy_utf8().lines().map(|l| l.chars().count()).max())
+                .max()
+                .unwrap_or(0)
+        };
+
+        // NOTE: these are all stable sorts, so files which compare equally keep their relative order
+        match self.sort_mode {
+            SortMode::Rg => files.sort_by_key(|file| {
+                file[0]
+                    .path()
+                    .and_then(|path| self.file_order.get(path).copied())
+            }),
+            SortMode::Path => files.sort_by_cached_key(|file| path(file)),
+            SortMode::MatchCount => files.sort_by_cached_key(|file| Reverse(match_count(file))),
+            SortMode::LineLength => files.sort_by_cached_key(|file| Reverse(line_length(file))),
+        }
+
+        // the files are reversed as a whole, so the items within each file keep their order
+        if self.reverse_files {
+            files.reverse();
+        }
+
+        // when filtering, the files with the best matches are shown first
+        if let Some(query) = self.active_fuzzy_query() {
+            let case_sensitive = self.is_fuzzy_case_sensitive();
+            files.sort_by_cached_key(|file| {
+                Reverse(
+                    file.iter()
+                        .filter_map(|item| fuzzy_score(query, case_sensitive, item))
+                        .max(),
+                )
+            });
+        }
+
+        // the files are grouped by the path they were found in, with a header for each group
+        if self.search_roots.is_grouped() {
+            let root_of = |file: &[Item]| {
+                file[0]
+                    .path_buf()
+                    .and_then(|path| self.search_roots.root_of(&path))
+            };
+            files.sort_by_cached_key(|file| root_of(file).unwrap_or(usize::MAX));
+
+            let mut prev_root = None;
+            for file in &mut files {
+                let root = root_of(file);
+                let header = match root {
+                    Some(i) if root != prev_root => Some(self.search_roots.label(i).to_owned()),
+                    _ => None,
+                };
+                file[0].set_root_header(header);
+                prev_root = root;
+            }
+        } else {
+            files
+                .iter_mut()
+                .for_each(|file| file[0].set_root_header(None));
+        }
+
+        self.list = files.into_iter().flatten().collect();
+        for (i, item) in self.list.iter_mut().enumerate() {
+            item.index = i;
+        }
+
+        if self.collapsed_duplicates.is_some() {
+            self.collapsed_duplicates = Some(DuplicateGroups::new(&self.list));
+        }
+
+        self.fuzzy_hidden = self.fuzzy_hidden_items();
+
+        // keep the same item selected after it's been moved (or hidden)
+        self.restore_selection();
+    }
+
+    /// Returns the identity of the selected item and submatch.
+    pub(crate) fn selected_position(&self) -> ItemPosition {
+        let item = &self.list[self.list_state.selected_item()];
+        ItemPosition {
+            kind: item.kind.clone(),
+            path: item.path().map(ArbitraryData::to_vec),
+            offset: item.offset(),
+            submatch: item
+                .sub_items()
+                .get(self.list_state.selected_submatch())
+                .map(|sub_item| sub_item.sub_match.range.clone()),
+        }
+    }
+
+    /// Returns the index of the item (and of its submatch) at the given position, if it's in the list.
+    pub(crate) fn locate_item(&self, position: &ItemPosition) -> Option<(usize, usize)> {
+        let item_idx = self.list.iter().position(|item| {
+            item.kind == position.kind
+                && item.offset() == position.offset
+                && item.path().map(ArbitraryData::to_vec) == position.path
+        })?;
+
+        let submatch_idx = self.list[item_idx]
+            .sub_items()
+            .iter()
+            .position(|sub_item| Some(&sub_item.sub_match.range) == position.submatch.as_ref())
+            .unwrap_or(0);
+
+        Some((item_idx, submatch_idx))
+    }
+
+    /// Remembers the selected item before the list is changed, unless the user has already selected one.
+    fn remember_selection(&mut self) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.selected_position());
+        }
+    }
+
+    /// Selects the item the user last selected after the list has changed. If it's been hidden, then the
+    /// item it was collapsed into (or the closest visible item) is selected instead.
+    pub(crate) fn restore_selection(&mut self) {
+        let (mut item_idx, mut submatch_idx) = match self
+            .selection_anchor
+            .as_ref()
+            .and_then(|p| self.locate_item(p))
+        {
+            Some(found) => found,
+            None => return,
+        };
+
+        if let Some(duplicates) = &self.collapsed_duplicates {
+            if duplicates.is_hidden(item_idx) {
+                item_idx = duplicates.representative(item_idx);
+            }
+        }
+
+        if let Some(closest) = self.closest_visible_item(item_idx) {
+            item_idx = closest;
+            submatch_idx = 0;
+        }
+
+        self.list_state.set_selected_item(item_idx);
+        self.list_state.set_selected_submatch(submatch_idx);
+    }
+
+    /// If the item is hidden, returns the closest selectable item which isn't (looking forwards first).
+    fn closest_visible_item(&self, item_idx: usize) -> Option<usize> {
+        if !self.is_item_hidden(item_idx) {
+            return None;
+        }
+
+        let is_visible = |i: &usize| self.list[*i].is_selectable() && !self.is_item_hidden(*i);
+        (item_idx + 1..self.list.len())
+            .find(is_visible)
+            .or_else(|| (0..item_idx).rev().find(is_visible))
+    }
+
+    /// Selects the closest visible item if the selected one has been hidden.
+    pub(crate) fn select_visible_item(&mut self) {
+        if let Some(closest) = self.closest_visible_item(self.list_state.selected_item()) {
+            self.list_state.set_selected_item(closest);
+            self.list_state.set_selected_submatch(0);
+        }
+    }
+
+    /// Returns the fuzzy query, if the user has entered one.
+    pub(crate) fn active_fuzzy_query(&self) -> Option<&str> {
+        self.fuzzy_query
+            .as_deref()
+            .filter(|query| !query.is_empty())
+    }
+
+    /// Whether the fuzzy query is matched case sensitively: as the user toggled it, or else with smart case.
+    pub(crate) fn is_fuzzy_case_sensitive(&self) -> bool {
+        self.fuzzy_case_sensitive.unwrap_or_else(|| {
+            is_smart_case_sensitive(self.fuzzy_query.as_deref().unwrap_or_default())
+        })
+    }
+
+    /// Returns whether each item in the list should be hidden by the fuzzy query. Lines are hidden if they
+    /// don't match it, and files are hidden if none of their lines do.
+    fn fuzzy_hidden_items(&self) -> Vec<bool> {
+        let query = match self.active_fuzzy_query() {
+            Some(query) => query,
+            None => return vec![],
+        };
+
+        let case_sensitive = self.is_fuzzy_case_sensitive();
+        let mut hidden = self
+            .list
+            .iter()
+            .map(|item| fuzzy_score(query, case_sensitive, item).is_none())
+            .collect::<Vec<_>>();
+
+        let mut file_start = 0;
+        for (i, item) in self.list.iter().enumerate() {
+            match item.kind {
+                RgMessageKind::Begin => file_start = i,
+                RgMessageKind::End => {
+                    let file_hidden = hidden[file_start + 1..i].iter().all(|h| *h);
+                    hidden[file_start] = file_hidden;
+                    hidden[i] = file_hidden;
+                }
+                _ => {}
+            }
+        }
+
+        hidden
+    }
+
+    /// Whether the item is hidden, because it's a collapsed duplicate of another item, it doesn't match the
+    /// fuzzy query, or it won't be changed and only the changes are shown (see `only_changes`).
+    pub(crate) fn is_item_hidden(&self, item_idx: usize) -> bool {
+        self.collapsed_duplicates
+            .as_ref()
+            .is_some_and(|d| d.is_hidden(item_idx))
+            || self.fuzzy_hidden.get(item_idx).copied().unwrap_or(false)
+            || (self.only_changes
+                && matches!(self.ui_state, AppUiState::ConfirmReplacement(_, _))
+                && !self.list[item_idx]
+                    .sub_items()
+                    .iter()
+                    .any(|s| s.should_replace))
+    }
+
+    /// Returns the 1-based position of the given submatch among all the matches in the list.
+    /// Returns `None` if the item at `item_idx` isn't a `Match`.
+    pub(crate) fn match_ordinal(&self, item_idx: usize, submatch_idx: usize) -> Option<usize> {
+        if !matches!(self.list.get(item_idx)?.kind, RgMessageKind::Match) {
+            return None;
         }
+
+        let matches_before = self.list[..item_idx]
+            .iter()
+            .filter(|i| matches!(i.kind, RgMessageKind::Match))
+            .map(|i| i.sub_items().len())
+            .sum::<usize>();
+
+        Some(matches_before + submatch_idx + 1)
+    }
+
+    /// Returns how many files have matches which will be replaced.
+    pub(crate) fn affected_file_count(&self) -> usize {
+        self.list
+            .iter()
+            .filter(|i| matches!(i.kind, RgMessageKind::Match) && i.replace_count() > 0)
+            .filter_map(|i| i.path())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Returns the total number of matches in the list.
+    pub(crate) fn match_total(&self) -> usize {
+        self.list
+            .iter()
+            .filter(|i| matches!(i.kind, RgMessageKind::Match))
+            .map(|i| i.sub_items().len())
+            .sum()
     }
 
     /// Consume the app and return `ReplacementCriteria`. This will return an `Err` if the app wasn't
@@ -76,11 +727,12 @@ impl App {
     pub fn get_replacement_criteria(self) -> Result<ReplacementCriteria> {
         match self.ui_state {
             AppUiState::InputReplacement(user_replacement, _)
-            | AppUiState::ConfirmReplacement(user_replacement, _) => Ok(ReplacementCriteria::new(
-                self.capture_pattern,
-                user_replacement,
-                self.list,
-            )),
+            | AppUiState::ConfirmReplacement(user_replacement, _) => {
+                let mut criteria =
+                    ReplacementCriteria::new(self.capture_pattern, user_replacement, self.list);
+                criteria.quiet = self.quiet_exit;
+                Ok(criteria)
+            }
             other => bail!(
                 "unexpected app ui state when calling App::get_replacement_criteria: {:?}",
                 other
@@ -0,0 +1,176 @@
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use encoding::DecoderTrap;
+
+use crate::encoding::get_encoder;
+use crate::rg::RgEncoding;
+
+/// A scrollable preview of a whole file, shown around the selected match.
+#[derive(Debug)]
+pub struct FilePreview {
+    pub path: PathBuf,
+    /// The lines of the file, or the reason it couldn't be read.
+    pub lines: Result<Vec<String>, String>,
+    /// The (0-based) lines of the selected match.
+    pub match_lines: Range<usize>,
+    /// The byte ranges of the submatches in each (0-based) line.
+    pub highlights: Vec<(usize, Range<usize>)>,
+    /// The (0-based) line in the middle of the preview.
+    center: usize,
+}
+
+impl FilePreview {
+    pub fn new(
+        path: PathBuf,
+        match_lines: Range<usize>,
+        highlights: Vec<(usize, Range<usize>)>,
+    ) -> FilePreview {
+        FilePreview {
+            lines: read_lines(&path).map_err(|e| e.to_string()),
+            center: match_lines.start,
+            path,
+            match_lines,
+            highlights,
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.center = self.center.saturating_sub(1);
+    }
+
+    pub fn scroll_down(&mut self) {
+        let line_count = self.lines.as_ref().map_or(0, |lines| lines.len());
+        self.center = (self.center + 1).min(line_count.saturating_sub(1));
+    }
+
+    /// Returns the lines which fit in a preview `height` lines tall. The preview is re-centered if it was
+    /// scrolled past the start or the end of the file, so scrolling back takes effect straight away.
+    pub fn window(&mut self, height: usize) -> Range<usize> {
+        let line_count = self.lines.as_ref().map_or(0, |lines| lines.len());
+        let window = window_of_lines(line_count, self.center, height);
+        self.center = (window.start + height / 2).min(line_count.saturating_sub(1));
+        window
+    }
+}
+
+/// Returns a window of `height` lines centered around the line `center`, moved so that it stays within the
+/// `line_count` lines of the file.
+pub fn window_of_lines(line_count: usize, center: usize, height: usize) -> Range<usize> {
+    let start = center.saturating_sub(height / 2);
+    let end = (start + height).min(line_count);
+    end.saturating_sub(height)..end
+}
+
+/// Splits the (byte) ranges of submatches in `text` into ranges within each of its lines, where the
+/// first line of `text` is the line `first_line` of the file.
+pub fn line_highlights(
+    text: &str,
+    first_line: usize,
+    ranges: &[Range<usize>],
+) -> Vec<(usize, Range<usize>)> {
+    let mut highlights = vec![];
+    let mut line_start = 0;
+    for (i, line) in text.split('\n').enumerate() {
+        let line_end = line_start + line.len();
+        for range in ranges {
+            let start = range.start.max(line_start);
+            let end = range.end.min(line_end);
+            if start < end {
+                highlights.push((first_line + i, start - line_start..end - line_start));
+            }
+        }
+
+        line_start = line_end + 1;
+    }
+
+    highlights
+}
+
+/// Reads the file and decodes it (in the same way as when replacing) into lines.
+fn read_lines(path: &Path) -> Result<Vec<String>> {
+    let mut bytes = fs::read(path)?;
+    let (bom, encoder) = get_encoder(&bytes, &RgEncoding::None, false);
+    if let Some(bom) = bom {
+        bytes.drain(..bom.len());
+    }
+
+    let decoded = encoder
+        .decode(&bytes, DecoderTrap::Replace)
+        .map_err(|e| anyhow!("Failed to decode file: {}", e))?;
+
+    Ok(decoded.lines().map(String::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::temp_file;
+    use crate::ui::app::preview::{line_highlights, window_of_lines, FilePreview};
+
+    #[test]
+    fn it_centers_the_window_of_lines() {
+        assert_eq!(window_of_lines(100, 50, 10), 45..55);
+        assert_eq!(window_of_lines(100, 50, 11), 45..56);
+    }
+
+    #[test]
+    fn it_keeps_the_window_of_lines_in_the_file() {
+        assert_eq!(window_of_lines(100, 2, 10), 0..10);
+        assert_eq!(window_of_lines(100, 98, 10), 90..100);
+        assert_eq!(window_of_lines(100, 500, 10), 90..100);
+        assert_eq!(window_of_lines(5, 3, 10), 0..5);
+        assert_eq!(window_of_lines(0, 0, 10), 0..0);
+        assert_eq!(window_of_lines(100, 50, 0), 50..50);
+    }
+
+    #[test]
+    fn it_splits_highlights_into_lines() {
+        let text = "foo bar\nbaz\nqux\n";
+        assert_eq!(
+            line_highlights(text, 9, &[4..7, 8..9]),
+            [(9, 4..7), (10, 0..1)]
+        );
+        assert_eq!(
+            line_highlights(text, 9, &[0..3, 4..14]),
+            [(9, 0..3), (9, 4..7), (10, 0..3), (11, 0..2)]
+        );
+    }
+
+    #[test]
+    fn it_scrolls_the_preview() {
+        let lines = (1..=100)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        let path = temp_file!(lines);
+        let mut preview = FilePreview::new(path, 2..3, vec![]);
+
+        assert_eq!(preview.lines.as_ref().unwrap()[2], "line 3");
+        assert_eq!(preview.window(10), 0..10);
+
+        // the preview was re-centered, so it scrolls straight away
+        preview.scroll_down();
+        assert_eq!(preview.window(10), 1..11);
+        preview.scroll_up();
+        preview.scroll_up();
+        assert_eq!(preview.window(10), 0..10);
+
+        for _ in 0..200 {
+            preview.scroll_down();
+        }
+        assert_eq!(preview.window(10), 90..100);
+        preview.scroll_up();
+        assert_eq!(preview.window(10), 89..99);
+    }
+
+    #[test]
+    fn it_previews_missing_files() {
+        let preview = FilePreview::new(PathBuf::from("/does/not/exist"), 0..1, vec![]);
+        assert!(preview.lines.is_err());
+    }
+}
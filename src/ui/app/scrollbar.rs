@@ -0,0 +1,59 @@
+use std::ops::Range;
+
+/// Returns the rows of the scrollbar's thumb, for a list which is `content_height` lines long and shows
+/// `viewport_height` of them at a time, starting at `window_start`. The thumb's size is the share of the list
+/// that's visible, and it's at the bottom of the scrollbar once the window reaches the end of the list.
+///
+/// Returns `None` when the whole list fits, since there's nothing to scroll.
+pub fn thumb(
+    content_height: usize,
+    viewport_height: usize,
+    window_start: usize,
+) -> Option<Range<usize>> {
+    if viewport_height == 0 || content_height <= viewport_height {
+        return None;
+    }
+
+    let size = (viewport_height * viewport_height / content_height).max(1);
+    let scrollable = content_height - viewport_height;
+    let track = viewport_height - size;
+    let start = (window_start.min(scrollable) * track + scrollable / 2) / scrollable;
+
+    Some(start..start + size)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::ui::app::scrollbar::thumb;
+
+    #[test]
+    fn it_has_no_thumb_when_the_list_fits() {
+        assert_eq!(thumb(0, 10, 0), None);
+        assert_eq!(thumb(10, 10, 0), None);
+        assert_eq!(thumb(10, 0, 0), None);
+    }
+
+    #[test]
+    fn it_sizes_the_thumb_by_the_visible_share() {
+        assert_eq!(thumb(20, 10, 0), Some(0..5));
+        assert_eq!(thumb(40, 10, 0), Some(0..2));
+        assert_eq!(thumb(11, 10, 0), Some(0..9));
+        // it's always at least one row
+        assert_eq!(thumb(1000, 10, 0), Some(0..1));
+    }
+
+    #[test]
+    fn it_positions_the_thumb_by_the_window_start() {
+        assert_eq!(thumb(20, 10, 5), Some(3..8));
+        assert_eq!(thumb(20, 10, 10), Some(5..10));
+        assert_eq!(thumb(110, 10, 50), Some(5..6));
+        assert_eq!(thumb(1000, 10, 495), Some(5..6));
+
+        // the window is at the end of the list
+        assert_eq!(thumb(1000, 10, 990), Some(9..10));
+        // the window can't start past the end of the list
+        assert_eq!(thumb(1000, 10, 5000), Some(9..10));
+    }
+}
@@ -0,0 +1,102 @@
+use std::path::{Component, Path, PathBuf};
+
+/// The paths `rg` was told to search. When there's more than one, the files in the list are grouped under the
+/// path they were found in, since `rg` interleaves them.
+#[derive(Debug, Default)]
+pub struct SearchRoots {
+    roots: Vec<String>,
+    /// The roots without any `.` components, which `rg` may or may not print in the paths it found.
+    normalized: Vec<PathBuf>,
+}
+
+/// Removes the `.` components of `path`, so `./src` and `src` are the same.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+impl SearchRoots {
+    pub fn new(roots: &[String]) -> SearchRoots {
+        SearchRoots {
+            roots: roots.to_vec(),
+            normalized: roots.iter().map(|r| normalize(Path::new(r))).collect(),
+        }
+    }
+
+    /// Whether the files should be grouped, which is only useful with more than one root.
+    pub fn is_grouped(&self) -> bool {
+        self.roots.len() > 1
+    }
+
+    /// Returns the index of the root that `path` was found in. When roots are nested, it's the innermost one,
+    /// and when the same root was passed more than once, it's the first one.
+    pub fn root_of(&self, path: &Path) -> Option<usize> {
+        let path = normalize(path);
+        self.normalized
+            .iter()
+            .enumerate()
+            .filter(|(_, root)| path.starts_with(root))
+            .rev()
+            .max_by_key(|(_, root)| root.components().count())
+            .map(|(i, _)| i)
+    }
+
+    /// The root as it was passed to `rg`.
+    pub fn label(&self, idx: usize) -> &str {
+        &self.roots[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::ui::app::search_roots::SearchRoots;
+
+    fn roots(roots: &[&str]) -> SearchRoots {
+        SearchRoots::new(&roots.iter().map(|r| r.to_string()).collect::<Vec<_>>())
+    }
+
+    fn root_of<'a>(roots: &'a SearchRoots, path: &str) -> Option<&'a str> {
+        roots.root_of(Path::new(path)).map(|i| roots.label(i))
+    }
+
+    #[test]
+    fn it_groups_with_more_than_one_root() {
+        assert!(!roots(&[]).is_grouped());
+        assert!(!roots(&["src"]).is_grouped());
+        assert!(roots(&["src", "tests"]).is_grouped());
+    }
+
+    #[test]
+    fn it_assigns_files_to_roots() {
+        let r = roots(&["src", "tests/", "./doc", "README.md"]);
+        assert_eq!(root_of(&r, "src/main.rs"), Some("src"));
+        assert_eq!(root_of(&r, "src/ui/app/mod.rs"), Some("src"));
+        assert_eq!(root_of(&r, "tests/it.rs"), Some("tests/"));
+        assert_eq!(root_of(&r, "doc/rgr.1.template"), Some("./doc"));
+        assert_eq!(root_of(&r, "./doc/rgr.1.template"), Some("./doc"));
+        // a file may be a root itself
+        assert_eq!(root_of(&r, "README.md"), Some("README.md"));
+
+        // only whole components are matched
+        assert_eq!(root_of(&r, "src2/main.rs"), None);
+        assert_eq!(root_of(&r, "other/main.rs"), None);
+    }
+
+    #[test]
+    fn it_assigns_files_to_nested_roots() {
+        let r = roots(&[".", "src", "src/ui", "/tmp/abs"]);
+        assert_eq!(root_of(&r, "./Cargo.toml"), Some("."));
+        assert_eq!(root_of(&r, "src/main.rs"), Some("src"));
+        assert_eq!(root_of(&r, "src/ui/tui.rs"), Some("src/ui"));
+        assert_eq!(root_of(&r, "/tmp/abs/a.txt"), Some("/tmp/abs"));
+
+        // the first of the same roots
+        let r = roots(&["src", "./src"]);
+        assert_eq!(r.root_of(Path::new("src/main.rs")), Some(0));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::rg::de::{Duration, Stats};
+
+const BYTE_UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+
+/// Formats a number of bytes with a binary unit, e.g. `1.5 KiB`.
+pub fn format_bytes(bytes: f64) -> String {
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < BYTE_UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", value as usize, BYTE_UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, BYTE_UNITS[unit])
+    }
+}
+
+/// Formats how many bytes `rg` searched each second, or `-` if it didn't take a measurable amount of time.
+pub fn format_throughput(stats: &Stats, elapsed: &Duration) -> String {
+    let secs = elapsed.secs as f64 + elapsed.nanos as f64 / 1_000_000_000.0;
+    if secs <= 0.0 {
+        return String::from("-");
+    }
+
+    format!("{}/s", format_bytes(stats.bytes_searched as f64 / secs))
+}
+
+/// The rows (a label and a value) of the search stats popup.
+pub fn stats_rows(stats: &Stats, elapsed_total: &Duration) -> Vec<(&'static str, String)> {
+    vec![
+        ("Elapsed", elapsed_total.human.clone()),
+        (
+            "Searched",
+            format!(
+                "{} in {} file(s)",
+                format_bytes(stats.bytes_searched as f64),
+                stats.searches
+            ),
+        ),
+        ("Throughput", format_throughput(stats, elapsed_total)),
+        (
+            "Matched",
+            format!(
+                "{} match(es) on {} line(s) in {} file(s)",
+                stats.matches, stats.matched_lines, stats.searches_with_match
+            ),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::rg::de::{Duration, Stats};
+    use crate::ui::app::search_stats::{format_bytes, format_throughput, stats_rows};
+
+    fn duration(secs: usize, nanos: usize) -> Duration {
+        Duration {
+            secs,
+            nanos,
+            human: format!("{}.{:09}s", secs, nanos),
+        }
+    }
+
+    fn stats(bytes_searched: usize) -> Stats {
+        Stats {
+            bytes_searched,
+            ..Stats::new()
+        }
+    }
+
+    #[test]
+    fn it_formats_bytes() {
+        assert_eq!(format_bytes(0.0), "0 B");
+        assert_eq!(format_bytes(1023.0), "1023 B");
+        assert_eq!(format_bytes(1024.0), "1.0 KiB");
+        assert_eq!(format_bytes(1536.0), "1.5 KiB");
+        assert_eq!(format_bytes(5.0 * 1024.0 * 1024.0), "5.0 MiB");
+        assert_eq!(format_bytes(2048.0 * 1024.0_f64.powi(4)), "2048.0 TiB");
+    }
+
+    #[test]
+    fn it_formats_throughput() {
+        assert_eq!(
+            format_throughput(&stats(2048), &duration(1, 0)),
+            "2.0 KiB/s"
+        );
+        assert_eq!(
+            format_throughput(&stats(2048), &duration(0, 500_000_000)),
+            "4.0 KiB/s"
+        );
+        assert_eq!(
+            format_throughput(&stats(3 * 1024 * 1024), &duration(2, 0)),
+            "1.5 MiB/s"
+        );
+        assert_eq!(format_throughput(&stats(100), &duration(0, 0)), "-");
+    }
+
+    #[test]
+    fn it_lists_stats() {
+        let stats = Stats {
+            bytes_searched: 18789,
+            searches: 2,
+            searches_with_match: 2,
+            matched_lines: 10,
+            matches: 12,
+            ..Stats::new()
+        };
+        let elapsed_total = Duration {
+            secs: 0,
+            nanos: 13_911_027,
+            human: "0.013911s".into(),
+        };
+
+        assert_eq!(
+            stats_rows(&stats, &elapsed_total),
+            [
+                ("Elapsed", "0.013911s".into()),
+                ("Searched", "18.3 KiB in 2 file(s)".into()),
+                ("Throughput", "1.3 MiB/s".into()),
+                ("Matched", "12 match(es) on 10 line(s) in 2 file(s)".into()),
+            ]
+        );
+    }
+}
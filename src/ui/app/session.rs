@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{PrintableStyle, SortMode};
+use crate::rg::de::{ArbitraryData, RgMessageKind};
+use crate::ui::app::App;
+
+/// A submatch, identified by its file, the offset of its line and its range in the line.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SessionMatch {
+    pub path: ArbitraryData,
+    pub offset: usize,
+    pub range: Range<usize>,
+}
+
+/// The state of the interface, which is saved with `--session` when `rgr` exits and restored the next time the
+/// same search is run.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// The `rg` command line which was searched with. The session is only restored for the same one.
+    pub rg_cmdline: String,
+    /// The submatches which were deselected. Every other match is selected when the session is restored, so
+    /// matches which weren't found before are selected too.
+    pub deselected: Vec<SessionMatch>,
+    /// The query the list was fuzzy filtered with, if any.
+    pub fuzzy_query: Option<String>,
+    pub sort_mode: SortMode,
+    pub printable_style: PrintableStyle,
+}
+
+impl Session {
+    /// Reads the session saved at `path`, if there is one.
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Session>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|s| Ok(serde_json::from_str(&s)?))
+            .map(Some)
+            .with_context(|| format!("Failed to load the session from {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to save the session to {}", path.display()))
+    }
+}
+
+impl App {
+    /// Returns the state of the interface, so it can be restored the next time the same search is run.
+    pub fn session(&self) -> Session {
+        let deselected = self
+            .list
+            .iter()
+            .filter(|item| matches!(item.kind, RgMessageKind::Match))
+            .flat_map(|item| {
+                item.sub_items()
+                    .iter()
+                    .filter(|sub_item| !sub_item.should_replace)
+                    .filter_map(move |sub_item| {
+                        Some(SessionMatch {
+                            path: item.path()?.clone(),
+                            offset: item.offset()?,
+                            range: sub_item.sub_match.range.clone(),
+                        })
+                    })
+            })
+            .collect();
+
+        Session {
+            rg_cmdline: self.rg_cmdline.clone(),
+            deselected,
+            fuzzy_query: self.active_fuzzy_query().map(String::from),
+            sort_mode: self.sort_mode,
+            printable_style: self.printable_style,
+        }
+    }
+
+    /// Restores the state of the interface from a session. Returns `false` (and changes nothing) if the session
+    /// was saved for a different search.
+    pub fn restore_session(&mut self, session: Session) -> bool {
+        if session.rg_cmdline != self.rg_cmdline {
+            return false;
+        }
+
+        let deselected = session.deselected.into_iter().collect::<HashSet<_>>();
+        for item in self.list.iter_mut() {
+            let (path, offset) = match (item.path().cloned(), item.offset()) {
+                (Some(path), Some(offset)) => (path, offset),
+                _ => continue,
+            };
+
+            for idx in 0..item.sub_items().len() {
+                let session_match = SessionMatch {
+                    path: path.clone(),
+                    offset,
+                    range: item.sub_items()[idx].sub_match.range.clone(),
+                };
+                item.set_should_replace(idx, !deselected.contains(&session_match));
+            }
+        }
+
+        self.printable_style = session.printable_style;
+        self.sort_mode = session.sort_mode;
+        self.fuzzy_query = session.fuzzy_query;
+        // sorting also filters the list with the query
+        self.sort_list();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::model::{PrintableStyle, SortMode};
+    use crate::rg::de::test_utilities::*;
+    use crate::rg::de::{ArbitraryData, RgMessage, RgMessageKind, Stats, SubMatch};
+    use crate::ui::app::session::{Session, SessionMatch};
+    use crate::ui::app::App;
+
+    fn new_app() -> App {
+        let other_file = |kind| RgMessageBuilder::new(kind).with_path_text("b.txt");
+        App::new(
+            None,
+            "rg foo".to_string(),
+            vec![
+                RgMessage::from_str(RG_JSON_BEGIN),
+                RgMessage::from_str(RG_JSON_MATCH),
+                RgMessage::from_str(RG_JSON_END),
+                other_file(RgMessageKind::Begin).build(),
+                other_file(RgMessageKind::Match)
+                    .with_lines_text("foo foo\n")
+                    .with_line_number(1)
+                    .with_offset(0)
+                    .with_submatches(vec![
+                        SubMatch::new_text("foo", 0..3),
+                        SubMatch::new_text("foo", 4..7),
+                    ])
+                    .build(),
+                other_file(RgMessageKind::End)
+                    .with_stats(Stats::new())
+                    .build(),
+                RgMessage::from_str(RG_JSON_SUMMARY),
+            ],
+        )
+    }
+
+    fn selection(app: &App) -> Vec<Vec<bool>> {
+        app.list
+            .iter()
+            .filter(|item| matches!(item.kind, RgMessageKind::Match))
+            .map(|item| item.sub_items().iter().map(|s| s.should_replace).collect())
+            .collect()
+    }
+
+    #[test]
+    fn it_round_trips_a_session() {
+        let session = Session {
+            rg_cmdline: "rg foo".into(),
+            deselected: vec![SessionMatch {
+                path: ArbitraryData::Text {
+                    text: "src/model/item.rs".into(),
+                },
+                offset: 5522,
+                range: 14..20,
+            }],
+            fuzzy_query: Some("item".into()),
+            sort_mode: SortMode::MatchCount,
+            printable_style: PrintableStyle::Common(true),
+        };
+
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), Some(session));
+
+        // there's nothing to restore until a session is saved
+        assert_eq!(Session::load(path.with_extension("missing")).unwrap(), None);
+    }
+
+    #[test]
+    fn it_restores_a_session() {
+        let mut app = new_app();
+        app.list[1].set_should_replace(1, false);
+        app.list[4].set_should_replace_all(false);
+        app.printable_style = PrintableStyle::All(false);
+        app.sort_mode = SortMode::Path;
+        app.fuzzy_query = Some("foo".into());
+        app.sort_list();
+        let session = app.session();
+        assert_eq!(session.deselected.len(), 3);
+
+        let mut restored = new_app();
+        assert!(restored.restore_session(session));
+        assert_eq!(selection(&restored), selection(&app));
+        assert_eq!(restored.sort_mode, SortMode::Path);
+        assert_eq!(restored.printable_style, PrintableStyle::All(false));
+        assert_eq!(restored.fuzzy_query.as_deref(), Some("foo"));
+    }
+
+    #[test]
+    fn it_only_restores_a_session_for_the_same_search() {
+        let mut app = new_app();
+        app.list[1].set_should_replace_all(false);
+        let mut session = app.session();
+        session.rg_cmdline = "rg bar".into();
+
+        let mut restored = new_app();
+        assert!(!restored.restore_session(session));
+        assert!(selection(&restored).iter().flatten().all(|s| *s));
+    }
+}
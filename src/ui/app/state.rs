@@ -1,5 +1,8 @@
-use ratatui::style::{Color, Style};
-use ratatui::text::Span;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
 use ratatui::widgets::ListState;
 
 #[derive(Debug)]
@@ -10,12 +13,21 @@ pub struct AppListState {
     selected_submatch: usize,
     /// The position of the indicator on the left of the main list view
     indicator: ListState,
+    /// Set when the selected item has been scrolled out of the visible window (e.g. with the mouse wheel), so
+    /// the indicator isn't shown.
+    indicator_hidden: bool,
     /// The position of the start of the visible window in the main list view.
     /// We only send the visible lines to the renderer for performance reasons, and
     /// this represents the beginning of the visible window.
     window_start: usize,
 }
 
+impl Default for AppListState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AppListState {
     pub fn new() -> AppListState {
         let mut list_state = ListState::default();
@@ -24,6 +36,7 @@ impl AppListState {
             selected_item: 0,
             selected_submatch: 0,
             indicator: list_state,
+            indicator_hidden: false,
             window_start: 0,
         }
     }
@@ -34,6 +47,18 @@ impl AppListState {
 
     pub fn set_indicator_pos(&mut self, idx: usize) {
         self.indicator.select(Some(idx));
+        self.indicator_hidden = false;
+    }
+
+    /// Hides the indicator, until its position is set again.
+    pub fn hide_indicator(&mut self) {
+        // the list still needs a selection, otherwise it isn't indented by the (blank) indicator
+        self.indicator.select(Some(0));
+        self.indicator_hidden = true;
+    }
+
+    pub fn is_indicator_hidden(&self) -> bool {
+        self.indicator_hidden
     }
 
     pub fn window_start(&self) -> usize {
@@ -66,6 +91,10 @@ pub enum AppState {
     Running,
     Cancelled,
     Complete,
+    /// The user wants to open the given file (at the given line) in their editor.
+    OpenEditor(PathBuf, Option<usize>),
+    /// The user wants to search again, with the given number of context lines.
+    Rerun(usize),
 }
 
 /// Describes the various states that `App` can be in.
@@ -75,6 +104,26 @@ pub enum AppUiState {
     Help,
     /// The main matches list: select or deselect the found matches.
     SelectMatches,
+    /// Scroll the main matches list horizontally (only available with `WrapMode::NoWrap`).
+    ScrollLines,
+    /// Prompt the user for a query to fuzzy filter the main matches list with.
+    FilterMatches,
+    /// Prompt the user for how many lines of context to search again with.
+    InputContext(String),
+    /// Prompt the user for a regex, to select the matches whose lines it matches.
+    InputSelectRegex(String),
+    /// Show a scrollable preview of the whole file around the selected match.
+    Preview,
+    /// Show the lines of the selected match wrapped to the whole width of the terminal, scrolled down by the
+    /// given number of rows.
+    FocusMatch(usize),
+    /// Show how long `ripgrep` took to search, and how much it searched.
+    SearchStats,
+    /// Choose which files (with `-l/--files-with-matches`) to show the matches of.
+    PickFiles,
+    /// Select a range of items to toggle, from the anchor (the index of the item where it started) to the
+    /// selected item.
+    VisualSelect(usize),
     /// Prompt the user for the replacement text.
     /// (ReplacementText, CharPosition)
     InputReplacement(String, usize),
@@ -101,23 +150,49 @@ impl AppUiState {
 
     /// Represent the `AppUiState` as a `Text`.
     /// This is displayed as the "mode" in the stats line.
-    pub fn to_span(&self) -> Span {
+    pub fn to_span(&self) -> Span<'_> {
         let style = Style::default().fg(Color::Black);
         match self {
             AppUiState::Help => Span::styled(" HELP ", style.bg(Color::Green)),
             AppUiState::SelectMatches => Span::styled(" SELECT ", style.bg(Color::Cyan)),
+            AppUiState::ScrollLines => Span::styled(" SCROLL ", style.bg(Color::Blue)),
+            AppUiState::FilterMatches => Span::styled(" FILTER ", style.bg(Color::Yellow)),
+            AppUiState::InputContext(_) => Span::styled(" CONTEXT ", style.bg(Color::LightCyan)),
+            AppUiState::InputSelectRegex(_) => Span::styled(" REGEX ", style.bg(Color::LightRed)),
+            AppUiState::Preview => Span::styled(" PREVIEW ", style.bg(Color::Magenta)),
+            AppUiState::FocusMatch(_) => Span::styled(" FOCUS ", style.bg(Color::LightMagenta)),
+            AppUiState::SearchStats => Span::styled(" STATS ", style.bg(Color::LightBlue)),
+            AppUiState::PickFiles => Span::styled(" FILES ", style.bg(Color::LightGreen)),
+            AppUiState::VisualSelect(_) => Span::styled(" VISUAL ", style.bg(Color::LightYellow)),
             AppUiState::InputReplacement(_, _) => Span::styled(" REPLACE ", style.bg(Color::White)),
             AppUiState::ConfirmReplacement(_, _) => Span::styled(" CONFIRM ", style.bg(Color::Red)),
         }
     }
 }
 
-/// A small struct to manage scrolling the text in the help view.
+/// An occurrence of the search query in the help text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HelpTextHit {
+    /// The index of the line it's in.
+    pub line: usize,
+    /// The range of bytes it spans in the line.
+    pub range: Range<usize>,
+}
+
+/// A small struct to manage scrolling (and searching) the text in the help view.
 #[derive(Debug)]
 pub struct HelpTextState {
     pub pos: usize,
     pub max: usize,
     help_text: &'static str,
+    /// The text to search for (ignoring ASCII case), if the user has entered any.
+    query: Option<String>,
+    /// Set while the user is typing the query.
+    editing_query: bool,
+    /// Every occurrence of the query, in the order they're in the help text.
+    hits: Vec<HelpTextHit>,
+    /// The index (in `hits`) of the occurrence which was last jumped to.
+    current_hit: usize,
 }
 
 impl HelpTextState {
@@ -126,25 +201,333 @@ impl HelpTextState {
             pos: 0,
             max: help_text.lines().count() - 1,
             help_text,
+            query: None,
+            editing_query: false,
+            hits: vec![],
+            current_hit: 0,
         }
     }
 
-    pub fn incr(&mut self) {
-        if self.pos < self.max {
-            self.pos += 1;
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    pub fn is_editing_query(&self) -> bool {
+        self.editing_query
+    }
+
+    pub fn hits(&self) -> &[HelpTextHit] {
+        &self.hits
+    }
+
+    /// The occurrence of the query which was last jumped to, if there are any.
+    pub fn current_hit(&self) -> Option<&HelpTextHit> {
+        self.hits.get(self.current_hit)
+    }
+
+    /// Starts typing a new query.
+    pub fn start_search(&mut self) {
+        self.editing_query = true;
+        self.find("");
+    }
+
+    /// Stops typing the query, but keeps its occurrences so they can be cycled through. An empty query is
+    /// cleared instead.
+    pub fn accept_search(&mut self) {
+        self.editing_query = false;
+        if self.query.as_ref().is_some_and(|q| q.is_empty()) {
+            self.clear_search();
+        }
+    }
+
+    pub fn clear_search(&mut self) {
+        self.query = None;
+        self.editing_query = false;
+        self.hits.clear();
+        self.current_hit = 0;
+    }
+
+    /// Finds every occurrence of `query` (ignoring ASCII case) and scrolls to the first one at or after the
+    /// current position (or the first one, if there are none after it). Returns how many there are.
+    pub fn find(&mut self, query: &str) -> usize {
+        let needle = query.to_ascii_lowercase();
+        self.hits = if needle.is_empty() {
+            vec![]
+        } else {
+            self.help_text
+                .lines()
+                .enumerate()
+                .flat_map(|(line, text)| {
+                    text.to_ascii_lowercase()
+                        .match_indices(&needle)
+                        .map(|(start, m)| HelpTextHit {
+                            line,
+                            range: start..start + m.len(),
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        };
+        self.query = Some(query.to_owned());
+
+        self.current_hit = self
+            .hits
+            .iter()
+            .position(|hit| hit.line >= self.pos)
+            .unwrap_or(0);
+        self.scroll_to_hit();
+
+        self.hits.len()
+    }
+
+    /// Scrolls to the next occurrence of the query, or back to the first one after the last.
+    pub fn next_hit(&mut self) {
+        if !self.hits.is_empty() {
+            self.current_hit = (self.current_hit + 1) % self.hits.len();
+            self.scroll_to_hit();
         }
     }
 
+    /// Scrolls to the previous occurrence of the query, or to the last one before the first.
+    pub fn prev_hit(&mut self) {
+        if !self.hits.is_empty() {
+            self.current_hit = (self.current_hit + self.hits.len() - 1) % self.hits.len();
+            self.scroll_to_hit();
+        }
+    }
+
+    fn scroll_to_hit(&mut self) {
+        if let Some(hit) = self.current_hit() {
+            self.pos = hit.line.min(self.max);
+        }
+    }
+
+    pub fn incr(&mut self) {
+        self.incr_by(1);
+    }
+
     pub fn decr(&mut self) {
-        self.pos = self.pos.saturating_sub(1);
+        self.decr_by(1);
     }
 
-    pub fn text(&self, num_lines: usize) -> String {
-        self.help_text
+    /// Scrolls forward by `n` lines (e.g. a page), but no further than the last line.
+    pub fn incr_by(&mut self, n: usize) {
+        self.pos = (self.pos + n).min(self.max);
+    }
+
+    /// Scrolls backward by `n` lines (e.g. a page), but no further than the first line.
+    pub fn decr_by(&mut self, n: usize) {
+        self.pos = self.pos.saturating_sub(n);
+    }
+
+    /// The lines of the help text from the current position, with the occurrences of the query highlighted (and
+    /// the current one more so).
+    pub fn highlighted_text(&self, num_lines: usize) -> Text<'static> {
+        let hit_style = Style::default().fg(Color::Black).bg(Color::Yellow);
+        let current_style = hit_style.bg(Color::LightRed).add_modifier(Modifier::BOLD);
+
+        let lines = self
+            .help_text
             .lines()
+            .enumerate()
             .skip(self.pos)
             .take(num_lines)
-            .collect::<Vec<_>>()
-            .join("\n")
+            .map(|(i, text)| {
+                let mut spans = vec![];
+                let mut end = 0;
+                for (hit_idx, hit) in self.hits.iter().enumerate().filter(|(_, h)| h.line == i) {
+                    spans.push(Span::from(&text[end..hit.range.start]));
+                    let style = if hit_idx == self.current_hit {
+                        current_style
+                    } else {
+                        hit_style
+                    };
+                    spans.push(Span::styled(&text[hit.range.clone()], style));
+                    end = hit.range.end;
+                }
+                spans.push(Span::from(&text[end..]));
+
+                Line::from(spans)
+            })
+            .collect::<Vec<_>>();
+
+        Text::from(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use ratatui::style::Color;
+
+    use crate::ui::app::state::{HelpTextHit, HelpTextState};
+
+    impl HelpTextState {
+        fn text(&self, num_lines: usize) -> String {
+            self.highlighted_text(num_lines)
+                .lines
+                .iter()
+                .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+                .collect::<Vec<String>>()
+                .join("\n")
+        }
+    }
+
+    #[test]
+    fn help_text_pages_are_clamped() {
+        let mut state = HelpTextState::new("0\n1\n2\n3\n4\n5\n6\n7\n8\n9");
+        assert_eq!(state.max, 9);
+
+        // at the top
+        state.decr_by(5);
+        assert_eq!(state.pos, 0);
+
+        state.incr_by(4);
+        assert_eq!(state.pos, 4);
+        assert_eq!(state.text(2), "4\n5");
+        state.incr_by(4);
+        assert_eq!(state.pos, 8);
+
+        // at the bottom
+        state.incr_by(4);
+        assert_eq!(state.pos, 9);
+        state.incr();
+        assert_eq!(state.pos, 9);
+        assert_eq!(state.text(4), "9");
+
+        state.decr_by(4);
+        assert_eq!(state.pos, 5);
+        state.decr_by(6);
+        assert_eq!(state.pos, 0);
+    }
+
+    const SEARCH_TEXT: &str = "rgr\nToggle a match\n\nmatches are toggled\nMATCH\nthe end";
+
+    #[test]
+    fn it_finds_text_in_the_help() {
+        let mut state = HelpTextState::new(SEARCH_TEXT);
+        assert_eq!(state.find("match"), 3);
+        assert_eq!(state.query(), Some("match"));
+        assert_eq!(
+            state.hits(),
+            [
+                HelpTextHit {
+                    line: 1,
+                    range: 9..14
+                },
+                HelpTextHit {
+                    line: 3,
+                    range: 0..5
+                },
+                HelpTextHit {
+                    line: 4,
+                    range: 0..5
+                },
+            ]
+        );
+        // jumps to the first occurrence
+        assert_eq!(state.current_hit(), Some(&state.hits()[0]));
+        assert_eq!(state.pos, 1);
+        assert_eq!(state.text(1), "Toggle a match");
+
+        assert_eq!(state.find("missing"), 0);
+        assert_eq!(state.current_hit(), None);
+        assert_eq!(state.pos, 1);
+        assert_eq!(state.find(""), 0);
+    }
+
+    #[test]
+    fn it_finds_text_after_the_current_position() {
+        let mut state = HelpTextState::new(SEARCH_TEXT);
+        state.incr_by(2);
+        state.find("match");
+        assert_eq!(state.pos, 3);
+
+        // wraps around to the first one
+        state.incr_by(5);
+        state.find("toggle");
+        assert_eq!(state.pos, 1);
+    }
+
+    #[test]
+    fn it_cycles_through_hits() {
+        let mut state = HelpTextState::new(SEARCH_TEXT);
+        state.find("match");
+
+        let mut lines = vec![];
+        for _ in 0..4 {
+            state.next_hit();
+            lines.push(state.pos);
+        }
+        assert_eq!(lines, [3, 4, 1, 3]);
+
+        lines.clear();
+        for _ in 0..4 {
+            state.prev_hit();
+            lines.push(state.pos);
+        }
+        assert_eq!(lines, [1, 4, 3, 1]);
+    }
+
+    #[test]
+    fn it_edits_and_clears_the_search() {
+        let mut state = HelpTextState::new(SEARCH_TEXT);
+        state.start_search();
+        assert!(state.is_editing_query());
+        assert_eq!(state.query(), Some(""));
+
+        // an empty search is cleared when it's accepted
+        state.accept_search();
+        assert!(!state.is_editing_query());
+        assert_eq!(state.query(), None);
+
+        state.start_search();
+        state.find("end");
+        state.accept_search();
+        assert_eq!(state.query(), Some("end"));
+        assert_eq!(state.hits().len(), 1);
+        assert_eq!(state.pos, 5);
+
+        state.clear_search();
+        assert_eq!(state.query(), None);
+        assert!(state.hits().is_empty());
+        // the position is kept
+        assert_eq!(state.pos, 5);
+    }
+
+    #[test]
+    fn it_highlights_hits() {
+        let mut state = HelpTextState::new(SEARCH_TEXT);
+        state.find("toggle");
+        state.next_hit();
+        state.pos = 1;
+        let text = state.highlighted_text(3);
+        let spans = text.lines[0]
+            .spans
+            .iter()
+            .map(|s| (s.content.as_ref(), s.style.bg))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [
+                ("", None),
+                ("Toggle", Some(Color::Yellow)),
+                (" a match", None)
+            ]
+        );
+        let spans = text.lines[2]
+            .spans
+            .iter()
+            .map(|s| (s.content.as_ref(), s.style.bg))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [
+                ("matches are ", None),
+                ("toggle", Some(Color::LightRed)),
+                ("d", None)
+            ]
+        );
     }
 }
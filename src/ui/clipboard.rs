@@ -0,0 +1,69 @@
+use anyhow::Result;
+
+/// Somewhere text can be copied to from the interface.
+pub trait Clipboard {
+    /// Copies `text`, and returns a short message saying where it was copied to.
+    fn copy(&mut self, text: &str) -> Result<String>;
+}
+
+/// Returns the clipboard used by the interface. With the `clipboard` feature this is the terminal's
+/// clipboard, otherwise the text is written to the log.
+pub fn default_clipboard() -> Box<dyn Clipboard> {
+    #[cfg(feature = "clipboard")]
+    return Box::new(TerminalClipboard);
+
+    #[cfg(not(feature = "clipboard"))]
+    return Box::new(LogClipboard);
+}
+
+/// Copies text to the clipboard with the terminal's OSC 52 escape sequence, which also works over SSH. Not
+/// every terminal supports it (and some need it to be enabled).
+#[cfg(feature = "clipboard")]
+pub struct TerminalClipboard;
+
+#[cfg(feature = "clipboard")]
+impl Clipboard for TerminalClipboard {
+    fn copy(&mut self, text: &str) -> Result<String> {
+        use std::io::{self, Write};
+
+        let mut stdout = io::stdout();
+        stdout.write_all(osc52_sequence(text).as_bytes())?;
+        stdout.flush()?;
+
+        Ok(String::from("Copied to the clipboard"))
+    }
+}
+
+#[cfg(feature = "clipboard")]
+fn osc52_sequence(text: &str) -> String {
+    use base64_simd::STANDARD as base64;
+
+    format!("\x1b]52;c;{}\x07", base64.encode_to_string(text))
+}
+
+/// Writes text to the log, for when `rgr` was built without the `clipboard` feature.
+#[cfg(not(feature = "clipboard"))]
+pub struct LogClipboard;
+
+#[cfg(not(feature = "clipboard"))]
+impl Clipboard for LogClipboard {
+    fn copy(&mut self, text: &str) -> Result<String> {
+        log::warn!("Copied: {}", text);
+
+        Ok(String::from(
+            "Written to the log (rgr was built without the clipboard feature)",
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "clipboard"))]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::ui::clipboard::osc52_sequence;
+
+    #[test]
+    fn it_encodes_osc52_sequences() {
+        assert_eq!(osc52_sequence("rg foo"), "\x1b]52;c;cmcgZm9v\x07");
+    }
+}
@@ -0,0 +1,101 @@
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Result};
+
+/// Environment variables which are checked (in order) for the user's editor.
+const EDITOR_VARS: [&str; 2] = ["EDITOR", "VISUAL"];
+
+/// Returns the user's editor, if one has been configured.
+pub fn editor_from_env() -> Option<String> {
+    EDITOR_VARS
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+}
+
+/// Builds the argv used to open `path` in `editor`, at `line_number` if one is given.
+///
+/// The editor may contain its own arguments (e.g.: `code --wait`), and the line number is passed
+/// with the `+N` syntax that most editors understand. Returns `None` if `editor` is empty.
+pub fn editor_argv(editor: &str, path: &Path, line_number: Option<usize>) -> Option<Vec<OsString>> {
+    let mut argv = editor
+        .split_whitespace()
+        .map(OsString::from)
+        .collect::<Vec<_>>();
+
+    if argv.is_empty() {
+        return None;
+    }
+
+    if let Some(n) = line_number {
+        argv.push(format!("+{}", n).into());
+    }
+
+    argv.push(path.as_os_str().to_owned());
+    Some(argv)
+}
+
+/// Opens `path` in the user's editor, and waits for it to exit.
+pub fn open_in_editor(path: &Path, line_number: Option<usize>) -> Result<()> {
+    let editor = match editor_from_env() {
+        Some(editor) => editor,
+        None => bail!("Neither $EDITOR nor $VISUAL are set"),
+    };
+
+    let argv = match editor_argv(&editor, path, line_number) {
+        Some(argv) => argv,
+        None => bail!("Failed to parse editor: {:?}", editor),
+    };
+
+    let status = Command::new(&argv[0]).args(&argv[1..]).status()?;
+    if !status.success() {
+        bail!("Editor exited with {}", status);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::path::Path;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::ui::editor::editor_argv;
+
+    fn argv(args: &[&str]) -> Option<Vec<OsString>> {
+        Some(args.iter().map(OsString::from).collect())
+    }
+
+    #[test]
+    fn it_builds_editor_argv() {
+        let path = Path::new("./src/main.rs");
+        assert_eq!(
+            editor_argv("vim", path, Some(42)),
+            argv(&["vim", "+42", "./src/main.rs"])
+        );
+        assert_eq!(
+            editor_argv("vim", path, None),
+            argv(&["vim", "./src/main.rs"])
+        );
+    }
+
+    #[test]
+    fn it_builds_editor_argv_with_editor_args() {
+        let path = Path::new("foo bar.txt");
+        assert_eq!(
+            editor_argv("  emacsclient -nw  ", path, Some(1)),
+            argv(&["emacsclient", "-nw", "+1", "foo bar.txt"])
+        );
+    }
+
+    #[test]
+    fn it_builds_no_editor_argv_if_editor_is_empty() {
+        assert_eq!(editor_argv("", Path::new("foo"), Some(1)), None);
+        assert_eq!(editor_argv("   ", Path::new("foo"), None), None);
+    }
+}
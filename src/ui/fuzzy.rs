@@ -0,0 +1,222 @@
+use std::ops::Range;
+
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CAMEL_CASE: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 8;
+
+/// The result of fuzzy matching a query against some text.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// Higher scores are better matches.
+    pub score: i64,
+    /// The (char) positions in the text of each character of the query.
+    pub positions: Vec<usize>,
+}
+
+/// Whether `query` should be matched case sensitively when the user hasn't chosen: only if it contains an
+/// uppercase character (i.e. "smart case").
+pub fn is_smart_case_sensitive(query: &str) -> bool {
+    query.chars().any(char::is_uppercase)
+}
+
+/// Fuzzy matches `query` against `text`, in the style of `fzf`. Each character of the query must appear in
+/// the text in the same order, but not necessarily next to each other. Unless `case_sensitive` is set, the
+/// case of the characters is ignored.
+///
+/// Returns `None` if the text doesn't match the query.
+pub fn fuzzy_match(query: &str, text: &str, case_sensitive: bool) -> Option<FuzzyMatch> {
+    let normalise = |ch: char| {
+        if case_sensitive {
+            ch
+        } else {
+            ch.to_lowercase().next().unwrap_or(ch)
+        }
+    };
+
+    let query = query.chars().map(normalise).collect::<Vec<_>>();
+    let text_chars = text.chars().collect::<Vec<_>>();
+    let text = text_chars
+        .iter()
+        .copied()
+        .map(normalise)
+        .collect::<Vec<_>>();
+    if query.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: vec![],
+        });
+    }
+
+    // find where the first occurrence of the whole query ends
+    let mut q = 0;
+    let mut end = None;
+    for (i, ch) in text.iter().enumerate() {
+        if *ch == query[q] {
+            q += 1;
+            if q == query.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // then scan backwards from there, to find the shortest match which ends there
+    let mut q = query.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if text[i] == query[q - 1] {
+            q -= 1;
+            if q == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut q = 0;
+    for (i, ch) in text.iter().enumerate().take(end + 1).skip(start) {
+        if q < query.len() && *ch == query[q] {
+            positions.push(i);
+            q += 1;
+        }
+    }
+
+    let score = positions
+        .iter()
+        .enumerate()
+        .map(|(i, pos)| {
+            let mut score = SCORE_MATCH;
+            let prev_char = pos.checked_sub(1).map(|p| text_chars[p]);
+            match prev_char {
+                None => score += BONUS_BOUNDARY,
+                Some(prev) if !prev.is_alphanumeric() => score += BONUS_BOUNDARY,
+                Some(prev) if prev.is_lowercase() && text_chars[*pos].is_uppercase() => {
+                    score += BONUS_CAMEL_CASE
+                }
+                _ => {}
+            }
+
+            if i > 0 {
+                let gap = pos - positions[i - 1] - 1;
+                if gap == 0 {
+                    score += BONUS_CONSECUTIVE;
+                } else {
+                    score += SCORE_GAP_START + SCORE_GAP_EXTENSION * (gap as i64 - 1);
+                }
+            }
+
+            score
+        })
+        .sum();
+
+    Some(FuzzyMatch { score, positions })
+}
+
+/// Merges (sorted) positions into ranges of consecutive positions, so they can be highlighted together.
+pub fn highlight_ranges(positions: &[usize]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for pos in positions {
+        match ranges.last_mut() {
+            Some(range) if range.end == *pos => range.end += 1,
+            _ => ranges.push(*pos..*pos + 1),
+        }
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::ui::fuzzy::{fuzzy_match, highlight_ranges, is_smart_case_sensitive};
+
+    fn positions(query: &str, text: &str) -> Option<Vec<usize>> {
+        fuzzy_match(query, text, is_smart_case_sensitive(query)).map(|m| m.positions)
+    }
+
+    fn score(query: &str, text: &str) -> i64 {
+        fuzzy_match(query, text, false).unwrap().score
+    }
+
+    #[test]
+    fn it_matches_subsequences() {
+        assert_eq!(positions("", "foo"), Some(vec![]));
+        assert_eq!(positions("fb", "foo bar"), Some(vec![0, 4]));
+        assert_eq!(positions("oba", "foo bar"), Some(vec![2, 4, 5]));
+        assert_eq!(positions("bf", "foo bar"), None);
+        assert_eq!(positions("foo", "fo"), None);
+    }
+
+    #[test]
+    fn it_matches_with_smart_case() {
+        assert_eq!(positions("fb", "Foo Bar"), Some(vec![0, 4]));
+        assert_eq!(positions("FB", "Foo Bar"), Some(vec![0, 4]));
+        assert_eq!(positions("FB", "foo bar"), None);
+    }
+
+    #[test]
+    fn it_decides_case_sensitivity_with_smart_case() {
+        assert!(!is_smart_case_sensitive(""));
+        assert!(!is_smart_case_sensitive("foo bar"));
+        assert!(!is_smart_case_sensitive("foo_123!"));
+        assert!(is_smart_case_sensitive("fooBar"));
+        assert!(is_smart_case_sensitive("É"));
+    }
+
+    #[test]
+    fn it_matches_with_case_sensitivity() {
+        let positions = |query, text, case_sensitive| {
+            fuzzy_match(query, text, case_sensitive).map(|m| m.positions)
+        };
+
+        assert_eq!(positions("fb", "Foo Bar", true), None);
+        assert_eq!(positions("fb", "Foo Bar", false), Some(vec![0, 4]));
+        assert_eq!(positions("FB", "foo bar", true), None);
+        assert_eq!(positions("FB", "foo bar", false), Some(vec![0, 4]));
+        assert_eq!(positions("Fb", "Foo Bar foo bar", true), Some(vec![0, 12]));
+    }
+
+    #[test]
+    fn it_prefers_the_shortest_match() {
+        // the first "a" would be matched greedily, but the "a" closest to the "b" is better
+        assert_eq!(positions("ab", "a___ab"), Some(vec![4, 5]));
+    }
+
+    #[test]
+    fn it_scores_matches() {
+        // consecutive characters are better than gaps
+        assert!(score("foo", "foo") > score("foo", "f_o_o"));
+        assert!(score("foo", "f_o_o") > score("foo", "f___o___o"));
+        // word boundaries are better than the middle of words
+        assert!(score("bar", "foo bar") > score("bar", "foobar"));
+        assert!(score("fb", "fooBar") > score("fb", "foobar"));
+    }
+
+    #[test]
+    fn it_ranks_matches() {
+        let texts = ["xfxoxo", "f_o_o", "foo", "fo_o"];
+        let mut ranked = texts
+            .iter()
+            .map(|text| (score("foo", text), *text))
+            .collect::<Vec<_>>();
+        ranked.sort_by_key(|(score, _)| -score);
+
+        assert_eq!(
+            ranked.into_iter().map(|(_, text)| text).collect::<Vec<_>>(),
+            ["foo", "fo_o", "f_o_o", "xfxoxo"]
+        );
+    }
+
+    #[test]
+    fn it_computes_highlight_ranges() {
+        assert!(highlight_ranges(&[]).is_empty());
+        assert_eq!(highlight_ranges(&[3, 5]), [3..4, 5..6]);
+        assert_eq!(highlight_ranges(&[0, 1, 2, 5, 7, 8]), [0..3, 5..6, 7..9]);
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::{fmt, fs};
+
+use anyhow::{anyhow, bail, Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+
+use crate::util::config_dir;
+
+/// The actions in the interface which can be bound to keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    // Available in every mode (other than HELP).
+    PageUp,
+    PageDown,
+    CyclePrintableStyle,
+
+    // SELECT (and HELP, PREVIEW and VISUAL, for moving up and down).
+    MoveUp,
+    MoveDown,
+    PrevFile,
+    NextFile,
+    MoveLeft,
+    MoveRight,
+    MoveToFirst,
+    MoveToLast,
+    Toggle,
+    ToggleLine,
+    ToggleAll,
+    DeselectAll,
+    Invert,
+    InvertMatch,
+    InvertAll,
+    VisualSelect,
+    SelectRegex,
+    CollapseDuplicates,
+    ToggleLineNumbers,
+    CycleSort,
+    ReverseFiles,
+    OpenEditor,
+    Preview,
+    FocusMatch,
+    SearchStats,
+    CopyCmdline,
+    ChangeContext,
+    Filter,
+    ScrollLines,
+    Help,
+    EnterReplace,
+    Quit,
+
+    // SCROLL
+    ScrollLeft,
+    ScrollRight,
+    ScrollLeftHalfPage,
+    ScrollRightHalfPage,
+    ScrollStart,
+
+    // REPLACE
+    AcceptReplacement,
+    ReplaceSelectedOnly,
+
+    // REPLACE and CONFIRM
+    CopyReplacedLine,
+
+    // CONFIRM
+    Confirm,
+    ConfirmQuietly,
+    ShowOnlyChanges,
+
+    // SCROLL, HELP, PREVIEW, FOCUS, STATS, VISUAL and CONFIRM
+    Back,
+}
+
+/// The keys bound to each action by default.
+const DEFAULT_BINDINGS: &[(Action, &[&str])] = &[
+    (Action::PageUp, &["ctrl+b"]),
+    (Action::PageDown, &["ctrl+f"]),
+    (Action::CyclePrintableStyle, &["ctrl+v"]),
+    (Action::MoveUp, &["k", "up"]),
+    (Action::MoveDown, &["j", "down"]),
+    (Action::PrevFile, &["K", "shift+up"]),
+    (Action::NextFile, &["J", "shift+down"]),
+    (Action::MoveLeft, &["h", "H", "left", "shift+left"]),
+    (Action::MoveRight, &["l", "L", "right", "shift+right"]),
+    (Action::MoveToFirst, &["g"]),
+    (Action::MoveToLast, &["G"]),
+    (Action::Toggle, &["space"]),
+    (Action::ToggleLine, &["s", "S"]),
+    (Action::ToggleAll, &["a", "A"]),
+    (Action::DeselectAll, &["ctrl+a"]),
+    (Action::Invert, &["v"]),
+    (Action::InvertMatch, &["c"]),
+    (Action::InvertAll, &["V"]),
+    (Action::VisualSelect, &["m"]),
+    (Action::SelectRegex, &["T"]),
+    (Action::CollapseDuplicates, &["d"]),
+    (Action::ToggleLineNumbers, &["n"]),
+    (Action::CycleSort, &["o"]),
+    (Action::ReverseFiles, &["O"]),
+    (Action::OpenEditor, &["e"]),
+    (Action::Preview, &["p"]),
+    (Action::FocusMatch, &["f"]),
+    (Action::SearchStats, &["i"]),
+    (Action::CopyCmdline, &["y"]),
+    (Action::ChangeContext, &["C"]),
+    (Action::Filter, &["/"]),
+    (Action::ScrollLines, &["z"]),
+    (Action::Help, &["?"]),
+    (Action::EnterReplace, &["enter", "r", "R"]),
+    (Action::Quit, &["q", "esc"]),
+    (Action::ScrollLeft, &["h", "left"]),
+    (Action::ScrollRight, &["l", "right"]),
+    (Action::ScrollLeftHalfPage, &["H", "shift+left"]),
+    (Action::ScrollRightHalfPage, &["L", "shift+right"]),
+    (Action::ScrollStart, &["0"]),
+    (Action::AcceptReplacement, &["ctrl+s"]),
+    (Action::ReplaceSelectedOnly, &["ctrl+o"]),
+    (Action::CopyReplacedLine, &["ctrl+y"]),
+    (Action::Confirm, &["enter"]),
+    (Action::ConfirmQuietly, &["w"]),
+    (Action::ShowOnlyChanges, &["c"]),
+    (Action::Back, &["q", "esc"]),
+];
+
+/// Names of the keys which aren't written as a single character.
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("space", KeyCode::Char(' ')),
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+/// A key (along with any modifiers) which triggers an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> KeyBinding {
+        KeyBinding { code, modifiers }
+    }
+
+    /// Parses a key binding such as `j`, `space`, `shift+up` or `ctrl+s`.
+    pub fn parse(s: &str) -> Result<KeyBinding> {
+        let (modifiers, key) = match s.rsplit_once('+') {
+            // `+` is a key, too
+            Some((modifiers, "")) if modifiers.is_empty() || modifiers.ends_with('+') => {
+                (modifiers.strip_suffix('+').unwrap_or(modifiers), "+")
+            }
+            Some((modifiers, key)) => (modifiers, key),
+            None => ("", s),
+        };
+
+        let mut binding = KeyBinding::new(KeyCode::Null, KeyModifiers::empty());
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            binding.modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "shift" => KeyModifiers::SHIFT,
+                "alt" => KeyModifiers::ALT,
+                _ => bail!("unknown modifier `{}` in key `{}`", modifier, s),
+            };
+        }
+
+        let mut chars = key.chars();
+        binding.code = match (chars.next(), chars.next()) {
+            (Some(ch), None) => KeyCode::Char(ch),
+            _ => match KEY_NAMES
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            {
+                Some((_, code)) => *code,
+                None => bail!("unknown key `{}`", s),
+            },
+        };
+
+        Ok(binding)
+    }
+
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        // the case of a character already says whether shift was pressed (and terminals don't agree on
+        // whether to report it or not)
+        let ignored = match key.code {
+            KeyCode::Char(_) => KeyModifiers::SHIFT,
+            _ => KeyModifiers::empty(),
+        };
+
+        self.code == key.code && self.modifiers - ignored == key.modifiers - ignored
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "control"),
+            (KeyModifiers::ALT, "alt"),
+            (KeyModifiers::SHIFT, "shift"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{} + ", name)?;
+            }
+        }
+
+        match KEY_NAMES.iter().find(|(_, code)| *code == self.code) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => match self.code {
+                KeyCode::Char(ch) => write!(f, "{}", ch),
+                code => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+/// The keys bound to an action in the config file: either a single key or a list of them.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ConfigKeys {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Maps the actions in the interface to the keys which trigger them.
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: HashMap<Action, Vec<KeyBinding>>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .map(|(action, keys)| {
+                let keys = keys
+                    .iter()
+                    .map(|key| KeyBinding::parse(key).expect("invalid default key binding"))
+                    .collect();
+                (*action, keys)
+            })
+            .collect();
+
+        KeyMap { bindings }
+    }
+}
+
+impl KeyMap {
+    /// Returns the path to the user's keybindings config file.
+    pub fn config_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("keys.toml"))
+    }
+
+    /// Loads the user's keybindings config file, or the default keybindings if there isn't one.
+    pub fn load() -> Result<KeyMap> {
+        match KeyMap::config_path() {
+            Some(path) if path.exists() => fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|s| KeyMap::from_toml(&s))
+                .with_context(|| format!("Failed to load keybindings from {}", path.display())),
+            _ => Ok(KeyMap::default()),
+        }
+    }
+
+    /// Parses a keybindings config, which looks like `toggle = ["t", "space"]`. Actions which aren't in
+    /// it keep their default keys.
+    pub fn from_toml(s: &str) -> Result<KeyMap> {
+        let config: HashMap<String, ConfigKeys> = toml::from_str(s)?;
+
+        let mut keymap = KeyMap::default();
+        for (name, keys) in config {
+            let action = Action::deserialize(name.as_str().into_deserializer()).map_err(
+                |e: serde::de::value::Error| anyhow!("invalid action `{}`: {}", name, e),
+            )?;
+            let keys = match keys {
+                ConfigKeys::One(key) => vec![key],
+                ConfigKeys::Many(keys) => keys,
+            };
+
+            let keys = keys
+                .iter()
+                .map(|key| KeyBinding::parse(key))
+                .collect::<Result<Vec<_>>>()?;
+            keymap.bindings.insert(action, keys);
+        }
+
+        Ok(keymap)
+    }
+
+    /// Whether the key triggers the action.
+    pub fn is(&self, action: Action, key: &KeyEvent) -> bool {
+        self.bindings
+            .get(&action)
+            .is_some_and(|keys| keys.iter().any(|k| k.matches(key)))
+    }
+
+    /// Returns the first of the given actions which the key triggers.
+    pub fn find(&self, actions: &[Action], key: &KeyEvent) -> Option<Action> {
+        actions.iter().copied().find(|action| self.is(*action, key))
+    }
+
+    /// Describes the keys bound to the action, for the help view.
+    pub fn describe(&self, action: Action) -> String {
+        self.bindings
+            .get(&action)
+            .map(|keys| {
+                keys.iter()
+                    .map(|k| k.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use pretty_assertions::assert_eq;
+
+    use crate::ui::keymap::{Action, KeyBinding, KeyMap};
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn it_parses_key_bindings() {
+        let parse = |s| KeyBinding::parse(s).unwrap();
+        let binding = KeyBinding::new;
+        assert_eq!(
+            parse("j"),
+            binding(KeyCode::Char('j'), KeyModifiers::empty())
+        );
+        assert_eq!(
+            parse("J"),
+            binding(KeyCode::Char('J'), KeyModifiers::empty())
+        );
+        assert_eq!(
+            parse("+"),
+            binding(KeyCode::Char('+'), KeyModifiers::empty())
+        );
+        assert_eq!(
+            parse("space"),
+            binding(KeyCode::Char(' '), KeyModifiers::empty())
+        );
+        assert_eq!(
+            parse("Enter"),
+            binding(KeyCode::Enter, KeyModifiers::empty())
+        );
+        assert_eq!(parse("shift+up"), binding(KeyCode::Up, KeyModifiers::SHIFT));
+        assert_eq!(
+            parse("ctrl+s"),
+            binding(KeyCode::Char('s'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            parse("ctrl+alt++"),
+            binding(
+                KeyCode::Char('+'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            )
+        );
+    }
+
+    #[test]
+    fn it_fails_to_parse_invalid_key_bindings() {
+        assert!(KeyBinding::parse("foo").is_err());
+        assert!(KeyBinding::parse("super+j").is_err());
+        assert!(KeyBinding::parse("").is_err());
+    }
+
+    #[test]
+    fn it_matches_keys() {
+        let keymap = KeyMap::default();
+        let none = KeyModifiers::empty();
+        assert!(keymap.is(Action::MoveUp, &key(KeyCode::Up, none)));
+        assert!(!keymap.is(Action::MoveUp, &key(KeyCode::Up, KeyModifiers::SHIFT)));
+        assert!(keymap.is(Action::PrevFile, &key(KeyCode::Up, KeyModifiers::SHIFT)));
+        // shift is ignored for characters
+        assert!(keymap.is(Action::PrevFile, &key(KeyCode::Char('K'), none)));
+        assert!(keymap.is(
+            Action::PrevFile,
+            &key(KeyCode::Char('K'), KeyModifiers::SHIFT)
+        ));
+        assert!(!keymap.is(Action::PageUp, &key(KeyCode::Char('b'), none)));
+        assert!(keymap.is(
+            Action::PageUp,
+            &key(KeyCode::Char('b'), KeyModifiers::CONTROL)
+        ));
+    }
+
+    #[test]
+    fn it_finds_the_first_matching_action() {
+        let keymap = KeyMap::default();
+        let esc = key(KeyCode::Esc, KeyModifiers::empty());
+        assert_eq!(
+            keymap.find(&[Action::Back, Action::Quit], &esc),
+            Some(Action::Back)
+        );
+        assert_eq!(keymap.find(&[Action::Toggle], &esc), None);
+    }
+
+    #[test]
+    fn it_loads_a_custom_keymap() {
+        let keymap = KeyMap::from_toml("toggle = \"t\"\nquit = [\"x\", \"ctrl+c\"]\n").unwrap();
+        assert_eq!(keymap.describe(Action::Toggle), "t");
+        assert_eq!(keymap.describe(Action::Quit), "x, control + c");
+        // other actions keep their defaults
+        assert_eq!(keymap.describe(Action::MoveUp), "k, up");
+    }
+
+    #[test]
+    fn it_fails_to_load_an_invalid_keymap() {
+        assert!(KeyMap::from_toml("not_an_action = \"t\"").is_err());
+        assert!(KeyMap::from_toml("toggle = \"not a key\"").is_err());
+        assert!(KeyMap::from_toml("toggle = 1").is_err());
+    }
+}
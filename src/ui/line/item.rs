@@ -1,23 +1,334 @@
 use std::ops::Range;
 use std::path::PathBuf;
 
-use ratatui::style::{Color, Style};
+use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
+use regex::bytes::Regex;
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::format_line_number;
-use crate::model::{Printable, PrintableStyle};
+use crate::model::{expand_tabs, Printable, PrintableStyle, WrapMode};
 use crate::rg::de::{ArbitraryData, RgMessage, RgMessageKind};
 use crate::ui::app::AppUiState;
-use crate::ui::line::SubItem;
+use crate::ui::fuzzy::{fuzzy_match, highlight_ranges};
+use crate::ui::line::{
+    SubItem, CONTEXT_SEPARATOR, NO_WRAP_CLIP_MARKER, PATH_ELLIPSIS, WRAP_INDENT_MARKER,
+};
 use crate::ui::render::UiItemContext;
 
+/// Returns the replacement text as it's shown in the interface: any capturing groups are expanded with the first
+/// match of the capture pattern in the item's lines.
+fn expand_replacement(capture_pattern: Option<&Regex>, replacement: &str, lines: &[u8]) -> Vec<u8> {
+    let replacement = replacement.as_bytes().to_vec();
+    match capture_pattern.and_then(|re| re.captures(lines)) {
+        Some(captures) => {
+            let mut s = Vec::new();
+            captures.expand(&replacement, &mut s);
+            s
+        }
+        None => replacement,
+    }
+}
+
 #[derive(Debug, Clone)]
 struct CachedLineCount {
     list_width: u16,
+    wrap_mode: WrapMode,
+    show_line_numbers: bool,
+    tab_width: usize,
     value: usize,
 }
 
+/// Returns the width available to each wrapped continuation row.
+fn continuation_width(list_width: usize, available_width: usize, wrap_mode: WrapMode) -> usize {
+    match wrap_mode {
+        WrapMode::Wrap | WrapMode::NoWrap => available_width,
+        WrapMode::WrapIndent => list_width.saturating_sub(WRAP_INDENT_MARKER.width()),
+    }
+}
+
+/// Whitespace as it's rendered by each `PrintableStyle` (spaces and tabs, or the symbols shown for them).
+const TRAILING_WHITESPACE: &[char] = &[' ', '\t', '␣', '→', '␠', '␉'];
+/// The symbols shown for line endings by some `PrintableStyle`s, which may follow trailing whitespace.
+const LINE_ENDINGS: &[char] = &['¬', '¤', '␊', '␍'];
+
+/// Expands the tabs which are left in the lines (by `PrintableStyle::Hidden`) to the next tab stop. The columns are
+/// counted from the start of the text on each line, after its line number.
+fn expand_span_tabs<'a>(
+    span_lines: Vec<Vec<Span<'a>>>,
+    tab_width: usize,
+    has_line_number: impl Fn(usize) -> bool,
+) -> Vec<Vec<Span<'a>>> {
+    span_lines
+        .into_iter()
+        .enumerate()
+        .map(|(i, spans)| {
+            let skip = usize::from(has_line_number(i));
+            let mut column = 0;
+            spans
+                .into_iter()
+                .enumerate()
+                .map(|(j, span)| {
+                    if j < skip {
+                        span
+                    } else if span.content.contains('\t') {
+                        Span::styled(
+                            expand_tabs(&span.content, tab_width, &mut column),
+                            span.style,
+                        )
+                    } else {
+                        column += span.width();
+                        span
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Gives the trailing whitespace of each line a subtle background, so it stands out whichever `PrintableStyle`
+/// is used. Spans which already have a background (such as submatches) keep it.
+fn highlight_trailing_whitespace<'a>(
+    span_lines: Vec<Vec<Span<'a>>>,
+    has_line_number: impl Fn(usize) -> bool,
+) -> Vec<Vec<Span<'a>>> {
+    span_lines
+        .into_iter()
+        .enumerate()
+        .map(|(i, spans)| {
+            let skip = usize::from(has_line_number(i));
+            let text = spans
+                .iter()
+                .skip(skip)
+                .map(|span| span.content.as_ref())
+                .collect::<String>();
+            let end = text.trim_end_matches(LINE_ENDINGS).len();
+            let start = text[..end].trim_end_matches(TRAILING_WHITESPACE).len();
+            if start == end {
+                return spans;
+            }
+
+            let mut pos = 0;
+            let mut new_spans = vec![];
+            for (j, span) in spans.into_iter().enumerate() {
+                if j < skip {
+                    new_spans.push(span);
+                    continue;
+                }
+
+                let span_range = pos..pos + span.content.len();
+                pos = span_range.end;
+                if span_range.end <= start || span_range.start >= end {
+                    new_spans.push(span);
+                    continue;
+                }
+
+                // split the span where the trailing whitespace starts and ends
+                let ws_start = start.max(span_range.start) - span_range.start;
+                let ws_end = end.min(span_range.end) - span_range.start;
+                let ws_style = match span.style.bg {
+                    Some(_) => span.style,
+                    None => span.style.bg(Color::DarkGray),
+                };
+                let content = span.content.as_ref();
+                for (part, style) in [
+                    (&content[..ws_start], span.style),
+                    (&content[ws_start..ws_end], ws_style),
+                    (&content[ws_end..], span.style),
+                ] {
+                    if !part.is_empty() {
+                        new_spans.push(Span::styled(part.to_owned(), style));
+                    }
+                }
+            }
+
+            new_spans
+        })
+        .collect()
+}
+
+/// Emphasises the characters in `span_lines` which fuzzy match `query`. The first span of each line for which
+/// `has_line_number` returns true is a line number, and isn't matched against.
+fn highlight_fuzzy_match<'a>(
+    span_lines: Vec<Vec<Span<'a>>>,
+    query: &str,
+    case_sensitive: bool,
+    has_line_number: impl Fn(usize) -> bool,
+) -> Vec<Vec<Span<'a>>> {
+    let text = span_lines
+        .iter()
+        .enumerate()
+        .flat_map(|(i, spans)| spans.iter().skip(usize::from(has_line_number(i))))
+        .map(|span| span.content.as_ref())
+        .collect::<String>();
+
+    let ranges = match fuzzy_match(query, &text, case_sensitive) {
+        Some(m) => highlight_ranges(&m.positions),
+        None => return span_lines,
+    };
+
+    let mut pos = 0;
+    span_lines
+        .into_iter()
+        .enumerate()
+        .map(|(i, spans)| {
+            let skip = usize::from(has_line_number(i));
+            let mut new_spans = vec![];
+            for (j, span) in spans.into_iter().enumerate() {
+                if j < skip {
+                    new_spans.push(span);
+                    continue;
+                }
+
+                let style_of = |highlighted: bool| match highlighted {
+                    true => span
+                        .style
+                        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                    false => span.style,
+                };
+
+                let mut part = String::new();
+                let mut part_highlighted = false;
+                for ch in span.content.chars() {
+                    let highlighted = ranges.iter().any(|r| r.contains(&pos));
+                    if highlighted != part_highlighted && !part.is_empty() {
+                        new_spans.push(Span::styled(
+                            std::mem::take(&mut part),
+                            style_of(part_highlighted),
+                        ));
+                    }
+
+                    part_highlighted = highlighted;
+                    part.push(ch);
+                    pos += 1;
+                }
+
+                new_spans.push(Span::styled(part, style_of(part_highlighted)));
+            }
+
+            new_spans
+        })
+        .collect()
+}
+
+/// Returns the (1-based) row the character at `pos` will be on once the line is wrapped.
+fn wrapped_row_of(pos: usize, first_row_width: usize, other_row_width: usize) -> usize {
+    if pos < first_row_width {
+        1
+    } else {
+        2 + (pos - first_row_width) / other_row_width
+    }
+}
+
+/// Returns how many rows a line of `line_width` will take up once it's wrapped.
+fn wrapped_row_count(line_width: usize, first_row_width: usize, other_row_width: usize) -> usize {
+    if line_width <= first_row_width {
+        1
+    } else {
+        let remaining = line_width - first_row_width;
+        1 + (remaining + other_row_width - 1) / other_row_width
+    }
+}
+
+/// Returns the portion of `spans` which is visible when scrolled `h_scroll` columns to the right
+/// in a view `max_width` columns wide. If any text is clipped on either side, then the column at
+/// that edge is replaced with a marker.
+fn scroll_spans(spans: Vec<Span>, h_scroll: usize, max_width: usize) -> Vec<Span> {
+    let line_width = spans.iter().map(|s| s.width()).sum::<usize>();
+    let clipped_left = h_scroll > 0 && line_width > 0;
+    let clipped_right = line_width > h_scroll + max_width;
+
+    // the range of columns which are rendered (after making room for any markers)
+    let start = h_scroll + usize::from(clipped_left);
+    let end = (h_scroll + max_width).saturating_sub(usize::from(clipped_right));
+
+    let marker_style = Style::default().fg(Color::DarkGray);
+    let mut scrolled_spans = vec![];
+    if clipped_left {
+        scrolled_spans.push(Span::styled(NO_WRAP_CLIP_MARKER, marker_style));
+    }
+
+    let mut col = 0;
+    for span in spans {
+        let mut content = String::new();
+        for ch in span.content.chars() {
+            // NOTE: all control characters (except "\n") should have been removed via the `Printable` trait
+            // and "\n" should have been removed when building Spans from the item, but if any slip through they're
+            // treated as zero-width rather than panicking
+            let char_width = ch.width().unwrap_or(0);
+            if col >= start && col + char_width <= end {
+                content.push(ch);
+            }
+
+            col += char_width;
+        }
+
+        if !content.is_empty() {
+            scrolled_spans.push(Span::styled(content, span.style));
+        }
+    }
+
+    if clipped_right {
+        scrolled_spans.push(Span::styled(NO_WRAP_CLIP_MARKER, marker_style));
+    }
+
+    scrolled_spans
+}
+
+/// Shortens `path` to fit in `max_width` columns by replacing the middle of it with an ellipsis, e.g.
+/// `src/…/item.rs`. The file name is kept, and as many of the leading directories as fit.
+fn truncate_path_middle(path: &str, max_width: usize) -> String {
+    if path.width() <= max_width {
+        return path.to_owned();
+    }
+
+    let separators = ['/', '\\'];
+    let (dir, name) = path.split_at(path.rfind(separators).unwrap_or(0));
+    let budget = max_width.saturating_sub(name.width() + PATH_ELLIPSIS.width());
+
+    // the file name doesn't fit on its own, so only its end is shown
+    if name.width() + PATH_ELLIPSIS.width() > max_width {
+        let mut width = 0;
+        let mut tail = name
+            .chars()
+            .rev()
+            .take_while(|ch| {
+                width += ch.width().unwrap_or(0);
+                width + PATH_ELLIPSIS.width() <= max_width
+            })
+            .collect::<Vec<_>>();
+        tail.reverse();
+        return match max_width {
+            0 => String::new(),
+            _ => format!("{}{}", PATH_ELLIPSIS, tail.into_iter().collect::<String>()),
+        };
+    }
+
+    // keep whole directories if any fit, otherwise as much of the first one as fits
+    let head = dir
+        .match_indices(separators)
+        .map(|(i, sep)| &dir[..i + sep.len()])
+        .take_while(|head| head.width() <= budget)
+        .last()
+        .map(String::from)
+        .unwrap_or_else(|| {
+            let mut width = 0;
+            dir.chars()
+                .take_while(|ch| {
+                    width += ch.width().unwrap_or(0);
+                    width <= budget
+                })
+                .collect()
+        });
+
+    format!("{}{}{}", head, PATH_ELLIPSIS, name)
+}
+
+/// Shown after the path of binary files.
+const BINARY_MARKER: &str = "[binary]";
+/// Shown after matches whose line ripgrep would have truncated (`--max-columns`).
+const TRUNCATED_MARKER: &str = "[truncated]";
+
 #[derive(Debug, Clone)]
 pub struct Item {
     pub index: usize,
@@ -25,6 +336,17 @@ pub struct Item {
     rg_message: RgMessage,
 
     sub_items: Vec<SubItem>,
+    /// Set when this item's file can't be written to.
+    read_only: bool,
+    /// Set when this item's file is binary, so it won't be written to unless `--allow-binary` was passed.
+    binary: bool,
+    /// Set when this match's line is longer than `--max-columns`, so its offsets may not match the file and it
+    /// won't be replaced.
+    truncated: bool,
+    /// Set when this item doesn't directly follow the previous line in its file.
+    context_separator: bool,
+    /// Set on the `Begin` item of the first file found in each path `rg` searched, when there were several.
+    root_header: Option<String>,
 
     cached_line_count: Option<CachedLineCount>,
 }
@@ -37,6 +359,11 @@ impl PartialEq for Item {
             && self.kind == other.kind
             && self.rg_message == other.rg_message
             && self.sub_items == other.sub_items
+            && self.read_only == other.read_only
+            && self.binary == other.binary
+            && self.truncated == other.truncated
+            && self.context_separator == other.context_separator
+            && self.root_header == other.root_header
     }
 }
 impl Eq for Item {}
@@ -65,6 +392,11 @@ impl Item {
             kind,
             rg_message,
             sub_items,
+            read_only: false,
+            binary: false,
+            truncated: false,
+            context_separator: false,
+            root_header: None,
             cached_line_count: None,
         }
     }
@@ -77,6 +409,14 @@ impl Item {
         self.sub_items[idx].should_replace = should_replace
     }
 
+    /// Sets the text which replaces the submatch at `idx` instead of the replacement text used for the other
+    /// matches. The submatch is selected, so it'll be replaced.
+    pub fn set_replacement_override(&mut self, idx: usize, replacement: Vec<u8>) {
+        let sub_item = &mut self.sub_items[idx];
+        sub_item.should_replace = true;
+        sub_item.replacement_override = Some(replacement);
+    }
+
     pub fn get_should_replace_all(&self) -> bool {
         self.sub_items.iter().all(|s| s.should_replace)
     }
@@ -93,6 +433,72 @@ impl Item {
         }
     }
 
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.binary
+    }
+
+    pub fn set_binary(&mut self, binary: bool) {
+        self.binary = binary;
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn set_truncated(&mut self, truncated: bool) {
+        self.truncated = truncated;
+    }
+
+    /// Whether ripgrep would truncate any of this match's lines when run with `--max-columns=max_columns`. Like
+    /// ripgrep, this counts the bytes of each line, without its line terminator.
+    pub fn exceeds_max_columns(&self, max_columns: u64) -> bool {
+        match &self.rg_message {
+            RgMessage::Match { lines, .. } => lines.to_vec().split(|b| *b == b'\n').any(|line| {
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                line.len() as u64 > max_columns
+            }),
+            _ => false,
+        }
+    }
+
+    /// Whether this item shows that its file is binary: either ripgrep found binary data in it (the `End`
+    /// message has a `binary_offset`), or its lines aren't valid UTF-8 (so ripgrep base64 encoded them).
+    pub fn has_binary_data(&self) -> bool {
+        match &self.rg_message {
+            RgMessage::End { binary_offset, .. } => binary_offset.is_some(),
+            RgMessage::Match { lines, .. } | RgMessage::Context { lines, .. } => {
+                matches!(lines, ArbitraryData::Base64 { .. })
+            }
+            _ => false,
+        }
+    }
+
+    pub fn has_context_separator(&self) -> bool {
+        self.context_separator
+    }
+
+    pub fn set_context_separator(&mut self, context_separator: bool) {
+        self.context_separator = context_separator;
+        self.cached_line_count = None;
+    }
+
+    pub fn root_header(&self) -> Option<&str> {
+        self.root_header.as_deref()
+    }
+
+    pub fn set_root_header(&mut self, root_header: Option<String>) {
+        self.root_header = root_header;
+        self.cached_line_count = None;
+    }
+
     pub fn is_selectable(&self) -> bool {
         matches!(self.kind, RgMessageKind::Begin | RgMessageKind::Match)
     }
@@ -119,6 +525,10 @@ impl Item {
         self.sub_items.iter().filter(|s| s.should_replace).count()
     }
 
+    pub fn rg_message(&self) -> &RgMessage {
+        &self.rg_message
+    }
+
     pub fn sub_items(&self) -> &[SubItem] {
         &self.sub_items
     }
@@ -133,6 +543,13 @@ impl Item {
         }
     }
 
+    pub fn lines(&self) -> Option<&ArbitraryData> {
+        match &self.rg_message {
+            RgMessage::Match { lines, .. } | RgMessage::Context { lines, .. } => Some(lines),
+            _ => None,
+        }
+    }
+
     pub fn path_buf(&self) -> Option<PathBuf> {
         self.path().and_then(|data| data.to_path_buf().ok())
     }
@@ -142,9 +559,14 @@ impl Item {
         match_idx: usize,
         list_width: u16,
         style: PrintableStyle,
+        wrap_mode: WrapMode,
+        show_line_numbers: bool,
+        tab_width: usize,
     ) -> usize {
         match &self.rg_message {
-            RgMessage::Begin { .. } | RgMessage::End { .. } => 0,
+            // the file's path is on the row after its root's header
+            RgMessage::Begin { .. } => 1 + usize::from(self.root_header.is_some()),
+            RgMessage::End { .. } => 0,
             RgMessage::Match { lines, .. } | RgMessage::Context { lines, .. } => {
                 let list_width = list_width as usize;
                 let line_number = self.line_number().unwrap();
@@ -166,31 +588,49 @@ impl Item {
                     .iter()
                     .enumerate()
                     .map(|(i, line)| {
-                        let available_width =
-                            list_width.saturating_sub(format_line_number!(line_number + i).width());
-                        let line_width = line.width();
-                        let height = line_width / available_width;
-                        if line_width % available_width == 0 && i == lines.len() + 1 {
-                            height
-                        } else {
-                            height + 1
+                        if wrap_mode == WrapMode::NoWrap {
+                            return 1;
                         }
+
+                        let available_width = if show_line_numbers {
+                            list_width.saturating_sub(format_line_number!(line_number + i).width())
+                        } else {
+                            list_width
+                        };
+                        wrapped_row_of(
+                            expand_tabs(line, tab_width, &mut 0).width(),
+                            available_width,
+                            continuation_width(list_width, available_width, wrap_mode),
+                        )
                     })
                     .sum::<usize>()
+                    + usize::from(self.context_separator)
             }
             RgMessage::Summary { .. } => 0,
         }
     }
 
-    pub fn line_count(&mut self, list_width: u16, style: PrintableStyle) -> usize {
+    pub fn line_count(
+        &mut self,
+        list_width: u16,
+        style: PrintableStyle,
+        wrap_mode: WrapMode,
+        show_line_numbers: bool,
+        tab_width: usize,
+    ) -> usize {
         if let Some(cache) = &self.cached_line_count {
-            if cache.list_width == list_width {
+            if cache.list_width == list_width
+                && cache.wrap_mode == wrap_mode
+                && cache.show_line_numbers == show_line_numbers
+                && cache.tab_width == tab_width
+            {
                 return cache.value;
             }
         }
 
         let count = match &self.rg_message {
-            RgMessage::Begin { .. } | RgMessage::End { .. } => 1,
+            RgMessage::Begin { .. } => 1 + usize::from(self.root_header.is_some()),
+            RgMessage::End { .. } => 1,
             RgMessage::Match { lines, .. } | RgMessage::Context { lines, .. } => {
                 let list_width = list_width as usize;
                 let line_number = self.line_number().unwrap();
@@ -200,65 +640,170 @@ impl Item {
                     .iter()
                     .enumerate()
                     .map(|(i, line)| {
-                        let available_width =
-                            list_width.saturating_sub(format_line_number!(line_number + i).width());
-                        let line_width = line.width();
-                        let height = line_width / available_width;
-                        if line_width > 0 && line_width % available_width == 0 {
-                            height
-                        } else {
-                            height + 1
+                        if wrap_mode == WrapMode::NoWrap {
+                            return 1;
                         }
+
+                        let available_width = if show_line_numbers {
+                            list_width.saturating_sub(format_line_number!(line_number + i).width())
+                        } else {
+                            list_width
+                        };
+                        wrapped_row_count(
+                            expand_tabs(line, tab_width, &mut 0).width(),
+                            available_width,
+                            continuation_width(list_width, available_width, wrap_mode),
+                        )
                     })
                     .sum::<usize>()
+                    + usize::from(self.context_separator)
             }
             RgMessage::Summary { .. } => 0,
         };
 
         self.cached_line_count = Some(CachedLineCount {
             list_width,
+            wrap_mode,
+            show_line_numbers,
+            tab_width,
             value: count,
         });
         count
     }
 
-    pub fn to_span_lines(&self, ctx: &UiItemContext) -> Vec<Line> {
+    /// Returns the lines of a `Match` item as plain text, with the submatches which will be replaced replaced by
+    /// `replacement` (as `to_span_lines` shows them when replacing). The trailing line ending is removed.
+    pub fn replaced_text(
+        &self,
+        capture_pattern: Option<&Regex>,
+        replacement: &str,
+    ) -> Option<String> {
+        let lines = match &self.rg_message {
+            RgMessage::Match { lines, .. } => lines.to_vec(),
+            _ => return None,
+        };
+
+        let replacement = expand_replacement(capture_pattern, replacement, &lines);
+        let mut text = vec![];
+        let mut offset = 0;
+        for sub_item in &self.sub_items {
+            let Range { start, end } = sub_item.sub_match.range;
+            text.extend_from_slice(&lines[offset..start]);
+            if sub_item.should_replace {
+                match &sub_item.replacement_override {
+                    Some(replacement) => text.extend_from_slice(replacement),
+                    None => text.extend_from_slice(&replacement),
+                }
+            } else {
+                text.extend_from_slice(&lines[start..end]);
+            }
+            offset = end;
+        }
+        text.extend_from_slice(&lines[offset..]);
+
+        let mut text = String::from_utf8_lossy(&text).into_owned();
+        if text.ends_with('\n') {
+            text.pop();
+            if text.ends_with('\r') {
+                text.pop();
+            }
+        }
+
+        Some(text)
+    }
+
+    pub fn to_span_lines(&self, ctx: &UiItemContext) -> Vec<Line<'_>> {
         let is_replacing = ctx.app_ui_state.is_replacing();
         let is_selected = ctx.app_list_state.selected_item() == self.index;
+        // in the VISUAL mode, the items between the anchor and the selected item are highlighted
+        let is_in_visual_range = match ctx.app_ui_state {
+            AppUiState::VisualSelect(anchor) => {
+                let selected_item = ctx.app_list_state.selected_item();
+                (*anchor.min(&selected_item)..=*anchor.max(&selected_item)).contains(&self.index)
+            }
+            _ => false,
+        };
 
         let mut base_style = Style::default();
         if !is_replacing && is_selected {
             base_style = base_style.fg(Color::Yellow);
         }
 
-        // pushes a span to `spans` which contains the given line number content
+        // pushes a span to `spans` which contains the given line number content (unless they're hidden)
         macro_rules! push_line_number_span {
             ($spans:expr, $content:expr) => {{
-                let mut line_number_style = base_style;
-                if !is_selected || is_replacing {
-                    line_number_style = line_number_style.fg(Color::DarkGray);
-                }
+                if ctx.show_line_numbers {
+                    let mut line_number_style = base_style;
+                    if is_in_visual_range {
+                        line_number_style = line_number_style.fg(Color::Black).bg(Color::Yellow);
+                    } else if !is_selected || is_replacing {
+                        line_number_style = line_number_style.fg(Color::DarkGray);
+                    }
 
-                $spans.push(Span::styled(
-                    format_line_number!($content),
-                    line_number_style,
-                ));
+                    $spans.push(Span::styled(
+                        format_line_number!($content),
+                        line_number_style,
+                    ));
+                }
             }};
         }
 
-        let span_lines = match &self.rg_message {
-            RgMessage::Begin { .. } => vec![vec![Span::styled(
-                format!("{}", self.path_buf().unwrap().display()).to_printable(ctx.printable_style),
-                if !is_replacing && is_selected {
-                    base_style.fg(Color::Black).bg(Color::Yellow)
+        let mut span_lines = match &self.rg_message {
+            RgMessage::Begin { .. } => {
+                // long paths are shortened so the header fits on one row, leaving room for the binary marker
+                let max_width = (ctx.list_rect.width as usize).saturating_sub(if self.binary {
+                    BINARY_MARKER.width() + 1
                 } else {
-                    base_style.fg(Color::Magenta)
-                },
-            )]],
+                    0
+                });
+                let path = format!("{}", self.path_buf().unwrap().display())
+                    .to_printable(ctx.printable_style);
+                let mut spans = vec![Span::styled(
+                    truncate_path_middle(&path, max_width),
+                    match (!is_replacing && is_selected, self.read_only) {
+                        (true, false) => base_style.fg(Color::Black).bg(Color::Yellow),
+                        (true, true) => base_style.fg(Color::Black).bg(Color::Red),
+                        (false, false) => base_style.fg(Color::Magenta),
+                        // files which can't be written are highlighted, since they will be skipped
+                        (false, true) => base_style.fg(Color::Red),
+                    },
+                )];
+
+                // binary files are marked, since replacing in them may corrupt them
+                if self.binary {
+                    spans.push(Span::raw(" "));
+                    spans.push(Span::styled(
+                        BINARY_MARKER,
+                        base_style
+                            .fg(Color::Black)
+                            .bg(Color::LightRed)
+                            .add_modifier(Modifier::BOLD),
+                    ));
+                }
+
+                match &self.root_header {
+                    Some(root) => vec![
+                        vec![Span::styled(
+                            truncate_path_middle(root, ctx.list_rect.width as usize),
+                            Style::default()
+                                .fg(Color::Blue)
+                                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                        )],
+                        spans,
+                    ],
+                    None => vec![spans],
+                }
+            }
 
             RgMessage::Context {
                 lines, line_number, ..
             } => {
+                // with `--passthru` most of the lines are context, so they're dimmed to keep the matches easy to find
+                let style = match ctx.passthru {
+                    true => base_style.fg(Color::DarkGray),
+                    false => base_style,
+                };
+
                 let mut span_lines = vec![];
                 for (i, line) in lines.to_printable(ctx.printable_style).lines().enumerate() {
                     let mut spans = vec![];
@@ -268,7 +813,7 @@ impl Item {
                         }
                     }
 
-                    spans.push(Span::styled(line.to_string(), base_style));
+                    spans.push(Span::styled(line.to_string(), style));
                     span_lines.push(spans);
                 }
 
@@ -282,19 +827,9 @@ impl Item {
 
                 // Read the lines as bytes since we split it at the byte ranges that ripgrep gives us in each of the submatches.
                 let lines_bytes = lines.to_vec();
-                let replacement_spans = ctx.replacement_text.map(|user| {
-                    let user = user.as_bytes().to_vec();
-                    let text = match ctx.capture_pattern.and_then(|re| re.captures(&lines_bytes)) {
-                        Some(captures) => {
-                            let mut s = Vec::new();
-                            captures.expand(&user, &mut s);
-                            s
-                        }
-                        None => user,
-                    };
-
+                let to_replacement_spans = |text: &[u8]| {
                     let replacement_style = base_style.fg(Color::Green);
-                    let mut spans = text
+                    let mut spans = String::from_utf8_lossy(text)
                         .to_printable(ctx.printable_style)
                         .lines()
                         .map(|line| Span::styled(line.to_owned(), replacement_style))
@@ -307,6 +842,13 @@ impl Item {
                     }
 
                     spans
+                };
+                let replacement_spans = ctx.replacement_text.map(|user| {
+                    to_replacement_spans(&expand_replacement(
+                        ctx.capture_pattern,
+                        user,
+                        &lines_bytes,
+                    ))
                 });
 
                 let mut span_lines = vec![];
@@ -314,14 +856,25 @@ impl Item {
 
                 macro_rules! push_utf8_slice {
                     ($range:ident) => {{
+                        let is_trailing = $range.end == lines_bytes.len();
                         let mut content = String::from_utf8_lossy(&lines_bytes[$range]).to_printable(ctx.printable_style);
                         // remove trailing new line if one exists since lines are already handled
-                        if content.ends_with("\n") {
+                        if is_trailing && content.ends_with("\n") {
                             content.pop();
                         }
-                        // NOTE: don't handle multiple lines in the match because AFAICT ripgrep doesn't return multiline
-                        // text in between submatches in a "match" item.
-                        spans.push(Span::styled(content, base_style));
+                        // When `--multiline` is used the text in between submatches may span multiple lines, so each
+                        // of them needs to be on its own line (with its own line number).
+                        for (i, part) in content.split('\n').enumerate() {
+                            if i > 0 {
+                                span_lines.push(spans.drain(..).collect::<Vec<Span>>());
+                                if let Some(n) = line_number.as_mut() {
+                                    *n += 1;
+                                    push_line_number_span!(spans, n);
+                                }
+                            }
+
+                            spans.push(Span::styled(part.to_string(), base_style));
+                        }
                     }}
                 }
 
@@ -336,6 +889,10 @@ impl Item {
                     };
                 }
 
+                let show_submatch_badge = is_selected
+                    && self.sub_items.len() > 1
+                    && matches!(ctx.app_ui_state, AppUiState::SelectMatches);
+
                 let mut offset = 0;
                 for (idx, sub_item) in self.sub_items.iter().enumerate() {
                     let Range { start, end } = sub_item.sub_match.range;
@@ -356,7 +913,7 @@ impl Item {
                     // Match text, also may contain any leading line numbers and text from before.
                     let confirm_replacement =
                         matches!(ctx.app_ui_state, AppUiState::ConfirmReplacement(_, _));
-                    if !confirm_replacement || !sub_item.should_replace {
+                    if !confirm_replacement || !sub_item.should_replace || self.truncated {
                         let sub_span_lines = sub_item.to_span_lines(ctx, is_selected);
                         let sub_span_lines_len = sub_span_lines.len();
                         for (i, span) in sub_span_lines.into_iter().enumerate() {
@@ -374,9 +931,24 @@ impl Item {
                         }
                     }
 
-                    // Replacement text.
-                    if sub_item.should_replace {
-                        if let Some(replacement_span_lines) = replacement_spans.as_ref() {
+                    // when there are several submatches in the line, show which of them the cursor is on
+                    if show_submatch_badge && ctx.app_list_state.selected_submatch() == idx {
+                        spans.push(Span::styled(
+                            format!("[{}/{}]", idx + 1, self.sub_items.len()),
+                            base_style.fg(Color::Cyan).add_modifier(Modifier::BOLD),
+                        ));
+                    }
+
+                    // Replacement text (or the text chosen for just this submatch). Truncated lines aren't replaced.
+                    if sub_item.should_replace && !self.truncated {
+                        let override_spans = sub_item
+                            .replacement_override
+                            .as_deref()
+                            .filter(|_| is_replacing)
+                            .map(&to_replacement_spans);
+                        if let Some(replacement_span_lines) =
+                            override_spans.as_ref().or(replacement_spans.as_ref())
+                        {
                             for (i, span) in replacement_span_lines.iter().enumerate() {
                                 if i == 0 {
                                     // reset the line number
@@ -401,6 +973,18 @@ impl Item {
                     push_utf8_slice!(trailing);
                 }
 
+                // lines which ripgrep truncated are marked, since they won't be replaced
+                if self.truncated {
+                    spans.push(Span::raw(" "));
+                    spans.push(Span::styled(
+                        TRUNCATED_MARKER,
+                        base_style
+                            .fg(Color::Black)
+                            .bg(Color::LightRed)
+                            .add_modifier(Modifier::BOLD),
+                    ));
+                }
+
                 span_lines.push(spans);
                 span_lines
             }
@@ -409,11 +993,74 @@ impl Item {
             RgMessage::Summary { .. } => unreachable!(),
         };
 
+        let has_line_number = ctx.show_line_numbers && self.line_number().is_some();
+        span_lines = match self.kind {
+            RgMessageKind::Context => {
+                expand_span_tabs(span_lines, ctx.tab_width, |i| i == 0 && has_line_number)
+            }
+            _ => expand_span_tabs(span_lines, ctx.tab_width, |_| has_line_number),
+        };
+
+        if let Some(query) = ctx.fuzzy_query.filter(|_| !is_replacing) {
+            let has_line_number = ctx.show_line_numbers && self.line_number().is_some();
+            let case_sensitive = ctx.fuzzy_case_sensitive;
+            span_lines = match self.kind {
+                RgMessageKind::Match => {
+                    highlight_fuzzy_match(span_lines, query, case_sensitive, |_| has_line_number)
+                }
+                RgMessageKind::Context => {
+                    highlight_fuzzy_match(span_lines, query, case_sensitive, |i| {
+                        i == 0 && has_line_number
+                    })
+                }
+                _ => span_lines,
+            };
+        }
+
+        let has_line_number = ctx.show_line_numbers && self.line_number().is_some();
+        span_lines = match self.kind {
+            RgMessageKind::Match => highlight_trailing_whitespace(span_lines, |_| has_line_number),
+            RgMessageKind::Context => {
+                highlight_trailing_whitespace(span_lines, |i| i == 0 && has_line_number)
+            }
+            _ => span_lines,
+        };
+
+        if self.context_separator {
+            span_lines.insert(
+                0,
+                vec![Span::styled(
+                    CONTEXT_SEPARATOR,
+                    Style::default().fg(Color::DarkGray),
+                )],
+            );
+        }
+
         let max_width = ctx.list_rect.width as usize;
-        Self::wrap_span_lines(span_lines, max_width)
+        match ctx.wrap_mode {
+            WrapMode::NoWrap => span_lines
+                .into_iter()
+                .map(|spans| Line::from(scroll_spans(spans, ctx.h_scroll, max_width)))
+                .collect(),
+            WrapMode::Wrap | WrapMode::WrapIndent => {
+                Self::wrap_span_lines(span_lines, max_width, ctx.wrap_mode)
+            }
+        }
     }
 
-    fn wrap_span_lines(span_lines: Vec<Vec<Span>>, max_width: usize) -> Vec<Line> {
+    fn wrap_span_lines(
+        span_lines: Vec<Vec<Span>>,
+        max_width: usize,
+        wrap_mode: WrapMode,
+    ) -> Vec<Line> {
+        let indent = match wrap_mode {
+            WrapMode::Wrap | WrapMode::NoWrap => None,
+            WrapMode::WrapIndent => Some(Span::styled(
+                WRAP_INDENT_MARKER,
+                Style::default().fg(Color::DarkGray),
+            )),
+        };
+
         span_lines
             .into_iter()
             .flat_map(|spans| {
@@ -426,17 +1073,22 @@ impl Item {
                         let mut chars = vec![];
                         for ch in span.content.chars() {
                             // NOTE: all control characters (except "\n") should have been removed via the `Printable` trait
-                            // and "\n" should have been removed when building Spans from the item
-                            let char_width = ch.width().expect(
-                                "encountered unexpected control character while wrapping lines",
-                            );
+                            // and "\n" should have been removed when building Spans from the item, but if any slip
+                            // through they're treated as zero-width rather than panicking. Zero-width characters (such
+                            // as combining marks) never start a new row, so they stay with the character before them
+                            let char_width = ch.width().unwrap_or(0);
                             if len + char_width > max_width {
                                 tmp.push(Span::styled(
                                     chars.drain(..).collect::<String>(),
                                     span.style,
                                 ));
-                                wrapped_spans.push(Line::from(tmp.drain(..).collect::<Vec<_>>()));
+                                wrapped_spans.push(Line::from(std::mem::take(&mut tmp)));
                                 len = 0;
+
+                                if let Some(indent) = &indent {
+                                    len += indent.width();
+                                    tmp.push(indent.clone());
+                                }
                             }
 
                             len += char_width;
@@ -452,7 +1104,7 @@ impl Item {
                     }
                 }
 
-                wrapped_spans.push(Line::from(tmp.drain(..).collect::<Vec<_>>()));
+                wrapped_spans.push(Line::from(std::mem::take(&mut tmp)));
                 wrapped_spans
             })
             .collect()
@@ -467,8 +1119,11 @@ mod tests {
     use insta::assert_debug_snapshot;
     use pretty_assertions::assert_eq;
     use ratatui::layout::Rect;
+    use ratatui::style::{Color, Style};
+    use ratatui::text::Span;
     use regex::bytes::Regex;
 
+    use super::{scroll_spans, truncate_path_middle};
     use crate::model::*;
     use crate::rg::de::test_utilities::*;
     use crate::rg::de::*;
@@ -507,6 +1162,38 @@ mod tests {
         assert_eq!(new_item(RG_JSON_SUMMARY).sub_items().len(), 0);
     }
 
+    #[test]
+    fn replaced_text() {
+        let mut item = new_item(RG_JSON_MATCH);
+        assert_eq!(
+            item.replaced_text(None, "X").as_deref(),
+            Some("    X::new(X)")
+        );
+
+        // only the submatches which will be replaced are
+        item.set_should_replace(0, false);
+        assert_eq!(
+            item.replaced_text(None, "X").as_deref(),
+            Some("    Item::new(X)")
+        );
+
+        // capturing groups are expanded
+        let re = Regex::new(r"(\w+)::new").unwrap();
+        assert_eq!(
+            item.replaced_text(Some(&re), "<$1>").as_deref(),
+            Some("    Item::new(<Item>)")
+        );
+
+        let item = new_item(RG_JSON_MATCH_MULTILINE);
+        assert_eq!(
+            item.replaced_text(None, "_").as_deref(),
+            Some("baz _ bar _")
+        );
+
+        assert_eq!(new_item(RG_JSON_CONTEXT).replaced_text(None, "X"), None);
+        assert_eq!(new_item(RG_JSON_BEGIN).replaced_text(None, "X"), None);
+    }
+
     #[test]
     fn sub_items() {
         assert_eq!(new_item(RG_JSON_BEGIN).sub_items(), &[]);
@@ -569,7 +1256,7 @@ mod tests {
             Item::new(
                 0,
                 RgMessageBuilder::new(kind)
-                    .with_path_base64(base64.encode_to_string(&invalid_utf8_name_bytes))
+                    .with_path_base64(base64.encode_to_string(invalid_utf8_name_bytes))
                     .with_lines_text("foo bar baz")
                     .with_submatches(vec![SubMatch::new_text("foo", 0..3)])
                     .with_stats(Stats::new())
@@ -622,10 +1309,17 @@ mod tests {
         UiItemContext {
             capture_pattern: None,
             printable_style: PrintableStyle::Hidden,
+            wrap_mode: WrapMode::Wrap,
+            show_line_numbers: true,
+            passthru: false,
+            tab_width: DEFAULT_TAB_WIDTH,
+            h_scroll: 0,
             replacement_text,
             app_list_state,
             app_ui_state,
             list_rect: Rect::new(0, 0, 80, 24),
+            fuzzy_query: None,
+            fuzzy_case_sensitive: false,
         }
     }
 
@@ -733,6 +1427,17 @@ mod tests {
         assert_debug_snapshot!(item.to_span_lines(&ctx));
     }
 
+    #[test]
+    fn to_span_lines_with_submatch_badge() {
+        let mut app_list_state = new_app_list_state();
+        app_list_state.set_selected_item(0);
+        app_list_state.set_selected_submatch(1);
+        let app_ui_state = AppUiState::SelectMatches;
+        let ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+
+        assert_debug_snapshot!(new_item(RG_JSON_MATCH).to_span_lines(&ctx));
+    }
+
     #[test]
     fn to_span_lines_with_deselected_submatch_input_replacement() {
         let mut app_list_state = new_app_list_state();
@@ -831,6 +1536,140 @@ mod tests {
         assert_debug_snapshot!(new_item(RG_JSON_MATCH).to_span_lines(&ctx));
     }
 
+    #[test]
+    fn to_span_lines_context_separator() {
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+
+        let mut item = new_item(RG_JSON_MATCH);
+        item.set_context_separator(true);
+        assert_debug_snapshot!(item.to_span_lines(&ctx));
+    }
+
+    #[test]
+    fn to_span_lines_passthru() {
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let mut ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        let item = new_item(RG_JSON_CONTEXT);
+        let text_style = |ctx: &UiItemContext| item.to_span_lines(ctx)[0].spans[1].style;
+
+        assert_eq!(text_style(&ctx).fg, None);
+        ctx.passthru = true;
+        assert_eq!(text_style(&ctx).fg, Some(Color::DarkGray));
+    }
+
+    #[test]
+    fn to_span_lines_fuzzy_query() {
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let mut ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        ctx.fuzzy_query = Some("Imsg");
+
+        assert_debug_snapshot!(new_item(RG_JSON_MATCH).to_span_lines(&ctx));
+    }
+
+    #[test]
+    fn to_span_lines_fuzzy_query_no_match() {
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let mut ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        let item = new_item(RG_JSON_MATCH);
+        let expected = item.to_span_lines(&ctx);
+
+        ctx.fuzzy_query = Some("zzz");
+        assert_eq!(item.to_span_lines(&ctx), expected);
+    }
+
+    #[test]
+    fn to_span_lines_trailing_whitespace() {
+        use crate::rg::de::test_utilities::RgMessageBuilder;
+
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let mut ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        let new_item = |kind, submatches| {
+            Item::new(
+                0,
+                RgMessageBuilder::new(kind)
+                    .with_path_text("a.txt")
+                    .with_lines_text("foo bar \t \n")
+                    .with_line_number(1)
+                    .with_offset(0)
+                    .with_submatches(submatches)
+                    .build(),
+            )
+        };
+        let match_item = new_item(
+            RgMessageKind::Match,
+            vec![SubMatch::new_text("bar \t", 4..9)],
+        );
+        let context_item = new_item(RgMessageKind::Context, vec![]);
+
+        // the trailing whitespace is highlighted, except where it's part of a match
+        assert_debug_snapshot!(match_item.to_span_lines(&ctx));
+        assert_debug_snapshot!(context_item.to_span_lines(&ctx));
+
+        // and also when whitespace is shown as symbols (before the line ending's symbol)
+        ctx.printable_style = PrintableStyle::Common(false);
+        assert_debug_snapshot!(context_item.to_span_lines(&ctx));
+    }
+
+    #[test]
+    fn to_span_lines_read_only_begin() {
+        let mut app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let mut item = new_item(RG_JSON_BEGIN);
+        item.set_read_only(true);
+
+        app_list_state.set_selected_item(1);
+        let ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        assert_debug_snapshot!(item.to_span_lines(&ctx));
+
+        app_list_state.set_selected_item(0);
+        let ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        assert_debug_snapshot!(item.to_span_lines(&ctx));
+    }
+
+    #[test]
+    fn to_span_lines_root_header() {
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let mut item = new_item(RG_JSON_BEGIN);
+        item.set_root_header(Some("src".into()));
+
+        let ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        assert_debug_snapshot!(item.to_span_lines(&ctx));
+    }
+
+    #[test]
+    fn to_span_lines_binary_begin() {
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let mut item = new_item(RG_JSON_BEGIN);
+        item.set_binary(true);
+
+        let ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        assert_debug_snapshot!(item.to_span_lines(&ctx));
+    }
+
+    #[test]
+    fn has_binary_data() {
+        assert!(!new_item(RG_JSON_BEGIN).has_binary_data());
+        assert!(!new_item(RG_JSON_MATCH).has_binary_data());
+        assert!(!new_item(RG_JSON_END).has_binary_data());
+        assert!(!new_item(RG_B64_JSON_BEGIN).has_binary_data());
+        assert!(!new_item(RG_B64_JSON_END).has_binary_data());
+
+        assert!(new_item(RG_B64_JSON_MATCH).has_binary_data());
+        assert!(new_item(RG_B64_JSON_CONTEXT).has_binary_data());
+        assert!(
+            new_item(&RG_JSON_END.replace(r#""binary_offset":null"#, r#""binary_offset":5"#))
+                .has_binary_data()
+        );
+    }
+
     #[test]
     fn to_span_lines_with_multiline_matches() {
         let app_list_state = new_app_list_state();
@@ -860,6 +1699,25 @@ mod tests {
         assert_debug_snapshot!(new_item(RG_JSON_MATCH_MULTILINE).to_span_lines(&ctx));
     }
 
+    #[test]
+    fn to_span_lines_with_multiline_gap_between_matches() {
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+
+        assert_debug_snapshot!(new_item(RG_JSON_MATCH_MULTILINE_GAP).to_span_lines(&ctx));
+    }
+
+    #[test]
+    fn to_span_lines_input_replacement_with_multiline_gap_between_matches() {
+        let replacement = "foobar";
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::InputReplacement(String::from(replacement), 0);
+        let ctx = new_ui_item_ctx(Some(replacement), &app_list_state, &app_ui_state);
+
+        assert_debug_snapshot!(new_item(RG_JSON_MATCH_MULTILINE_GAP).to_span_lines(&ctx));
+    }
+
     #[test]
     fn to_span_lines_line_wrapping() {
         let mut app_list_state = new_app_list_state();
@@ -898,28 +1756,103 @@ mod tests {
         assert_debug_snapshot!(new_item(RG_JSON_CONTEXT_LINE_WRAP).to_span_lines(&ctx));
     }
 
+    #[test]
+    fn to_span_lines_line_wrapping_wrap_indent() {
+        let mut app_list_state = new_app_list_state();
+        app_list_state.set_selected_item(0);
+        app_list_state.set_selected_submatch(0);
+        let app_ui_state = AppUiState::SelectMatches;
+        let mut ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        ctx.wrap_mode = WrapMode::WrapIndent;
+
+        assert_debug_snapshot!(new_item(RG_JSON_MATCH_LINE_WRAP).to_span_lines(&ctx));
+        assert_debug_snapshot!(new_item(RG_JSON_MATCH_LINE_WRAP_MULTI).to_span_lines(&ctx));
+        assert_debug_snapshot!(new_item(RG_JSON_CONTEXT_LINE_WRAP).to_span_lines(&ctx));
+    }
+
     #[test]
     fn line_count_hidden() {
         let w = 80_u16;
         let s = PrintableStyle::Hidden;
-        assert_eq!(new_item(RG_JSON_BEGIN).line_count(w, s), 1);
-        assert_eq!(new_item(RG_JSON_MATCH).line_count(w, s), 1);
-        assert_eq!(new_item(RG_JSON_MATCH_LINE_WRAP).line_count(w, s), 2);
-        assert_eq!(new_item(RG_JSON_MATCH_LINE_WRAP_MULTI).line_count(w, s), 3);
-        assert_eq!(new_item(RG_JSON_CONTEXT_LINE_WRAP).line_count(w, s), 2);
-        assert_eq!(new_item(RG_JSON_CONTEXT).line_count(w, s), 1);
-        assert_eq!(new_item(RG_JSON_END).line_count(w, s), 1);
-        assert_eq!(new_item(RG_JSON_SUMMARY).line_count(w, s), 0);
+        let m = WrapMode::Wrap;
+        assert_eq!(
+            new_item(RG_JSON_BEGIN).line_count(w, s, m, true, DEFAULT_TAB_WIDTH),
+            1
+        );
+        assert_eq!(
+            new_item(RG_JSON_MATCH).line_count(w, s, m, true, DEFAULT_TAB_WIDTH),
+            1
+        );
+        assert_eq!(
+            new_item(RG_JSON_MATCH_LINE_WRAP).line_count(w, s, m, true, DEFAULT_TAB_WIDTH),
+            2
+        );
+        assert_eq!(
+            new_item(RG_JSON_MATCH_LINE_WRAP_MULTI).line_count(w, s, m, true, DEFAULT_TAB_WIDTH),
+            3
+        );
+        assert_eq!(
+            new_item(RG_JSON_CONTEXT_LINE_WRAP).line_count(w, s, m, true, DEFAULT_TAB_WIDTH),
+            2
+        );
+        assert_eq!(
+            new_item(RG_JSON_CONTEXT).line_count(w, s, m, true, DEFAULT_TAB_WIDTH),
+            1
+        );
+        assert_eq!(
+            new_item(RG_JSON_END).line_count(w, s, m, true, DEFAULT_TAB_WIDTH),
+            1
+        );
+        assert_eq!(
+            new_item(RG_JSON_SUMMARY).line_count(w, s, m, true, DEFAULT_TAB_WIDTH),
+            0
+        );
     }
 
     macro_rules! assert_line_count {
-        ($json:expr, $width:expr, $style:expr, $line_count:expr, $submatch_counts:expr) => {{
+        ($json:expr, $width:expr, $style:expr, $line_count:expr, $submatch_counts:expr) => {
+            assert_line_count!(
+                $json,
+                $width,
+                $style,
+                WrapMode::Wrap,
+                $line_count,
+                $submatch_counts
+            )
+        };
+        ($json:expr, $width:expr, $style:expr, $wrap_mode:expr, $line_count:expr, $submatch_counts:expr) => {
+            assert_line_count!(
+                $json,
+                $width,
+                $style,
+                $wrap_mode,
+                true,
+                $line_count,
+                $submatch_counts
+            )
+        };
+        ($json:expr, $width:expr, $style:expr, $wrap_mode:expr, $show_line_numbers:expr, $line_count:expr, $submatch_counts:expr) => {{
             let mut item = new_item($json);
-            let line_count = item.line_count($width, $style);
+            let line_count = item.line_count(
+                $width,
+                $style,
+                $wrap_mode,
+                $show_line_numbers,
+                DEFAULT_TAB_WIDTH,
+            );
 
             let expected_submatch_counts: &[usize] = $submatch_counts;
             let actual_submatch_counts: Vec<usize> = (0..item.sub_items.len())
-                .map(|i| item.line_count_at(i, $width, $style))
+                .map(|i| {
+                    item.line_count_at(
+                        i,
+                        $width,
+                        $style,
+                        $wrap_mode,
+                        $show_line_numbers,
+                        DEFAULT_TAB_WIDTH,
+                    )
+                })
                 .collect();
             assert_eq!(
                 (line_count, &actual_submatch_counts[..]),
@@ -959,6 +1892,7 @@ mod tests {
             assert_line_count!(RG_JSON_BEGIN, w, s, 1, &[]);
             assert_line_count!(RG_JSON_MATCH, w, s, 1, &[1, 1]);
             assert_line_count!(RG_JSON_MATCH_MULTILINE, w, s, 3, &[1, 3]);
+            assert_line_count!(RG_JSON_MATCH_MULTILINE_GAP, w, s, 3, &[1, 3]);
             assert_line_count!(RG_JSON_MATCH_LINE_WRAP, w, s, 2, &[2]);
             assert_line_count!(
                 RG_JSON_MATCH_LINE_WRAP_MULTI,
@@ -981,6 +1915,7 @@ mod tests {
             assert_line_count!(RG_JSON_BEGIN, w, s, 1, &[]);
             assert_line_count!(RG_JSON_MATCH, w, s, 1, &[1, 1]);
             assert_line_count!(RG_JSON_MATCH_MULTILINE, w, s, 1, &[1, 1]);
+            assert_line_count!(RG_JSON_MATCH_MULTILINE_GAP, w, s, 1, &[1, 1]);
             assert_line_count!(RG_JSON_MATCH_LINE_WRAP, w, s, 2, &[2]);
             assert_line_count!(
                 RG_JSON_MATCH_LINE_WRAP_MULTI,
@@ -996,6 +1931,398 @@ mod tests {
         }
     }
 
+    #[test]
+    fn line_count_wrap_indent() {
+        let w = 80_u16;
+        let s = PrintableStyle::Hidden;
+        let m = WrapMode::WrapIndent;
+        assert_line_count!(RG_JSON_MATCH, w, s, m, 1, &[1, 1]);
+        assert_line_count!(RG_JSON_MATCH_LINE_WRAP, w, s, m, 2, &[2]);
+        assert_line_count!(
+            RG_JSON_MATCH_LINE_WRAP_MULTI,
+            w,
+            s,
+            m,
+            3,
+            &[1, 1, 1, 2, 2, 2, 3]
+        );
+        assert_line_count!(RG_JSON_CONTEXT_LINE_WRAP, w, s, m, 2, &[]);
+    }
+
+    #[test]
+    fn line_count_no_wrap() {
+        let w = 80_u16;
+        let s = PrintableStyle::Hidden;
+        let m = WrapMode::NoWrap;
+        assert_line_count!(RG_JSON_MATCH, w, s, m, 1, &[1, 1]);
+        assert_line_count!(RG_JSON_MATCH_LINE_WRAP, w, s, m, 1, &[1]);
+        assert_line_count!(
+            RG_JSON_MATCH_LINE_WRAP_MULTI,
+            w,
+            s,
+            m,
+            1,
+            &[1, 1, 1, 1, 1, 1, 1]
+        );
+        assert_line_count!(RG_JSON_CONTEXT_LINE_WRAP, w, s, m, 1, &[]);
+
+        // the cached count isn't used once the wrap mode changes
+        let mut item = new_item(RG_JSON_MATCH_LINE_WRAP);
+        assert_eq!(
+            item.line_count(w, s, WrapMode::Wrap, true, DEFAULT_TAB_WIDTH),
+            2
+        );
+        assert_eq!(item.line_count(w, s, m, true, DEFAULT_TAB_WIDTH), 1);
+    }
+
+    #[test]
+    fn line_count_root_header() {
+        let w = 80_u16;
+        let s = PrintableStyle::Hidden;
+        let m = WrapMode::Wrap;
+        let mut item = new_item(RG_JSON_BEGIN);
+        assert_eq!(item.line_count(w, s, m, true, DEFAULT_TAB_WIDTH), 1);
+        assert_eq!(item.line_count_at(0, w, s, m, true, DEFAULT_TAB_WIDTH), 1);
+        item.set_root_header(Some("src".into()));
+        assert_eq!(item.line_count(w, s, m, true, DEFAULT_TAB_WIDTH), 2);
+        assert_eq!(item.line_count_at(0, w, s, m, true, DEFAULT_TAB_WIDTH), 2);
+    }
+
+    #[test]
+    fn line_count_context_separator() {
+        let w = 80_u16;
+        let s = PrintableStyle::Hidden;
+        for m in [WrapMode::Wrap, WrapMode::NoWrap] {
+            let mut item = new_item(RG_JSON_MATCH_MULTILINE);
+            item.set_context_separator(true);
+            assert_eq!(item.line_count(w, s, m, true, DEFAULT_TAB_WIDTH), 4);
+            assert_eq!(item.line_count_at(0, w, s, m, true, DEFAULT_TAB_WIDTH), 2);
+            assert_eq!(item.line_count_at(1, w, s, m, true, DEFAULT_TAB_WIDTH), 4);
+        }
+    }
+
+    #[test]
+    fn line_count_without_line_numbers() {
+        let w = 80_u16;
+        let s = PrintableStyle::Hidden;
+        let m = WrapMode::Wrap;
+        // the lines have the whole width once the line number is gone
+        assert_line_count!(RG_JSON_MATCH_75_LONG, 78, s, m, true, 2, &[1]);
+        assert_line_count!(RG_JSON_MATCH_75_LONG, 78, s, m, false, 1, &[1]);
+        assert_line_count!(RG_JSON_MATCH_LINE_WRAP, w, s, m, false, 2, &[2]);
+        assert_line_count!(RG_JSON_MATCH_MULTILINE, w, s, m, false, 3, &[1, 3]);
+
+        // the cached count isn't used once the line numbers are toggled
+        let mut item = new_item(RG_JSON_MATCH_75_LONG);
+        assert_eq!(item.line_count(78, s, m, true, DEFAULT_TAB_WIDTH), 2);
+        assert_eq!(item.line_count(78, s, m, false, DEFAULT_TAB_WIDTH), 1);
+    }
+
+    fn new_item_with_tabs(lines: &str, submatches: Vec<SubMatch>) -> Item {
+        let kind = match submatches.is_empty() {
+            true => RgMessageKind::Context,
+            false => RgMessageKind::Match,
+        };
+
+        Item::new(
+            0,
+            RgMessageBuilder::new(kind)
+                .with_path_text("foo")
+                .with_line_number(1)
+                .with_lines_text(lines)
+                .with_offset(0)
+                .with_submatches(submatches)
+                .build(),
+        )
+    }
+
+    #[test]
+    fn line_count_tabs() {
+        let s = PrintableStyle::Hidden;
+        let m = WrapMode::Wrap;
+        // 28 columns are left after the line number, and the tabs take up 24 or 48 of them
+        // (the cached count isn't used once the tab width changes)
+        let mut item = new_item_with_tabs("\t\t\t\t\t\t\n", vec![]);
+        assert_eq!(item.line_count(30, s, m, true, 4), 1);
+        assert_eq!(item.line_count(30, s, m, true, 8), 2);
+
+        // the tabs before the submatch are expanded too
+        let mut item =
+            new_item_with_tabs("\t\t\t\t\t\tfoo\n", vec![SubMatch::new_text("foo", 6..9)]);
+        assert_eq!(item.line_count_at(0, 30, s, m, true, 4), 1);
+        let mut item =
+            new_item_with_tabs("\t\t\t\t\t\tfoo\n", vec![SubMatch::new_text("foo", 6..9)]);
+        assert_eq!(item.line_count_at(0, 30, s, m, true, 8), 2);
+    }
+
+    #[test]
+    fn to_span_lines_tabs() {
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let mut ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        ctx.tab_width = 4;
+        let text = |item: &Item, ctx: &UiItemContext| {
+            item.to_span_lines(ctx)[0].spans[1..]
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect::<String>()
+        };
+
+        // each tab is expanded to the next tab stop, counting from after the line number
+        let item = new_item_with_tabs("\ta\tbc\tdef\tghij\tk\n", vec![]);
+        assert_eq!(text(&item, &ctx), "    a   bc  def ghij    k");
+
+        // the columns are kept across the submatches
+        let item = new_item_with_tabs(
+            "a\tb\tc\td\n",
+            vec![
+                SubMatch::new_text("b\t", 2..4),
+                SubMatch::new_text("d", 6..7),
+            ],
+        );
+        assert_eq!(text(&item, &ctx), "a   b   c   d");
+
+        // other styles show tabs as a symbol instead
+        ctx.printable_style = PrintableStyle::Common(true);
+        assert_eq!(text(&item, &ctx), "a→b→c→d¬");
+    }
+
+    #[test]
+    fn exceeds_max_columns() {
+        // the line terminator isn't counted
+        assert!(!new_item(RG_JSON_MATCH).exceeds_max_columns(21));
+        assert!(new_item(RG_JSON_MATCH).exceeds_max_columns(20));
+
+        // each line of a multiline match is counted on its own
+        assert!(!new_item(RG_JSON_MATCH_MULTILINE).exceeds_max_columns(12));
+        assert!(new_item(RG_JSON_MATCH_MULTILINE).exceeds_max_columns(11));
+
+        // only the lines of matches are replaced
+        assert!(!new_item(RG_JSON_CONTEXT).exceeds_max_columns(1));
+        assert!(!new_item(RG_JSON_BEGIN).exceeds_max_columns(1));
+    }
+
+    #[test]
+    fn to_span_lines_truncated_match() {
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::InputReplacement("X".into(), 1);
+        let ctx = new_ui_item_ctx(Some("X"), &app_list_state, &app_ui_state);
+        let text = |item: &Item| {
+            item.to_span_lines(&ctx)[0].spans[1..]
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect::<String>()
+        };
+
+        let mut item = new_item(RG_JSON_MATCH);
+        assert_eq!(text(&item), "    ItemX::new(rg_msgX)");
+
+        // truncated lines are marked, and aren't shown with the replacement
+        item.set_truncated(true);
+        assert_eq!(text(&item), "    Item::new(rg_msg) [truncated]");
+    }
+
+    #[test]
+    fn wrap_span_lines_zero_width_chars() {
+        let wrap = |text: &'static str, max_width| {
+            Item::wrap_span_lines(vec![vec![Span::raw(text)]], max_width, WrapMode::Wrap)
+                .into_iter()
+                .map(|line| {
+                    line.spans
+                        .iter()
+                        .map(|span| span.content.as_ref())
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // combining diacritics stay with the character they're combined with
+        assert_eq!(
+            wrap("e\u{301}e\u{301}e\u{301}", 2),
+            vec!["e\u{301}e\u{301}", "e\u{301}"]
+        );
+        assert_eq!(
+            wrap("a\u{308}\u{323}bc", 1),
+            vec!["a\u{308}\u{323}", "b", "c"]
+        );
+        // zero-width joiners don't take up a column
+        assert_eq!(
+            wrap("👩\u{200d}👩\u{200d}👦ab", 4),
+            vec!["👩\u{200d}👩\u{200d}", "👦ab"]
+        );
+        // control characters which slipped through don't panic
+        assert_eq!(wrap("ab\u{7}\u{1b}cd", 2), vec!["ab\u{7}\u{1b}", "cd"]);
+        assert_eq!(
+            scroll_spans(vec![Span::raw("a\u{7}b\u{301}c")], 0, 2),
+            vec![
+                Span::raw("a\u{7}"),
+                Span::styled(NO_WRAP_CLIP_MARKER, Style::default().fg(Color::DarkGray))
+            ]
+        );
+    }
+
+    macro_rules! assert_scroll_spans {
+        ($spans:expr, $h_scroll:expr, $max_width:expr, $expected:expr) => {{
+            let expected: Vec<Span> = $expected;
+            assert_eq!(scroll_spans($spans, $h_scroll, $max_width), expected);
+        }};
+    }
+
+    #[test]
+    fn scroll_spans_no_clipping() {
+        let spans = || vec![Span::from("foo"), Span::raw(" bar")];
+        assert_scroll_spans!(spans(), 0, 10, spans());
+        assert_scroll_spans!(spans(), 0, 7, spans());
+        assert_scroll_spans!(vec![], 3, 10, vec![]);
+    }
+
+    #[test]
+    fn scroll_spans_clipped() {
+        let marker = Span::styled(NO_WRAP_CLIP_MARKER, Style::default().fg(Color::DarkGray));
+        let red = Style::default().fg(Color::Red);
+        let spans = || vec![Span::from("0123456789"), Span::styled("abcdefghij", red)];
+
+        // clipped on the right
+        assert_scroll_spans!(
+            spans(),
+            0,
+            12,
+            vec![
+                Span::from("0123456789"),
+                Span::styled("a", red),
+                marker.clone()
+            ]
+        );
+        // clipped on both sides
+        assert_scroll_spans!(
+            spans(),
+            5,
+            8,
+            vec![
+                marker.clone(),
+                Span::from("6789"),
+                Span::styled("ab", red),
+                marker.clone()
+            ]
+        );
+        // clipped on the left
+        assert_scroll_spans!(
+            spans(),
+            12,
+            10,
+            vec![marker.clone(), Span::styled("defghij", red)]
+        );
+        // scrolled past the end of the line
+        assert_scroll_spans!(spans(), 30, 10, vec![marker.clone()]);
+    }
+
+    #[test]
+    fn scroll_spans_wide_chars() {
+        let marker = Span::styled(NO_WRAP_CLIP_MARKER, Style::default().fg(Color::DarkGray));
+
+        // wide characters which don't entirely fit are dropped
+        assert_scroll_spans!(
+            vec![Span::from("🦀🦀🦀🦀")],
+            1,
+            6,
+            vec![marker.clone(), Span::from("🦀🦀"), marker.clone()]
+        );
+        assert_scroll_spans!(
+            vec![Span::from("🦀🦀🦀🦀")],
+            2,
+            5,
+            vec![marker.clone(), Span::from("🦀"), marker.clone()]
+        );
+    }
+
+    #[test]
+    fn truncate_path_middle_fits() {
+        assert_eq!(
+            truncate_path_middle("src/ui/line/item.rs", 19),
+            "src/ui/line/item.rs"
+        );
+        assert_eq!(
+            truncate_path_middle("src/ui/line/item.rs", 80),
+            "src/ui/line/item.rs"
+        );
+        assert_eq!(truncate_path_middle("", 0), "");
+    }
+
+    #[test]
+    fn truncate_path_middle_keeps_the_file_name() {
+        let path = "src/ui/line/item.rs";
+        assert_eq!(truncate_path_middle(path, 18), "src/ui/…/item.rs");
+        assert_eq!(truncate_path_middle(path, 16), "src/ui/…/item.rs");
+        assert_eq!(truncate_path_middle(path, 15), "src/…/item.rs");
+        assert_eq!(truncate_path_middle(path, 13), "src/…/item.rs");
+        // not even the first directory fits
+        assert_eq!(truncate_path_middle(path, 11), "sr…/item.rs");
+        assert_eq!(truncate_path_middle(path, 9), "…/item.rs");
+        assert_eq!(
+            truncate_path_middle("/home/user/projects/repgrep/src/main.rs", 24),
+            "/home/user/…/main.rs"
+        );
+        assert_eq!(
+            truncate_path_middle(r"C:\Users\me\src\main.rs", 14),
+            r"C:\…\main.rs"
+        );
+    }
+
+    #[test]
+    fn truncate_path_middle_long_file_names() {
+        let path = "src/a_very_long_file_name.rs";
+        assert_eq!(truncate_path_middle(path, 10), "…e_name.rs");
+        assert_eq!(
+            truncate_path_middle("a_very_long_file_name.rs", 8),
+            "…name.rs"
+        );
+        assert_eq!(truncate_path_middle(path, 1), "…");
+        assert_eq!(truncate_path_middle(path, 0), "");
+        // wide characters which don't fit are dropped
+        assert_eq!(truncate_path_middle("dir/🦀🦀🦀.rs", 6), "…🦀.rs");
+    }
+
+    #[test]
+    fn to_span_lines_long_begin_path() {
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let item = new_item(
+            &RG_JSON_BEGIN.replace("src/model/item.rs", "src/a/deeply/nested/directory/item.rs"),
+        );
+        let mut ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        ctx.list_rect.width = 20;
+        assert_eq!(
+            item.to_span_lines(&ctx)[0].spans[0].content,
+            "src/a/…/item.rs"
+        );
+    }
+
+    #[test]
+    fn to_span_lines_without_line_numbers() {
+        let app_list_state = new_app_list_state();
+        let app_ui_state = AppUiState::SelectMatches;
+        let mut ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        ctx.show_line_numbers = false;
+
+        assert_debug_snapshot!(new_item(RG_JSON_MATCH).to_span_lines(&ctx));
+        assert_debug_snapshot!(new_item(RG_JSON_MATCH_MULTILINE).to_span_lines(&ctx));
+        assert_debug_snapshot!(new_item(RG_JSON_CONTEXT).to_span_lines(&ctx));
+    }
+
+    #[test]
+    fn to_span_lines_no_wrap() {
+        let mut app_list_state = new_app_list_state();
+        app_list_state.set_selected_item(0);
+        app_list_state.set_selected_submatch(0);
+        let app_ui_state = AppUiState::SelectMatches;
+        let mut ctx = new_ui_item_ctx(None, &app_list_state, &app_ui_state);
+        ctx.wrap_mode = WrapMode::NoWrap;
+
+        assert_debug_snapshot!(new_item(RG_JSON_MATCH_LINE_WRAP).to_span_lines(&ctx));
+        ctx.h_scroll = 60;
+        assert_debug_snapshot!(new_item(RG_JSON_MATCH_LINE_WRAP).to_span_lines(&ctx));
+    }
+
     #[test]
     fn line_numbers_with_line_wrap_multi_submatch_input_replacement_multiline() {
         let replacement = "zip\nzap";
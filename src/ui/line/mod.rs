@@ -4,6 +4,18 @@ pub mod sub_item;
 pub use item::*;
 pub use sub_item::*;
 
+/// The marker prefixed to wrapped continuation lines when using `WrapMode::WrapIndent`.
+pub const WRAP_INDENT_MARKER: &str = "↪ ";
+
+/// The marker shown at the edges of truncated lines when using `WrapMode::NoWrap`.
+pub const NO_WRAP_CLIP_MARKER: &str = "…";
+
+/// The marker which replaces the middle of file paths that are too long for the list.
+pub const PATH_ELLIPSIS: &str = "…";
+
+/// The row rendered between non-adjacent lines in the same file.
+pub const CONTEXT_SEPARATOR: &str = "--";
+
 #[macro_export]
 macro_rules! format_line_number {
     ($content:expr) => {
@@ -10,6 +10,8 @@ pub struct SubItem {
     pub index: usize,
     pub sub_match: SubMatch,
     pub should_replace: bool,
+    /// Text which replaces this submatch (as-is) instead of the replacement text used for the other matches.
+    pub replacement_override: Option<Vec<u8>>,
 }
 
 impl SubItem {
@@ -18,6 +20,7 @@ impl SubItem {
             index,
             sub_match,
             should_replace: true,
+            replacement_override: None,
         }
     }
 }
@@ -25,7 +28,7 @@ impl SubItem {
 impl SubItem {
     /// A SubItem contains the "match". A match _may_ be over multiple lines, but there will only ever
     /// be a single span on each line. So this returns a list of "lines": one span for each line.
-    pub fn to_span_lines(&self, ctx: &UiItemContext, is_item_selected: bool) -> Vec<Span> {
+    pub fn to_span_lines(&self, ctx: &UiItemContext, is_item_selected: bool) -> Vec<Span<'_>> {
         let mut s = Style::default();
         if ctx.app_ui_state.is_replacing() {
             if self.should_replace {
@@ -1,4 +1,9 @@
 pub mod app;
+pub mod clipboard;
+pub mod editor;
+pub mod fuzzy;
+pub mod keymap;
 pub mod line;
 pub mod render;
+pub mod theme;
 pub mod tui;
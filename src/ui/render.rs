@@ -1,7 +1,7 @@
 use ratatui::layout::Rect;
 use regex::bytes::Regex;
 
-use crate::model::PrintableStyle;
+use crate::model::{PrintableStyle, WrapMode};
 use crate::ui::app::{AppListState, AppUiState};
 
 /// Used when building the UI from the App's state.
@@ -17,6 +17,20 @@ pub struct UiItemContext<'a> {
     pub app_ui_state: &'a AppUiState,
     /// The `PrintableStyle` with which the UI should be built.
     pub printable_style: PrintableStyle,
+    /// How lines that are too long for the list should be rendered.
+    pub wrap_mode: WrapMode,
+    /// Whether each line is prefixed with its line number.
+    pub show_line_numbers: bool,
+    /// How many columns apart the tab stops are, when tabs are expanded (with `PrintableStyle::Hidden`).
+    pub tab_width: usize,
+    /// Whether context lines are dimmed, since `rg` printed every line of the files (`--passthru`).
+    pub passthru: bool,
+    /// How many columns lines are scrolled horizontally (only used with `WrapMode::NoWrap`).
+    pub h_scroll: usize,
     /// The `Rect` that the items will be rendered into.
     pub list_rect: Rect,
+    /// The fuzzy query the list is being filtered with, if any.
+    pub fuzzy_query: Option<&'a str>,
+    /// Whether the fuzzy query is matched case sensitively.
+    pub fuzzy_case_sensitive: bool,
 }
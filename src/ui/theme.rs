@@ -0,0 +1,109 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use unicode_width::UnicodeWidthStr;
+
+use crate::util::config_dir;
+
+/// The symbols drawn next to the list of matches.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Shown before the selected item in the list.
+    pub indicator: String,
+    /// Shown before each line with matches which will be replaced.
+    pub selected_marker: String,
+    /// Shown before each line with matches, none of which will be replaced.
+    pub deselected_marker: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            indicator: String::from("-> "),
+            selected_marker: String::new(),
+            deselected_marker: String::new(),
+        }
+    }
+}
+
+impl Theme {
+    /// Returns the path to the user's theme config file.
+    pub fn config_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("theme.toml"))
+    }
+
+    /// Loads the user's theme config file, or the default theme if there isn't one.
+    pub fn load() -> Result<Theme> {
+        match Theme::config_path() {
+            Some(path) if path.exists() => fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|s| Theme::from_toml(&s))
+                .with_context(|| format!("Failed to load the theme from {}", path.display())),
+            _ => Ok(Theme::default()),
+        }
+    }
+
+    /// Parses a theme config, which looks like `indicator = "> "`. Symbols which aren't in it keep their defaults.
+    pub fn from_toml(s: &str) -> Result<Theme> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// How many columns the markers take up: both are padded to the width of the widest one, so the lines stay aligned.
+    pub fn marker_width(&self) -> usize {
+        self.selected_marker
+            .width()
+            .max(self.deselected_marker.width())
+    }
+
+    /// Returns the marker shown before a line with matches, padded to `marker_width`.
+    pub fn marker(&self, selected: bool) -> String {
+        let marker = match selected {
+            true => &self.selected_marker,
+            false => &self.deselected_marker,
+        };
+
+        format!(
+            "{}{}",
+            marker,
+            " ".repeat(self.marker_width() - marker.width())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::ui::theme::Theme;
+
+    #[test]
+    fn it_loads_a_custom_theme() {
+        let theme =
+            Theme::from_toml("selected_marker = \"✔ \"\ndeselected_marker = \"✘\"\n").unwrap();
+        // the indicator keeps its default
+        assert_eq!(theme.indicator, "-> ");
+        assert_eq!(theme.marker(true), "✔ ");
+        assert_eq!(theme.marker(false), "✘ ");
+
+        assert!(Theme::from_toml("indicator = 1").is_err());
+        assert!(Theme::from_toml("unknown = \"x\"").is_err());
+    }
+
+    #[test]
+    fn it_pads_markers_to_the_widest_one() {
+        let theme = Theme {
+            selected_marker: String::from("選 "),
+            deselected_marker: String::from("- "),
+            ..Theme::default()
+        };
+        assert_eq!(theme.marker_width(), 3);
+        assert_eq!(theme.marker(true), "選 ");
+        assert_eq!(theme.marker(false), "-  ");
+
+        assert_eq!(Theme::default().marker_width(), 0);
+        assert_eq!(Theme::default().marker(true), "");
+    }
+}
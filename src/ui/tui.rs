@@ -1,10 +1,16 @@
 use std::io::{self, Stdout};
-use std::sync::mpsc::{self, Receiver};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use crossterm::event::{self, Event, KeyCode};
+use anyhow::{bail, Result};
+use crossterm::event::{
+    self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+    Event, KeyCode,
+};
 use crossterm::execute;
 use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
 use ratatui::backend::CrosstermBackend;
@@ -12,11 +18,21 @@ use ratatui::layout::Rect;
 use ratatui::style::{Color, Style};
 use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
 use ratatui::Terminal;
-use regex::bytes::Regex;
 
+use crate::cli::{describe_rg_args, RgArgs};
 use crate::model::ReplacementCriteria;
 use crate::rg::de::RgMessage;
-use crate::ui::app::{App, AppState};
+use crate::rg::exec::run_ripgrep;
+use crate::ui::app::{App, AppState, Session};
+use crate::ui::editor;
+use crate::ui::keymap::KeyMap;
+use crate::ui::theme::Theme;
+
+/// How long the input thread waits for an event before checking if it has been paused.
+const INPUT_POLL_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// How often the interface is woken up when there's no input.
+const TICK_RATE: Duration = Duration::from_millis(250);
 
 const FALLBACK_MESSAGE: &str = r#"
 You may continue to use repgrep, however capturing groups will be ignored for this session."#;
@@ -24,6 +40,20 @@ You may continue to use repgrep, however capturing groups will be ignored for th
 pub struct Tui {
     term: Terminal<CrosstermBackend<Stdout>>,
     rx: Receiver<Event>,
+    /// Set when another process (such as the user's editor) needs to read from the terminal.
+    input_paused: Arc<AtomicBool>,
+}
+
+/// Decides what the main loop does after waiting (for up to `TICK_RATE`) for an event from the input thread.
+/// Returns the event if there was one, or `None` if it's time to tick, so the interface is woken up
+/// periodically even without any input (e.g. so toasts can expire). Fails if the input thread has stopped,
+/// since no more input can be received.
+fn next_event(received: Result<Event, RecvTimeoutError>) -> Result<Option<Event>> {
+    match received {
+        Ok(event) => Ok(Some(event)),
+        Err(RecvTimeoutError::Timeout) => Ok(None),
+        Err(RecvTimeoutError::Disconnected) => bail!("Stopped receiving input from the terminal"),
+    }
 }
 
 impl Tui {
@@ -32,7 +62,9 @@ impl Tui {
 
         let mut stdout = io::stdout();
         // NOTE: must match options in `Self::restore_terminal()`
-        execute!(stdout, EnterAlternateScreen)?;
+        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+        // NOTE: legacy Windows consoles don't support bracketed paste, so there pasted text arrives as key presses
+        let _ = execute!(stdout, EnableBracketedPaste);
 
         let backend = CrosstermBackend::new(stdout);
         let mut term = Terminal::new(backend)?;
@@ -40,8 +72,21 @@ impl Tui {
 
         // Setup input handling
         let (tx, rx) = mpsc::channel();
+        let input_paused = Arc::new(AtomicBool::new(false));
 
+        let paused = Arc::clone(&input_paused);
         thread::spawn(move || loop {
+            // NOTE: poll with a timeout rather than blocking on `event::read()`, so that this thread doesn't take
+            // any input away from another process while paused
+            if paused.load(Ordering::SeqCst) {
+                thread::sleep(INPUT_POLL_TIMEOUT);
+                continue;
+            }
+
+            if !event::poll(INPUT_POLL_TIMEOUT).expect("failed to poll event from terminal") {
+                continue;
+            }
+
             match tx.send(event::read().expect("failed to read event from terminal")) {
                 Ok(_) => {}
                 Err(e) => log::warn!("failed to send event to the main thread: {}", e),
@@ -50,7 +95,58 @@ impl Tui {
 
         term.clear()?;
 
-        Ok(Tui { term, rx })
+        Ok(Tui {
+            term,
+            rx,
+            input_paused,
+        })
+    }
+
+    /// Suspends the terminal interface and opens the given file in the user's editor, restoring
+    /// the interface once the editor exits.
+    fn open_in_editor(&mut self, path: &Path, line_number: Option<usize>) -> Result<()> {
+        // stop reading input, and wait for any in-progress poll to finish
+        self.input_paused.store(true, Ordering::SeqCst);
+        thread::sleep(INPUT_POLL_TIMEOUT * 2);
+
+        terminal::disable_raw_mode()?;
+        execute!(
+            self.term.backend_mut(),
+            LeaveAlternateScreen,
+            DisableMouseCapture
+        )?;
+        let _ = execute!(self.term.backend_mut(), DisableBracketedPaste);
+        self.term.show_cursor()?;
+
+        let result = editor::open_in_editor(path, line_number);
+
+        // NOTE: must match options in `Self::new()`
+        terminal::enable_raw_mode()?;
+        execute!(
+            self.term.backend_mut(),
+            EnterAlternateScreen,
+            EnableMouseCapture
+        )?;
+        let _ = execute!(self.term.backend_mut(), EnableBracketedPaste);
+        self.term.hide_cursor()?;
+        self.term.clear()?;
+
+        // discard any events which were received before the editor was opened
+        while self.rx.try_recv().is_ok() {}
+        self.input_paused.store(false, Ordering::SeqCst);
+
+        if let Err(e) = result {
+            self.draw_message_box(
+                "Error!",
+                format!(
+                    "Failed to open {} in editor!\n\nError: {}",
+                    path.display(),
+                    e
+                ),
+            )?;
+        }
+
+        Ok(())
     }
 
     fn draw_message_box(&mut self, title: impl AsRef<str>, body: impl AsRef<str>) -> Result<()> {
@@ -113,20 +209,17 @@ impl Tui {
         Ok(())
     }
 
+    /// Runs the interface with the results of running `rg` with `args`. The arguments are changed if the user
+    /// searches again (e.g. with a different number of context lines).
     pub fn start(
         mut self,
-        rg_cmdline: String,
+        args: &mut RgArgs,
         rg_messages: Vec<RgMessage>,
-        patterns: &[String],
     ) -> Result<Option<ReplacementCriteria>> {
-        // Parse patterns into `Regex` structs
-        let patterns = patterns
-            .into_iter()
-            .map(|p| Regex::new(p))
-            .collect::<Result<Vec<_>, _>>();
+        let preprocessor = args.preprocessor.as_deref();
 
         // Check if we should be performing replacements with capturing groups.
-        let capture_pattern = match patterns {
+        let capture_pattern = match args.build_capture_patterns() {
             // pattern with capturing group passed, and we only have one
             Ok(mut one) if one.len() == 1 => {
                 // SAFETY: we just checked for length in this match
@@ -169,38 +262,189 @@ impl Tui {
             }
         };
 
+        // Load the user's keybindings (if they have any).
+        let keymap = match KeyMap::load() {
+            Ok(keymap) => keymap,
+            Err(e) => {
+                self.draw_message_box(
+                    "Error!",
+                    format!(
+                        "{:#}\n\nYou may continue to use repgrep, however the default keybindings will be used for this session.",
+                        e
+                    ),
+                )?;
+
+                KeyMap::default()
+            }
+        };
+
+        // Load the user's theme (if they have one).
+        let theme = match Theme::load() {
+            Ok(theme) => theme,
+            Err(e) => {
+                self.draw_message_box(
+                    "Error!",
+                    format!(
+                        "{:#}\n\nYou may continue to use repgrep, however the default theme will be used for this session.",
+                        e
+                    ),
+                )?;
+
+                Theme::default()
+            }
+        };
+
+        // Warn that the matches can't be replaced if they're in the output of a preprocessor.
+        if let Some(preprocessor) = preprocessor {
+            self.draw_message_box(
+                "Replacements Disabled!",
+                format!(
+                    "{}\n\nPreprocessor: {}\n\n{}",
+                    "rg searched the output of a preprocessor (--pre) rather than the files themselves, so the matches can't be replaced.",
+                    preprocessor,
+                    "You may continue to use repgrep to browse the matches, however replacements can't be confirmed."
+                ),
+            )?;
+        }
+
         // main app event loop
-        let mut app = App::new(capture_pattern, rg_cmdline, rg_messages);
-        let mut term = self.term;
+        let mut app = App::new(capture_pattern, args.rg_cmdline(), rg_messages);
+        app.set_keymap(keymap);
+        app.set_theme(theme);
+        app.set_rg_options(describe_rg_args(&args.other_args));
+        app.set_preprocessor(preprocessor.map(String::from));
+        app.set_max_columns(args.max_columns);
+        app.set_search_scope(args.search_scope);
+        app.set_search_roots(&args.paths);
+        app.set_wrap_mode(args.options.wrap_mode);
+        app.set_passthru(args.passthru);
+        if let Some(tab_width) = args.options.tab_width {
+            app.set_tab_width(tab_width);
+        }
+        if let Some(confirm_threshold) = args.options.confirm_threshold {
+            app.set_confirm_threshold(confirm_threshold);
+        }
+        app.set_sort_mode(args.options.sort_mode);
+        if let Some(replacement) = &args.replacement {
+            app.set_initial_replacement(replacement);
+        }
+        if args.options.files_with_matches {
+            app.pick_files();
+        }
+
+        // Restore the state of the interface from the last time this search was run.
+        if let Some(path) = &args.options.session {
+            match Session::load(path) {
+                Ok(Some(session)) => {
+                    if app.restore_session(session) {
+                        app.set_toast("Restored the session");
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    self.draw_message_box(
+                        "Error!",
+                        format!(
+                            "{:#}\n\nYou may continue to use repgrep, however the session wasn't restored.",
+                            e
+                        ),
+                    )?;
+                }
+            }
+        }
+
+        let mut needs_draw = true;
         loop {
-            let before_draw = Instant::now();
-            term.draw(|mut f| app.draw(&mut f))?;
-
-            // If drawing to the terminal is slow, flush all keyboard events so they're not buffered.
-            // (Otherwise with very slow updates, the user has to wait for all keyboard events to be processed
-            // before being able to quit the app, etc).
-            if before_draw.elapsed() > Duration::from_millis(20) {
-                while let Ok(_) = self.rx.try_recv() {}
+            if needs_draw {
+                let before_draw = Instant::now();
+                self.term.draw(|f| app.draw(f))?;
+
+                // If drawing to the terminal is slow, flush all keyboard events so they're not buffered.
+                // (Otherwise with very slow updates, the user has to wait for all keyboard events to be
+                // processed before being able to quit the app, etc).
+                if before_draw.elapsed() > Duration::from_millis(20) {
+                    while self.rx.try_recv().is_ok() {}
+                }
             }
 
-            let event = self.rx.recv()?;
-            let term_size = term.get_frame().size();
+            let event = match next_event(self.rx.recv_timeout(TICK_RATE))? {
+                Some(event) => event,
+                None => {
+                    needs_draw = app.on_tick(Instant::now());
+                    continue;
+                }
+            };
+
+            needs_draw = true;
+            let term_size = self.term.get_frame().size();
             app.on_event(term_size, event)?;
 
-            match app.state {
+            // Save the state of the interface for the next time this search is run.
+            if matches!(app.state, AppState::Cancelled | AppState::Complete) {
+                if let Some(path) = &args.options.session {
+                    if let Err(e) = app.session().save(path) {
+                        self.draw_message_box("Error!", format!("{:#}", e))?;
+                    }
+                }
+            }
+
+            match &app.state {
                 AppState::Running => continue,
                 AppState::Cancelled => return Ok(None),
                 AppState::Complete => return Ok(Some(app.get_replacement_criteria()?)),
+                AppState::OpenEditor(path, line_number) => {
+                    let (path, line_number) = (path.clone(), *line_number);
+                    app.state = AppState::Running;
+                    self.open_in_editor(&path, line_number)?;
+                }
+                AppState::Rerun(context) => {
+                    let context = *context;
+                    app.state = AppState::Running;
+                    if let Err(e) = Tui::rerun(args, &mut app, context, term_size) {
+                        self.draw_message_box(
+                            "Error!",
+                            format!(
+                                "{:#}\n\nYou may continue to use repgrep with the previous results.",
+                                e
+                            ),
+                        )?;
+                    }
+                }
             }
         }
     }
 
+    /// Runs `rg` again with `context` lines of context, and shows its results in the app.
+    fn rerun(args: &mut RgArgs, app: &mut App, context: usize, term_size: Rect) -> Result<()> {
+        if !args.can_rerun() {
+            bail!("rg can't be run again, since its results were read from a file.");
+        }
+
+        args.set_context(context);
+        let mut rg_messages = run_ripgrep(args.rg_args())?;
+        args.trim_messages(&mut rg_messages);
+
+        app.reload(args.rg_cmdline(), rg_messages, term_size);
+        app.set_rg_options(describe_rg_args(&args.other_args));
+        app.set_toast(format!(
+            "Searched again with {} line(s) of context",
+            context
+        ));
+
+        Ok(())
+    }
+
     pub fn restore_terminal() -> Result<()> {
         let backend = CrosstermBackend::new(io::stdout());
         let mut term = Terminal::new(backend)?;
 
         terminal::disable_raw_mode()?;
-        execute!(term.backend_mut(), LeaveAlternateScreen)?;
+        execute!(
+            term.backend_mut(),
+            LeaveAlternateScreen,
+            DisableMouseCapture
+        )?;
+        let _ = execute!(term.backend_mut(), DisableBracketedPaste);
         term.show_cursor()?;
         term.clear()?;
         term.set_cursor(0, 0)?;
@@ -208,3 +452,38 @@ impl Tui {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::RecvTimeoutError;
+
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    use pretty_assertions::assert_eq;
+
+    use crate::ui::tui::next_event;
+
+    #[test]
+    fn it_handles_events() {
+        let event = Event::Key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE));
+        assert_eq!(next_event(Ok(event.clone())).unwrap(), Some(event));
+        assert_eq!(
+            next_event(Ok(Event::Resize(80, 24))).unwrap(),
+            Some(Event::Resize(80, 24))
+        );
+    }
+
+    #[test]
+    fn it_ticks_without_events() {
+        assert_eq!(next_event(Err(RecvTimeoutError::Timeout)).unwrap(), None);
+    }
+
+    #[test]
+    fn it_stops_without_input() {
+        assert_eq!(
+            next_event(Err(RecvTimeoutError::Disconnected))
+                .unwrap_err()
+                .to_string(),
+            "Stopped receiving input from the terminal"
+        );
+    }
+}
@@ -1,3 +1,18 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Returns the directory which holds the user's config files (e.g.: their keybindings).
+pub fn config_dir() -> Option<PathBuf> {
+    let non_empty = |var| {
+        env::var_os(var)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    non_empty("XDG_CONFIG_HOME")
+        .or_else(|| non_empty("HOME").map(|home| home.join(".config")))
+        .map(|dir| dir.join(env!("CARGO_PKG_NAME")))
+}
+
 pub fn clamp(val: usize, min: usize, max: usize) -> usize {
     if val <= min {
         min
@@ -8,7 +23,7 @@ pub fn clamp(val: usize, min: usize, max: usize) -> usize {
     }
 }
 
-pub fn byte_pos_from_char_pos(s: &String, char_pos: usize) -> usize {
+pub fn byte_pos_from_char_pos(s: &str, char_pos: usize) -> usize {
     let mut idx = 0;
     for (i, ch) in s.chars().enumerate() {
         if i == char_pos {
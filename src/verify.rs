@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::rg::de::{RgMessage, RgMessageKind};
+use crate::rg::read::NoMatches;
+use crate::ui::line::Item;
+
+/// How many times the replacement text was found in a file, after the replacements were written.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Verification {
+    pub path: PathBuf,
+    /// How many matches were replaced in the file.
+    pub expected: usize,
+    /// How many times the replacement text was found in the file. This may be more than `expected`, since
+    /// the file may have already contained the replacement text.
+    pub found: usize,
+}
+
+impl Verification {
+    /// Whether the replacement text was found fewer times than the number of matches that were replaced.
+    pub fn is_low(&self) -> bool {
+        self.found < self.expected
+    }
+}
+
+/// The arguments passed to `rg` to search for the (literal) `replacement` text in the files at `paths`.
+pub fn verify_args(replacement: &str, encoding: Option<&str>, paths: &[&PathBuf]) -> Vec<String> {
+    // ignore the user's config file, since it may change how the text is matched (e.g. `--smart-case`)
+    let mut args = vec![String::from("--no-config"), String::from("--fixed-strings")];
+    if replacement.contains('\n') {
+        args.push(String::from("--multiline"));
+    }
+    if let Some(encoding) = encoding {
+        args.push(format!("--encoding={}", encoding));
+    }
+    args.push(format!("--regexp={}", replacement));
+    args.push(String::from("--"));
+    args.extend(paths.iter().map(|p| p.to_string_lossy().to_string()));
+
+    args
+}
+
+/// Searches the files in `expected` (along with how many matches were replaced in each of them) for the
+/// `replacement` text with `search` (usually `run_ripgrep`), and returns how many times it was found in each.
+pub fn verify_replacements<F>(
+    expected: &[(PathBuf, usize)],
+    replacement: &str,
+    encoding: Option<&str>,
+    search: F,
+) -> Result<Vec<Verification>>
+where
+    F: FnOnce(Vec<String>) -> Result<Vec<RgMessage>>,
+{
+    let paths = expected.iter().map(|(path, _)| path).collect::<Vec<_>>();
+    let rg_messages = match search(verify_args(replacement, encoding, &paths)) {
+        Ok(rg_messages) => rg_messages,
+        // none of the files contain the replacement text
+        Err(e) if e.is::<NoMatches>() => vec![],
+        Err(e) => return Err(e),
+    };
+
+    let mut found: HashMap<PathBuf, usize> = HashMap::new();
+    for (i, rg_message) in rg_messages.into_iter().enumerate() {
+        let item = Item::new(i, rg_message);
+        if !matches!(item.kind, RgMessageKind::Match) {
+            continue;
+        }
+
+        if let Some(path) = item.path_buf() {
+            *found.entry(path).or_default() += item.sub_items().len();
+        }
+    }
+
+    Ok(expected
+        .iter()
+        .map(|(path, expected)| Verification {
+            path: path.clone(),
+            expected: *expected,
+            found: found.get(path).copied().unwrap_or(0),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use anyhow::{anyhow, Result};
+    use pretty_assertions::assert_eq;
+
+    use crate::model::ReplacementCriteria;
+    use crate::replace::perform_replacements;
+    use crate::rg::de::test_utilities::RgMessageBuilder;
+    use crate::rg::de::{RgMessage, RgMessageKind, SubMatch};
+    use crate::rg::read::NoMatches;
+    use crate::temp_file;
+    use crate::ui::line::Item;
+    use crate::verify::{verify_args, verify_replacements, Verification};
+
+    fn match_message(
+        path: &str,
+        line: &str,
+        offset: usize,
+        submatches: Vec<SubMatch>,
+    ) -> RgMessage {
+        RgMessageBuilder::new(RgMessageKind::Match)
+            .with_path_text(path)
+            .with_lines_text(line)
+            .with_submatches(submatches)
+            .with_offset(offset)
+            .build()
+    }
+
+    /// Searches like `rg --fixed-strings --json` would, by reading each file from disk.
+    fn fake_rg(args: Vec<String>) -> Result<Vec<RgMessage>> {
+        let needle = args
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--regexp="))
+            .unwrap();
+        let paths = args.iter().skip_while(|arg| *arg != "--").skip(1);
+
+        let mut rg_messages = vec![];
+        for path in paths {
+            let mut offset = 0;
+            for line in fs::read_to_string(path)?.split_inclusive('\n') {
+                let submatches = line
+                    .match_indices(needle)
+                    .map(|(i, m)| SubMatch::new_text(m, i..i + m.len()))
+                    .collect::<Vec<_>>();
+                if !submatches.is_empty() {
+                    rg_messages.push(match_message(path, line, offset, submatches));
+                }
+                offset += line.len();
+            }
+        }
+
+        if rg_messages.is_empty() {
+            Err(NoMatches.into())
+        } else {
+            Ok(rg_messages)
+        }
+    }
+
+    #[test]
+    fn it_builds_verify_args() {
+        let p = PathBuf::from("a.txt");
+        assert_eq!(
+            verify_args("foo", None, &[&p]),
+            [
+                "--no-config",
+                "--fixed-strings",
+                "--regexp=foo",
+                "--",
+                "a.txt"
+            ]
+        );
+        assert_eq!(
+            verify_args("-a\nb", Some("utf-16le"), &[&p]),
+            [
+                "--no-config",
+                "--fixed-strings",
+                "--multiline",
+                "--encoding=utf-16le",
+                "--regexp=-a\nb",
+                "--",
+                "a.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_verifies_replacements() {
+        let p = temp_file!("foo bar\nbar foo\n");
+        let path = p.to_string_lossy().to_string();
+        let items = vec![
+            Item::new(
+                0,
+                match_message(&path, "foo bar\n", 0, vec![SubMatch::new_text("foo", 0..3)]),
+            ),
+            Item::new(
+                1,
+                match_message(&path, "bar foo\n", 8, vec![SubMatch::new_text("foo", 4..7)]),
+            ),
+        ];
+        perform_replacements(ReplacementCriteria::new(None, "qux", items)).unwrap();
+
+        let verifications = verify_replacements(&[(p.clone(), 2)], "qux", None, fake_rg).unwrap();
+        assert_eq!(
+            verifications,
+            [Verification {
+                path: p,
+                expected: 2,
+                found: 2
+            }]
+        );
+        assert!(!verifications[0].is_low());
+    }
+
+    #[test]
+    fn it_flags_files_missing_the_replacement() {
+        let p = temp_file!("qux bar\nbar foo\n");
+        let other = temp_file!("baz\n");
+
+        let verifications =
+            verify_replacements(&[(p.clone(), 2), (other.clone(), 1)], "qux", None, fake_rg)
+                .unwrap();
+        assert_eq!(
+            verifications
+                .iter()
+                .map(|v| (v.found, v.is_low()))
+                .collect::<Vec<_>>(),
+            [(1, true), (0, true)]
+        );
+
+        // no matches at all isn't an error
+        let verifications = verify_replacements(&[(other, 1)], "qux", None, fake_rg).unwrap();
+        assert_eq!(verifications[0].found, 0);
+    }
+
+    #[test]
+    fn it_fails_when_rg_fails() {
+        let err = verify_replacements(&[(PathBuf::from("a.txt"), 1)], "qux", None, |_| {
+            Err(anyhow!("rg failed"))
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "rg failed");
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::rg::de::{RgMessage, RgMessageKind};
+use crate::ui::line::Item;
+
+/// Writes each match as `path:line:column:text`, like `rg --vimgrep`, so it can be loaded into an editor's
+/// quickfix list. The line and column (in bytes) are where the match starts, and both count from 1. The text is
+/// the line the match starts on, so a multiline match is only written once.
+pub fn write_vimgrep<W: Write>(mut out: W, rg_messages: Vec<RgMessage>) -> Result<()> {
+    for (i, rg_message) in rg_messages.into_iter().enumerate() {
+        let item = Item::new(i, rg_message);
+        if !matches!(item.kind, RgMessageKind::Match) {
+            continue;
+        }
+
+        let (path, line_number, lines) = match (item.path_buf(), item.line_number(), item.lines()) {
+            (Some(path), Some(line_number), Some(lines)) => (path, *line_number, lines.to_vec()),
+            _ => continue,
+        };
+
+        for sub_item in item.sub_items() {
+            let start = sub_item.sub_match.range.start.min(lines.len());
+            let line_start = lines[..start]
+                .iter()
+                .rposition(|b| *b == b'\n')
+                .map_or(0, |i| i + 1);
+            let line_end = lines[start..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .map_or(lines.len(), |i| start + i);
+            let text = &lines[line_start..line_end];
+            let text = text.strip_suffix(b"\r").unwrap_or(text);
+
+            write!(
+                out,
+                "{}:{}:{}:",
+                path.display(),
+                line_number + lines[..line_start].iter().filter(|b| **b == b'\n').count(),
+                start - line_start + 1
+            )?;
+            out.write_all(text)?;
+            writeln!(out)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::rg::de::test_utilities::*;
+    use crate::rg::de::*;
+    use crate::vimgrep::write_vimgrep;
+
+    fn vimgrep(rg_messages: Vec<RgMessage>) -> String {
+        let mut out = vec![];
+        write_vimgrep(&mut out, rg_messages).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn it_writes_each_submatch() {
+        let rg_messages = vec![
+            RgMessage::from_str(RG_JSON_BEGIN),
+            RgMessage::from_str(RG_JSON_MATCH),
+            RgMessage::from_str(RG_JSON_CONTEXT),
+            RgMessageBuilder::new(RgMessageKind::Match)
+                .with_path_text("src/a.rs")
+                .with_lines_text("foo bar foo\r\n")
+                .with_line_number(7)
+                .with_offset(100)
+                .with_submatches(vec![
+                    SubMatch::new_text("foo", 0..3),
+                    SubMatch::new_text("foo", 8..11),
+                ])
+                .build(),
+            RgMessage::from_str(RG_JSON_END),
+            RgMessage::from_str(RG_JSON_SUMMARY),
+        ];
+
+        assert_eq!(
+            vimgrep(rg_messages),
+            [
+                "src/model/item.rs:197:5:    Item::new(rg_msg)",
+                "src/model/item.rs:197:15:    Item::new(rg_msg)",
+                "src/a.rs:7:1:foo bar foo",
+                "src/a.rs:7:9:foo bar foo",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_writes_the_line_multiline_matches_start_on() {
+        assert_eq!(
+            vimgrep(vec![RgMessage::from_str(RG_JSON_MATCH_MULTILINE)]),
+            "./foo/baz:3:5:baz 1\n./foo/baz:5:9:333 bar 4444\n"
+        );
+    }
+
+    #[test]
+    fn it_writes_lines_which_are_not_utf8() {
+        let mut out = vec![];
+        write_vimgrep(&mut out, vec![RgMessage::from_str(RG_B64_JSON_MATCH)]).unwrap();
+        assert_eq!(
+            out,
+            b"src/model/item.rs:197:6:    \xffItem::\xffnew(rg_msg)\nsrc/model/item.rs:197:17:    \xffItem::\xffnew(rg_msg)\n"
+        );
+    }
+}